@@ -19,16 +19,22 @@ typedef enum {
     DIRUNKNOWN,
 } ProlensDirection;
 
+// new protocols are appended, existing values never change
 typedef enum {
-    ORDPACKET,
-    SMTP,
-    POP3,
-    IMAP,
-    HTTP,
-    FTPCMD,
-    FTPDATA,
-    SIP,
-    L7UNKNOWN,
+    ORDPACKET = 0,
+    SMTP      = 1,
+    POP3      = 2,
+    IMAP      = 3,
+    HTTP      = 4,
+    FTPCMD    = 5,
+    FTPDATA   = 6,
+    SIP       = 7,
+    L7UNKNOWN = 8,
+    DNSUDP    = 9,
+    HTTP2     = 10,
+    SIPTCP    = 11,
+    RTP       = 12,
+    DNSTCP    = 13,
 } L7Proto;
 
 typedef enum {
@@ -63,6 +69,61 @@ typedef enum {
     Chunked,
 } CEncoding;
 
+typedef enum {
+    TUNNEL_CONNECT,
+    TUNNEL_WEBSOCKET,
+    TUNNEL_H2C,
+    TUNNEL_OTHER,
+} CTunnelKind;
+
+typedef enum {
+    DECODE_UNSUPPORTED,
+    DECODE_CORRUPT,
+    DECODE_LIMIT,
+} CDecodeError;
+
+typedef enum {
+    HTTP_AUTH_BASIC,
+    HTTP_AUTH_DIGEST,
+    HTTP_AUTH_BEARER,
+    HTTP_AUTH_NTLM,
+    HTTP_AUTH_OTHER,
+} CHttpAuthScheme;
+
+typedef enum {
+    FTP_TYPE_ASCII,
+    FTP_TYPE_EBCDIC,
+    FTP_TYPE_IMAGE,
+    FTP_TYPE_LOCAL,
+} FtpType;
+
+typedef enum {
+    FTP_MODE_STREAM,
+    FTP_MODE_BLOCK,
+    FTP_MODE_COMPRESSED,
+} FtpMode;
+
+typedef enum {
+    FTP_ENTRY_FILE,
+    FTP_ENTRY_DIR,
+    FTP_ENTRY_LINK,
+    FTP_ENTRY_OTHER,
+    FTP_ENTRY_UNKNOWN,
+} FtpEntryType;
+
+typedef enum {
+    SIP_CALL_START,
+    SIP_CALL_ANSWERED,
+    SIP_CALL_END,
+} SipCallEvent;
+
+typedef enum {
+    SDP_SENDRECV,
+    SDP_SENDONLY,
+    SDP_RECVONLY,
+    SDP_INACTIVE,
+} SdpDir;
+
 #define MAX_ENCODING 8
 struct CEncodingArray {
     const CEncoding* ptr;
@@ -91,19 +152,493 @@ typedef struct {
     PacketFree free;
 } PacketVTable;
 
+typedef uint64_t (*PacketTimestamp)(void *pkt_ptr); // milliseconds
+
+typedef enum {
+    ESMTP_SIZE,
+    ESMTP_BODY,
+    ESMTP_SMTPUTF8,
+    ESMTP_NOTIFY,
+    ESMTP_ORCPT,
+    ESMTP_RET,
+    ESMTP_ENVID,
+    ESMTP_OTHER,
+} CEsmtpKey;
+
+typedef struct {
+    CEsmtpKey      key;
+    const uint8_t *name_ptr;
+    size_t         name_len;
+    const uint8_t *value_ptr; // NULL if the parameter has no value
+    size_t         value_len;
+} CEsmtpParam;
+
+typedef enum {
+    POP3_USER,
+    POP3_PASS,
+    POP3_APOP,
+    POP3_AUTH,
+    POP3_CAPA,
+    POP3_STAT,
+    POP3_LIST,
+    POP3_UIDL,
+    POP3_RETR,
+    POP3_TOP,
+    POP3_DELE,
+    POP3_NOOP,
+    POP3_RSET,
+    POP3_QUIT,
+    POP3_STLS,
+    POP3_OTHER,
+} CPop3Cmd;
+
+typedef enum {
+    IMAP_OK,
+    IMAP_NO,
+    IMAP_BAD,
+} CImapStatus;
+
+typedef enum {
+    IMAP_NOT_AUTH,
+    IMAP_AUTH,
+    IMAP_SELECTED,
+    IMAP_LOGOUT,
+} CImapState;
+
 typedef void (*CbStm)(const uint8_t *data, size_t data_len, uint32_t seq, const void *ctx);
 typedef void (*CbOrdPkt)(void *pkt_ptr, const void *ctx, ProlensDirection dir);
 typedef void (*CbData)(const uint8_t *data, size_t len, uint32_t seq, const void *ctx);
 typedef void (*CbDirData)(const uint8_t *data, size_t len, uint32_t seq, const void *ctx, ProlensDirection dir);
 typedef void (*CbDirEvt)(const void *ctx, ProlensDirection dir);
+typedef void (*CbSmtpAddr)(const uint8_t *data, size_t len, const CEsmtpParam *params, size_t params_len, uint32_t seq, const void *ctx);
+typedef void (*CbDataPair)(const uint8_t *data1, size_t len1, const uint8_t *data2, size_t len2, uint32_t seq, const void *ctx);
+typedef void (*CbPop3Reply)(CPop3Cmd cmd, const uint8_t *arg, size_t arg_len, bool ok, const uint8_t *text, size_t text_len,
+                            uint32_t seq, const void *ctx);
+typedef void (*CbPop3Size)(uint32_t num, uint64_t size, const void *ctx);
+typedef void (*CbPop3Uidl)(uint32_t num, const uint8_t *uid, size_t uid_len, const void *ctx);
+typedef void (*CbPop3Msg)(uint32_t num, const uint8_t *uid, size_t uid_len, bool partial, const void *ctx); // uid is NULL if unknown, partial for TOP
+typedef void (*CbImapReply)(const uint8_t *tag, size_t tag_len, const uint8_t *cmd, size_t cmd_len, CImapStatus status,
+                            const uint8_t *text, size_t text_len, uint32_t seq, const void *ctx);
+typedef void (*CbImapState)(CImapState state, const uint8_t *mailbox, size_t mailbox_len, bool read_only, const void *ctx); // mailbox is NULL if none
+typedef void (*CbImapBodyInfo)(const uint8_t *mailbox, size_t mailbox_len, uint32_t num, uint32_t uid, const uint8_t *section,
+                               size_t section_len, const void *ctx); // uid is 0 if unknown
+typedef struct {
+    const uint8_t *ptr;
+    size_t len;
+} CImapStr;
+
+// NULL pointer means NIL
+typedef struct {
+    const uint8_t *name_ptr;
+    size_t name_len;
+    const uint8_t *mailbox_ptr;
+    size_t mailbox_len;
+    const uint8_t *host_ptr;
+    size_t host_len;
+} CImapAddr;
+
+// missing fields are NULL, uid is 0 if unknown
+typedef struct {
+    const uint8_t *mailbox_ptr;
+    size_t mailbox_len;
+    uint32_t num;
+    uint32_t uid;
+    bool has_size;
+    uint32_t size;
+    const CImapStr *flags_ptr;
+    size_t flags_len;
+    const uint8_t *internal_date_ptr;
+    size_t internal_date_len;
+    bool has_envelope;
+    const uint8_t *date_ptr;
+    size_t date_len;
+    const uint8_t *subject_ptr;
+    size_t subject_len;
+    const CImapAddr *from_ptr;
+    size_t from_len;
+    const CImapAddr *sender_ptr;
+    size_t sender_len;
+    const CImapAddr *reply_to_ptr;
+    size_t reply_to_len;
+    const CImapAddr *to_ptr;
+    size_t to_len;
+    const CImapAddr *cc_ptr;
+    size_t cc_len;
+    const CImapAddr *bcc_ptr;
+    size_t bcc_len;
+    const uint8_t *in_reply_to_ptr;
+    size_t in_reply_to_len;
+    const uint8_t *message_id_ptr;
+    size_t message_id_len;
+} CImapFetch;
+
+typedef void (*CbImapFetch)(const CImapFetch *fetch, const void *ctx);
 typedef void (*CbBody)(const uint8_t *data, size_t len, uint32_t seq, const void *ctx, ProlensDirection dir, CTransferEncoding te);
 typedef void (*CbHttpBody)(const uint8_t *data, size_t len, uint32_t seq, const void *ctx, ProlensDirection dir,
                            struct CEncodingArray ce, struct CEncodingArray te);
+typedef void (*CbHttpTrans)(uint64_t id, const void *ctx, ProlensDirection dir); // transaction id, same for a request and its responses
+typedef struct {
+    const uint8_t *name_ptr;
+    size_t name_len;
+    const uint8_t *value_ptr;
+    size_t value_len;
+} CHttpHeader;
+
+// missing fields are NULL
+typedef struct {
+    uint64_t id;
+    const uint8_t *method_ptr;
+    size_t method_len;
+    const uint8_t *target_ptr;
+    size_t target_len;
+    const uint8_t *path_ptr;
+    size_t path_len;
+    const uint8_t *query_ptr;
+    size_t query_len;
+    const uint8_t *version_ptr;
+    size_t version_len;
+    const CHttpHeader *headers_ptr;
+    size_t headers_len;
+    const uint8_t *host_ptr;
+    size_t host_len;
+    const uint8_t *user_agent_ptr;
+    size_t user_agent_len;
+    const uint8_t *content_type_ptr;
+    size_t content_type_len;
+    const uint8_t *cookie_ptr;
+    size_t cookie_len;
+    const uint8_t *authorization_ptr;
+    size_t authorization_len;
+} CHttpRequest;
+
+// Set-Cookie may repeat, look it up in headers
+typedef struct {
+    uint64_t id;
+    const uint8_t *version_ptr;
+    size_t version_len;
+    uint16_t status;
+    const uint8_t *reason_ptr;
+    size_t reason_len;
+    const CHttpHeader *headers_ptr;
+    size_t headers_len;
+    const uint8_t *content_type_ptr;
+    size_t content_type_len;
+} CHttpResponse;
+
+// missing fields are NULL
+typedef struct {
+    uint64_t id;
+    size_t index;
+    size_t depth;
+    const uint8_t *name_ptr;
+    size_t name_len;
+    const uint8_t *filename_ptr;
+    size_t filename_len;
+    const uint8_t *content_type_ptr;
+    size_t content_type_len;
+    const CHttpHeader *headers_ptr;
+    size_t headers_len;
+} CHttpPart;
+
+typedef void (*CbHttpRequest)(const CHttpRequest *req, const void *ctx);
+typedef void (*CbHttpResponse)(const CHttpResponse *rsp, const void *ctx);
+typedef void (*CbHttpPart)(const CHttpPart *part, const void *ctx, ProlensDirection dir);
+typedef void (*CbHttpForm)(uint64_t id, const uint8_t *key, size_t key_len, const uint8_t *value, size_t value_len,
+                           const void *ctx, ProlensDirection dir); // url decoded, not called for bodies over 64K
+typedef void (*CbHttpTunnel)(uint64_t id, CTunnelKind kind, const uint8_t *target, size_t target_len,
+                             const void *ctx); // CONNECT host:port or the upgrade request target
+// payload is unmasked. large frames are split, offset is the payload offset in the frame
+typedef struct {
+    bool fin;
+    uint8_t opcode;
+    uint64_t len;
+    uint64_t offset;
+    const uint8_t *payload_ptr;
+    size_t payload_len;
+} CWsFrame;
+typedef void (*CbWsFrame)(const CWsFrame *frame, const void *ctx, ProlensDirection dir);
+typedef struct {
+    const uint8_t *ptr;
+    size_t len;
+} CTlsStr;
+// ServerHello: cipher_suites and alpn hold the selected one. absent fields are NULL
+typedef struct {
+    bool client;
+    uint16_t version;
+    const uint16_t *versions_ptr; // supported_versions extension
+    size_t versions_len;
+    const uint8_t *random_ptr;
+    size_t random_len;
+    const uint8_t *session_id_ptr;
+    size_t session_id_len;
+    const uint16_t *cipher_suites_ptr;
+    size_t cipher_suites_len;
+    const uint8_t *sni_ptr;
+    size_t sni_len;
+    const CTlsStr *alpn_ptr;
+    size_t alpn_len;
+} CTlsHello;
+typedef void (*CbTlsHello)(const CTlsHello *hello, const void *ctx, ProlensDirection dir);
+// 按scheme填写对应字段，没有的字段指针为NULL
+typedef struct {
+    uint64_t id;
+    bool proxy;
+    uint16_t status; // 请求中的凭证为0，结果见auth result
+    CHttpAuthScheme scheme;
+    const uint8_t *scheme_name_ptr;
+    size_t scheme_name_len;
+    const uint8_t *user_ptr;
+    size_t user_len;
+    const uint8_t *pass_ptr;
+    size_t pass_len;
+    const uint8_t *token_ptr;
+    size_t token_len;
+    const uint8_t *realm_ptr;
+    size_t realm_len;
+    const uint8_t *nonce_ptr;
+    size_t nonce_len;
+    const uint8_t *uri_ptr;
+    size_t uri_len;
+    const uint8_t *response_ptr;
+    size_t response_len;
+    const uint8_t *algorithm_ptr;
+    size_t algorithm_len;
+    const uint8_t *qop_ptr;
+    size_t qop_len;
+    const uint8_t *nc_ptr;
+    size_t nc_len;
+    const uint8_t *cnonce_ptr;
+    size_t cnonce_len;
+    const uint8_t *opaque_ptr;
+    size_t opaque_len;
+    const uint8_t *domain_ptr;
+    size_t domain_len;
+    const uint8_t *workstation_ptr;
+    size_t workstation_len;
+    const uint8_t *target_ptr;
+    size_t target_len;
+    uint32_t ntlm_type;
+    uint32_t ntlm_flags;
+    const uint8_t *challenge_ptr; // 8字节
+} CHttpAuth;
+typedef void (*CbHttpAuth)(const CHttpAuth *auth, const void *ctx, ProlensDirection dir);
+typedef void (*CbHttpAuthResult)(uint64_t id, bool proxy, uint16_t status, const void *ctx, ProlensDirection dir);
+// stream id。起始行和头与http/1.1格式相同，以\r\n结尾
+typedef void (*CbHttp2Line)(uint32_t stream, const uint8_t *data, size_t len, const void *ctx, ProlensDirection dir);
+typedef void (*CbHttp2BodyEvt)(uint32_t stream, const void *ctx, ProlensDirection dir);
+typedef void (*CbHttp2Body)(uint32_t stream, const uint8_t *data, size_t len, const void *ctx, ProlensDirection dir,
+                            struct CEncodingArray ce);
+typedef struct {
+    uint32_t stream;
+    const uint8_t *service_ptr;
+    size_t service_len;
+    const uint8_t *method_ptr;
+    size_t method_len;
+    const uint8_t *content_type_ptr;
+    size_t content_type_len;
+    const uint8_t *encoding_ptr; // grpc-encoding, NULL if absent
+    size_t encoding_len;
+} CGrpcCall;
+typedef void (*CbGrpcCall)(const CGrpcCall *call, const void *ctx, ProlensDirection dir);
+// 大的消息分多次回调，offset是data在消息中的偏移
+typedef struct {
+    uint32_t stream;
+    bool compressed;
+    uint32_t len;
+    uint32_t offset;
+    const uint8_t *data_ptr;
+    size_t data_len;
+} CGrpcMsg;
+typedef void (*CbGrpcMsg)(const CGrpcMsg *msg, const void *ctx, ProlensDirection dir);
+typedef struct {
+    uint32_t stream;
+    bool has_status;
+    uint32_t status;
+    const uint8_t *message_ptr; // percent-decoded grpc-message, NULL if absent
+    size_t message_len;
+} CGrpcStatus;
+typedef void (*CbGrpcStatus)(const CGrpcStatus *status, const void *ctx, ProlensDirection dir);
+typedef void (*CbHttpDecodeErr)(CDecodeError err, const void *ctx, ProlensDirection dir);
 typedef void (*CbFtpLink)(const uint8_t *ip_ptr, size_t ip_len, uint8_t ip_type, uint16_t port, const void *ctx, ProlensDirection dir);
 typedef void (*CbFtpBody)(const uint8_t *data, size_t len, uint32_t seq, const void *ctx, ProlensDirection dir);
+
+typedef struct {
+    uint64_t       session;
+    const uint8_t *user_ptr;
+    size_t         user_len;
+    const uint8_t *pass_ptr; // NULL if the server accepted USER alone
+    size_t         pass_len;
+    uint16_t       code;     // 230 on success
+} CFtpLogin;
+typedef void (*CbFtpLogin)(const CFtpLogin *login, const void *ctx);
+
+typedef struct {
+    uint64_t       session;
+    const uint8_t *cmd_ptr;
+    size_t         cmd_len;
+    const uint8_t *path_ptr;      // RNFR argument for RNTO
+    size_t         path_len;
+    const uint8_t *rename_to_ptr; // RNTO only, NULL otherwise
+    size_t         rename_to_len;
+    const uint8_t *cwd_ptr;       // NULL if not known yet
+    size_t         cwd_len;
+    uint16_t       code;
+} CFtpFile;
+typedef void (*CbFtpFile)(const CFtpFile *file, const void *ctx);
+
+typedef struct {
+    uint64_t       session;
+    const uint8_t *cmd_ptr;
+    size_t         cmd_len;
+    const uint8_t *path_ptr; // NULL if absent
+    size_t         path_len;
+    const uint8_t *cwd_ptr;  // NULL if not known yet
+    size_t         cwd_len;
+    uint16_t       code;     // 226 or 426
+    FtpType        data_type;
+    FtpMode        mode;
+    uint64_t       rest;
+} CFtpTransfer;
+typedef void (*CbFtpTransfer)(const CFtpTransfer *transfer, const void *ctx);
+
+typedef struct {
+    uint64_t       session;  // id of the control connection
+    const uint8_t *cmd_ptr;  // RETR/STOR/LIST..., NULL if not seen yet
+    size_t         cmd_len;
+    const uint8_t *path_ptr; // command argument, NULL if absent
+    size_t         path_len;
+} CFtpXfer;
+typedef void (*CbFtpXferEvt)(const CFtpXfer *xfer, const void *ctx, ProlensDirection dir);
+typedef void (*CbFtpXferBody)(const CFtpXfer *xfer, const uint8_t *data, size_t len, uint32_t seq, const void *ctx, ProlensDirection dir);
+
+typedef struct {
+    uint64_t       session;
+    const uint8_t *name_ptr;
+    size_t         name_len;
+    FtpEntryType   entry_type;
+    uint64_t       size;      // UINT64_MAX if unknown
+    const uint8_t *perm_ptr;  // NULL if absent
+    size_t         perm_len;
+    const uint8_t *mtime_ptr; // raw text as sent by the server, NULL if absent
+    size_t         mtime_len;
+    const uint8_t *link_ptr;  // symlink target, NULL if absent
+    size_t         link_len;
+} CFtpEntry;
+typedef void (*CbFtpListEntry)(const CFtpEntry *entry, const void *ctx, ProlensDirection dir);
+
+typedef struct {
+    SipCallEvent   event;
+    const uint8_t *call_id_ptr;
+    size_t         call_id_len;
+    const uint8_t *from_ptr;    // From URI
+    size_t         from_len;
+    const uint8_t *to_ptr;      // To URI
+    size_t         to_len;
+    uint16_t       status;      // final response to the INVITE, 0 if none yet
+    uint64_t       duration;    // milliseconds from answer to end, only for SIP_CALL_END
+} CSipCall;
+typedef void (*CbSipCall)(const CSipCall *call, const void *ctx);
+
+typedef struct {
+    uint8_t        payload_type;
+    const uint8_t *name_ptr;   // from a=rtpmap, or the RFC 3551 name for static types
+    size_t         name_len;
+    uint32_t       clock_rate;
+    uint8_t        channels;
+} CSdpCodec;
+
+typedef struct {
+    const uint8_t   *media_ptr;  // audio/video/...
+    size_t           media_len;
+    uint16_t         port;       // 0 if the stream is rejected
+    uint16_t         port_count;
+    const uint8_t   *proto_ptr;  // RTP/AVP, RTP/SAVP...
+    size_t           proto_len;
+    CIpAddr          conn;       // media level c= or the session level one
+    uint16_t         rtcp_port;
+    SdpDir           dir;
+    const CSdpCodec *codecs_ptr;
+    size_t           codecs_len;
+} CSdpMedia;
+
+typedef struct {
+    const uint8_t   *origin_ptr;       // NULL if absent
+    size_t           origin_len;
+    const uint8_t   *session_name_ptr; // NULL if absent
+    size_t           session_name_len;
+    CIpAddr          conn;
+    const CSdpMedia *media_ptr;
+    size_t           media_len;
+} CSdp;
+typedef void (*CbSipSdp)(const CSdp *sdp, const void *ctx, ProlensDirection dir);
+
+typedef struct {
+    const uint8_t *call_id_ptr; // NULL if absent
+    size_t         call_id_len;
+    const uint8_t *media_ptr;
+    size_t         media_len;
+    CIpAddr        ip;
+    uint16_t       rtp_port;
+    uint16_t       rtcp_port;
+} CSipMediaLink;
+typedef void (*CbSipMediaLink)(const CSipMediaLink *link, const void *ctx, ProlensDirection dir);
+
+typedef struct {
+    bool     marker;
+    uint8_t  payload_type;
+    uint16_t seq;
+    uint32_t timestamp;
+    uint32_t ssrc;
+    uint8_t  csrc_count;
+    bool     extension;
+} RtpHeader;
+typedef void (*CbRtpPkt)(const RtpHeader *hdr, const uint8_t *payload, size_t len, const void *ctx, ProlensDirection dir);
+
+typedef struct {
+    const uint8_t *call_id_ptr; // NULL unless the stream was predicted from SIP/SDP
+    size_t         call_id_len;
+    uint32_t       ssrc;
+    uint8_t        payload_type;
+    uint32_t       clock_rate;  // 0 if unknown
+    uint64_t       packets;
+    uint64_t       expected;
+    int64_t        lost;        // cumulative, negative with duplicates
+    uint64_t       reordered;
+    double         jitter;      // RFC 3550 interarrival jitter in milliseconds
+} CRtpStats;
+typedef void (*CbRtpStats)(const CRtpStats *stats, const void *ctx, ProlensDirection dir);
+
+typedef struct {
+    uint64_t ntp;
+    uint32_t rtp_ts;
+    uint32_t packets;
+    uint32_t octets;
+} RtcpSender;
+
+typedef struct {
+    uint32_t ssrc;
+    uint8_t  fraction_lost;
+    int32_t  lost;
+    uint32_t highest_seq;
+    uint32_t jitter;        // timestamp units
+    uint32_t lsr;
+    uint32_t dlsr;
+} RtcpBlock;
+
+typedef struct {
+    uint32_t         ssrc;
+    bool             has_sender; // SR
+    RtcpSender       sender;
+    const RtcpBlock *blocks_ptr;
+    size_t           blocks_len;
+} CRtcpReport;
+typedef void (*CbRtcpReport)(const CRtcpReport *report, const void *ctx, ProlensDirection dir);
+typedef void (*CbRtcpSdes)(uint32_t ssrc, uint8_t item, const uint8_t *value, size_t len, const void *ctx, ProlensDirection dir);
+typedef void (*CbRtcpBye)(const uint32_t *ssrcs, size_t ssrcs_len, const uint8_t *reason, size_t reason_len, const void *ctx, ProlensDirection dir);
 typedef void (*CbSipBody)(const uint8_t *data, size_t len, uint32_t seq, const void *ctx, ProlensDirection dir);
 
 void        protolens_init_vtable(PacketVTable vtable);
+void        protolens_set_timestamp_fn(PacketTimestamp timestamp); // optional, NULL if unknown
 FfiProlens *protolens_new(void);
 void        protolens_free(FfiProlens *prolens);
 
@@ -121,7 +656,9 @@ void protolens_set_cb_ord_pkt(FfiProlens *prolens, CbOrdPkt callback);
 void protolens_set_cb_smtp_user(FfiProlens *prolens, CbData callback);
 void protolens_set_cb_smtp_pass(FfiProlens *prolens, CbData callback);
 void protolens_set_cb_smtp_mailfrom(FfiProlens *prolens, CbData callback);
+void protolens_set_cb_smtp_mailfrom_ext(FfiProlens *prolens, CbSmtpAddr callback);
 void protolens_set_cb_smtp_rcpt(FfiProlens *prolens, CbData callback);
+void protolens_set_cb_smtp_rcpt_ext(FfiProlens *prolens, CbSmtpAddr callback);
 void protolens_set_cb_smtp_header(FfiProlens *prolens, CbDirData callback);
 void protolens_set_cb_smtp_body_start(FfiProlens *prolens, CbDirEvt callback);
 void protolens_set_cb_smtp_body(FfiProlens *prolens, CbBody callback);
@@ -134,6 +671,16 @@ void protolens_set_cb_pop3_body(FfiProlens *prolens, CbBody callback);
 void protolens_set_cb_pop3_body_stop(FfiProlens *prolens, CbDirEvt callback);
 void protolens_set_cb_pop3_clt(FfiProlens *prolens, CbDirData callback);
 void protolens_set_cb_pop3_srv(FfiProlens *prolens, CbDirData callback);
+void protolens_set_cb_pop3_user(FfiProlens *prolens, CbData callback);
+void protolens_set_cb_pop3_pass(FfiProlens *prolens, CbData callback);
+void protolens_set_cb_pop3_apop(FfiProlens *prolens, CbDataPair callback);
+void protolens_set_cb_pop3_auth(FfiProlens *prolens, CbDataPair callback);
+void protolens_set_cb_pop3_reply(FfiProlens *prolens, CbPop3Reply callback);
+void protolens_set_cb_pop3_capa(FfiProlens *prolens, CbData callback);
+void protolens_set_cb_pop3_stat(FfiProlens *prolens, CbPop3Size callback);
+void protolens_set_cb_pop3_list(FfiProlens *prolens, CbPop3Size callback);
+void protolens_set_cb_pop3_uidl(FfiProlens *prolens, CbPop3Uidl callback);
+void protolens_set_cb_pop3_msg(FfiProlens *prolens, CbPop3Msg callback);
 
 void protolens_set_cb_imap_header(FfiProlens *prolens, CbDirData callback);
 void protolens_set_cb_imap_body_start(FfiProlens *prolens, CbDirEvt callback);
@@ -141,71 +688,136 @@ void protolens_set_cb_imap_body(FfiProlens *prolens, CbBody callback);
 void protolens_set_cb_imap_body_stop(FfiProlens *prolens, CbDirEvt callback);
 void protolens_set_cb_imap_clt(FfiProlens *prolens, CbDirData callback);
 void protolens_set_cb_imap_srv(FfiProlens *prolens, CbDirData callback);
+void protolens_set_cb_imap_user(FfiProlens *prolens, CbData callback);
+void protolens_set_cb_imap_pass(FfiProlens *prolens, CbData callback);
+void protolens_set_cb_imap_auth(FfiProlens *prolens, CbDataPair callback);
+void protolens_set_cb_imap_reply(FfiProlens *prolens, CbImapReply callback);
+void protolens_set_cb_imap_state(FfiProlens *prolens, CbImapState callback);
+void protolens_set_cb_imap_body_info(FfiProlens *prolens, CbImapBodyInfo callback);
+void protolens_set_cb_imap_fetch(FfiProlens *prolens, CbImapFetch callback);
 
 void protolens_set_cb_http_start_line(FfiProlens *prolens, CbDirData callback);
 void protolens_set_cb_http_header(FfiProlens *prolens, CbDirData callback);
 void protolens_set_cb_http_body_start(FfiProlens *prolens, CbDirEvt callback);
 void protolens_set_cb_http_body(FfiProlens *prolens, CbHttpBody callback);
 void protolens_set_cb_http_body_stop(FfiProlens *prolens, CbDirEvt callback);
+void protolens_set_cb_http_trans(FfiProlens *prolens, CbHttpTrans callback);
+void protolens_set_cb_http_request(FfiProlens *prolens, CbHttpRequest callback);
+void protolens_set_cb_http_response(FfiProlens *prolens, CbHttpResponse callback);
+void protolens_set_cb_http_part(FfiProlens *prolens, CbHttpPart callback);
+void protolens_set_cb_http_form(FfiProlens *prolens, CbHttpForm callback);
+void protolens_set_cb_http_tunnel(FfiProlens *prolens, CbHttpTunnel callback);
+void protolens_set_cb_http_tunnel_data(FfiProlens *prolens, CbDirData callback); // raw bytes after CONNECT/upgrade, except websocket
+void protolens_set_cb_ws_frame(FfiProlens *prolens, CbWsFrame callback);
+void protolens_set_cb_tls_hello(FfiProlens *prolens, CbTlsHello callback); // TLS hellos after CONNECT, records still go to tunnel_data
+void protolens_set_cb_http_auth(FfiProlens *prolens, CbHttpAuth callback); // request credentials as parsed (status 0 = unknown), NTLM challenges with the response status
+void protolens_set_cb_http_auth_result(FfiProlens *prolens, CbHttpAuthResult callback); // final response status for each request credential
+// body is delivered decoded (gzip, deflate, br, dechunked) with empty ce/te. 0 means no limit
+void protolens_set_http_decode(FfiProlens *prolens, bool enable, size_t max_size, size_t max_ratio);
+void protolens_set_cb_http_decode_err(FfiProlens *prolens, CbHttpDecodeErr callback);
+
+// http2 prior knowledge or h2c upgrade from HTTP parser
+void protolens_set_cb_http2_start_line(FfiProlens *prolens, CbHttp2Line callback);
+void protolens_set_cb_http2_header(FfiProlens *prolens, CbHttp2Line callback);
+void protolens_set_cb_http2_body_start(FfiProlens *prolens, CbHttp2BodyEvt callback);
+void protolens_set_cb_http2_body(FfiProlens *prolens, CbHttp2Body callback);
+void protolens_set_cb_http2_body_stop(FfiProlens *prolens, CbHttp2BodyEvt callback);
+void protolens_set_cb_grpc_call(FfiProlens *prolens, CbGrpcCall callback);
+void protolens_set_cb_grpc_msg(FfiProlens *prolens, CbGrpcMsg callback);
+void protolens_set_cb_grpc_status(FfiProlens *prolens, CbGrpcStatus callback);
 
 void protolens_set_cb_ftp_clt(FfiProlens *prolens, CbDirData callback);
 void protolens_set_cb_ftp_srv(FfiProlens *prolens, CbDirData callback);
 void protolens_set_cb_ftp_link(FfiProlens *prolens, CbFtpLink callback);
+void protolens_set_cb_ftp_login(FfiProlens *prolens, CbFtpLogin callback);
+void protolens_set_cb_ftp_file(FfiProlens *prolens, CbFtpFile callback);
+void protolens_set_cb_ftp_transfer(FfiProlens *prolens, CbFtpTransfer callback);
 
 void protolens_set_cb_ftp_body_start(FfiProlens *prolens, CbDirEvt callback);
 void protolens_set_cb_ftp_body(FfiProlens *prolens, CbFtpBody callback);
 void protolens_set_cb_ftp_body_stop(FfiProlens *prolens, CbDirEvt callback);
 
+// only for data connections predicted from PORT/EPRT/PASV/EPSV on a control connection
+void protolens_set_cb_ftp_xfer_start(FfiProlens *prolens, CbFtpXferEvt callback);
+void protolens_set_cb_ftp_xfer_body(FfiProlens *prolens, CbFtpXferBody callback);
+void protolens_set_cb_ftp_xfer_stop(FfiProlens *prolens, CbFtpXferEvt callback);
+void protolens_set_cb_ftp_list_entry(FfiProlens *prolens, CbFtpListEntry callback);
+
 void protolens_set_cb_sip_start_line(FfiProlens *prolens, CbDirData callback);
 void protolens_set_cb_sip_header(FfiProlens *prolens, CbDirData callback);
 void protolens_set_cb_sip_body_start(FfiProlens *prolens, CbDirEvt callback);
 void protolens_set_cb_sip_body(FfiProlens *prolens, CbSipBody callback);
 void protolens_set_cb_sip_body_stop(FfiProlens *prolens, CbDirEvt callback);
+// needs protolens_set_timestamp_fn for the call duration
+void protolens_set_cb_sip_call(FfiProlens *prolens, CbSipCall callback);
+void protolens_set_cb_sip_sdp(FfiProlens *prolens, CbSipSdp callback);
+// one call per expected RTP/RTCP port pair, skips rejected, inactive and on-hold streams
+void protolens_set_cb_sip_media_link(FfiProlens *prolens, CbSipMediaLink callback);
+
+// with any rtp callback set, streams announced in SIP/SDP get the RTP parser automatically
+void protolens_set_cb_rtp_pkt(FfiProlens *prolens, CbRtpPkt callback);
+void protolens_set_cb_rtp_stats(FfiProlens *prolens, CbRtpStats callback);
+void protolens_set_cb_rtcp_report(FfiProlens *prolens, CbRtcpReport callback);
+void protolens_set_cb_rtcp_sdes(FfiProlens *prolens, CbRtcpSdes callback);
+void protolens_set_cb_rtcp_bye(FfiProlens *prolens, CbRtcpBye callback);
 
 typedef void (*CbSipBody)(const uint8_t *data, size_t len, uint32_t seq, const void *ctx, ProlensDirection dir);
 
 typedef enum {
-    QUERY  = 0,
-    IQUERY = 1,
-    STATUS = 2,
-    NOTIFY = 4,
-    UPDATE = 5,
+    QUERY           = 0,
+    IQUERY          = 1,
+    STATUS          = 2,
+    OPCODE_RESERVED = 255, // any other opcode
 } COpcode;
 
 typedef enum {
-    NOERROR   = 0,
-    FORMERR   = 1,
-    SERVFAIL  = 2,
-    NXDOMAIN  = 3,
-    NOTIMP    = 4,
-    REFUSED   = 5,
-    YXDOMAIN  = 6,
-    YXRRSET   = 7,
-    NXRRSET   = 8,
-    NOTAUTH   = 9,
-    NOTZONE   = 10,
-    BADVERS   = 16,
-    BADSIG    = 16,
-    BADKEY    = 17,
-    BADTIME   = 18,
-    BADMODE   = 19,
-    BADNAME   = 20,
-    BADALG    = 21,
-    BADTRUNC  = 22,
-    BADCOOKIE = 23,
+    NOERROR        = 0,
+    FORMERR        = 1,
+    SERVFAIL       = 2,
+    NXDOMAIN       = 3,
+    NOTIMP         = 4,
+    REFUSED        = 5,
+    RCODE_RESERVED = 255, // any other rcode
 } CRcode;
 
+// question type
 typedef enum {
-    A     = 1,
-    NS    = 2,
-    CNAME = 5,
-    SOA   = 6,
-    PTR   = 12,
-    MX    = 15,
-    TXT   = 16,
-    AAAA  = 28,
-    SRV   = 33,
-    OPT   = 41,
+    A      = 1,
+    NS     = 2,
+    MD     = 3,
+    MF     = 4,
+    CNAME  = 5,
+    SOA    = 6,
+    MB     = 7,
+    MG     = 8,
+    MR     = 9,
+    QNULL  = 10, // NULL, renamed because of the C macro
+    WKS    = 11,
+    PTR    = 12,
+    HINFO  = 13,
+    MINFO  = 14,
+    MX     = 15,
+    TXT    = 16,
+    AAAA   = 28,
+    SRV    = 33,
+    NAPTR  = 35,
+    DS     = 43,
+    SSHFP  = 44,
+    RRSIG  = 46,
+    NSEC   = 47,
+    DNSKEY = 48,
+    NSEC3  = 50,
+    TLSA   = 52,
+    SVCB   = 64,
+    HTTPS  = 65,
+    AXFR   = 252,
+    MAILB  = 253,
+    MAILA  = 254,
+    ALL    = 255,
+    URI    = 256,
+    CAA    = 257,
+    // unknown type
+    QTYPE_OTHER = 0,
 } CQtype;
 
 typedef enum {
@@ -216,6 +828,51 @@ typedef enum {
     ANY = 255,
 } CQclass;
 
+// resource record type
+typedef enum {
+    RR_A          = 1,
+    RR_NS         = 2,
+    RR_MD         = 3,
+    RR_MF         = 4,
+    RR_CNAME      = 5,
+    RR_SOA        = 6,
+    RR_MB         = 7,
+    RR_MG         = 8,
+    RR_MR         = 9,
+    RR_NULL       = 10,
+    RR_WKS        = 11,
+    RR_PTR        = 12,
+    RR_HINFO      = 13,
+    RR_MINFO      = 14,
+    RR_MX         = 15,
+    RR_TXT        = 16,
+    RR_AAAA       = 28,
+    RR_SRV        = 33,
+    RR_NAPTR      = 35,
+    RR_OPT        = 41,
+    RR_DS         = 43,
+    RR_SSHFP      = 44,
+    RR_RRSIG      = 46,
+    RR_NSEC       = 47,
+    RR_DNSKEY     = 48,
+    RR_NSEC3      = 50,
+    RR_NSEC3PARAM = 51,
+    RR_TLSA       = 52,
+    RR_SVCB       = 64,
+    RR_HTTPS      = 65,
+    RR_URI        = 256,
+    RR_CAA        = 257,
+    // unknown type, the value is in type_code
+    RR_OTHER      = 0,
+} CType;
+
+typedef enum {
+    CLASS_IN = 1,
+    CLASS_CS = 2,
+    CLASS_CH = 3,
+    CLASS_HS = 4,
+} CClass;
+
 typedef struct {
     uint16_t id;
     bool     qr;
@@ -224,77 +881,212 @@ typedef struct {
     bool     tc;
     bool     rd;
     bool     ra;
-    bool     z;
     bool     ad;
     bool     cd;
     CRcode   rcode;
-    uint16_t qdcount;
+    uint16_t qcount;
     uint16_t ancount;
     uint16_t nscount;
     uint16_t arcount;
 } CHeader;
 
 typedef struct {
-    uint32_t serial;
-    uint32_t refresh;
-    uint32_t retry;
-    uint32_t expire;
-    uint32_t minimum_ttl;
-    uint8_t  primary_ns[256];
-    size_t   primary_ns_len;
-    uint8_t  mailbox[256];
-    size_t   mailbox_len;
+    const uint8_t *primary_ns_ptr;
+    size_t         primary_ns_len;
+    const uint8_t *mailbox_ptr;
+    size_t         mailbox_len;
+    uint32_t       serial;
+    uint32_t       refresh;
+    uint32_t       retry;
+    uint32_t       expire;
+    uint32_t       minimum_ttl;
 } CRdSoa;
 
 typedef struct {
-    uint16_t priority;
-    uint16_t weight;
-    uint16_t port;
-    uint8_t  target[256];
-    size_t   target_len;
+    uint16_t       priority;
+    uint16_t       weight;
+    uint16_t       port;
+    const uint8_t *target_ptr;
+    size_t         target_len;
 } CRdSrv;
 
 typedef struct {
-    uint16_t preference;
-    uint8_t  exchange[256];
-    size_t   exchange_len;
+    uint16_t       preference;
+    const uint8_t *exchange_ptr;
+    size_t         exchange_len;
 } CRdMx;
 
 typedef struct {
-    uint8_t        name[256];
+    const uint8_t *ptr;
+    size_t         len;
+} CDnsBytes;
+
+// EDNS client subnet, family 0 means absent
+typedef struct {
+    uint16_t family;
+    uint8_t  source_prefix;
+    uint8_t  scope_prefix;
+    CIpAddr  addr;
+} CEdnsEcs;
+
+// extended DNS error (RFC 8914), only the first one
+typedef struct {
+    bool      present;
+    uint16_t  info_code;
+    CDnsBytes extra_text;
+} CEdnsEde;
+
+typedef struct {
+    CDnsBytes cpu;
+    CDnsBytes os;
+} CRdHinfo;
+
+typedef struct {
+    uint16_t  order;
+    uint16_t  preference;
+    CDnsBytes flags;
+    CDnsBytes services;
+    CDnsBytes regexp;
+    CDnsBytes replacement;
+} CRdNaptr;
+
+typedef struct {
+    uint16_t  key_tag;
+    uint8_t   algorithm;
+    uint8_t   digest_type;
+    CDnsBytes digest;
+} CRdDs;
+
+typedef struct {
+    uint8_t   algorithm;
+    uint8_t   fp_type;
+    CDnsBytes fingerprint;
+} CRdSshfp;
+
+typedef struct {
+    uint16_t  type_covered;
+    uint8_t   algorithm;
+    uint8_t   labels;
+    uint32_t  original_ttl;
+    uint32_t  expiration;
+    uint32_t  inception;
+    uint16_t  key_tag;
+    CDnsBytes signer;
+    CDnsBytes signature;
+} CRdRrsig;
+
+// type_bitmap is the raw window/bitmap encoding
+typedef struct {
+    CDnsBytes next;
+    CDnsBytes type_bitmap;
+} CRdNsec;
+
+typedef struct {
+    uint16_t  flags;
+    uint8_t   protocol;
+    uint8_t   algorithm;
+    CDnsBytes public_key;
+} CRdDnskey;
+
+typedef struct {
+    uint8_t   hash_algorithm;
+    uint8_t   flags;
+    uint16_t  iterations;
+    CDnsBytes salt;
+    CDnsBytes next_hashed;
+    CDnsBytes type_bitmap;
+} CRdNsec3;
+
+typedef struct {
+    uint8_t   usage;
+    uint8_t   selector;
+    uint8_t   matching_type;
+    CDnsBytes data;
+} CRdTlsa;
+
+// SVCB and HTTPS. alpn is the length-prefixed id list, ipv4hint/ipv6hint are packed addresses,
+// port 0 means absent, params holds all key/value pairs in wire format
+typedef struct {
+    uint16_t  priority;
+    CDnsBytes target;
+    CDnsBytes alpn;
+    bool      no_default_alpn;
+    uint16_t  port;
+    CDnsBytes ipv4hint;
+    CDnsBytes ech;
+    CDnsBytes ipv6hint;
+    CDnsBytes params;
+} CRdSvcb;
+
+typedef struct {
+    uint16_t  priority;
+    uint16_t  weight;
+    CDnsBytes target;
+} CRdUri;
+
+typedef struct {
+    uint8_t   flags;
+    CDnsBytes tag;
+    CDnsBytes value;
+} CRdCaa;
+
+// only the rdata field matching rtype is filled, rdata_ptr is the main variable-length part
+typedef struct {
+    bool           unicast; // mDNS cache-flush bit
+    const uint8_t *name_ptr;
     size_t         name_len;
-    CQtype         qtype;
-    CQclass        qclass;
+    CType          rtype;
+    CClass         rclass; // class in Rust
     uint32_t       ttl;
-    const uint8_t* rdata_ptr;
+    const uint8_t *rdata_ptr;
     size_t         rdata_len;
+    CRdSoa         soa_data;
+    CRdSrv         srv_data;
+    CRdMx          mx_data;
     uint8_t        ipv4_addr[4];
     uint8_t        ipv6_addr[16];
-    CRdSoa         soa;
-    CRdSrv         srv;
-    CRdMx          mx;
+    uint16_t       type_code;
+    CRdHinfo       hinfo_data;
+    CRdNaptr       naptr_data;
+    CRdDs          ds_data;
+    CRdSshfp       sshfp_data;
+    CRdRrsig       rrsig_data;
+    CRdNsec        nsec_data;
+    CRdDnskey      dnskey_data;
+    CRdNsec3       nsec3_data;
+    CRdTlsa        tlsa_data;
+    CRdSvcb        svcb_data;
+    CRdUri         uri_data;
+    CRdCaa         caa_data;
 } CRR;
 
+// EDNS OPT record of the additional section
 typedef struct {
-    uint8_t        name[256];
-    size_t         name_len;
-    CQtype         qtype;
-    CQclass        qclass;
-    uint32_t       ttl;
-    const uint8_t* rdata_ptr;
+    uint16_t       payload_size;
+    uint8_t        extrcode;
+    uint8_t        version;
+    uint16_t       flags;
+    const uint8_t *rdata_ptr;
     size_t         rdata_len;
     uint8_t        ipv4_addr[4];
     uint8_t        ipv6_addr[16];
-    CRdSoa         soa;
-    CRdSrv         srv;
-    CRdMx          mx;
+    bool           dnssec_ok;
+    CEdnsEcs       ecs;
+    CDnsBytes      nsid;
+    CDnsBytes      cookie_client;
+    CDnsBytes      cookie_server; // empty in queries
+    bool           keepalive_present;
+    uint16_t       keepalive_timeout; // units of 100 ms, 0 in queries
+    size_t         padding_len;
+    CEdnsEde       ede;
 } COptRR;
 
-typedef void (*CbDnsHeader)(const CHeader *header, const void *ctx);
-typedef void (*CbDnsQuery)(const uint8_t *name, size_t name_len, CQtype qtype, CQclass qclass, const void *ctx);
-typedef void (*CbDnsRr)(const CRR *rr, const void *ctx);
-typedef void (*CbDnsOptRr)(const COptRR *opt_rr, const void *ctx);
-typedef void (*CbDnsEnd)(const void *ctx);
+// offset is the position of the entry in the dns message
+typedef void (*CbDnsHeader)(CHeader header, size_t offset, void *ctx);
+typedef void (*CbDnsQuery)(const uint8_t *name, size_t name_len, CQtype qtype, CQclass qclass, bool unicast, size_t offset, void *ctx);
+typedef void (*CbDnsRr)(CRR rr, size_t offset, void *ctx);
+typedef void (*CbDnsOptRr)(COptRR opt_rr, size_t offset, void *ctx);
+typedef void (*CbDnsEnd)(void *ctx);
 
 void protolens_set_cb_dns_header(FfiProlens *prolens, CbDnsHeader callback);
 void protolens_set_cb_dns_query(FfiProlens *prolens, CbDnsQuery callback);
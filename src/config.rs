@@ -7,10 +7,17 @@ pub(crate) const IMAP_PORT: u16 = 143;
 pub(crate) const HTTP_PORT: u16 = 80;
 pub(crate) const FTP_PORT: u16 = 21;
 
+pub(crate) const HTTP_DECODE_MAX: usize = 16 * 1024 * 1024;
+pub(crate) const HTTP_DECODE_RATIO: usize = 100;
+
 #[derive(Clone, Debug)]
 pub struct Config {
     pub pkt_buff: usize,
     pub read_buff: usize,
+    // http body解压之后再交给cb_http_body
+    pub http_decode: bool,
+    pub http_decode_max: usize,   // 单个body解压后的最大长度，0表示不限制
+    pub http_decode_ratio: usize, // 最大解压比例，0表示不限制
 }
 
 impl Config {
@@ -18,6 +25,9 @@ impl Config {
         Config {
             pkt_buff: 0,
             read_buff: 0,
+            http_decode: false,
+            http_decode_max: 0,
+            http_decode_ratio: 0,
         }
     }
 }
@@ -27,6 +37,8 @@ impl Default for Config {
         let mut conf = Self::new();
         conf.pkt_buff = MAX_PKT_BUFF;
         conf.read_buff = MAX_READ_BUFF;
+        conf.http_decode_max = HTTP_DECODE_MAX;
+        conf.http_decode_ratio = HTTP_DECODE_RATIO;
         conf
     }
 }
@@ -0,0 +1,481 @@
+// http body的content-coding/transfer-coding流式解码
+use crate::Encoding;
+use crate::inflate::Inflate;
+use brotli_decompressor::{
+    BrotliDecoderIsFinished, BrotliDecompressStream, BrotliResult, BrotliState, StandardAlloc,
+};
+
+// 解压比例只在输出超过这个大小之后才检查，避免小body误判
+const RATIO_MIN_OUT: usize = 64 * 1024;
+// brotli每次解压的输出块大小
+const BROTLI_CHUNK: usize = 4096;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum DecodeError {
+    Unsupported, // lzma, compress
+    Corrupt,
+    Limit, // 超过解压大小或者解压比例
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct DecodeLimit {
+    pub(crate) max_size: usize, // 0表示不限制
+    pub(crate) max_ratio: usize,
+}
+
+impl DecodeLimit {
+    // 已经输入in_size、输出out_size之后，还允许输出的字节数
+    fn budget(&self, in_size: usize, out_size: usize) -> usize {
+        let mut allow = usize::MAX;
+        if self.max_size != 0 {
+            allow = self.max_size;
+        }
+        if self.max_ratio != 0 {
+            let ratio = in_size
+                .saturating_mul(self.max_ratio)
+                .saturating_add(self.max_ratio - 1)
+                .max(RATIO_MIN_OUT);
+            allow = allow.min(ratio);
+        }
+        allow.saturating_sub(out_size)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Gzip,
+    Deflate,
+    Brotli,
+}
+
+type Brotli = BrotliState<StandardAlloc, StandardAlloc, StandardAlloc>;
+
+struct Stage {
+    kind: Kind,
+    head: Vec<u8>,
+    inflate: Option<Inflate>,
+    brotli: Option<Box<Brotli>>,
+    out_size: usize,
+}
+
+impl Stage {
+    fn new(kind: Kind) -> Self {
+        let brotli = (kind == Kind::Brotli).then(|| {
+            let mut state = Box::new(BrotliState::new(
+                StandardAlloc::default(),
+                StandardAlloc::default(),
+                StandardAlloc::default(),
+            ));
+            // 只接受RFC 7932的窗口大小
+            state.large_window = false;
+            state
+        });
+        Self {
+            kind,
+            head: Vec::new(),
+            inflate: None,
+            brotli,
+            out_size: 0,
+        }
+    }
+
+    // 本次最多输出budget字节，超过立即停止并返回Limit
+    fn feed(&mut self, data: &[u8], out: &mut Vec<u8>, budget: usize) -> Result<(), DecodeError> {
+        let start = out.len();
+        let ret = self.feed_inner(data, out, budget);
+        self.out_size += out.len() - start;
+        ret
+    }
+
+    fn feed_inner(
+        &mut self,
+        data: &[u8],
+        out: &mut Vec<u8>,
+        budget: usize,
+    ) -> Result<(), DecodeError> {
+        if let Some(brotli) = &mut self.brotli {
+            return brotli_feed(brotli, data, out, budget);
+        }
+        if let Some(inflate) = &mut self.inflate {
+            return inflate_feed(inflate, data, out, budget);
+        }
+
+        self.head.extend_from_slice(data);
+        let skip = match self.kind {
+            Kind::Gzip => gzip_header(&self.head)?,
+            Kind::Deflate => zlib_header(&self.head)?,
+            Kind::Brotli => return Ok(()),
+        };
+        let Some(skip) = skip else {
+            return Ok(());
+        };
+        let head = std::mem::take(&mut self.head);
+        let inflate = self.inflate.insert(Inflate::new());
+        inflate_feed(inflate, &head[skip..], out, budget)
+    }
+}
+
+fn inflate_feed(
+    inflate: &mut Inflate,
+    data: &[u8],
+    out: &mut Vec<u8>,
+    budget: usize,
+) -> Result<(), DecodeError> {
+    // 结束之后是gzip trailer或者垃圾数据，忽略
+    if inflate.is_done() {
+        return Ok(());
+    }
+    // 多解出一个字节用来判断是否超出budget
+    let start = out.len();
+    let max = start.saturating_add(budget).saturating_add(1);
+    inflate
+        .inflate_max(data, out, max)
+        .map_err(|_| DecodeError::Corrupt)?;
+    if out.len() - start > budget {
+        return Err(DecodeError::Limit);
+    }
+    Ok(())
+}
+
+fn brotli_feed(
+    brotli: &mut Brotli,
+    data: &[u8],
+    out: &mut Vec<u8>,
+    budget: usize,
+) -> Result<(), DecodeError> {
+    if BrotliDecoderIsFinished(brotli) {
+        return Ok(());
+    }
+    let mut avail_in = data.len();
+    let mut in_off = 0;
+    let mut chunk = [0u8; BROTLI_CHUNK];
+    let mut total = 0;
+    let mut produced = 0;
+    loop {
+        let mut avail_out = chunk.len();
+        let mut out_off = 0;
+        let ret = BrotliDecompressStream(
+            &mut avail_in,
+            &mut in_off,
+            data,
+            &mut avail_out,
+            &mut out_off,
+            &mut chunk,
+            &mut total,
+            brotli,
+        );
+        produced += out_off;
+        if produced > budget {
+            return Err(DecodeError::Limit);
+        }
+        out.extend_from_slice(&chunk[..out_off]);
+        match ret {
+            BrotliResult::NeedsMoreOutput => {}
+            BrotliResult::NeedsMoreInput | BrotliResult::ResultSuccess => return Ok(()),
+            BrotliResult::ResultFailure => return Err(DecodeError::Corrupt),
+        }
+    }
+}
+
+// RFC 1952。返回header长度，数据不够返回None
+fn gzip_header(buf: &[u8]) -> Result<Option<usize>, DecodeError> {
+    const FHCRC: u8 = 0x02;
+    const FEXTRA: u8 = 0x04;
+    const FNAME: u8 = 0x08;
+    const FCOMMENT: u8 = 0x10;
+
+    if buf.len() < 10 {
+        return Ok(None);
+    }
+    if buf[0] != 0x1f || buf[1] != 0x8b || buf[2] != 8 {
+        return Err(DecodeError::Corrupt);
+    }
+    let flags = buf[3];
+    let mut pos = 10;
+    if flags & FEXTRA != 0 {
+        if buf.len() < pos + 2 {
+            return Ok(None);
+        }
+        pos += 2 + u16::from_le_bytes([buf[pos], buf[pos + 1]]) as usize;
+    }
+    for flag in [FNAME, FCOMMENT] {
+        if flags & flag != 0 {
+            match buf.get(pos..).and_then(|rest| memchr::memchr(0, rest)) {
+                Some(end) => pos += end + 1,
+                None => return Ok(None),
+            }
+        }
+    }
+    if flags & FHCRC != 0 {
+        pos += 2;
+    }
+    if buf.len() < pos {
+        return Ok(None);
+    }
+    Ok(Some(pos))
+}
+
+// http的deflate应该是zlib格式(RFC 1950)，但有些实现直接发raw deflate
+fn zlib_header(buf: &[u8]) -> Result<Option<usize>, DecodeError> {
+    if buf.len() < 2 {
+        return Ok(None);
+    }
+    let cmf = buf[0];
+    let flg = buf[1];
+    if cmf & 0x0f != 8 || !(cmf as u16 * 256 + flg as u16).is_multiple_of(31) {
+        return Ok(Some(0));
+    }
+    // 不支持preset dictionary
+    if flg & 0x20 != 0 {
+        return Err(DecodeError::Corrupt);
+    }
+    Ok(Some(2))
+}
+
+pub(crate) struct BodyDecoder {
+    stages: Vec<Stage>,
+    limit: DecodeLimit,
+    in_size: usize,
+    out_size: usize,
+}
+
+impl BodyDecoder {
+    // 编码按施加顺序排列：先content-coding，再transfer-coding。解码时反过来
+    pub(crate) fn new(
+        ce: &Option<Vec<Encoding>>,
+        te: &Option<Vec<Encoding>>,
+        limit: DecodeLimit,
+    ) -> Result<Self, DecodeError> {
+        let codings = ce.iter().flatten().chain(te.iter().flatten());
+        let mut stages = Vec::new();
+        for coding in codings.rev() {
+            match coding {
+                Encoding::Gzip => stages.push(Stage::new(Kind::Gzip)),
+                Encoding::Deflate => stages.push(Stage::new(Kind::Deflate)),
+                Encoding::Br => stages.push(Stage::new(Kind::Brotli)),
+                Encoding::Identity | Encoding::Chunked => {}
+                Encoding::Compress | Encoding::Lzma => {
+                    return Err(DecodeError::Unsupported);
+                }
+            }
+        }
+        Ok(Self {
+            stages,
+            limit,
+            in_size: 0,
+            out_size: 0,
+        })
+    }
+
+    pub(crate) fn decode(&mut self, data: &[u8], out: &mut Vec<u8>) -> Result<(), DecodeError> {
+        self.in_size += data.len();
+
+        // 每一级的输出都受限制，中间结果不会无限膨胀
+        let mut buf = data.to_vec();
+        for stage in &mut self.stages {
+            let budget = self.limit.budget(self.in_size, stage.out_size);
+            let mut next = Vec::new();
+            stage.feed(&buf, &mut next, budget)?;
+            buf = next;
+        }
+
+        if buf.len() > self.limit.budget(self.in_size, self.out_size) {
+            return Err(DecodeError::Limit);
+        }
+        self.out_size += buf.len();
+        out.extend_from_slice(&buf);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NO_LIMIT: DecodeLimit = DecodeLimit {
+        max_size: 0,
+        max_ratio: 0,
+    };
+
+    const GZIP: &[u8] = &[
+        0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x03, 0xcb, 0x48, 0xcd, 0xc9, 0xc9,
+        0x57, 0x48, 0xaf, 0xca, 0x2c, 0x50, 0x48, 0xca, 0x4f, 0xa9, 0xd4, 0x51, 0xc8, 0x20, 0x20,
+        0xc0, 0x05, 0x00, 0xb0, 0x06, 0xc5, 0x66, 0x32, 0x00, 0x00, 0x00,
+    ];
+    const GZIP_TEXT: &[u8] = b"hello gzip body, hello gzip body, hello gzip body\n";
+
+    fn decode_all(
+        ce: Option<Vec<Encoding>>,
+        te: Option<Vec<Encoding>>,
+        chunks: &[&[u8]],
+    ) -> Result<Vec<u8>, DecodeError> {
+        let mut dec = BodyDecoder::new(&ce, &te, NO_LIMIT)?;
+        let mut out = Vec::new();
+        for chunk in chunks {
+            dec.decode(chunk, &mut out)?;
+        }
+        Ok(out)
+    }
+
+    #[test]
+    fn test_decode_gzip() {
+        let out = decode_all(Some(vec![Encoding::Gzip]), None, &[GZIP]).unwrap();
+        assert_eq!(out, GZIP_TEXT);
+
+        // header被切开
+        let chunks: Vec<&[u8]> = GZIP.chunks(3).collect();
+        let out = decode_all(Some(vec![Encoding::Gzip]), None, &chunks).unwrap();
+        assert_eq!(out, GZIP_TEXT);
+
+        // FNAME
+        let named = [
+            0x1f, 0x8b, 0x08, 0x08, 0x00, 0x00, 0x00, 0x00, 0x02, 0xff, 0x61, 0x2e, 0x74, 0x78,
+            0x74, 0x00, 0xcb, 0x4b, 0xcc, 0x4d, 0x4d, 0x01, 0x00, 0x87, 0xcc, 0xe0, 0x71, 0x05,
+            0x00, 0x00, 0x00,
+        ];
+        let out = decode_all(
+            Some(vec![Encoding::Gzip]),
+            None,
+            &[&named[..12], &named[12..]],
+        );
+        assert_eq!(out.unwrap(), b"named");
+    }
+
+    #[test]
+    fn test_decode_deflate() {
+        let zlib = [
+            0x78, 0x9c, 0xcb, 0x48, 0xcd, 0xc9, 0xc9, 0x57, 0xa8, 0xca, 0xc9, 0x4c, 0x52, 0x48,
+            0xca, 0x4f, 0xa9, 0x04, 0x00, 0x2d, 0xc9, 0x05, 0xb4,
+        ];
+        let out = decode_all(Some(vec![Encoding::Deflate]), None, &[&zlib]).unwrap();
+        assert_eq!(out, b"hello zlib body");
+
+        let raw = [
+            0xcb, 0x48, 0xcd, 0xc9, 0xc9, 0x57, 0x28, 0x4a, 0x2c, 0x57, 0x48, 0xca, 0x4f, 0xa9,
+            0x04, 0x00,
+        ];
+        let out = decode_all(Some(vec![Encoding::Deflate]), None, &[&raw]).unwrap();
+        assert_eq!(out, b"hello raw body");
+    }
+
+    #[test]
+    fn test_decode_transfer_coding() {
+        // Transfer-Encoding: gzip, chunked。chunked已经由parser处理
+        let out = decode_all(None, Some(vec![Encoding::Gzip, Encoding::Chunked]), &[GZIP]);
+        assert_eq!(out.unwrap(), GZIP_TEXT);
+
+        let out = decode_all(None, Some(vec![Encoding::Chunked]), &[b"plain"]);
+        assert_eq!(out.unwrap(), b"plain");
+    }
+
+    const BROTLI: &[u8] = &[
+        0x1b, 0x37, 0x00, 0xf8, 0x45, 0x77, 0xf8, 0xf7, 0x5c, 0xf3, 0xf7, 0x5e, 0x29, 0x8a, 0x31,
+        0xd2, 0x53, 0x29, 0x52, 0x55, 0x88, 0x50, 0xed, 0x22, 0x85, 0x58, 0x1d, 0x07, 0xa1, 0x7c,
+        0x47, 0x33,
+    ];
+    const BROTLI_TEXT: &[u8] = b"hello brotli body, hello brotli body, hello brotli body\n";
+
+    #[test]
+    fn test_decode_brotli() {
+        let out = decode_all(Some(vec![Encoding::Br]), None, &[BROTLI]).unwrap();
+        assert_eq!(out, BROTLI_TEXT);
+
+        // 逐字节输入
+        let chunks: Vec<&[u8]> = BROTLI.chunks(1).collect();
+        let out = decode_all(Some(vec![Encoding::Br]), None, &chunks).unwrap();
+        assert_eq!(out, BROTLI_TEXT);
+
+        // 结束之后的数据忽略
+        let out = decode_all(Some(vec![Encoding::Br]), None, &[BROTLI, b"trailing"]).unwrap();
+        assert_eq!(out, BROTLI_TEXT);
+
+        // Content-Encoding: br, gzip 先gzip解压再brotli解压
+        let gzip_br = [
+            0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x03, 0x93, 0x36, 0x67, 0xf8,
+            0xe1, 0x5a, 0xfe, 0xe3, 0x7b, 0xcc, 0xe7, 0xef, 0x71, 0x9a, 0x5d, 0x86, 0x97, 0x82,
+            0x35, 0x83, 0x42, 0x3b, 0x02, 0xde, 0x2a, 0xb5, 0x46, 0xc8, 0xb2, 0x2f, 0xac, 0x71,
+            0x37, 0x06, 0x00, 0x6b, 0x70, 0xda, 0x3e, 0x20, 0x00, 0x00, 0x00,
+        ];
+        let out = decode_all(Some(vec![Encoding::Br, Encoding::Gzip]), None, &[&gzip_br]);
+        assert_eq!(out.unwrap(), BROTLI_TEXT);
+
+        assert_eq!(
+            decode_all(
+                Some(vec![Encoding::Br]),
+                None,
+                &[b"\xff\xff\xff\xff not brotli"]
+            ),
+            Err(DecodeError::Corrupt)
+        );
+    }
+
+    #[test]
+    fn test_decode_error() {
+        assert_eq!(
+            BodyDecoder::new(&Some(vec![Encoding::Lzma]), &None, NO_LIMIT).err(),
+            Some(DecodeError::Unsupported)
+        );
+        assert_eq!(
+            decode_all(Some(vec![Encoding::Gzip]), None, &[b"not gzip data"]),
+            Err(DecodeError::Corrupt)
+        );
+    }
+
+    #[test]
+    fn test_decode_limit() {
+        let limit = DecodeLimit {
+            max_size: 10,
+            max_ratio: 0,
+        };
+        let mut dec = BodyDecoder::new(&Some(vec![Encoding::Gzip]), &None, limit).unwrap();
+        let mut out = Vec::new();
+        assert_eq!(dec.decode(GZIP, &mut out), Err(DecodeError::Limit));
+        assert!(out.is_empty());
+
+        // 200000个0压缩成217字节
+        let mut bomb = vec![
+            0x78, 0xda, 0xed, 0xc1, 0x31, 0x01, 0x00, 0x00, 0x00, 0xc2, 0xa0, 0xf5, 0x4f, 0x6d,
+            0x06, 0x7f, 0xa0,
+        ];
+        bomb.extend_from_slice(&[0u8; 193]);
+        bomb.extend_from_slice(&[0x80, 0xd7, 0x00, 0x0d, 0x6d, 0x00, 0x01]);
+        let limit = DecodeLimit {
+            max_size: 0,
+            max_ratio: 100,
+        };
+        let mut dec = BodyDecoder::new(&Some(vec![Encoding::Deflate]), &None, limit).unwrap();
+        let mut out = Vec::new();
+        assert_eq!(dec.decode(&bomb, &mut out), Err(DecodeError::Limit));
+        // 超出之后立即停止，不会把整个chunk都解压出来
+        let stage = &dec.stages[0];
+        assert!(stage.out_size <= RATIO_MIN_OUT + 258);
+
+        // 200000个0的brotli只有14字节
+        let bomb = [
+            0x5b, 0x3f, 0x0d, 0x83, 0x7f, 0x02, 0x20, 0x1e, 0x0b, 0x04, 0xf2, 0x09, 0x06, 0x00,
+        ];
+        let limit = DecodeLimit {
+            max_size: 1000,
+            max_ratio: 0,
+        };
+        let mut dec = BodyDecoder::new(&Some(vec![Encoding::Br]), &None, limit).unwrap();
+        let mut out = Vec::new();
+        assert_eq!(dec.decode(&bomb, &mut out), Err(DecodeError::Limit));
+        assert!(out.is_empty());
+        assert!(dec.stages[0].out_size <= 1000);
+
+        // 中间一级同样受限
+        let mut dec = BodyDecoder::new(
+            &Some(vec![Encoding::Br, Encoding::Gzip]),
+            &None,
+            DecodeLimit {
+                max_size: 16,
+                max_ratio: 0,
+            },
+        )
+        .unwrap();
+        assert_eq!(dec.decode(GZIP, &mut out), Err(DecodeError::Limit));
+        assert!(dec.stages[0].out_size <= GZIP_TEXT.len());
+        assert_eq!(dec.stages[1].out_size, 0);
+    }
+}
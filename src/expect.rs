@@ -0,0 +1,139 @@
+// 控制信令(FTP PORT/PASV、SIP/SDP)预测的后续流。新流的第一个包命中之后由run_task设置对应的parser
+use crate::FtpXferRef;
+use crate::Packet;
+use crate::RtpMediaRef;
+use crate::TransProto;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::net::IpAddr;
+use std::rc::Rc;
+
+const MAX_EXPECT: usize = 2048;
+
+// 命中之后使用的parser和它需要的上下文
+#[derive(Debug, Clone)]
+pub(crate) enum Expected {
+    FtpData(FtpXferRef),
+    Rtp(RtpMediaRef),
+}
+
+// ip/port是等待连接的一端，peer是会连过来的另一端，不知道时为None
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct ExpectKey {
+    pub(crate) proto: TransProto,
+    pub(crate) ip: IpAddr,
+    pub(crate) port: u16,
+    pub(crate) peer: Option<IpAddr>,
+}
+
+impl ExpectKey {
+    fn hit(&self, proto: TransProto, ip: IpAddr, port: u16, peer: IpAddr) -> bool {
+        self.proto == proto
+            && self.ip == ip
+            && self.port == port
+            && self.peer.is_none_or(|p| p == peer)
+    }
+}
+
+pub(crate) type ExpectRef = Rc<RefCell<ExpectTable>>;
+
+#[derive(Default)]
+pub(crate) struct ExpectTable {
+    next_session: u64,
+    table: VecDeque<(ExpectKey, Expected)>,
+}
+
+impl ExpectTable {
+    // FTP控制连接的id，数据连接通过FtpXfer.session和它关联
+    pub(crate) fn session(&mut self) -> u64 {
+        self.next_session += 1;
+        self.next_session
+    }
+
+    // 相同的key只保留最新的
+    pub(crate) fn add(&mut self, key: ExpectKey, value: Expected) {
+        self.table.retain(|(k, _)| *k != key);
+        if self.table.len() >= MAX_EXPECT {
+            self.table.pop_front();
+        }
+        self.table.push_back((key, value));
+    }
+
+    // 一般是流的第一个包匹配目的地址。抓包从中间开始时，第一个包可能是反方向的。
+    // 命中之后这条流两端的其它预测也去掉，比如SDP的offer和answer两端都登记了
+    pub(crate) fn take<T: Packet>(&mut self, pkt: &T) -> Option<Expected> {
+        if self.table.is_empty() {
+            return None;
+        }
+        let proto = pkt.trans_proto();
+        let (sip, sport, dip, dport) = (pkt.sip(), pkt.tu_sport(), pkt.dip(), pkt.tu_dport());
+        let index = self
+            .table
+            .iter()
+            .position(|(k, _)| k.hit(proto, dip, dport, sip))
+            .or_else(|| {
+                self.table
+                    .iter()
+                    .position(|(k, _)| k.hit(proto, sip, sport, dip))
+            })?;
+        let (_, value) = self.table.remove(index)?;
+        self.table
+            .retain(|(k, _)| !k.hit(proto, dip, dport, sip) && !k.hit(proto, sip, sport, dip));
+        Some(value)
+    }
+
+    #[cfg(test)]
+    pub(crate) fn len(&self) -> usize {
+        self.table.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RtpMedia;
+    use crate::test_utils::*;
+
+    fn rtp() -> Expected {
+        Expected::Rtp(Rc::new(RtpMedia::default()))
+    }
+
+    #[test]
+    fn test_expect_take() {
+        let clt: IpAddr = "192.168.1.1".parse().unwrap();
+        let srv: IpAddr = "192.168.1.2".parse().unwrap();
+        let mut table = ExpectTable::default();
+        let key = |proto, ip, port, peer| ExpectKey {
+            proto,
+            ip,
+            port,
+            peer,
+        };
+        table.add(key(TransProto::Udp, srv, 4000, None), rtp());
+        table.add(key(TransProto::Udp, clt, 5000, None), rtp());
+        table.add(key(TransProto::Tcp, srv, 6000, Some(clt)), rtp());
+        table.add(key(TransProto::Tcp, srv, 6000, Some(clt)), rtp());
+        assert_eq!(table.len(), 3);
+
+        // 协议不同不匹配
+        let pkt = build_pkt_payload2(1, b"x", 1234, 4000, false);
+        let _ = pkt.decode();
+        assert!(table.take(&pkt).is_none());
+
+        // 对端地址不同不匹配
+        let pkt =
+            build_pkt_payload_inner(1, b"", [192, 168, 1, 9], [192, 168, 1, 2], 1, 6000, false);
+        let _ = pkt.decode();
+        assert!(table.take(&pkt).is_none());
+        let pkt = build_pkt_payload2(1, b"", 1, 6000, false);
+        let _ = pkt.decode();
+        assert!(table.take(&pkt).is_some());
+        assert_eq!(table.len(), 2);
+
+        // 反方向的包也能命中，两端的预测一起去掉
+        let pkt = build_udp_payload(b"x", 4000, 5000, true);
+        let _ = pkt.decode();
+        assert!(matches!(table.take(&pkt), Some(Expected::Rtp(_))));
+        assert_eq!(table.len(), 0);
+    }
+}
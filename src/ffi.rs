@@ -1,23 +1,84 @@
 extern crate libc;
+use crate::DecodeError;
 use crate::Encoding;
+use crate::EsmtpKey;
+use crate::EsmtpParam;
+use crate::FtpEntry;
+use crate::FtpEntryType;
+use crate::FtpFile;
+use crate::FtpLogin;
+use crate::FtpMode;
+use crate::FtpTransfer;
+use crate::FtpType;
+use crate::FtpXfer;
+use crate::GrpcCall;
+use crate::GrpcMsg;
+use crate::GrpcStatus;
+use crate::HttpAuth;
+use crate::HttpCred;
+use crate::HttpHeaders;
+use crate::HttpPart;
+use crate::HttpRequest;
+use crate::HttpResponse;
+use crate::ImapAddr;
+use crate::ImapFetch;
+use crate::ImapState;
+use crate::ImapStatus;
 use crate::L7Proto;
+use crate::Pop3Cmd;
 use crate::Prolens;
+use crate::RtcpBlock;
+use crate::RtcpReport;
+use crate::RtcpSender;
+use crate::RtpHeader;
+use crate::RtpStats;
+use crate::Sdp;
+use crate::SdpCodec;
+use crate::SdpDir;
+use crate::SdpMedia;
+use crate::SipCall;
+use crate::SipCallEvent;
+use crate::SipMediaLink;
 use crate::Task;
+use crate::TlsHello;
 use crate::TransferEncoding;
+use crate::TunnelKind;
+use crate::WsFrame;
 use crate::packet::Direction;
 use crate::packet::TransProto;
-use crate::parser::dnsudp::{Class, Header, Opcode, OptRR, Qclass, Qtype, RR, Rcode, Rdata, Type};
+use crate::parser::dnsudp::{
+    Class, EdnsOpt, Header, Opcode, OptRR, Qclass, Qtype, RR, Rcode, Rdata, Type,
+};
+use std::cell::Cell;
 use std::cell::RefCell;
 use std::ffi::c_void;
 use std::net::IpAddr;
 
 #[repr(C)]
-#[derive(Copy, Clone)]
+#[derive(Debug, Copy, Clone)]
 pub struct CIpAddr {
     pub ip_type: u8, // 0: Invalid, 1: IPv4, 2: IPv6
     pub octets: [u8; 16],
 }
 
+impl From<Option<IpAddr>> for CIpAddr {
+    fn from(ip: Option<IpAddr>) -> Self {
+        let mut octets = [0; 16];
+        let ip_type = match ip {
+            Some(IpAddr::V4(ipv4)) => {
+                octets[..4].copy_from_slice(&ipv4.octets());
+                1
+            }
+            Some(IpAddr::V6(ipv6)) => {
+                octets.copy_from_slice(&ipv6.octets());
+                2
+            }
+            None => 0,
+        };
+        CIpAddr { ip_type, octets }
+    }
+}
+
 #[repr(C)]
 #[derive(Copy, Clone)]
 pub struct PacketVTable {
@@ -34,6 +95,8 @@ pub struct PacketVTable {
     pub free: extern "C" fn(*mut std::ffi::c_void),
 }
 
+type PacketTimestamp = extern "C" fn(*mut std::ffi::c_void) -> u64;
+
 extern "C" fn missing_trans_proto(_: *mut std::ffi::c_void) -> TransProto {
     panic!("VTABLE not initialized")
 }
@@ -83,6 +146,7 @@ thread_local! {
         payload: missing_ptr,
         free: missing_free,
     });
+    static TIMESTAMP_FN: Cell<Option<PacketTimestamp>> = const { Cell::new(None) };
 }
 
 #[unsafe(no_mangle)]
@@ -92,6 +156,12 @@ pub extern "C" fn protolens_init_vtable(vtable: PacketVTable) {
     });
 }
 
+// 包时间戳不在PacketVTable里，保持vtable的布局不变。NULL表示不知道
+#[unsafe(no_mangle)]
+pub extern "C" fn protolens_set_timestamp_fn(timestamp: Option<PacketTimestamp>) {
+    TIMESTAMP_FN.with(|f| f.set(timestamp));
+}
+
 #[derive(Debug, Eq, Ord, PartialEq, PartialOrd, Clone)]
 pub struct FfiPacket {
     packet_ptr: *mut std::ffi::c_void,
@@ -161,6 +231,13 @@ impl crate::Packet for FfiPacket {
             std::slice::from_raw_parts(ptr, len)
         })
     }
+
+    fn timestamp(&self) -> u64 {
+        match TIMESTAMP_FN.with(Cell::get) {
+            Some(timestamp) => timestamp(self.packet_ptr),
+            None => 0,
+        }
+    }
 }
 
 impl Drop for FfiPacket {
@@ -225,15 +302,22 @@ pub extern "C" fn protolens_task_dbinfo(prolens: *mut FfiProlens, task: *mut Tas
 
 #[repr(C)]
 #[derive(Debug, Copy, Clone, PartialEq)]
+// 和protolens.h的L7Proto一致，新的协议加在最后，已有的值不能变
 pub enum CL7Proto {
     OrdPacket = 0,
-    Smtp,
-    Pop3,
-    Imap,
-    Http,
-    FtpCmd,
-    FtpData,
-    Unknown,
+    Smtp = 1,
+    Pop3 = 2,
+    Imap = 3,
+    Http = 4,
+    FtpCmd = 5,
+    FtpData = 6,
+    Sip = 7,
+    Unknown = 8,
+    DnsUdp = 9,
+    Http2 = 10,
+    SipTcp = 11,
+    Rtp = 12,
+    DnsTcp = 13,
 }
 
 impl From<CL7Proto> for L7Proto {
@@ -246,6 +330,12 @@ impl From<CL7Proto> for L7Proto {
             CL7Proto::Http => L7Proto::Http,
             CL7Proto::FtpCmd => L7Proto::FtpCmd,
             CL7Proto::FtpData => L7Proto::FtpData,
+            CL7Proto::Sip => L7Proto::Sip,
+            CL7Proto::DnsUdp => L7Proto::DnsUdp,
+            CL7Proto::Http2 => L7Proto::Http2,
+            CL7Proto::SipTcp => L7Proto::SipTcp,
+            CL7Proto::Rtp => L7Proto::Rtp,
+            CL7Proto::DnsTcp => L7Proto::DnsTcp,
             CL7Proto::Unknown => L7Proto::Unknown,
         }
     }
@@ -515,6 +605,113 @@ pub extern "C" fn protolens_set_cb_smtp_rcpt(prolens: *mut FfiProlens, callback:
     prolens.0.set_cb_smtp_rcpt(wrapper);
 }
 
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum CEsmtpKey {
+    Size,
+    Body,
+    SmtpUtf8,
+    Notify,
+    Orcpt,
+    Ret,
+    EnvId,
+    Other,
+}
+
+impl From<EsmtpKey> for CEsmtpKey {
+    fn from(key: EsmtpKey) -> Self {
+        match key {
+            EsmtpKey::Size => CEsmtpKey::Size,
+            EsmtpKey::Body => CEsmtpKey::Body,
+            EsmtpKey::SmtpUtf8 => CEsmtpKey::SmtpUtf8,
+            EsmtpKey::Notify => CEsmtpKey::Notify,
+            EsmtpKey::Orcpt => CEsmtpKey::Orcpt,
+            EsmtpKey::Ret => CEsmtpKey::Ret,
+            EsmtpKey::EnvId => CEsmtpKey::EnvId,
+            EsmtpKey::Other => CEsmtpKey::Other,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct CEsmtpParam {
+    pub key: CEsmtpKey,
+    pub name_ptr: *const u8,
+    pub name_len: usize,
+    pub value_ptr: *const u8, // 没有值时为null
+    pub value_len: usize,
+}
+
+impl From<&EsmtpParam<'_>> for CEsmtpParam {
+    fn from(param: &EsmtpParam) -> Self {
+        CEsmtpParam {
+            key: param.key.into(),
+            name_ptr: param.name.as_ptr(),
+            name_len: param.name.len(),
+            value_ptr: param.value.map_or(std::ptr::null(), |v| v.as_ptr()),
+            value_len: param.value.map_or(0, |v| v.len()),
+        }
+    }
+}
+
+type CbSmtpAddr = extern "C" fn(
+    data: *const u8,
+    len: usize,
+    params: *const CEsmtpParam,
+    params_len: usize,
+    seq: u32,
+    ctx: *const c_void,
+);
+
+#[unsafe(no_mangle)]
+pub extern "C" fn protolens_set_cb_smtp_mailfrom_ext(
+    prolens: *mut FfiProlens,
+    callback: Option<CbSmtpAddr>,
+) {
+    if prolens.is_null() || callback.is_none() {
+        return;
+    }
+
+    let prolens = unsafe { &mut *prolens };
+    let wrapper = move |data: &[u8], params: &[EsmtpParam], seq: u32, ctx: *mut c_void| {
+        let c_params: Vec<CEsmtpParam> = params.iter().map(CEsmtpParam::from).collect();
+        callback.unwrap()(
+            data.as_ptr(),
+            data.len(),
+            c_params.as_ptr(),
+            c_params.len(),
+            seq,
+            ctx,
+        );
+    };
+    prolens.0.set_cb_smtp_mailfrom_ext(wrapper);
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn protolens_set_cb_smtp_rcpt_ext(
+    prolens: *mut FfiProlens,
+    callback: Option<CbSmtpAddr>,
+) {
+    if prolens.is_null() || callback.is_none() {
+        return;
+    }
+
+    let prolens = unsafe { &mut *prolens };
+    let wrapper = move |data: &[u8], params: &[EsmtpParam], seq: u32, ctx: *mut c_void| {
+        let c_params: Vec<CEsmtpParam> = params.iter().map(CEsmtpParam::from).collect();
+        callback.unwrap()(
+            data.as_ptr(),
+            data.len(),
+            c_params.as_ptr(),
+            c_params.len(),
+            seq,
+            ctx,
+        );
+    };
+    prolens.0.set_cb_smtp_rcpt_ext(wrapper);
+}
+
 #[unsafe(no_mangle)]
 pub extern "C" fn protolens_set_cb_smtp_header(
     prolens: *mut FfiProlens,
@@ -685,72 +882,155 @@ pub extern "C" fn protolens_set_cb_pop3_srv(prolens: *mut FfiProlens, callback:
 }
 
 #[unsafe(no_mangle)]
-pub extern "C" fn protolens_set_cb_imap_header(
-    prolens: *mut FfiProlens,
-    callback: Option<CbDirData>,
-) {
+pub extern "C" fn protolens_set_cb_pop3_user(prolens: *mut FfiProlens, callback: Option<CbData>) {
     if prolens.is_null() || callback.is_none() {
         return;
     }
 
     let prolens = unsafe { &mut *prolens };
-    let wrapper = move |data: &[u8], seq: u32, ctx: *mut c_void, dir: Direction| {
-        callback.unwrap()(data.as_ptr(), data.len(), seq, ctx, dir);
+    let wrapper = move |data: &[u8], seq: u32, ctx: *mut c_void| {
+        callback.unwrap()(data.as_ptr(), data.len(), seq, ctx);
     };
-    prolens.0.set_cb_imap_header(wrapper);
+    prolens.0.set_cb_pop3_user(wrapper);
 }
 
 #[unsafe(no_mangle)]
-pub extern "C" fn protolens_set_cb_imap_body_start(
+pub extern "C" fn protolens_set_cb_pop3_pass(prolens: *mut FfiProlens, callback: Option<CbData>) {
+    if prolens.is_null() || callback.is_none() {
+        return;
+    }
+
+    let prolens = unsafe { &mut *prolens };
+    let wrapper = move |data: &[u8], seq: u32, ctx: *mut c_void| {
+        callback.unwrap()(data.as_ptr(), data.len(), seq, ctx);
+    };
+    prolens.0.set_cb_pop3_pass(wrapper);
+}
+
+type CbDataPair = extern "C" fn(
+    data1: *const u8,
+    len1: usize,
+    data2: *const u8,
+    len2: usize,
+    seq: u32,
+    ctx: *const c_void,
+);
+
+#[unsafe(no_mangle)]
+pub extern "C" fn protolens_set_cb_pop3_apop(
     prolens: *mut FfiProlens,
-    callback: Option<CbDirEvt>,
+    callback: Option<CbDataPair>,
 ) {
     if prolens.is_null() || callback.is_none() {
         return;
     }
 
     let prolens = unsafe { &mut *prolens };
-    let wrapper = move |ctx: *mut c_void, dir: Direction| {
-        callback.unwrap()(ctx, dir);
+    let wrapper = move |a: &[u8], b: &[u8], seq: u32, ctx: *mut c_void| {
+        callback.unwrap()(a.as_ptr(), a.len(), b.as_ptr(), b.len(), seq, ctx);
     };
-    prolens.0.set_cb_imap_body_start(wrapper);
+    prolens.0.set_cb_pop3_apop(wrapper);
 }
 
 #[unsafe(no_mangle)]
-pub extern "C" fn protolens_set_cb_imap_body(prolens: *mut FfiProlens, callback: Option<CbBody>) {
+pub extern "C" fn protolens_set_cb_pop3_auth(
+    prolens: *mut FfiProlens,
+    callback: Option<CbDataPair>,
+) {
     if prolens.is_null() || callback.is_none() {
         return;
     }
 
     let prolens = unsafe { &mut *prolens };
-    let wrapper = move |data: &[u8],
-                        seq: u32,
-                        ctx: *mut c_void,
-                        dir: Direction,
-                        te: Option<TransferEncoding>| {
-        callback.unwrap()(data.as_ptr(), data.len(), seq, ctx, dir, te.into());
+    let wrapper = move |a: &[u8], b: &[u8], seq: u32, ctx: *mut c_void| {
+        callback.unwrap()(a.as_ptr(), a.len(), b.as_ptr(), b.len(), seq, ctx);
     };
-    prolens.0.set_cb_imap_body(wrapper);
+    prolens.0.set_cb_pop3_auth(wrapper);
 }
 
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum CPop3Cmd {
+    User,
+    Pass,
+    Apop,
+    Auth,
+    Capa,
+    Stat,
+    List,
+    Uidl,
+    Retr,
+    Top,
+    Dele,
+    Noop,
+    Rset,
+    Quit,
+    Stls,
+    Other,
+}
+
+impl From<Pop3Cmd> for CPop3Cmd {
+    fn from(cmd: Pop3Cmd) -> Self {
+        match cmd {
+            Pop3Cmd::User => CPop3Cmd::User,
+            Pop3Cmd::Pass => CPop3Cmd::Pass,
+            Pop3Cmd::Apop => CPop3Cmd::Apop,
+            Pop3Cmd::Auth => CPop3Cmd::Auth,
+            Pop3Cmd::Capa => CPop3Cmd::Capa,
+            Pop3Cmd::Stat => CPop3Cmd::Stat,
+            Pop3Cmd::List => CPop3Cmd::List,
+            Pop3Cmd::Uidl => CPop3Cmd::Uidl,
+            Pop3Cmd::Retr => CPop3Cmd::Retr,
+            Pop3Cmd::Top => CPop3Cmd::Top,
+            Pop3Cmd::Dele => CPop3Cmd::Dele,
+            Pop3Cmd::Noop => CPop3Cmd::Noop,
+            Pop3Cmd::Rset => CPop3Cmd::Rset,
+            Pop3Cmd::Quit => CPop3Cmd::Quit,
+            Pop3Cmd::Stls => CPop3Cmd::Stls,
+            Pop3Cmd::Other => CPop3Cmd::Other,
+        }
+    }
+}
+
+type CbPop3Reply = extern "C" fn(
+    cmd: CPop3Cmd,
+    arg: *const u8,
+    arg_len: usize,
+    ok: bool,
+    text: *const u8,
+    text_len: usize,
+    seq: u32,
+    ctx: *const c_void,
+);
+
 #[unsafe(no_mangle)]
-pub extern "C" fn protolens_set_cb_imap_body_stop(
+pub extern "C" fn protolens_set_cb_pop3_reply(
     prolens: *mut FfiProlens,
-    callback: Option<CbDirEvt>,
+    callback: Option<CbPop3Reply>,
 ) {
     if prolens.is_null() || callback.is_none() {
         return;
     }
 
     let prolens = unsafe { &mut *prolens };
-    let wrapper = move |ctx: *mut c_void, dir: Direction| {
-        callback.unwrap()(ctx, dir);
-    };
-    prolens.0.set_cb_imap_body_stop(wrapper);
+    let wrapper =
+        move |cmd: Pop3Cmd, arg: &[u8], ok: bool, text: &[u8], seq: u32, ctx: *mut c_void| {
+            callback.unwrap()(
+                cmd.into(),
+                arg.as_ptr(),
+                arg.len(),
+                ok,
+                text.as_ptr(),
+                text.len(),
+                seq,
+                ctx,
+            );
+        };
+    prolens.0.set_cb_pop3_reply(wrapper);
 }
 
 #[unsafe(no_mangle)]
-pub extern "C" fn protolens_set_cb_imap_clt(prolens: *mut FfiProlens, callback: Option<CbData>) {
+pub extern "C" fn protolens_set_cb_pop3_capa(prolens: *mut FfiProlens, callback: Option<CbData>) {
     if prolens.is_null() || callback.is_none() {
         return;
     }
@@ -759,40 +1039,86 @@ pub extern "C" fn protolens_set_cb_imap_clt(prolens: *mut FfiProlens, callback:
     let wrapper = move |data: &[u8], seq: u32, ctx: *mut c_void| {
         callback.unwrap()(data.as_ptr(), data.len(), seq, ctx);
     };
-    prolens.0.set_cb_imap_clt(wrapper);
+    prolens.0.set_cb_pop3_capa(wrapper);
 }
 
+type CbPop3Size = extern "C" fn(num: u32, size: u64, ctx: *const c_void);
+
 #[unsafe(no_mangle)]
-pub extern "C" fn protolens_set_cb_imap_srv(prolens: *mut FfiProlens, callback: Option<CbData>) {
+pub extern "C" fn protolens_set_cb_pop3_stat(
+    prolens: *mut FfiProlens,
+    callback: Option<CbPop3Size>,
+) {
     if prolens.is_null() || callback.is_none() {
         return;
     }
 
     let prolens = unsafe { &mut *prolens };
-    let wrapper = move |data: &[u8], seq: u32, ctx: *mut c_void| {
-        callback.unwrap()(data.as_ptr(), data.len(), seq, ctx);
+    let wrapper = move |num: u32, size: u64, ctx: *mut c_void| {
+        callback.unwrap()(num, size, ctx);
     };
-    prolens.0.set_cb_imap_srv(wrapper);
+    prolens.0.set_cb_pop3_stat(wrapper);
 }
 
 #[unsafe(no_mangle)]
-pub extern "C" fn protolens_set_cb_http_start_line(
+pub extern "C" fn protolens_set_cb_pop3_list(
     prolens: *mut FfiProlens,
-    callback: Option<CbDirData>,
+    callback: Option<CbPop3Size>,
 ) {
     if prolens.is_null() || callback.is_none() {
         return;
     }
 
     let prolens = unsafe { &mut *prolens };
-    let wrapper = move |data: &[u8], seq: u32, ctx: *mut c_void, dir: Direction| {
-        callback.unwrap()(data.as_ptr(), data.len(), seq, ctx, dir);
+    let wrapper = move |num: u32, size: u64, ctx: *mut c_void| {
+        callback.unwrap()(num, size, ctx);
     };
-    prolens.0.set_cb_http_start_line(wrapper);
+    prolens.0.set_cb_pop3_list(wrapper);
 }
 
+type CbPop3Uidl = extern "C" fn(num: u32, uid: *const u8, uid_len: usize, ctx: *const c_void);
+
 #[unsafe(no_mangle)]
-pub extern "C" fn protolens_set_cb_http_header(
+pub extern "C" fn protolens_set_cb_pop3_uidl(
+    prolens: *mut FfiProlens,
+    callback: Option<CbPop3Uidl>,
+) {
+    if prolens.is_null() || callback.is_none() {
+        return;
+    }
+
+    let prolens = unsafe { &mut *prolens };
+    let wrapper = move |num: u32, uid: &[u8], ctx: *mut c_void| {
+        callback.unwrap()(num, uid.as_ptr(), uid.len(), ctx);
+    };
+    prolens.0.set_cb_pop3_uidl(wrapper);
+}
+
+// 没有uidl时uid为null, partial表示TOP取回的部分邮件
+type CbPop3Msg =
+    extern "C" fn(num: u32, uid: *const u8, uid_len: usize, partial: bool, ctx: *const c_void);
+
+#[unsafe(no_mangle)]
+pub extern "C" fn protolens_set_cb_pop3_msg(prolens: *mut FfiProlens, callback: Option<CbPop3Msg>) {
+    if prolens.is_null() || callback.is_none() {
+        return;
+    }
+
+    let prolens = unsafe { &mut *prolens };
+    let wrapper = move |num: u32, uid: Option<&[u8]>, partial: bool, ctx: *mut c_void| {
+        callback.unwrap()(
+            num,
+            uid.map_or(std::ptr::null(), |u| u.as_ptr()),
+            uid.map_or(0, |u| u.len()),
+            partial,
+            ctx,
+        );
+    };
+    prolens.0.set_cb_pop3_msg(wrapper);
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn protolens_set_cb_imap_header(
     prolens: *mut FfiProlens,
     callback: Option<CbDirData>,
 ) {
@@ -804,11 +1130,11 @@ pub extern "C" fn protolens_set_cb_http_header(
     let wrapper = move |data: &[u8], seq: u32, ctx: *mut c_void, dir: Direction| {
         callback.unwrap()(data.as_ptr(), data.len(), seq, ctx, dir);
     };
-    prolens.0.set_cb_http_header(wrapper);
+    prolens.0.set_cb_imap_header(wrapper);
 }
 
 #[unsafe(no_mangle)]
-pub extern "C" fn protolens_set_cb_http_body_start(
+pub extern "C" fn protolens_set_cb_imap_body_start(
     prolens: *mut FfiProlens,
     callback: Option<CbDirEvt>,
 ) {
@@ -820,14 +1146,11 @@ pub extern "C" fn protolens_set_cb_http_body_start(
     let wrapper = move |ctx: *mut c_void, dir: Direction| {
         callback.unwrap()(ctx, dir);
     };
-    prolens.0.set_cb_http_body_start(wrapper);
+    prolens.0.set_cb_imap_body_start(wrapper);
 }
 
 #[unsafe(no_mangle)]
-pub extern "C" fn protolens_set_cb_http_body(
-    prolens: *mut FfiProlens,
-    callback: Option<CbHttpBody>,
-) {
+pub extern "C" fn protolens_set_cb_imap_body(prolens: *mut FfiProlens, callback: Option<CbBody>) {
     if prolens.is_null() || callback.is_none() {
         return;
     }
@@ -837,23 +1160,14 @@ pub extern "C" fn protolens_set_cb_http_body(
                         seq: u32,
                         ctx: *mut c_void,
                         dir: Direction,
-                        ce: &Option<Vec<Encoding>>,
-                        te: &Option<Vec<Encoding>>| {
-        callback.unwrap()(
-            data.as_ptr(),
-            data.len(),
-            seq,
-            ctx,
-            dir,
-            CEncodingArray::from(ce.clone()),
-            CEncodingArray::from(te.clone()),
-        );
+                        te: Option<TransferEncoding>| {
+        callback.unwrap()(data.as_ptr(), data.len(), seq, ctx, dir, te.into());
     };
-    prolens.0.set_cb_http_body(wrapper);
+    prolens.0.set_cb_imap_body(wrapper);
 }
 
 #[unsafe(no_mangle)]
-pub extern "C" fn protolens_set_cb_http_body_stop(
+pub extern "C" fn protolens_set_cb_imap_body_stop(
     prolens: *mut FfiProlens,
     callback: Option<CbDirEvt>,
 ) {
@@ -865,11 +1179,11 @@ pub extern "C" fn protolens_set_cb_http_body_stop(
     let wrapper = move |ctx: *mut c_void, dir: Direction| {
         callback.unwrap()(ctx, dir);
     };
-    prolens.0.set_cb_http_body_stop(wrapper);
+    prolens.0.set_cb_imap_body_stop(wrapper);
 }
 
 #[unsafe(no_mangle)]
-pub extern "C" fn protolens_set_cb_ftp_clt(prolens: *mut FfiProlens, callback: Option<CbData>) {
+pub extern "C" fn protolens_set_cb_imap_clt(prolens: *mut FfiProlens, callback: Option<CbData>) {
     if prolens.is_null() || callback.is_none() {
         return;
     }
@@ -878,11 +1192,11 @@ pub extern "C" fn protolens_set_cb_ftp_clt(prolens: *mut FfiProlens, callback: O
     let wrapper = move |data: &[u8], seq: u32, ctx: *mut c_void| {
         callback.unwrap()(data.as_ptr(), data.len(), seq, ctx);
     };
-    prolens.0.set_cb_ftp_clt(wrapper);
+    prolens.0.set_cb_imap_clt(wrapper);
 }
 
 #[unsafe(no_mangle)]
-pub extern "C" fn protolens_set_cb_ftp_srv(prolens: *mut FfiProlens, callback: Option<CbData>) {
+pub extern "C" fn protolens_set_cb_imap_srv(prolens: *mut FfiProlens, callback: Option<CbData>) {
     if prolens.is_null() || callback.is_none() {
         return;
     }
@@ -891,86 +1205,1708 @@ pub extern "C" fn protolens_set_cb_ftp_srv(prolens: *mut FfiProlens, callback: O
     let wrapper = move |data: &[u8], seq: u32, ctx: *mut c_void| {
         callback.unwrap()(data.as_ptr(), data.len(), seq, ctx);
     };
-    prolens.0.set_cb_ftp_srv(wrapper);
+    prolens.0.set_cb_imap_srv(wrapper);
 }
 
 #[unsafe(no_mangle)]
-pub extern "C" fn protolens_set_cb_ftp_link(prolens: *mut FfiProlens, callback: Option<CbFtpLink>) {
+pub extern "C" fn protolens_set_cb_imap_user(prolens: *mut FfiProlens, callback: Option<CbData>) {
     if prolens.is_null() || callback.is_none() {
         return;
     }
 
     let prolens = unsafe { &mut *prolens };
-    let wrapper = move |ip: Option<IpAddr>, port: u16, ctx: *mut c_void, dir: Direction| {
-        let mut ip_bytes: [u8; 16] = [0; 16];
-        let mut ip_len: usize = 0;
-        let mut ip_type: u8 = 0;
-
-        if let Some(addr) = ip {
-            match addr {
-                IpAddr::V4(ipv4) => {
-                    let octets = ipv4.octets();
-                    ip_bytes[..4].copy_from_slice(&octets);
-                    ip_len = 4;
-                    ip_type = 1;
-                }
-                IpAddr::V6(ipv6) => {
-                    let octets = ipv6.octets();
-                    ip_bytes.copy_from_slice(&octets);
-                    ip_len = 16;
-                    ip_type = 2;
-                }
-            }
-        }
-
-        callback.unwrap()(ip_bytes.as_ptr(), ip_len, ip_type, port, ctx, dir);
+    let wrapper = move |data: &[u8], seq: u32, ctx: *mut c_void| {
+        callback.unwrap()(data.as_ptr(), data.len(), seq, ctx);
     };
-    prolens.0.set_cb_ftp_link(wrapper);
+    prolens.0.set_cb_imap_user(wrapper);
 }
 
 #[unsafe(no_mangle)]
-pub extern "C" fn protolens_set_cb_ftp_body_start(
-    prolens: *mut FfiProlens,
-    callback: Option<CbDirEvt>,
-) {
+pub extern "C" fn protolens_set_cb_imap_pass(prolens: *mut FfiProlens, callback: Option<CbData>) {
     if prolens.is_null() || callback.is_none() {
         return;
     }
 
     let prolens = unsafe { &mut *prolens };
-    let wrapper = move |ctx: *mut c_void, dir: Direction| {
-        callback.unwrap()(ctx, dir);
+    let wrapper = move |data: &[u8], seq: u32, ctx: *mut c_void| {
+        callback.unwrap()(data.as_ptr(), data.len(), seq, ctx);
     };
-    prolens.0.set_cb_ftp_body_start(wrapper);
+    prolens.0.set_cb_imap_pass(wrapper);
 }
 
 #[unsafe(no_mangle)]
-pub extern "C" fn protolens_set_cb_ftp_body(prolens: *mut FfiProlens, callback: Option<CbDirData>) {
+pub extern "C" fn protolens_set_cb_imap_auth(
+    prolens: *mut FfiProlens,
+    callback: Option<CbDataPair>,
+) {
     if prolens.is_null() || callback.is_none() {
         return;
     }
 
     let prolens = unsafe { &mut *prolens };
-    let wrapper = move |data: &[u8], seq: u32, ctx: *mut c_void, dir: Direction| {
-        callback.unwrap()(data.as_ptr(), data.len(), seq, ctx, dir);
+    let wrapper = move |a: &[u8], b: &[u8], seq: u32, ctx: *mut c_void| {
+        callback.unwrap()(a.as_ptr(), a.len(), b.as_ptr(), b.len(), seq, ctx);
     };
-    prolens.0.set_cb_ftp_body(wrapper);
+    prolens.0.set_cb_imap_auth(wrapper);
 }
 
-#[unsafe(no_mangle)]
-pub extern "C" fn protolens_set_cb_ftp_body_stop(
-    prolens: *mut FfiProlens,
-    callback: Option<CbDirEvt>,
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum CImapStatus {
+    Ok,
+    No,
+    Bad,
+}
+
+impl From<ImapStatus> for CImapStatus {
+    fn from(status: ImapStatus) -> Self {
+        match status {
+            ImapStatus::Ok => CImapStatus::Ok,
+            ImapStatus::No => CImapStatus::No,
+            ImapStatus::Bad => CImapStatus::Bad,
+        }
+    }
+}
+
+type CbImapReply = extern "C" fn(
+    tag: *const u8,
+    tag_len: usize,
+    cmd: *const u8,
+    cmd_len: usize,
+    status: CImapStatus,
+    text: *const u8,
+    text_len: usize,
+    seq: u32,
+    ctx: *const c_void,
+);
+
+#[unsafe(no_mangle)]
+pub extern "C" fn protolens_set_cb_imap_reply(
+    prolens: *mut FfiProlens,
+    callback: Option<CbImapReply>,
+) {
+    if prolens.is_null() || callback.is_none() {
+        return;
+    }
+
+    let prolens = unsafe { &mut *prolens };
+    let wrapper = move |tag: &[u8],
+                        cmd: &[u8],
+                        status: ImapStatus,
+                        text: &[u8],
+                        seq: u32,
+                        ctx: *mut c_void| {
+        callback.unwrap()(
+            tag.as_ptr(),
+            tag.len(),
+            cmd.as_ptr(),
+            cmd.len(),
+            status.into(),
+            text.as_ptr(),
+            text.len(),
+            seq,
+            ctx,
+        );
+    };
+    prolens.0.set_cb_imap_reply(wrapper);
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum CImapState {
+    NotAuth,
+    Auth,
+    Selected,
+    Logout,
+}
+
+impl From<ImapState> for CImapState {
+    fn from(state: ImapState) -> Self {
+        match state {
+            ImapState::NotAuth => CImapState::NotAuth,
+            ImapState::Auth => CImapState::Auth,
+            ImapState::Selected => CImapState::Selected,
+            ImapState::Logout => CImapState::Logout,
+        }
+    }
+}
+
+// 没有选中邮箱时mailbox为null
+type CbImapState = extern "C" fn(
+    state: CImapState,
+    mailbox: *const u8,
+    mailbox_len: usize,
+    read_only: bool,
+    ctx: *const c_void,
+);
+
+#[unsafe(no_mangle)]
+pub extern "C" fn protolens_set_cb_imap_state(
+    prolens: *mut FfiProlens,
+    callback: Option<CbImapState>,
+) {
+    if prolens.is_null() || callback.is_none() {
+        return;
+    }
+
+    let prolens = unsafe { &mut *prolens };
+    let wrapper =
+        move |state: ImapState, mailbox: Option<&[u8]>, read_only: bool, ctx: *mut c_void| {
+            callback.unwrap()(
+                state.into(),
+                mailbox.map_or(std::ptr::null(), |m| m.as_ptr()),
+                mailbox.map_or(0, |m| m.len()),
+                read_only,
+                ctx,
+            );
+        };
+    prolens.0.set_cb_imap_state(wrapper);
+}
+
+// 没有选中邮箱时mailbox为null，uid未知时为0
+type CbImapBodyInfo = extern "C" fn(
+    mailbox: *const u8,
+    mailbox_len: usize,
+    num: u32,
+    uid: u32,
+    section: *const u8,
+    section_len: usize,
+    ctx: *const c_void,
+);
+
+#[unsafe(no_mangle)]
+pub extern "C" fn protolens_set_cb_imap_body_info(
+    prolens: *mut FfiProlens,
+    callback: Option<CbImapBodyInfo>,
+) {
+    if prolens.is_null() || callback.is_none() {
+        return;
+    }
+
+    let prolens = unsafe { &mut *prolens };
+    let wrapper = move |mailbox: Option<&[u8]>,
+                        num: u32,
+                        uid: Option<u32>,
+                        section: &[u8],
+                        ctx: *mut c_void| {
+        callback.unwrap()(
+            mailbox.map_or(std::ptr::null(), |m| m.as_ptr()),
+            mailbox.map_or(0, |m| m.len()),
+            num,
+            uid.unwrap_or(0),
+            section.as_ptr(),
+            section.len(),
+            ctx,
+        );
+    };
+    prolens.0.set_cb_imap_body_info(wrapper);
+}
+
+fn opt_ptr(data: Option<&[u8]>) -> (*const u8, usize) {
+    data.map_or((std::ptr::null(), 0), |d| (d.as_ptr(), d.len()))
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct CImapStr {
+    pub ptr: *const u8,
+    pub len: usize,
+}
+
+// 各字段为NIL时指针为null
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct CImapAddr {
+    pub name_ptr: *const u8,
+    pub name_len: usize,
+    pub mailbox_ptr: *const u8,
+    pub mailbox_len: usize,
+    pub host_ptr: *const u8,
+    pub host_len: usize,
+}
+
+impl From<&ImapAddr<'_>> for CImapAddr {
+    fn from(addr: &ImapAddr) -> Self {
+        let (name_ptr, name_len) = opt_ptr(addr.name);
+        let (mailbox_ptr, mailbox_len) = opt_ptr(addr.mailbox);
+        let (host_ptr, host_len) = opt_ptr(addr.host);
+        CImapAddr {
+            name_ptr,
+            name_len,
+            mailbox_ptr,
+            mailbox_len,
+            host_ptr,
+            host_len,
+        }
+    }
+}
+
+// 没有的字段指针为null，uid为0表示没有uid
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct CImapFetch {
+    pub mailbox_ptr: *const u8,
+    pub mailbox_len: usize,
+    pub num: u32,
+    pub uid: u32,
+    pub has_size: bool,
+    pub size: u32,
+    pub flags_ptr: *const CImapStr,
+    pub flags_len: usize,
+    pub internal_date_ptr: *const u8,
+    pub internal_date_len: usize,
+    pub has_envelope: bool,
+    pub date_ptr: *const u8,
+    pub date_len: usize,
+    pub subject_ptr: *const u8,
+    pub subject_len: usize,
+    pub from_ptr: *const CImapAddr,
+    pub from_len: usize,
+    pub sender_ptr: *const CImapAddr,
+    pub sender_len: usize,
+    pub reply_to_ptr: *const CImapAddr,
+    pub reply_to_len: usize,
+    pub to_ptr: *const CImapAddr,
+    pub to_len: usize,
+    pub cc_ptr: *const CImapAddr,
+    pub cc_len: usize,
+    pub bcc_ptr: *const CImapAddr,
+    pub bcc_len: usize,
+    pub in_reply_to_ptr: *const u8,
+    pub in_reply_to_len: usize,
+    pub message_id_ptr: *const u8,
+    pub message_id_len: usize,
+}
+
+type CbImapFetch = extern "C" fn(fetch: *const CImapFetch, ctx: *const c_void);
+
+#[unsafe(no_mangle)]
+pub extern "C" fn protolens_set_cb_imap_fetch(
+    prolens: *mut FfiProlens,
+    callback: Option<CbImapFetch>,
+) {
+    if prolens.is_null() || callback.is_none() {
+        return;
+    }
+
+    let prolens = unsafe { &mut *prolens };
+    let wrapper = move |fetch: &ImapFetch, ctx: *mut c_void| {
+        let addrs = |addrs: Option<&Vec<ImapAddr>>| -> Vec<CImapAddr> {
+            addrs.into_iter().flatten().map(CImapAddr::from).collect()
+        };
+        let env = fetch.envelope.as_ref();
+        let flags: Vec<CImapStr> = fetch
+            .flags
+            .iter()
+            .map(|flag| CImapStr {
+                ptr: flag.as_ptr(),
+                len: flag.len(),
+            })
+            .collect();
+        let from = addrs(env.map(|e| &e.from));
+        let sender = addrs(env.map(|e| &e.sender));
+        let reply_to = addrs(env.map(|e| &e.reply_to));
+        let to = addrs(env.map(|e| &e.to));
+        let cc = addrs(env.map(|e| &e.cc));
+        let bcc = addrs(env.map(|e| &e.bcc));
+
+        let (mailbox_ptr, mailbox_len) = opt_ptr(fetch.mailbox);
+        let (internal_date_ptr, internal_date_len) =
+            opt_ptr(fetch.internal_date.map(|d| d.as_bytes()));
+        let (date_ptr, date_len) = opt_ptr(env.and_then(|e| e.date));
+        let (subject_ptr, subject_len) = opt_ptr(env.and_then(|e| e.subject));
+        let (in_reply_to_ptr, in_reply_to_len) = opt_ptr(env.and_then(|e| e.in_reply_to));
+        let (message_id_ptr, message_id_len) = opt_ptr(env.and_then(|e| e.message_id));
+
+        let c_fetch = CImapFetch {
+            mailbox_ptr,
+            mailbox_len,
+            num: fetch.num,
+            uid: fetch.uid.unwrap_or(0),
+            has_size: fetch.size.is_some(),
+            size: fetch.size.unwrap_or(0),
+            flags_ptr: flags.as_ptr(),
+            flags_len: flags.len(),
+            internal_date_ptr,
+            internal_date_len,
+            has_envelope: env.is_some(),
+            date_ptr,
+            date_len,
+            subject_ptr,
+            subject_len,
+            from_ptr: from.as_ptr(),
+            from_len: from.len(),
+            sender_ptr: sender.as_ptr(),
+            sender_len: sender.len(),
+            reply_to_ptr: reply_to.as_ptr(),
+            reply_to_len: reply_to.len(),
+            to_ptr: to.as_ptr(),
+            to_len: to.len(),
+            cc_ptr: cc.as_ptr(),
+            cc_len: cc.len(),
+            bcc_ptr: bcc.as_ptr(),
+            bcc_len: bcc.len(),
+            in_reply_to_ptr,
+            in_reply_to_len,
+            message_id_ptr,
+            message_id_len,
+        };
+        callback.unwrap()(&c_fetch, ctx);
+    };
+    prolens.0.set_cb_imap_fetch(wrapper);
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn protolens_set_cb_http_start_line(
+    prolens: *mut FfiProlens,
+    callback: Option<CbDirData>,
+) {
+    if prolens.is_null() || callback.is_none() {
+        return;
+    }
+
+    let prolens = unsafe { &mut *prolens };
+    let wrapper = move |data: &[u8], seq: u32, ctx: *mut c_void, dir: Direction| {
+        callback.unwrap()(data.as_ptr(), data.len(), seq, ctx, dir);
+    };
+    prolens.0.set_cb_http_start_line(wrapper);
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn protolens_set_cb_http_header(
+    prolens: *mut FfiProlens,
+    callback: Option<CbDirData>,
+) {
+    if prolens.is_null() || callback.is_none() {
+        return;
+    }
+
+    let prolens = unsafe { &mut *prolens };
+    let wrapper = move |data: &[u8], seq: u32, ctx: *mut c_void, dir: Direction| {
+        callback.unwrap()(data.as_ptr(), data.len(), seq, ctx, dir);
+    };
+    prolens.0.set_cb_http_header(wrapper);
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn protolens_set_cb_http_body_start(
+    prolens: *mut FfiProlens,
+    callback: Option<CbDirEvt>,
+) {
+    if prolens.is_null() || callback.is_none() {
+        return;
+    }
+
+    let prolens = unsafe { &mut *prolens };
+    let wrapper = move |ctx: *mut c_void, dir: Direction| {
+        callback.unwrap()(ctx, dir);
+    };
+    prolens.0.set_cb_http_body_start(wrapper);
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn protolens_set_cb_http_body(
+    prolens: *mut FfiProlens,
+    callback: Option<CbHttpBody>,
+) {
+    if prolens.is_null() || callback.is_none() {
+        return;
+    }
+
+    let prolens = unsafe { &mut *prolens };
+    let wrapper = move |data: &[u8],
+                        seq: u32,
+                        ctx: *mut c_void,
+                        dir: Direction,
+                        ce: &Option<Vec<Encoding>>,
+                        te: &Option<Vec<Encoding>>| {
+        callback.unwrap()(
+            data.as_ptr(),
+            data.len(),
+            seq,
+            ctx,
+            dir,
+            CEncodingArray::from(ce.clone()),
+            CEncodingArray::from(te.clone()),
+        );
+    };
+    prolens.0.set_cb_http_body(wrapper);
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn protolens_set_cb_http_body_stop(
+    prolens: *mut FfiProlens,
+    callback: Option<CbDirEvt>,
+) {
+    if prolens.is_null() || callback.is_none() {
+        return;
+    }
+
+    let prolens = unsafe { &mut *prolens };
+    let wrapper = move |ctx: *mut c_void, dir: Direction| {
+        callback.unwrap()(ctx, dir);
+    };
+    prolens.0.set_cb_http_body_stop(wrapper);
+}
+
+type CbHttpTrans = extern "C" fn(id: u64, ctx: *const c_void, dir: Direction);
+
+#[unsafe(no_mangle)]
+pub extern "C" fn protolens_set_cb_http_trans(
+    prolens: *mut FfiProlens,
+    callback: Option<CbHttpTrans>,
+) {
+    if prolens.is_null() || callback.is_none() {
+        return;
+    }
+
+    let prolens = unsafe { &mut *prolens };
+    let wrapper = move |id: u64, ctx: *mut c_void, dir: Direction| {
+        callback.unwrap()(id, ctx, dir);
+    };
+    prolens.0.set_cb_http_trans(wrapper);
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct CHttpHeader {
+    pub name_ptr: *const u8,
+    pub name_len: usize,
+    pub value_ptr: *const u8,
+    pub value_len: usize,
+}
+
+fn c_headers(headers: &HttpHeaders) -> Vec<CHttpHeader> {
+    headers
+        .0
+        .iter()
+        .map(|hdr| CHttpHeader {
+            name_ptr: hdr.name.as_ptr(),
+            name_len: hdr.name.len(),
+            value_ptr: hdr.value.as_ptr(),
+            value_len: hdr.value.len(),
+        })
+        .collect()
+}
+
+fn opt_str(data: Option<&str>) -> (*const u8, usize) {
+    opt_ptr(data.map(|d| d.as_bytes()))
+}
+
+// 没有的字段指针为null
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct CHttpRequest {
+    pub id: u64,
+    pub method_ptr: *const u8,
+    pub method_len: usize,
+    pub target_ptr: *const u8,
+    pub target_len: usize,
+    pub path_ptr: *const u8,
+    pub path_len: usize,
+    pub query_ptr: *const u8,
+    pub query_len: usize,
+    pub version_ptr: *const u8,
+    pub version_len: usize,
+    pub headers_ptr: *const CHttpHeader,
+    pub headers_len: usize,
+    pub host_ptr: *const u8,
+    pub host_len: usize,
+    pub user_agent_ptr: *const u8,
+    pub user_agent_len: usize,
+    pub content_type_ptr: *const u8,
+    pub content_type_len: usize,
+    pub cookie_ptr: *const u8,
+    pub cookie_len: usize,
+    pub authorization_ptr: *const u8,
+    pub authorization_len: usize,
+}
+
+type CbHttpRequest = extern "C" fn(req: *const CHttpRequest, ctx: *const c_void);
+
+#[unsafe(no_mangle)]
+pub extern "C" fn protolens_set_cb_http_request(
+    prolens: *mut FfiProlens,
+    callback: Option<CbHttpRequest>,
+) {
+    if prolens.is_null() || callback.is_none() {
+        return;
+    }
+
+    let prolens = unsafe { &mut *prolens };
+    let wrapper = move |req: &HttpRequest, ctx: *mut c_void| {
+        let headers = c_headers(&req.headers);
+        let (query_ptr, query_len) = opt_str(req.query);
+        let (host_ptr, host_len) = opt_str(req.host());
+        let (user_agent_ptr, user_agent_len) = opt_str(req.user_agent());
+        let (content_type_ptr, content_type_len) = opt_str(req.content_type());
+        let (cookie_ptr, cookie_len) = opt_str(req.cookie());
+        let (authorization_ptr, authorization_len) = opt_str(req.authorization());
+
+        let c_req = CHttpRequest {
+            id: req.id,
+            method_ptr: req.method.as_ptr(),
+            method_len: req.method.len(),
+            target_ptr: req.target.as_ptr(),
+            target_len: req.target.len(),
+            path_ptr: req.path.as_ptr(),
+            path_len: req.path.len(),
+            query_ptr,
+            query_len,
+            version_ptr: req.version.as_ptr(),
+            version_len: req.version.len(),
+            headers_ptr: headers.as_ptr(),
+            headers_len: headers.len(),
+            host_ptr,
+            host_len,
+            user_agent_ptr,
+            user_agent_len,
+            content_type_ptr,
+            content_type_len,
+            cookie_ptr,
+            cookie_len,
+            authorization_ptr,
+            authorization_len,
+        };
+        callback.unwrap()(&c_req, ctx);
+    };
+    prolens.0.set_cb_http_request(wrapper);
+}
+
+// Set-Cookie可能有多个，在headers里找
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct CHttpResponse {
+    pub id: u64,
+    pub version_ptr: *const u8,
+    pub version_len: usize,
+    pub status: u16,
+    pub reason_ptr: *const u8,
+    pub reason_len: usize,
+    pub headers_ptr: *const CHttpHeader,
+    pub headers_len: usize,
+    pub content_type_ptr: *const u8,
+    pub content_type_len: usize,
+}
+
+type CbHttpResponse = extern "C" fn(rsp: *const CHttpResponse, ctx: *const c_void);
+
+#[unsafe(no_mangle)]
+pub extern "C" fn protolens_set_cb_http_response(
+    prolens: *mut FfiProlens,
+    callback: Option<CbHttpResponse>,
+) {
+    if prolens.is_null() || callback.is_none() {
+        return;
+    }
+
+    let prolens = unsafe { &mut *prolens };
+    let wrapper = move |rsp: &HttpResponse, ctx: *mut c_void| {
+        let headers = c_headers(&rsp.headers);
+        let (content_type_ptr, content_type_len) = opt_str(rsp.content_type());
+
+        let c_rsp = CHttpResponse {
+            id: rsp.id,
+            version_ptr: rsp.version.as_ptr(),
+            version_len: rsp.version.len(),
+            status: rsp.status,
+            reason_ptr: rsp.reason.as_ptr(),
+            reason_len: rsp.reason.len(),
+            headers_ptr: headers.as_ptr(),
+            headers_len: headers.len(),
+            content_type_ptr,
+            content_type_len,
+        };
+        callback.unwrap()(&c_rsp, ctx);
+    };
+    prolens.0.set_cb_http_response(wrapper);
+}
+
+// 没有的字段指针为null
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct CHttpPart {
+    pub id: u64,
+    pub index: usize,
+    pub depth: usize,
+    pub name_ptr: *const u8,
+    pub name_len: usize,
+    pub filename_ptr: *const u8,
+    pub filename_len: usize,
+    pub content_type_ptr: *const u8,
+    pub content_type_len: usize,
+    pub headers_ptr: *const CHttpHeader,
+    pub headers_len: usize,
+}
+
+type CbHttpPart = extern "C" fn(part: *const CHttpPart, ctx: *const c_void, dir: Direction);
+
+#[unsafe(no_mangle)]
+pub extern "C" fn protolens_set_cb_http_part(
+    prolens: *mut FfiProlens,
+    callback: Option<CbHttpPart>,
+) {
+    if prolens.is_null() || callback.is_none() {
+        return;
+    }
+
+    let prolens = unsafe { &mut *prolens };
+    let wrapper = move |part: &HttpPart, ctx: *mut c_void, dir: Direction| {
+        let headers = c_headers(&part.headers);
+        let (name_ptr, name_len) = opt_str(part.name);
+        let (filename_ptr, filename_len) = opt_str(part.filename.as_deref());
+        let (content_type_ptr, content_type_len) = opt_str(part.content_type);
+
+        let c_part = CHttpPart {
+            id: part.id,
+            index: part.index,
+            depth: part.depth,
+            name_ptr,
+            name_len,
+            filename_ptr,
+            filename_len,
+            content_type_ptr,
+            content_type_len,
+            headers_ptr: headers.as_ptr(),
+            headers_len: headers.len(),
+        };
+        callback.unwrap()(&c_part, ctx, dir);
+    };
+    prolens.0.set_cb_http_part(wrapper);
+}
+
+type CbHttpForm = extern "C" fn(
+    id: u64,
+    key: *const u8,
+    key_len: usize,
+    value: *const u8,
+    value_len: usize,
+    ctx: *const c_void,
+    dir: Direction,
+);
+
+#[unsafe(no_mangle)]
+pub extern "C" fn protolens_set_cb_http_form(
+    prolens: *mut FfiProlens,
+    callback: Option<CbHttpForm>,
+) {
+    if prolens.is_null() || callback.is_none() {
+        return;
+    }
+
+    let prolens = unsafe { &mut *prolens };
+    let wrapper = move |id: u64, key: &[u8], value: &[u8], ctx: *mut c_void, dir: Direction| {
+        callback.unwrap()(
+            id,
+            key.as_ptr(),
+            key.len(),
+            value.as_ptr(),
+            value.len(),
+            ctx,
+            dir,
+        );
+    };
+    prolens.0.set_cb_http_form(wrapper);
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum CTunnelKind {
+    Connect,
+    WebSocket,
+    H2c,
+    Other,
+}
+
+impl From<TunnelKind> for CTunnelKind {
+    fn from(kind: TunnelKind) -> Self {
+        match kind {
+            TunnelKind::Connect => CTunnelKind::Connect,
+            TunnelKind::WebSocket => CTunnelKind::WebSocket,
+            TunnelKind::H2c => CTunnelKind::H2c,
+            TunnelKind::Other => CTunnelKind::Other,
+        }
+    }
+}
+
+type CbHttpTunnel = extern "C" fn(
+    id: u64,
+    kind: CTunnelKind,
+    target: *const u8,
+    target_len: usize,
+    ctx: *const c_void,
+);
+
+#[unsafe(no_mangle)]
+pub extern "C" fn protolens_set_cb_http_tunnel(
+    prolens: *mut FfiProlens,
+    callback: Option<CbHttpTunnel>,
+) {
+    if prolens.is_null() || callback.is_none() {
+        return;
+    }
+
+    let prolens = unsafe { &mut *prolens };
+    let wrapper = move |id: u64, kind: TunnelKind, target: &[u8], ctx: *mut c_void| {
+        callback.unwrap()(
+            id,
+            CTunnelKind::from(kind),
+            target.as_ptr(),
+            target.len(),
+            ctx,
+        );
+    };
+    prolens.0.set_cb_http_tunnel(wrapper);
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn protolens_set_cb_http_tunnel_data(
+    prolens: *mut FfiProlens,
+    callback: Option<CbDirData>,
+) {
+    if prolens.is_null() || callback.is_none() {
+        return;
+    }
+
+    let prolens = unsafe { &mut *prolens };
+    let wrapper = move |data: &[u8], seq: u32, ctx: *mut c_void, dir: Direction| {
+        callback.unwrap()(data.as_ptr(), data.len(), seq, ctx, dir);
+    };
+    prolens.0.set_cb_http_tunnel_data(wrapper);
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct CWsFrame {
+    pub fin: bool,
+    pub opcode: u8,
+    pub len: u64,
+    pub offset: u64,
+    pub payload_ptr: *const u8,
+    pub payload_len: usize,
+}
+
+type CbWsFrame = extern "C" fn(frame: *const CWsFrame, ctx: *const c_void, dir: Direction);
+
+#[unsafe(no_mangle)]
+pub extern "C" fn protolens_set_cb_ws_frame(prolens: *mut FfiProlens, callback: Option<CbWsFrame>) {
+    if prolens.is_null() || callback.is_none() {
+        return;
+    }
+
+    let prolens = unsafe { &mut *prolens };
+    let wrapper = move |frame: &WsFrame, ctx: *mut c_void, dir: Direction| {
+        let c_frame = CWsFrame {
+            fin: frame.fin,
+            opcode: frame.opcode,
+            len: frame.len,
+            offset: frame.offset,
+            payload_ptr: frame.payload.as_ptr(),
+            payload_len: frame.payload.len(),
+        };
+        callback.unwrap()(&c_frame, ctx, dir);
+    };
+    prolens.0.set_cb_ws_frame(wrapper);
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct CTlsStr {
+    pub ptr: *const u8,
+    pub len: usize,
+}
+
+// ServerHello时cipher_suites和alpn只有选中的一个。没有的字段指针为null
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct CTlsHello {
+    pub client: bool,
+    pub version: u16,
+    pub versions_ptr: *const u16,
+    pub versions_len: usize,
+    pub random_ptr: *const u8,
+    pub random_len: usize,
+    pub session_id_ptr: *const u8,
+    pub session_id_len: usize,
+    pub cipher_suites_ptr: *const u16,
+    pub cipher_suites_len: usize,
+    pub sni_ptr: *const u8,
+    pub sni_len: usize,
+    pub alpn_ptr: *const CTlsStr,
+    pub alpn_len: usize,
+}
+
+type CbTlsHello = extern "C" fn(hello: *const CTlsHello, ctx: *const c_void, dir: Direction);
+
+#[unsafe(no_mangle)]
+pub extern "C" fn protolens_set_cb_tls_hello(
+    prolens: *mut FfiProlens,
+    callback: Option<CbTlsHello>,
+) {
+    if prolens.is_null() || callback.is_none() {
+        return;
+    }
+
+    let prolens = unsafe { &mut *prolens };
+    let wrapper = move |hello: &TlsHello, ctx: *mut c_void, dir: Direction| {
+        let alpn: Vec<CTlsStr> = hello
+            .alpn
+            .iter()
+            .map(|proto| CTlsStr {
+                ptr: proto.as_ptr(),
+                len: proto.len(),
+            })
+            .collect();
+        let (sni_ptr, sni_len) = opt_str(hello.sni);
+        let c_hello = CTlsHello {
+            client: hello.client,
+            version: hello.version,
+            versions_ptr: hello.versions.as_ptr(),
+            versions_len: hello.versions.len(),
+            random_ptr: hello.random.as_ptr(),
+            random_len: hello.random.len(),
+            session_id_ptr: hello.session_id.as_ptr(),
+            session_id_len: hello.session_id.len(),
+            cipher_suites_ptr: hello.cipher_suites.as_ptr(),
+            cipher_suites_len: hello.cipher_suites.len(),
+            sni_ptr,
+            sni_len,
+            alpn_ptr: alpn.as_ptr(),
+            alpn_len: alpn.len(),
+        };
+        callback.unwrap()(&c_hello, ctx, dir);
+    };
+    prolens.0.set_cb_tls_hello(wrapper);
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum CHttpAuthScheme {
+    Basic,
+    Digest,
+    Bearer,
+    Ntlm,
+    Other,
+}
+
+// 按scheme填写对应字段，没有的字段指针为null。
+// Basic: user, pass。Digest: user(username)及realm等。Bearer: token。
+// Ntlm: ntlm_type, ntlm_flags, domain, user, workstation, type 2的target和challenge。
+// Other: scheme_name, token
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct CHttpAuth {
+    pub id: u64,
+    pub proxy: bool,
+    pub status: u16, // 请求中的凭证为0，结果见auth result
+    pub scheme: CHttpAuthScheme,
+    pub scheme_name_ptr: *const u8,
+    pub scheme_name_len: usize,
+    pub user_ptr: *const u8,
+    pub user_len: usize,
+    pub pass_ptr: *const u8,
+    pub pass_len: usize,
+    pub token_ptr: *const u8,
+    pub token_len: usize,
+    pub realm_ptr: *const u8,
+    pub realm_len: usize,
+    pub nonce_ptr: *const u8,
+    pub nonce_len: usize,
+    pub uri_ptr: *const u8,
+    pub uri_len: usize,
+    pub response_ptr: *const u8,
+    pub response_len: usize,
+    pub algorithm_ptr: *const u8,
+    pub algorithm_len: usize,
+    pub qop_ptr: *const u8,
+    pub qop_len: usize,
+    pub nc_ptr: *const u8,
+    pub nc_len: usize,
+    pub cnonce_ptr: *const u8,
+    pub cnonce_len: usize,
+    pub opaque_ptr: *const u8,
+    pub opaque_len: usize,
+    pub domain_ptr: *const u8,
+    pub domain_len: usize,
+    pub workstation_ptr: *const u8,
+    pub workstation_len: usize,
+    pub target_ptr: *const u8,
+    pub target_len: usize,
+    pub ntlm_type: u32,
+    pub ntlm_flags: u32,
+    pub challenge_ptr: *const u8, // 8字节
+}
+
+type CbHttpAuth = extern "C" fn(auth: *const CHttpAuth, ctx: *const c_void, dir: Direction);
+
+#[unsafe(no_mangle)]
+pub extern "C" fn protolens_set_cb_http_auth(
+    prolens: *mut FfiProlens,
+    callback: Option<CbHttpAuth>,
+) {
+    if prolens.is_null() || callback.is_none() {
+        return;
+    }
+
+    let prolens = unsafe { &mut *prolens };
+    let wrapper = move |auth: &HttpAuth, ctx: *mut c_void, dir: Direction| {
+        let null = (std::ptr::null(), 0);
+        let mut c_auth = CHttpAuth {
+            id: auth.id,
+            proxy: auth.proxy,
+            status: auth.status,
+            scheme: CHttpAuthScheme::Other,
+            scheme_name_ptr: null.0,
+            scheme_name_len: null.1,
+            user_ptr: null.0,
+            user_len: null.1,
+            pass_ptr: null.0,
+            pass_len: null.1,
+            token_ptr: null.0,
+            token_len: null.1,
+            realm_ptr: null.0,
+            realm_len: null.1,
+            nonce_ptr: null.0,
+            nonce_len: null.1,
+            uri_ptr: null.0,
+            uri_len: null.1,
+            response_ptr: null.0,
+            response_len: null.1,
+            algorithm_ptr: null.0,
+            algorithm_len: null.1,
+            qop_ptr: null.0,
+            qop_len: null.1,
+            nc_ptr: null.0,
+            nc_len: null.1,
+            cnonce_ptr: null.0,
+            cnonce_len: null.1,
+            opaque_ptr: null.0,
+            opaque_len: null.1,
+            domain_ptr: null.0,
+            domain_len: null.1,
+            workstation_ptr: null.0,
+            workstation_len: null.1,
+            target_ptr: null.0,
+            target_len: null.1,
+            ntlm_type: 0,
+            ntlm_flags: 0,
+            challenge_ptr: std::ptr::null(),
+        };
+        match &auth.cred {
+            HttpCred::Basic { user, pass } => {
+                c_auth.scheme = CHttpAuthScheme::Basic;
+                (c_auth.user_ptr, c_auth.user_len) = (user.as_ptr(), user.len());
+                (c_auth.pass_ptr, c_auth.pass_len) = (pass.as_ptr(), pass.len());
+            }
+            HttpCred::Digest(digest) => {
+                c_auth.scheme = CHttpAuthScheme::Digest;
+                (c_auth.user_ptr, c_auth.user_len) = opt_str(digest.username);
+                (c_auth.realm_ptr, c_auth.realm_len) = opt_str(digest.realm);
+                (c_auth.nonce_ptr, c_auth.nonce_len) = opt_str(digest.nonce);
+                (c_auth.uri_ptr, c_auth.uri_len) = opt_str(digest.uri);
+                (c_auth.response_ptr, c_auth.response_len) = opt_str(digest.response);
+                (c_auth.algorithm_ptr, c_auth.algorithm_len) = opt_str(digest.algorithm);
+                (c_auth.qop_ptr, c_auth.qop_len) = opt_str(digest.qop);
+                (c_auth.nc_ptr, c_auth.nc_len) = opt_str(digest.nc);
+                (c_auth.cnonce_ptr, c_auth.cnonce_len) = opt_str(digest.cnonce);
+                (c_auth.opaque_ptr, c_auth.opaque_len) = opt_str(digest.opaque);
+            }
+            HttpCred::Bearer(token) => {
+                c_auth.scheme = CHttpAuthScheme::Bearer;
+                (c_auth.token_ptr, c_auth.token_len) = opt_str(Some(token));
+            }
+            HttpCred::Ntlm(msg) => {
+                c_auth.scheme = CHttpAuthScheme::Ntlm;
+                c_auth.ntlm_type = msg.msg_type;
+                c_auth.ntlm_flags = msg.flags;
+                (c_auth.domain_ptr, c_auth.domain_len) = opt_str(msg.domain.as_deref());
+                (c_auth.user_ptr, c_auth.user_len) = opt_str(msg.user.as_deref());
+                (c_auth.workstation_ptr, c_auth.workstation_len) =
+                    opt_str(msg.workstation.as_deref());
+                (c_auth.target_ptr, c_auth.target_len) = opt_str(msg.target.as_deref());
+                if let Some(challenge) = &msg.challenge {
+                    c_auth.challenge_ptr = challenge.as_ptr();
+                }
+            }
+            HttpCred::Other {
+                scheme,
+                credentials,
+            } => {
+                (c_auth.scheme_name_ptr, c_auth.scheme_name_len) = opt_str(Some(scheme));
+                (c_auth.token_ptr, c_auth.token_len) = opt_str(Some(credentials));
+            }
+        }
+        callback.unwrap()(&c_auth, ctx, dir);
+    };
+    prolens.0.set_cb_http_auth(wrapper);
+}
+
+type CbHttpAuthResult =
+    extern "C" fn(id: u64, proxy: bool, status: u16, ctx: *const c_void, dir: Direction);
+
+#[unsafe(no_mangle)]
+pub extern "C" fn protolens_set_cb_http_auth_result(
+    prolens: *mut FfiProlens,
+    callback: Option<CbHttpAuthResult>,
+) {
+    if prolens.is_null() || callback.is_none() {
+        return;
+    }
+
+    let prolens = unsafe { &mut *prolens };
+    let wrapper = move |id: u64, proxy: bool, status: u16, ctx: *mut c_void, dir: Direction| {
+        callback.unwrap()(id, proxy, status, ctx, dir);
+    };
+    prolens.0.set_cb_http_auth_result(wrapper);
+}
+
+// max_size, max_ratio为0表示不限制
+#[unsafe(no_mangle)]
+pub extern "C" fn protolens_set_http_decode(
+    prolens: *mut FfiProlens,
+    enable: bool,
+    max_size: usize,
+    max_ratio: usize,
+) {
+    if prolens.is_null() {
+        return;
+    }
+
+    let prolens = unsafe { &mut *prolens };
+    prolens.0.conf.http_decode = enable;
+    prolens.0.conf.http_decode_max = max_size;
+    prolens.0.conf.http_decode_ratio = max_ratio;
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum CDecodeError {
+    Unsupported,
+    Corrupt,
+    Limit,
+}
+
+impl From<DecodeError> for CDecodeError {
+    fn from(err: DecodeError) -> Self {
+        match err {
+            DecodeError::Unsupported => CDecodeError::Unsupported,
+            DecodeError::Corrupt => CDecodeError::Corrupt,
+            DecodeError::Limit => CDecodeError::Limit,
+        }
+    }
+}
+
+type CbHttpDecodeErr = extern "C" fn(err: CDecodeError, ctx: *const c_void, dir: Direction);
+
+#[unsafe(no_mangle)]
+pub extern "C" fn protolens_set_cb_http_decode_err(
+    prolens: *mut FfiProlens,
+    callback: Option<CbHttpDecodeErr>,
+) {
+    if prolens.is_null() || callback.is_none() {
+        return;
+    }
+
+    let prolens = unsafe { &mut *prolens };
+    let wrapper = move |err: DecodeError, ctx: *mut c_void, dir: Direction| {
+        callback.unwrap()(CDecodeError::from(err), ctx, dir);
+    };
+    prolens.0.set_cb_http_decode_err(wrapper);
+}
+
+type CbHttp2Line =
+    extern "C" fn(stream: u32, data: *const u8, len: usize, ctx: *const c_void, dir: Direction);
+type CbHttp2BodyEvt = extern "C" fn(stream: u32, ctx: *const c_void, dir: Direction);
+type CbHttp2Body = extern "C" fn(
+    stream: u32,
+    data: *const u8,
+    len: usize,
+    ctx: *const c_void,
+    dir: Direction,
+    ce: CEncodingArray,
+);
+
+#[unsafe(no_mangle)]
+pub extern "C" fn protolens_set_cb_http2_start_line(
+    prolens: *mut FfiProlens,
+    callback: Option<CbHttp2Line>,
+) {
+    if prolens.is_null() || callback.is_none() {
+        return;
+    }
+
+    let prolens = unsafe { &mut *prolens };
+    let wrapper = move |stream: u32, data: &[u8], ctx: *mut c_void, dir: Direction| {
+        callback.unwrap()(stream, data.as_ptr(), data.len(), ctx, dir);
+    };
+    prolens.0.set_cb_http2_start_line(wrapper);
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn protolens_set_cb_http2_header(
+    prolens: *mut FfiProlens,
+    callback: Option<CbHttp2Line>,
+) {
+    if prolens.is_null() || callback.is_none() {
+        return;
+    }
+
+    let prolens = unsafe { &mut *prolens };
+    let wrapper = move |stream: u32, data: &[u8], ctx: *mut c_void, dir: Direction| {
+        callback.unwrap()(stream, data.as_ptr(), data.len(), ctx, dir);
+    };
+    prolens.0.set_cb_http2_header(wrapper);
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn protolens_set_cb_http2_body_start(
+    prolens: *mut FfiProlens,
+    callback: Option<CbHttp2BodyEvt>,
+) {
+    if prolens.is_null() || callback.is_none() {
+        return;
+    }
+
+    let prolens = unsafe { &mut *prolens };
+    let wrapper = move |stream: u32, ctx: *mut c_void, dir: Direction| {
+        callback.unwrap()(stream, ctx, dir);
+    };
+    prolens.0.set_cb_http2_body_start(wrapper);
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn protolens_set_cb_http2_body(
+    prolens: *mut FfiProlens,
+    callback: Option<CbHttp2Body>,
+) {
+    if prolens.is_null() || callback.is_none() {
+        return;
+    }
+
+    let prolens = unsafe { &mut *prolens };
+    let wrapper = move |stream: u32,
+                        data: &[u8],
+                        ctx: *mut c_void,
+                        dir: Direction,
+                        ce: &Option<Vec<Encoding>>| {
+        callback.unwrap()(
+            stream,
+            data.as_ptr(),
+            data.len(),
+            ctx,
+            dir,
+            CEncodingArray::from(ce.clone()),
+        );
+    };
+    prolens.0.set_cb_http2_body(wrapper);
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn protolens_set_cb_http2_body_stop(
+    prolens: *mut FfiProlens,
+    callback: Option<CbHttp2BodyEvt>,
+) {
+    if prolens.is_null() || callback.is_none() {
+        return;
+    }
+
+    let prolens = unsafe { &mut *prolens };
+    let wrapper = move |stream: u32, ctx: *mut c_void, dir: Direction| {
+        callback.unwrap()(stream, ctx, dir);
+    };
+    prolens.0.set_cb_http2_body_stop(wrapper);
+}
+
+// service和method来自:path，encoding没有时指针为null
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct CGrpcCall {
+    pub stream: u32,
+    pub service_ptr: *const u8,
+    pub service_len: usize,
+    pub method_ptr: *const u8,
+    pub method_len: usize,
+    pub content_type_ptr: *const u8,
+    pub content_type_len: usize,
+    pub encoding_ptr: *const u8,
+    pub encoding_len: usize,
+}
+
+type CbGrpcCall = extern "C" fn(call: *const CGrpcCall, ctx: *const c_void, dir: Direction);
+
+#[unsafe(no_mangle)]
+pub extern "C" fn protolens_set_cb_grpc_call(
+    prolens: *mut FfiProlens,
+    callback: Option<CbGrpcCall>,
+) {
+    if prolens.is_null() || callback.is_none() {
+        return;
+    }
+
+    let prolens = unsafe { &mut *prolens };
+    let wrapper = move |call: &GrpcCall, ctx: *mut c_void, dir: Direction| {
+        let (encoding_ptr, encoding_len) = opt_str(call.encoding);
+        let c_call = CGrpcCall {
+            stream: call.stream,
+            service_ptr: call.service.as_ptr(),
+            service_len: call.service.len(),
+            method_ptr: call.method.as_ptr(),
+            method_len: call.method.len(),
+            content_type_ptr: call.content_type.as_ptr(),
+            content_type_len: call.content_type.len(),
+            encoding_ptr,
+            encoding_len,
+        };
+        callback.unwrap()(&c_call, ctx, dir);
+    };
+    prolens.0.set_cb_grpc_call(wrapper);
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct CGrpcMsg {
+    pub stream: u32,
+    pub compressed: bool,
+    pub len: u32,
+    pub offset: u32,
+    pub data_ptr: *const u8,
+    pub data_len: usize,
+}
+
+type CbGrpcMsg = extern "C" fn(msg: *const CGrpcMsg, ctx: *const c_void, dir: Direction);
+
+#[unsafe(no_mangle)]
+pub extern "C" fn protolens_set_cb_grpc_msg(prolens: *mut FfiProlens, callback: Option<CbGrpcMsg>) {
+    if prolens.is_null() || callback.is_none() {
+        return;
+    }
+
+    let prolens = unsafe { &mut *prolens };
+    let wrapper = move |msg: &GrpcMsg, ctx: *mut c_void, dir: Direction| {
+        let c_msg = CGrpcMsg {
+            stream: msg.stream,
+            compressed: msg.compressed,
+            len: msg.len,
+            offset: msg.offset,
+            data_ptr: msg.data.as_ptr(),
+            data_len: msg.data.len(),
+        };
+        callback.unwrap()(&c_msg, ctx, dir);
+    };
+    prolens.0.set_cb_grpc_msg(wrapper);
+}
+
+// has_status为false时status无意义。message没有时指针为null
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct CGrpcStatus {
+    pub stream: u32,
+    pub has_status: bool,
+    pub status: u32,
+    pub message_ptr: *const u8,
+    pub message_len: usize,
+}
+
+type CbGrpcStatus = extern "C" fn(status: *const CGrpcStatus, ctx: *const c_void, dir: Direction);
+
+#[unsafe(no_mangle)]
+pub extern "C" fn protolens_set_cb_grpc_status(
+    prolens: *mut FfiProlens,
+    callback: Option<CbGrpcStatus>,
+) {
+    if prolens.is_null() || callback.is_none() {
+        return;
+    }
+
+    let prolens = unsafe { &mut *prolens };
+    let wrapper = move |status: &GrpcStatus, ctx: *mut c_void, dir: Direction| {
+        let (message_ptr, message_len) = opt_str(status.message);
+        let c_status = CGrpcStatus {
+            stream: status.stream,
+            has_status: status.status.is_some(),
+            status: status.status.unwrap_or(0),
+            message_ptr,
+            message_len,
+        };
+        callback.unwrap()(&c_status, ctx, dir);
+    };
+    prolens.0.set_cb_grpc_status(wrapper);
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn protolens_set_cb_ftp_clt(prolens: *mut FfiProlens, callback: Option<CbData>) {
+    if prolens.is_null() || callback.is_none() {
+        return;
+    }
+
+    let prolens = unsafe { &mut *prolens };
+    let wrapper = move |data: &[u8], seq: u32, ctx: *mut c_void| {
+        callback.unwrap()(data.as_ptr(), data.len(), seq, ctx);
+    };
+    prolens.0.set_cb_ftp_clt(wrapper);
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn protolens_set_cb_ftp_srv(prolens: *mut FfiProlens, callback: Option<CbData>) {
+    if prolens.is_null() || callback.is_none() {
+        return;
+    }
+
+    let prolens = unsafe { &mut *prolens };
+    let wrapper = move |data: &[u8], seq: u32, ctx: *mut c_void| {
+        callback.unwrap()(data.as_ptr(), data.len(), seq, ctx);
+    };
+    prolens.0.set_cb_ftp_srv(wrapper);
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn protolens_set_cb_ftp_link(prolens: *mut FfiProlens, callback: Option<CbFtpLink>) {
+    if prolens.is_null() || callback.is_none() {
+        return;
+    }
+
+    let prolens = unsafe { &mut *prolens };
+    let wrapper = move |ip: Option<IpAddr>, port: u16, ctx: *mut c_void, dir: Direction| {
+        let mut ip_bytes: [u8; 16] = [0; 16];
+        let mut ip_len: usize = 0;
+        let mut ip_type: u8 = 0;
+
+        if let Some(addr) = ip {
+            match addr {
+                IpAddr::V4(ipv4) => {
+                    let octets = ipv4.octets();
+                    ip_bytes[..4].copy_from_slice(&octets);
+                    ip_len = 4;
+                    ip_type = 1;
+                }
+                IpAddr::V6(ipv6) => {
+                    let octets = ipv6.octets();
+                    ip_bytes.copy_from_slice(&octets);
+                    ip_len = 16;
+                    ip_type = 2;
+                }
+            }
+        }
+
+        callback.unwrap()(ip_bytes.as_ptr(), ip_len, ip_type, port, ctx, dir);
+    };
+    prolens.0.set_cb_ftp_link(wrapper);
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct CFtpLogin {
+    pub session: u64,
+    pub user_ptr: *const u8,
+    pub user_len: usize,
+    pub pass_ptr: *const u8,
+    pub pass_len: usize,
+    pub code: u16,
+}
+
+type CbFtpLogin = extern "C" fn(login: *const CFtpLogin, ctx: *const c_void);
+
+#[unsafe(no_mangle)]
+pub extern "C" fn protolens_set_cb_ftp_login(
+    prolens: *mut FfiProlens,
+    callback: Option<CbFtpLogin>,
+) {
+    if prolens.is_null() || callback.is_none() {
+        return;
+    }
+
+    let prolens = unsafe { &mut *prolens };
+    let wrapper = move |login: &FtpLogin, ctx: *mut c_void| {
+        let (pass_ptr, pass_len) = opt_str(login.pass);
+        let c_login = CFtpLogin {
+            session: login.session,
+            user_ptr: login.user.as_ptr(),
+            user_len: login.user.len(),
+            pass_ptr,
+            pass_len,
+            code: login.code,
+        };
+        callback.unwrap()(&c_login, ctx);
+    };
+    prolens.0.set_cb_ftp_login(wrapper);
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct CFtpFile {
+    pub session: u64,
+    pub cmd_ptr: *const u8,
+    pub cmd_len: usize,
+    pub path_ptr: *const u8,
+    pub path_len: usize,
+    pub rename_to_ptr: *const u8,
+    pub rename_to_len: usize,
+    pub cwd_ptr: *const u8,
+    pub cwd_len: usize,
+    pub code: u16,
+}
+
+type CbFtpFile = extern "C" fn(file: *const CFtpFile, ctx: *const c_void);
+
+#[unsafe(no_mangle)]
+pub extern "C" fn protolens_set_cb_ftp_file(prolens: *mut FfiProlens, callback: Option<CbFtpFile>) {
+    if prolens.is_null() || callback.is_none() {
+        return;
+    }
+
+    let prolens = unsafe { &mut *prolens };
+    let wrapper = move |file: &FtpFile, ctx: *mut c_void| {
+        let (rename_to_ptr, rename_to_len) = opt_str(file.rename_to);
+        let (cwd_ptr, cwd_len) = opt_str(file.cwd);
+        let c_file = CFtpFile {
+            session: file.session,
+            cmd_ptr: file.cmd.as_ptr(),
+            cmd_len: file.cmd.len(),
+            path_ptr: file.path.as_ptr(),
+            path_len: file.path.len(),
+            rename_to_ptr,
+            rename_to_len,
+            cwd_ptr,
+            cwd_len,
+            code: file.code,
+        };
+        callback.unwrap()(&c_file, ctx);
+    };
+    prolens.0.set_cb_ftp_file(wrapper);
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct CFtpTransfer {
+    pub session: u64,
+    pub cmd_ptr: *const u8,
+    pub cmd_len: usize,
+    pub path_ptr: *const u8,
+    pub path_len: usize,
+    pub cwd_ptr: *const u8,
+    pub cwd_len: usize,
+    pub code: u16,
+    pub data_type: FtpType,
+    pub mode: FtpMode,
+    pub rest: u64,
+}
+
+type CbFtpTransfer = extern "C" fn(transfer: *const CFtpTransfer, ctx: *const c_void);
+
+#[unsafe(no_mangle)]
+pub extern "C" fn protolens_set_cb_ftp_transfer(
+    prolens: *mut FfiProlens,
+    callback: Option<CbFtpTransfer>,
+) {
+    if prolens.is_null() || callback.is_none() {
+        return;
+    }
+
+    let prolens = unsafe { &mut *prolens };
+    let wrapper = move |transfer: &FtpTransfer, ctx: *mut c_void| {
+        let (path_ptr, path_len) = opt_str(transfer.path);
+        let (cwd_ptr, cwd_len) = opt_str(transfer.cwd);
+        let c_transfer = CFtpTransfer {
+            session: transfer.session,
+            cmd_ptr: transfer.cmd.as_ptr(),
+            cmd_len: transfer.cmd.len(),
+            path_ptr,
+            path_len,
+            cwd_ptr,
+            cwd_len,
+            code: transfer.code,
+            data_type: transfer.data_type,
+            mode: transfer.mode,
+            rest: transfer.rest,
+        };
+        callback.unwrap()(&c_transfer, ctx);
+    };
+    prolens.0.set_cb_ftp_transfer(wrapper);
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn protolens_set_cb_ftp_body_start(
+    prolens: *mut FfiProlens,
+    callback: Option<CbDirEvt>,
+) {
+    if prolens.is_null() || callback.is_none() {
+        return;
+    }
+
+    let prolens = unsafe { &mut *prolens };
+    let wrapper = move |ctx: *mut c_void, dir: Direction| {
+        callback.unwrap()(ctx, dir);
+    };
+    prolens.0.set_cb_ftp_body_start(wrapper);
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn protolens_set_cb_ftp_body(prolens: *mut FfiProlens, callback: Option<CbDirData>) {
+    if prolens.is_null() || callback.is_none() {
+        return;
+    }
+
+    let prolens = unsafe { &mut *prolens };
+    let wrapper = move |data: &[u8], seq: u32, ctx: *mut c_void, dir: Direction| {
+        callback.unwrap()(data.as_ptr(), data.len(), seq, ctx, dir);
+    };
+    prolens.0.set_cb_ftp_body(wrapper);
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn protolens_set_cb_ftp_body_stop(
+    prolens: *mut FfiProlens,
+    callback: Option<CbDirEvt>,
+) {
+    if prolens.is_null() || callback.is_none() {
+        return;
+    }
+
+    let prolens = unsafe { &mut *prolens };
+    let wrapper = move |ctx: *mut c_void, dir: Direction| {
+        callback.unwrap()(ctx, dir);
+    };
+    prolens.0.set_cb_ftp_body_stop(wrapper);
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct CFtpXfer {
+    pub session: u64,
+    pub cmd_ptr: *const u8,
+    pub cmd_len: usize,
+    pub path_ptr: *const u8,
+    pub path_len: usize,
+}
+
+impl CFtpXfer {
+    fn new(xfer: &FtpXfer) -> Self {
+        let (cmd_ptr, cmd_len) = opt_str(xfer.cmd.as_deref());
+        let (path_ptr, path_len) = opt_str(xfer.path.as_deref());
+        CFtpXfer {
+            session: xfer.session,
+            cmd_ptr,
+            cmd_len,
+            path_ptr,
+            path_len,
+        }
+    }
+}
+
+type CbFtpXferEvt = extern "C" fn(xfer: *const CFtpXfer, ctx: *const c_void, dir: Direction);
+type CbFtpXferBody = extern "C" fn(
+    xfer: *const CFtpXfer,
+    data: *const u8,
+    len: usize,
+    seq: u32,
+    ctx: *const c_void,
+    dir: Direction,
+);
+
+#[unsafe(no_mangle)]
+pub extern "C" fn protolens_set_cb_ftp_xfer_start(
+    prolens: *mut FfiProlens,
+    callback: Option<CbFtpXferEvt>,
+) {
+    if prolens.is_null() || callback.is_none() {
+        return;
+    }
+
+    let prolens = unsafe { &mut *prolens };
+    let wrapper = move |xfer: &FtpXfer, ctx: *mut c_void, dir: Direction| {
+        callback.unwrap()(&CFtpXfer::new(xfer), ctx, dir);
+    };
+    prolens.0.set_cb_ftp_xfer_start(wrapper);
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn protolens_set_cb_ftp_xfer_body(
+    prolens: *mut FfiProlens,
+    callback: Option<CbFtpXferBody>,
+) {
+    if prolens.is_null() || callback.is_none() {
+        return;
+    }
+
+    let prolens = unsafe { &mut *prolens };
+    let wrapper = move |xfer: &FtpXfer, data: &[u8], seq: u32, ctx: *mut c_void, dir: Direction| {
+        callback.unwrap()(
+            &CFtpXfer::new(xfer),
+            data.as_ptr(),
+            data.len(),
+            seq,
+            ctx,
+            dir,
+        );
+    };
+    prolens.0.set_cb_ftp_xfer_body(wrapper);
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn protolens_set_cb_ftp_xfer_stop(
+    prolens: *mut FfiProlens,
+    callback: Option<CbFtpXferEvt>,
 ) {
     if prolens.is_null() || callback.is_none() {
         return;
     }
 
     let prolens = unsafe { &mut *prolens };
-    let wrapper = move |ctx: *mut c_void, dir: Direction| {
-        callback.unwrap()(ctx, dir);
+    let wrapper = move |xfer: &FtpXfer, ctx: *mut c_void, dir: Direction| {
+        callback.unwrap()(&CFtpXfer::new(xfer), ctx, dir);
     };
-    prolens.0.set_cb_ftp_body_stop(wrapper);
+    prolens.0.set_cb_ftp_xfer_stop(wrapper);
+}
+
+// size为u64::MAX表示没有大小，没有的字符串指针为null
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct CFtpEntry {
+    pub session: u64,
+    pub name_ptr: *const u8,
+    pub name_len: usize,
+    pub entry_type: FtpEntryType,
+    pub size: u64,
+    pub perm_ptr: *const u8,
+    pub perm_len: usize,
+    pub mtime_ptr: *const u8,
+    pub mtime_len: usize,
+    pub link_ptr: *const u8,
+    pub link_len: usize,
+}
+
+type CbFtpListEntry = extern "C" fn(entry: *const CFtpEntry, ctx: *const c_void, dir: Direction);
+
+#[unsafe(no_mangle)]
+pub extern "C" fn protolens_set_cb_ftp_list_entry(
+    prolens: *mut FfiProlens,
+    callback: Option<CbFtpListEntry>,
+) {
+    if prolens.is_null() || callback.is_none() {
+        return;
+    }
+
+    let prolens = unsafe { &mut *prolens };
+    let wrapper = move |entry: &FtpEntry, ctx: *mut c_void, dir: Direction| {
+        let (perm_ptr, perm_len) = opt_str(entry.perm);
+        let (mtime_ptr, mtime_len) = opt_str(entry.mtime);
+        let (link_ptr, link_len) = opt_str(entry.link);
+        let c_entry = CFtpEntry {
+            session: entry.session,
+            name_ptr: entry.name.as_ptr(),
+            name_len: entry.name.len(),
+            entry_type: entry.entry_type,
+            size: entry.size.unwrap_or(u64::MAX),
+            perm_ptr,
+            perm_len,
+            mtime_ptr,
+            mtime_len,
+            link_ptr,
+            link_len,
+        };
+        callback.unwrap()(&c_entry, ctx, dir);
+    };
+    prolens.0.set_cb_ftp_list_entry(wrapper);
 }
 
 #[unsafe(no_mangle)]
@@ -1053,6 +2989,356 @@ pub extern "C" fn protolens_set_cb_sip_body_stop(
     prolens.0.set_cb_sip_body_stop(wrapper);
 }
 
+// status为0表示还没有最终应答，duration只在End时有效
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct CSipCall {
+    pub event: SipCallEvent,
+    pub call_id_ptr: *const u8,
+    pub call_id_len: usize,
+    pub from_ptr: *const u8,
+    pub from_len: usize,
+    pub to_ptr: *const u8,
+    pub to_len: usize,
+    pub status: u16,
+    pub duration: u64,
+}
+
+type CbSipCall = extern "C" fn(call: *const CSipCall, ctx: *const c_void);
+
+#[unsafe(no_mangle)]
+pub extern "C" fn protolens_set_cb_sip_call(prolens: *mut FfiProlens, callback: Option<CbSipCall>) {
+    if prolens.is_null() || callback.is_none() {
+        return;
+    }
+
+    let prolens = unsafe { &mut *prolens };
+    let wrapper = move |call: &SipCall, ctx: *mut c_void| {
+        let c_call = CSipCall {
+            event: call.event,
+            call_id_ptr: call.call_id.as_ptr(),
+            call_id_len: call.call_id.len(),
+            from_ptr: call.from.as_ptr(),
+            from_len: call.from.len(),
+            to_ptr: call.to.as_ptr(),
+            to_len: call.to.len(),
+            status: call.status,
+            duration: call.duration,
+        };
+        callback.unwrap()(&c_call, ctx);
+    };
+    prolens.0.set_cb_sip_call(wrapper);
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct CSdpCodec {
+    pub payload_type: u8,
+    pub name_ptr: *const u8,
+    pub name_len: usize,
+    pub clock_rate: u32,
+    pub channels: u8,
+}
+
+impl From<&SdpCodec<'_>> for CSdpCodec {
+    fn from(codec: &SdpCodec) -> Self {
+        CSdpCodec {
+            payload_type: codec.payload_type,
+            name_ptr: codec.name.as_ptr(),
+            name_len: codec.name.len(),
+            clock_rate: codec.clock_rate,
+            channels: codec.channels,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct CSdpMedia {
+    pub media_ptr: *const u8,
+    pub media_len: usize,
+    pub port: u16,
+    pub port_count: u16,
+    pub proto_ptr: *const u8,
+    pub proto_len: usize,
+    pub conn: CIpAddr,
+    pub rtcp_port: u16,
+    pub dir: SdpDir,
+    pub codecs_ptr: *const CSdpCodec,
+    pub codecs_len: usize,
+}
+
+// codecs_ptr指向codecs，调用回调期间有效
+impl CSdpMedia {
+    fn new(media: &SdpMedia, codecs: &[CSdpCodec]) -> Self {
+        CSdpMedia {
+            media_ptr: media.media.as_ptr(),
+            media_len: media.media.len(),
+            port: media.port,
+            port_count: media.port_count,
+            proto_ptr: media.proto.as_ptr(),
+            proto_len: media.proto.len(),
+            conn: CIpAddr::from(media.conn),
+            rtcp_port: media.rtcp_port,
+            dir: media.dir,
+            codecs_ptr: codecs.as_ptr(),
+            codecs_len: codecs.len(),
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct CSdp {
+    pub origin_ptr: *const u8,
+    pub origin_len: usize,
+    pub session_name_ptr: *const u8,
+    pub session_name_len: usize,
+    pub conn: CIpAddr,
+    pub media_ptr: *const CSdpMedia,
+    pub media_len: usize,
+}
+
+type CbSipSdp = extern "C" fn(sdp: *const CSdp, ctx: *const c_void, dir: Direction);
+
+#[unsafe(no_mangle)]
+pub extern "C" fn protolens_set_cb_sip_sdp(prolens: *mut FfiProlens, callback: Option<CbSipSdp>) {
+    if prolens.is_null() || callback.is_none() {
+        return;
+    }
+
+    let prolens = unsafe { &mut *prolens };
+    let wrapper = move |sdp: &Sdp, ctx: *mut c_void, dir: Direction| {
+        let codecs: Vec<Vec<CSdpCodec>> = sdp
+            .media
+            .iter()
+            .map(|media| media.codecs.iter().map(CSdpCodec::from).collect())
+            .collect();
+        let media: Vec<CSdpMedia> = sdp
+            .media
+            .iter()
+            .zip(&codecs)
+            .map(|(media, codecs)| CSdpMedia::new(media, codecs))
+            .collect();
+        let (origin_ptr, origin_len) = opt_str(sdp.origin);
+        let (session_name_ptr, session_name_len) = opt_str(sdp.session_name);
+        let c_sdp = CSdp {
+            origin_ptr,
+            origin_len,
+            session_name_ptr,
+            session_name_len,
+            conn: CIpAddr::from(sdp.conn),
+            media_ptr: media.as_ptr(),
+            media_len: media.len(),
+        };
+        callback.unwrap()(&c_sdp, ctx, dir);
+    };
+    prolens.0.set_cb_sip_sdp(wrapper);
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct CSipMediaLink {
+    pub call_id_ptr: *const u8,
+    pub call_id_len: usize,
+    pub media_ptr: *const u8,
+    pub media_len: usize,
+    pub ip: CIpAddr,
+    pub rtp_port: u16,
+    pub rtcp_port: u16,
+}
+
+type CbSipMediaLink = extern "C" fn(link: *const CSipMediaLink, ctx: *const c_void, dir: Direction);
+
+#[unsafe(no_mangle)]
+pub extern "C" fn protolens_set_cb_sip_media_link(
+    prolens: *mut FfiProlens,
+    callback: Option<CbSipMediaLink>,
+) {
+    if prolens.is_null() || callback.is_none() {
+        return;
+    }
+
+    let prolens = unsafe { &mut *prolens };
+    let wrapper = move |link: &SipMediaLink, ctx: *mut c_void, dir: Direction| {
+        let (call_id_ptr, call_id_len) = opt_str(link.call_id);
+        let c_link = CSipMediaLink {
+            call_id_ptr,
+            call_id_len,
+            media_ptr: link.media.as_ptr(),
+            media_len: link.media.len(),
+            ip: CIpAddr::from(Some(link.ip)),
+            rtp_port: link.rtp_port,
+            rtcp_port: link.rtcp_port,
+        };
+        callback.unwrap()(&c_link, ctx, dir);
+    };
+    prolens.0.set_cb_sip_media_link(wrapper);
+}
+
+type CbRtpPkt = extern "C" fn(
+    hdr: *const RtpHeader,
+    payload: *const u8,
+    len: usize,
+    ctx: *const c_void,
+    dir: Direction,
+);
+
+#[unsafe(no_mangle)]
+pub extern "C" fn protolens_set_cb_rtp_pkt(prolens: *mut FfiProlens, callback: Option<CbRtpPkt>) {
+    if prolens.is_null() || callback.is_none() {
+        return;
+    }
+
+    let prolens = unsafe { &mut *prolens };
+    let wrapper = move |hdr: &RtpHeader, payload: &[u8], ctx: *mut c_void, dir: Direction| {
+        callback.unwrap()(hdr, payload.as_ptr(), payload.len(), ctx, dir);
+    };
+    prolens.0.set_cb_rtp_pkt(wrapper);
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct CRtpStats {
+    pub call_id_ptr: *const u8,
+    pub call_id_len: usize,
+    pub ssrc: u32,
+    pub payload_type: u8,
+    pub clock_rate: u32,
+    pub packets: u64,
+    pub expected: u64,
+    pub lost: i64,
+    pub reordered: u64,
+    pub jitter: f64,
+}
+
+type CbRtpStats = extern "C" fn(stats: *const CRtpStats, ctx: *const c_void, dir: Direction);
+
+#[unsafe(no_mangle)]
+pub extern "C" fn protolens_set_cb_rtp_stats(
+    prolens: *mut FfiProlens,
+    callback: Option<CbRtpStats>,
+) {
+    if prolens.is_null() || callback.is_none() {
+        return;
+    }
+
+    let prolens = unsafe { &mut *prolens };
+    let wrapper = move |stats: &RtpStats, ctx: *mut c_void, dir: Direction| {
+        let (call_id_ptr, call_id_len) = opt_str(stats.call_id);
+        let c_stats = CRtpStats {
+            call_id_ptr,
+            call_id_len,
+            ssrc: stats.ssrc,
+            payload_type: stats.payload_type,
+            clock_rate: stats.clock_rate,
+            packets: stats.packets,
+            expected: stats.expected,
+            lost: stats.lost,
+            reordered: stats.reordered,
+            jitter: stats.jitter,
+        };
+        callback.unwrap()(&c_stats, ctx, dir);
+    };
+    prolens.0.set_cb_rtp_stats(wrapper);
+}
+
+// RR没有sender，has_sender为false
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct CRtcpReport {
+    pub ssrc: u32,
+    pub has_sender: bool,
+    pub sender: RtcpSender,
+    pub blocks_ptr: *const RtcpBlock,
+    pub blocks_len: usize,
+}
+
+type CbRtcpReport = extern "C" fn(report: *const CRtcpReport, ctx: *const c_void, dir: Direction);
+
+#[unsafe(no_mangle)]
+pub extern "C" fn protolens_set_cb_rtcp_report(
+    prolens: *mut FfiProlens,
+    callback: Option<CbRtcpReport>,
+) {
+    if prolens.is_null() || callback.is_none() {
+        return;
+    }
+
+    let prolens = unsafe { &mut *prolens };
+    let wrapper = move |report: &RtcpReport, ctx: *mut c_void, dir: Direction| {
+        let c_report = CRtcpReport {
+            ssrc: report.ssrc,
+            has_sender: report.sender.is_some(),
+            sender: report.sender.unwrap_or(RtcpSender {
+                ntp: 0,
+                rtp_ts: 0,
+                packets: 0,
+                octets: 0,
+            }),
+            blocks_ptr: report.blocks.as_ptr(),
+            blocks_len: report.blocks.len(),
+        };
+        callback.unwrap()(&c_report, ctx, dir);
+    };
+    prolens.0.set_cb_rtcp_report(wrapper);
+}
+
+type CbRtcpSdes = extern "C" fn(
+    ssrc: u32,
+    item: u8,
+    value: *const u8,
+    len: usize,
+    ctx: *const c_void,
+    dir: Direction,
+);
+
+#[unsafe(no_mangle)]
+pub extern "C" fn protolens_set_cb_rtcp_sdes(
+    prolens: *mut FfiProlens,
+    callback: Option<CbRtcpSdes>,
+) {
+    if prolens.is_null() || callback.is_none() {
+        return;
+    }
+
+    let prolens = unsafe { &mut *prolens };
+    let wrapper = move |ssrc: u32, item: u8, value: &[u8], ctx: *mut c_void, dir: Direction| {
+        callback.unwrap()(ssrc, item, value.as_ptr(), value.len(), ctx, dir);
+    };
+    prolens.0.set_cb_rtcp_sdes(wrapper);
+}
+
+type CbRtcpBye = extern "C" fn(
+    ssrcs: *const u32,
+    ssrcs_len: usize,
+    reason: *const u8,
+    reason_len: usize,
+    ctx: *const c_void,
+    dir: Direction,
+);
+
+#[unsafe(no_mangle)]
+pub extern "C" fn protolens_set_cb_rtcp_bye(prolens: *mut FfiProlens, callback: Option<CbRtcpBye>) {
+    if prolens.is_null() || callback.is_none() {
+        return;
+    }
+
+    let prolens = unsafe { &mut *prolens };
+    let wrapper = move |ssrcs: &[u32], reason: Option<&[u8]>, ctx: *mut c_void, dir: Direction| {
+        let (reason_ptr, reason_len) = opt_ptr(reason);
+        callback.unwrap()(
+            ssrcs.as_ptr(),
+            ssrcs.len(),
+            reason_ptr,
+            reason_len,
+            ctx,
+            dir,
+        );
+    };
+    prolens.0.set_cb_rtcp_bye(wrapper);
+}
+
 #[repr(C)]
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum COpcode {
@@ -1160,10 +3446,23 @@ pub enum CQtype {
     Txt = 16,
     Aaaa = 28,
     Srv = 33,
+    Naptr = 35,
+    Ds = 43,
+    Sshfp = 44,
+    Rrsig = 46,
+    Nsec = 47,
+    Dnskey = 48,
+    Nsec3 = 50,
+    Tlsa = 52,
+    Svcb = 64,
+    Https = 65,
     Axfr = 252,
     Mailb = 253,
     Maila = 254,
     All = 255,
+    Uri = 256,
+    Caa = 257,
+    Other = 0,
 }
 
 impl From<Qtype> for CQtype {
@@ -1187,10 +3486,23 @@ impl From<Qtype> for CQtype {
             Qtype::Txt => CQtype::Txt,
             Qtype::Aaaa => CQtype::Aaaa,
             Qtype::Srv => CQtype::Srv,
+            Qtype::Naptr => CQtype::Naptr,
+            Qtype::Ds => CQtype::Ds,
+            Qtype::Sshfp => CQtype::Sshfp,
+            Qtype::Rrsig => CQtype::Rrsig,
+            Qtype::Nsec => CQtype::Nsec,
+            Qtype::Dnskey => CQtype::Dnskey,
+            Qtype::Nsec3 => CQtype::Nsec3,
+            Qtype::Tlsa => CQtype::Tlsa,
+            Qtype::Svcb => CQtype::Svcb,
+            Qtype::Https => CQtype::Https,
             Qtype::Axfr => CQtype::Axfr,
             Qtype::Mailb => CQtype::Mailb,
             Qtype::Maila => CQtype::Maila,
             Qtype::All => CQtype::All,
+            Qtype::Uri => CQtype::Uri,
+            Qtype::Caa => CQtype::Caa,
+            Qtype::Other(_) => CQtype::Other,
         }
     }
 }
@@ -1217,6 +3529,7 @@ impl From<Qclass> for CQclass {
     }
 }
 
+// 不认识的类型是Other，类型值在Crr的type_code中
 #[repr(C)]
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum CType {
@@ -1238,8 +3551,21 @@ pub enum CType {
     Txt = 16,
     Aaaa = 28,
     Srv = 33,
+    Naptr = 35,
     Opt = 41,
+    Ds = 43,
+    Sshfp = 44,
+    Rrsig = 46,
     Nsec = 47,
+    Dnskey = 48,
+    Nsec3 = 50,
+    Nsec3param = 51,
+    Tlsa = 52,
+    Svcb = 64,
+    Https = 65,
+    Uri = 256,
+    Caa = 257,
+    Other = 0,
 }
 
 impl From<Type> for CType {
@@ -1263,8 +3589,21 @@ impl From<Type> for CType {
             Type::Txt => CType::Txt,
             Type::Aaaa => CType::Aaaa,
             Type::Srv => CType::Srv,
+            Type::Naptr => CType::Naptr,
             Type::Opt => CType::Opt,
+            Type::Ds => CType::Ds,
+            Type::Sshfp => CType::Sshfp,
+            Type::Rrsig => CType::Rrsig,
             Type::Nsec => CType::Nsec,
+            Type::Dnskey => CType::Dnskey,
+            Type::Nsec3 => CType::Nsec3,
+            Type::Nsec3param => CType::Nsec3param,
+            Type::Tlsa => CType::Tlsa,
+            Type::Svcb => CType::Svcb,
+            Type::Https => CType::Https,
+            Type::Uri => CType::Uri,
+            Type::Caa => CType::Caa,
+            Type::Other(_) => CType::Other,
         }
     }
 }
@@ -1305,6 +3644,19 @@ pub struct Crr {
     pub mx_data: CRdMx,
     pub ipv4_addr: [u8; 4],
     pub ipv6_addr: [u8; 16],
+    pub type_code: u16,
+    pub hinfo_data: CRdHinfo,
+    pub naptr_data: CRdNaptr,
+    pub ds_data: CRdDs,
+    pub sshfp_data: CRdSshfp,
+    pub rrsig_data: CRdRrsig,
+    pub nsec_data: CRdNsec,
+    pub dnskey_data: CRdDnskey,
+    pub nsec3_data: CRdNsec3,
+    pub tlsa_data: CRdTlsa,
+    pub svcb_data: CRdSvcb,
+    pub uri_data: CRdUri,
+    pub caa_data: CRdCaa,
 }
 
 #[repr(C)]
@@ -1318,6 +3670,34 @@ pub struct COptRR {
     pub rdata_len: usize,
     pub ipv4_addr: [u8; 4],
     pub ipv6_addr: [u8; 16],
+    pub dnssec_ok: bool,
+    pub ecs: CEdnsEcs,
+    pub nsid: CDnsBytes,
+    pub cookie_client: CDnsBytes,
+    pub cookie_server: CDnsBytes,
+    pub keepalive_present: bool,
+    pub keepalive_timeout: u16,
+    pub padding_len: usize,
+    pub ede: CEdnsEde,
+}
+
+// family为0表示没有ECS
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct CEdnsEcs {
+    pub family: u16,
+    pub source_prefix: u8,
+    pub scope_prefix: u8,
+    pub addr: CIpAddr,
+}
+
+// 可以有多个EDE，这里只有第一个
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default)]
+pub struct CEdnsEde {
+    pub present: bool,
+    pub info_code: u16,
+    pub extra_text: CDnsBytes,
 }
 
 #[repr(C)]
@@ -1344,12 +3724,370 @@ pub struct CRdSrv {
     pub target_len: usize,
 }
 
-#[repr(C)]
-#[derive(Debug, Copy, Clone)]
-pub struct CRdMx {
-    pub preference: u16,
-    pub exchange_ptr: *const u8,
-    pub exchange_len: usize,
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct CRdMx {
+    pub preference: u16,
+    pub exchange_ptr: *const u8,
+    pub exchange_len: usize,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct CDnsBytes {
+    pub ptr: *const u8,
+    pub len: usize,
+}
+
+impl Default for CDnsBytes {
+    fn default() -> Self {
+        CDnsBytes {
+            ptr: std::ptr::null(),
+            len: 0,
+        }
+    }
+}
+
+impl From<&[u8]> for CDnsBytes {
+    fn from(data: &[u8]) -> Self {
+        CDnsBytes {
+            ptr: data.as_ptr(),
+            len: data.len(),
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default)]
+pub struct CRdHinfo {
+    pub cpu: CDnsBytes,
+    pub os: CDnsBytes,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default)]
+pub struct CRdNaptr {
+    pub order: u16,
+    pub preference: u16,
+    pub flags: CDnsBytes,
+    pub services: CDnsBytes,
+    pub regexp: CDnsBytes,
+    pub replacement: CDnsBytes,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default)]
+pub struct CRdDs {
+    pub key_tag: u16,
+    pub algorithm: u8,
+    pub digest_type: u8,
+    pub digest: CDnsBytes,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default)]
+pub struct CRdSshfp {
+    pub algorithm: u8,
+    pub fp_type: u8,
+    pub fingerprint: CDnsBytes,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default)]
+pub struct CRdRrsig {
+    pub type_covered: u16,
+    pub algorithm: u8,
+    pub labels: u8,
+    pub original_ttl: u32,
+    pub expiration: u32,
+    pub inception: u32,
+    pub key_tag: u16,
+    pub signer: CDnsBytes,
+    pub signature: CDnsBytes,
+}
+
+// type_bitmap是rdata中的原始格式
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default)]
+pub struct CRdNsec {
+    pub next: CDnsBytes,
+    pub type_bitmap: CDnsBytes,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default)]
+pub struct CRdDnskey {
+    pub flags: u16,
+    pub protocol: u8,
+    pub algorithm: u8,
+    pub public_key: CDnsBytes,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default)]
+pub struct CRdNsec3 {
+    pub hash_algorithm: u8,
+    pub flags: u8,
+    pub iterations: u16,
+    pub salt: CDnsBytes,
+    pub next_hashed: CDnsBytes,
+    pub type_bitmap: CDnsBytes,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default)]
+pub struct CRdTlsa {
+    pub usage: u8,
+    pub selector: u8,
+    pub matching_type: u8,
+    pub data: CDnsBytes,
+}
+
+// SVCB和HTTPS共用。alpn/ipv4hint/ipv6hint是线上格式，port为0表示没有，params是全部参数
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default)]
+pub struct CRdSvcb {
+    pub priority: u16,
+    pub target: CDnsBytes,
+    pub alpn: CDnsBytes,
+    pub no_default_alpn: bool,
+    pub port: u16,
+    pub ipv4hint: CDnsBytes,
+    pub ech: CDnsBytes,
+    pub ipv6hint: CDnsBytes,
+    pub params: CDnsBytes,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default)]
+pub struct CRdUri {
+    pub priority: u16,
+    pub weight: u16,
+    pub target: CDnsBytes,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default)]
+pub struct CRdCaa {
+    pub flags: u8,
+    pub tag: CDnsBytes,
+    pub value: CDnsBytes,
+}
+
+impl Crr {
+    fn new(rr: &RR) -> Self {
+        Crr {
+            unicast: rr.unicast,
+            name_ptr: rr.name.as_ptr(),
+            name_len: rr.name.len(),
+            rtype: rr.rtype.into(),
+            class: rr.class.into(),
+            ttl: rr.ttl,
+            rdata_ptr: std::ptr::null(),
+            rdata_len: 0,
+            soa_data: CRdSoa {
+                primary_ns_ptr: std::ptr::null(),
+                primary_ns_len: 0,
+                mailbox_ptr: std::ptr::null(),
+                mailbox_len: 0,
+                serial: 0,
+                refresh: 0,
+                retry: 0,
+                expire: 0,
+                minimum_ttl: 0,
+            },
+            srv_data: CRdSrv {
+                priority: 0,
+                weight: 0,
+                port: 0,
+                target_ptr: std::ptr::null(),
+                target_len: 0,
+            },
+            mx_data: CRdMx {
+                preference: 0,
+                exchange_ptr: std::ptr::null(),
+                exchange_len: 0,
+            },
+            ipv4_addr: [0; 4],
+            ipv6_addr: [0; 16],
+            type_code: rr.rtype.into(),
+            hinfo_data: CRdHinfo::default(),
+            naptr_data: CRdNaptr::default(),
+            ds_data: CRdDs::default(),
+            sshfp_data: CRdSshfp::default(),
+            rrsig_data: CRdRrsig::default(),
+            nsec_data: CRdNsec::default(),
+            dnskey_data: CRdDnskey::default(),
+            nsec3_data: CRdNsec3::default(),
+            tlsa_data: CRdTlsa::default(),
+            svcb_data: CRdSvcb::default(),
+            uri_data: CRdUri::default(),
+            caa_data: CRdCaa::default(),
+        }
+    }
+
+    // A/AAAA的rdata_ptr指向自身，要在回调用的变量上调用
+    fn set_rdata(&mut self, rdata: &Rdata) {
+        let main: &[u8] = match rdata {
+            Rdata::A(addr) => {
+                self.ipv4_addr = addr.octets();
+                self.rdata_ptr = self.ipv4_addr.as_ptr();
+                self.rdata_len = 4;
+                return;
+            }
+            Rdata::Aaaa(addr) => {
+                self.ipv6_addr = addr.octets();
+                self.rdata_ptr = self.ipv6_addr.as_ptr();
+                self.rdata_len = 16;
+                return;
+            }
+            Rdata::Cname(data) | Rdata::NS(data) | Rdata::Ptr(data) => data,
+            Rdata::Txt(data) | Rdata::Unknown(data) => data,
+            Rdata::MX(mx) => {
+                self.mx_data.preference = mx.preference;
+                self.mx_data.exchange_ptr = mx.exchange.as_ptr();
+                self.mx_data.exchange_len = mx.exchange.len();
+                mx.exchange
+            }
+            Rdata::Soa(soa) => {
+                self.soa_data.primary_ns_ptr = soa.primary_ns.as_ptr();
+                self.soa_data.primary_ns_len = soa.primary_ns.len();
+                self.soa_data.mailbox_ptr = soa.mailbox.as_ptr();
+                self.soa_data.mailbox_len = soa.mailbox.len();
+                self.soa_data.serial = soa.serial;
+                self.soa_data.refresh = soa.refresh;
+                self.soa_data.retry = soa.retry;
+                self.soa_data.expire = soa.expire;
+                self.soa_data.minimum_ttl = soa.minimum_ttl;
+                soa.primary_ns
+            }
+            Rdata::Srv(srv) => {
+                self.srv_data.priority = srv.priority;
+                self.srv_data.weight = srv.weight;
+                self.srv_data.port = srv.port;
+                self.srv_data.target_ptr = srv.target.as_ptr();
+                self.srv_data.target_len = srv.target.len();
+                srv.target
+            }
+            Rdata::Hinfo(hinfo) => {
+                self.hinfo_data = CRdHinfo {
+                    cpu: hinfo.cpu.into(),
+                    os: hinfo.os.into(),
+                };
+                hinfo.cpu
+            }
+            Rdata::Naptr(naptr) => {
+                self.naptr_data = CRdNaptr {
+                    order: naptr.order,
+                    preference: naptr.preference,
+                    flags: naptr.flags.into(),
+                    services: naptr.services.into(),
+                    regexp: naptr.regexp.into(),
+                    replacement: naptr.replacement.into(),
+                };
+                naptr.replacement
+            }
+            Rdata::Ds(ds) => {
+                self.ds_data = CRdDs {
+                    key_tag: ds.key_tag,
+                    algorithm: ds.algorithm,
+                    digest_type: ds.digest_type,
+                    digest: ds.digest.into(),
+                };
+                ds.digest
+            }
+            Rdata::Sshfp(sshfp) => {
+                self.sshfp_data = CRdSshfp {
+                    algorithm: sshfp.algorithm,
+                    fp_type: sshfp.fp_type,
+                    fingerprint: sshfp.fingerprint.into(),
+                };
+                sshfp.fingerprint
+            }
+            Rdata::Rrsig(rrsig) => {
+                self.rrsig_data = CRdRrsig {
+                    type_covered: rrsig.type_covered.into(),
+                    algorithm: rrsig.algorithm,
+                    labels: rrsig.labels,
+                    original_ttl: rrsig.original_ttl,
+                    expiration: rrsig.expiration,
+                    inception: rrsig.inception,
+                    key_tag: rrsig.key_tag,
+                    signer: rrsig.signer.into(),
+                    signature: rrsig.signature.into(),
+                };
+                rrsig.signature
+            }
+            Rdata::Nsec(nsec) => {
+                self.nsec_data = CRdNsec {
+                    next: nsec.next.into(),
+                    type_bitmap: nsec.type_bitmap.into(),
+                };
+                nsec.next
+            }
+            Rdata::Dnskey(dnskey) => {
+                self.dnskey_data = CRdDnskey {
+                    flags: dnskey.flags,
+                    protocol: dnskey.protocol,
+                    algorithm: dnskey.algorithm,
+                    public_key: dnskey.public_key.into(),
+                };
+                dnskey.public_key
+            }
+            Rdata::Nsec3(nsec3) => {
+                self.nsec3_data = CRdNsec3 {
+                    hash_algorithm: nsec3.hash_algorithm,
+                    flags: nsec3.flags,
+                    iterations: nsec3.iterations,
+                    salt: nsec3.salt.into(),
+                    next_hashed: nsec3.next_hashed.into(),
+                    type_bitmap: nsec3.type_bitmap.into(),
+                };
+                nsec3.next_hashed
+            }
+            Rdata::Tlsa(tlsa) => {
+                self.tlsa_data = CRdTlsa {
+                    usage: tlsa.usage,
+                    selector: tlsa.selector,
+                    matching_type: tlsa.matching_type,
+                    data: tlsa.data.into(),
+                };
+                tlsa.data
+            }
+            Rdata::Svcb(svcb) => {
+                self.svcb_data = CRdSvcb {
+                    priority: svcb.priority,
+                    target: svcb.target.into(),
+                    alpn: svcb.alpn.into(),
+                    no_default_alpn: svcb.no_default_alpn,
+                    port: svcb.port.unwrap_or(0),
+                    ipv4hint: svcb.ipv4hint.into(),
+                    ech: svcb.ech.into(),
+                    ipv6hint: svcb.ipv6hint.into(),
+                    params: svcb.params.into(),
+                };
+                svcb.target
+            }
+            Rdata::Uri(uri) => {
+                self.uri_data = CRdUri {
+                    priority: uri.priority,
+                    weight: uri.weight,
+                    target: uri.target.into(),
+                };
+                uri.target
+            }
+            Rdata::Caa(caa) => {
+                self.caa_data = CRdCaa {
+                    flags: caa.flags,
+                    tag: caa.tag.into(),
+                    value: caa.value.into(),
+                };
+                caa.value
+            }
+        };
+        self.rdata_ptr = main.as_ptr();
+        self.rdata_len = main.len();
+    }
 }
 
 type DnsHeaderCbFn = extern "C" fn(header: CHeader, offset: usize, ctx: *mut c_void);
@@ -1422,104 +4160,8 @@ pub extern "C" fn protolens_set_cb_dns_answer(
 
     let prolens = unsafe { &mut *prolens };
     let wrapper = move |rr: RR, offset: usize, ctx: *mut c_void| {
-        let mut c_rr = Crr {
-            unicast: rr.unicast,
-            name_ptr: rr.name.as_ptr(),
-            name_len: rr.name.len(),
-            rtype: rr.rtype.into(),
-            class: rr.class.into(),
-            ttl: rr.ttl,
-            rdata_ptr: std::ptr::null(),
-            rdata_len: 0,
-            soa_data: CRdSoa {
-                primary_ns_ptr: std::ptr::null(),
-                primary_ns_len: 0,
-                mailbox_ptr: std::ptr::null(),
-                mailbox_len: 0,
-                serial: 0,
-                refresh: 0,
-                retry: 0,
-                expire: 0,
-                minimum_ttl: 0,
-            },
-            srv_data: CRdSrv {
-                priority: 0,
-                weight: 0,
-                port: 0,
-                target_ptr: std::ptr::null(),
-                target_len: 0,
-            },
-            mx_data: CRdMx {
-                preference: 0,
-                exchange_ptr: std::ptr::null(),
-                exchange_len: 0,
-            },
-            ipv4_addr: [0; 4],
-            ipv6_addr: [0; 16],
-        };
-
-        match &rr.rdata {
-            Rdata::A(addr) => {
-                c_rr.ipv4_addr = addr.octets();
-                c_rr.rdata_ptr = c_rr.ipv4_addr.as_ptr();
-                c_rr.rdata_len = 4;
-            }
-            Rdata::Aaaa(addr) => {
-                c_rr.ipv6_addr = addr.octets();
-                c_rr.rdata_ptr = c_rr.ipv6_addr.as_ptr();
-                c_rr.rdata_len = 16;
-            }
-            Rdata::Cname(data) => {
-                c_rr.rdata_ptr = data.as_ptr();
-                c_rr.rdata_len = data.len();
-            }
-            Rdata::MX(mx) => {
-                c_rr.mx_data.preference = mx.preference;
-                c_rr.mx_data.exchange_ptr = mx.exchange.as_ptr();
-                c_rr.mx_data.exchange_len = mx.exchange.len();
-                c_rr.rdata_ptr = mx.exchange.as_ptr();
-                c_rr.rdata_len = mx.exchange.len();
-            }
-            Rdata::NS(data) => {
-                c_rr.rdata_ptr = data.as_ptr();
-                c_rr.rdata_len = data.len();
-            }
-            Rdata::Ptr(data) => {
-                c_rr.rdata_ptr = data.as_ptr();
-                c_rr.rdata_len = data.len();
-            }
-            Rdata::Soa(soa) => {
-                c_rr.soa_data.primary_ns_ptr = soa.primary_ns.as_ptr();
-                c_rr.soa_data.primary_ns_len = soa.primary_ns.len();
-                c_rr.soa_data.mailbox_ptr = soa.mailbox.as_ptr();
-                c_rr.soa_data.mailbox_len = soa.mailbox.len();
-                c_rr.soa_data.serial = soa.serial;
-                c_rr.soa_data.refresh = soa.refresh;
-                c_rr.soa_data.retry = soa.retry;
-                c_rr.soa_data.expire = soa.expire;
-                c_rr.soa_data.minimum_ttl = soa.minimum_ttl;
-                c_rr.rdata_ptr = soa.primary_ns.as_ptr();
-                c_rr.rdata_len = soa.primary_ns.len();
-            }
-            Rdata::Srv(srv) => {
-                c_rr.srv_data.priority = srv.priority;
-                c_rr.srv_data.weight = srv.weight;
-                c_rr.srv_data.port = srv.port;
-                c_rr.srv_data.target_ptr = srv.target.as_ptr();
-                c_rr.srv_data.target_len = srv.target.len();
-                c_rr.rdata_ptr = srv.target.as_ptr();
-                c_rr.rdata_len = srv.target.len();
-            }
-            Rdata::Txt(data) => {
-                c_rr.rdata_ptr = data.as_ptr();
-                c_rr.rdata_len = data.len();
-            }
-            Rdata::Unknown(data) => {
-                c_rr.rdata_ptr = data.as_ptr();
-                c_rr.rdata_len = data.len();
-            }
-        };
-
+        let mut c_rr = Crr::new(&rr);
+        c_rr.set_rdata(&rr.rdata);
         callback.unwrap()(c_rr, offset, ctx);
     };
     prolens.0.set_cb_dns_answer(wrapper);
@@ -1533,104 +4175,8 @@ pub extern "C" fn protolens_set_cb_dns_auth(prolens: *mut FfiProlens, callback:
 
     let prolens = unsafe { &mut *prolens };
     let wrapper = move |rr: RR, offset: usize, ctx: *mut c_void| {
-        let mut c_rr = Crr {
-            unicast: rr.unicast,
-            name_ptr: rr.name.as_ptr(),
-            name_len: rr.name.len(),
-            rtype: rr.rtype.into(),
-            class: rr.class.into(),
-            ttl: rr.ttl,
-            rdata_ptr: std::ptr::null(),
-            rdata_len: 0,
-            soa_data: CRdSoa {
-                primary_ns_ptr: std::ptr::null(),
-                primary_ns_len: 0,
-                mailbox_ptr: std::ptr::null(),
-                mailbox_len: 0,
-                serial: 0,
-                refresh: 0,
-                retry: 0,
-                expire: 0,
-                minimum_ttl: 0,
-            },
-            srv_data: CRdSrv {
-                priority: 0,
-                weight: 0,
-                port: 0,
-                target_ptr: std::ptr::null(),
-                target_len: 0,
-            },
-            mx_data: CRdMx {
-                preference: 0,
-                exchange_ptr: std::ptr::null(),
-                exchange_len: 0,
-            },
-            ipv4_addr: [0; 4],
-            ipv6_addr: [0; 16],
-        };
-
-        match &rr.rdata {
-            Rdata::A(addr) => {
-                c_rr.ipv4_addr = addr.octets();
-                c_rr.rdata_ptr = c_rr.ipv4_addr.as_ptr();
-                c_rr.rdata_len = 4;
-            }
-            Rdata::Aaaa(addr) => {
-                c_rr.ipv6_addr = addr.octets();
-                c_rr.rdata_ptr = c_rr.ipv6_addr.as_ptr();
-                c_rr.rdata_len = 16;
-            }
-            Rdata::Cname(data) => {
-                c_rr.rdata_ptr = data.as_ptr();
-                c_rr.rdata_len = data.len();
-            }
-            Rdata::MX(mx) => {
-                c_rr.mx_data.preference = mx.preference;
-                c_rr.mx_data.exchange_ptr = mx.exchange.as_ptr();
-                c_rr.mx_data.exchange_len = mx.exchange.len();
-                c_rr.rdata_ptr = mx.exchange.as_ptr();
-                c_rr.rdata_len = mx.exchange.len();
-            }
-            Rdata::NS(data) => {
-                c_rr.rdata_ptr = data.as_ptr();
-                c_rr.rdata_len = data.len();
-            }
-            Rdata::Ptr(data) => {
-                c_rr.rdata_ptr = data.as_ptr();
-                c_rr.rdata_len = data.len();
-            }
-            Rdata::Soa(soa) => {
-                c_rr.soa_data.primary_ns_ptr = soa.primary_ns.as_ptr();
-                c_rr.soa_data.primary_ns_len = soa.primary_ns.len();
-                c_rr.soa_data.mailbox_ptr = soa.mailbox.as_ptr();
-                c_rr.soa_data.mailbox_len = soa.mailbox.len();
-                c_rr.soa_data.serial = soa.serial;
-                c_rr.soa_data.refresh = soa.refresh;
-                c_rr.soa_data.retry = soa.retry;
-                c_rr.soa_data.expire = soa.expire;
-                c_rr.soa_data.minimum_ttl = soa.minimum_ttl;
-                c_rr.rdata_ptr = soa.primary_ns.as_ptr();
-                c_rr.rdata_len = soa.primary_ns.len();
-            }
-            Rdata::Srv(srv) => {
-                c_rr.srv_data.priority = srv.priority;
-                c_rr.srv_data.weight = srv.weight;
-                c_rr.srv_data.port = srv.port;
-                c_rr.srv_data.target_ptr = srv.target.as_ptr();
-                c_rr.srv_data.target_len = srv.target.len();
-                c_rr.rdata_ptr = srv.target.as_ptr();
-                c_rr.rdata_len = srv.target.len();
-            }
-            Rdata::Txt(data) => {
-                c_rr.rdata_ptr = data.as_ptr();
-                c_rr.rdata_len = data.len();
-            }
-            Rdata::Unknown(data) => {
-                c_rr.rdata_ptr = data.as_ptr();
-                c_rr.rdata_len = data.len();
-            }
-        };
-
+        let mut c_rr = Crr::new(&rr);
+        c_rr.set_rdata(&rr.rdata);
         callback.unwrap()(c_rr, offset, ctx);
     };
     prolens.0.set_cb_dns_auth(wrapper);
@@ -1644,105 +4190,8 @@ pub extern "C" fn protolens_set_cb_dns_add(prolens: *mut FfiProlens, callback: O
 
     let prolens = unsafe { &mut *prolens };
     let wrapper = move |rr: RR, offset: usize, ctx: *mut c_void| {
-        // 使用与dns_answer相同的逻辑
-        let mut c_rr = Crr {
-            unicast: rr.unicast,
-            name_ptr: rr.name.as_ptr(),
-            name_len: rr.name.len(),
-            rtype: rr.rtype.into(),
-            class: rr.class.into(),
-            ttl: rr.ttl,
-            rdata_ptr: std::ptr::null(),
-            rdata_len: 0,
-            soa_data: CRdSoa {
-                primary_ns_ptr: std::ptr::null(),
-                primary_ns_len: 0,
-                mailbox_ptr: std::ptr::null(),
-                mailbox_len: 0,
-                serial: 0,
-                refresh: 0,
-                retry: 0,
-                expire: 0,
-                minimum_ttl: 0,
-            },
-            srv_data: CRdSrv {
-                priority: 0,
-                weight: 0,
-                port: 0,
-                target_ptr: std::ptr::null(),
-                target_len: 0,
-            },
-            mx_data: CRdMx {
-                preference: 0,
-                exchange_ptr: std::ptr::null(),
-                exchange_len: 0,
-            },
-            ipv4_addr: [0; 4],
-            ipv6_addr: [0; 16],
-        };
-
-        match &rr.rdata {
-            Rdata::A(addr) => {
-                c_rr.ipv4_addr = addr.octets();
-                c_rr.rdata_ptr = c_rr.ipv4_addr.as_ptr();
-                c_rr.rdata_len = 4;
-            }
-            Rdata::Aaaa(addr) => {
-                c_rr.ipv6_addr = addr.octets();
-                c_rr.rdata_ptr = c_rr.ipv6_addr.as_ptr();
-                c_rr.rdata_len = 16;
-            }
-            Rdata::Cname(data) => {
-                c_rr.rdata_ptr = data.as_ptr();
-                c_rr.rdata_len = data.len();
-            }
-            Rdata::MX(mx) => {
-                c_rr.mx_data.preference = mx.preference;
-                c_rr.mx_data.exchange_ptr = mx.exchange.as_ptr();
-                c_rr.mx_data.exchange_len = mx.exchange.len();
-                c_rr.rdata_ptr = mx.exchange.as_ptr();
-                c_rr.rdata_len = mx.exchange.len();
-            }
-            Rdata::NS(data) => {
-                c_rr.rdata_ptr = data.as_ptr();
-                c_rr.rdata_len = data.len();
-            }
-            Rdata::Ptr(data) => {
-                c_rr.rdata_ptr = data.as_ptr();
-                c_rr.rdata_len = data.len();
-            }
-            Rdata::Soa(soa) => {
-                c_rr.soa_data.primary_ns_ptr = soa.primary_ns.as_ptr();
-                c_rr.soa_data.primary_ns_len = soa.primary_ns.len();
-                c_rr.soa_data.mailbox_ptr = soa.mailbox.as_ptr();
-                c_rr.soa_data.mailbox_len = soa.mailbox.len();
-                c_rr.soa_data.serial = soa.serial;
-                c_rr.soa_data.refresh = soa.refresh;
-                c_rr.soa_data.retry = soa.retry;
-                c_rr.soa_data.expire = soa.expire;
-                c_rr.soa_data.minimum_ttl = soa.minimum_ttl;
-                c_rr.rdata_ptr = soa.primary_ns.as_ptr();
-                c_rr.rdata_len = soa.primary_ns.len();
-            }
-            Rdata::Srv(srv) => {
-                c_rr.srv_data.priority = srv.priority;
-                c_rr.srv_data.weight = srv.weight;
-                c_rr.srv_data.port = srv.port;
-                c_rr.srv_data.target_ptr = srv.target.as_ptr();
-                c_rr.srv_data.target_len = srv.target.len();
-                c_rr.rdata_ptr = srv.target.as_ptr();
-                c_rr.rdata_len = srv.target.len();
-            }
-            Rdata::Txt(data) => {
-                c_rr.rdata_ptr = data.as_ptr();
-                c_rr.rdata_len = data.len();
-            }
-            Rdata::Unknown(data) => {
-                c_rr.rdata_ptr = data.as_ptr();
-                c_rr.rdata_len = data.len();
-            }
-        };
-
+        let mut c_rr = Crr::new(&rr);
+        c_rr.set_rdata(&rr.rdata);
         callback.unwrap()(c_rr, offset, ctx);
     };
     prolens.0.set_cb_dns_add(wrapper);
@@ -1768,8 +4217,56 @@ pub extern "C" fn protolens_set_cb_dns_opt_add(
             rdata_len: 0,
             ipv4_addr: [0; 4],
             ipv6_addr: [0; 16],
+            dnssec_ok: opt_rr.dnssec_ok(),
+            ecs: CEdnsEcs {
+                family: 0,
+                source_prefix: 0,
+                scope_prefix: 0,
+                addr: CIpAddr::from(None),
+            },
+            nsid: CDnsBytes::default(),
+            cookie_client: CDnsBytes::default(),
+            cookie_server: CDnsBytes::default(),
+            keepalive_present: false,
+            keepalive_timeout: 0,
+            padding_len: 0,
+            ede: CEdnsEde::default(),
         };
 
+        for opt in opt_rr.options() {
+            match opt {
+                EdnsOpt::Nsid(nsid) => c_opt_rr.nsid = nsid.into(),
+                EdnsOpt::ClientSubnet(ecs) => {
+                    c_opt_rr.ecs = CEdnsEcs {
+                        family: ecs.family,
+                        source_prefix: ecs.source_prefix,
+                        scope_prefix: ecs.scope_prefix,
+                        addr: CIpAddr::from(Some(ecs.addr)),
+                    }
+                }
+                EdnsOpt::Cookie { client, server } => {
+                    c_opt_rr.cookie_client = client.into();
+                    c_opt_rr.cookie_server = server.into();
+                }
+                EdnsOpt::TcpKeepalive(timeout) => {
+                    c_opt_rr.keepalive_present = true;
+                    c_opt_rr.keepalive_timeout = timeout.unwrap_or(0);
+                }
+                EdnsOpt::Padding(len) => c_opt_rr.padding_len += len,
+                EdnsOpt::ExtendedError {
+                    info_code,
+                    extra_text,
+                } if !c_opt_rr.ede.present => {
+                    c_opt_rr.ede = CEdnsEde {
+                        present: true,
+                        info_code,
+                        extra_text: extra_text.into(),
+                    }
+                }
+                _ => {}
+            }
+        }
+
         // 根据rdata类型设置相应的数据
         match &opt_rr.rdata {
             Rdata::A(addr) => {
@@ -1814,6 +4311,7 @@ pub extern "C" fn protolens_set_cb_dns_opt_add(
                 c_opt_rr.rdata_ptr = data.as_ptr();
                 c_opt_rr.rdata_len = data.len();
             }
+            _ => {}
         };
 
         callback.unwrap()(c_opt_rr, offset, ctx);
@@ -1833,3 +4331,88 @@ pub extern "C" fn protolens_set_cb_dns_end(prolens: *mut FfiProlens, callback: O
     };
     prolens.0.set_cb_dns_end(wrapper);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::mem::{offset_of, size_of};
+
+    #[test]
+    fn test_l7proto_ordinal() {
+        assert_eq!(CL7Proto::OrdPacket as i32, 0);
+        assert_eq!(CL7Proto::Sip as i32, 7);
+        assert_eq!(CL7Proto::Unknown as i32, 8);
+        assert_eq!(CL7Proto::DnsUdp as i32, 9);
+        assert_eq!(CL7Proto::Http2 as i32, 10);
+        assert_eq!(CL7Proto::SipTcp as i32, 11);
+        assert_eq!(CL7Proto::Rtp as i32, 12);
+        assert_eq!(CL7Proto::DnsTcp as i32, 13);
+        assert_eq!(L7Proto::from(CL7Proto::DnsUdp), L7Proto::DnsUdp);
+        assert_eq!(L7Proto::from(CL7Proto::Unknown), L7Proto::Unknown);
+    }
+
+    // 和dist/protolens.h保持一致，数值来自用gcc编译头文件的结果(x86_64)
+    #[test]
+    fn test_dns_layout() {
+        assert_eq!(CType::Hinfo as i32, 13);
+        assert_eq!(CType::Opt as i32, 41);
+        assert_eq!(CType::Nsec3param as i32, 51);
+        assert_eq!(CType::Caa as i32, 257);
+        assert_eq!(CType::Other as i32, 0);
+        assert_eq!(CQtype::Hinfo as i32, 13);
+        assert_eq!(CQtype::All as i32, 255);
+        assert_eq!(COpcode::Reserved as i32, 255);
+        assert_eq!(CRcode::Reserved as i32, 255);
+
+        assert_eq!(size_of::<CHeader>(), 28);
+        assert_eq!(offset_of!(CHeader, opcode), 4);
+        assert_eq!(offset_of!(CHeader, rcode), 16);
+        assert_eq!(offset_of!(CHeader, qcount), 20);
+        assert_eq!(offset_of!(CHeader, arcount), 26);
+        assert_eq!(size_of::<CRdSoa>(), 56);
+        assert_eq!(size_of::<CRdSrv>(), 24);
+        assert_eq!(size_of::<CRdMx>(), 24);
+
+        assert_eq!(size_of::<Crr>(), 704);
+        assert_eq!(offset_of!(Crr, name_ptr), 8);
+        assert_eq!(offset_of!(Crr, rtype), 24);
+        assert_eq!(offset_of!(Crr, class), 28);
+        assert_eq!(offset_of!(Crr, ttl), 32);
+        assert_eq!(offset_of!(Crr, rdata_ptr), 40);
+        assert_eq!(offset_of!(Crr, soa_data), 56);
+        assert_eq!(offset_of!(Crr, srv_data), 112);
+        assert_eq!(offset_of!(Crr, mx_data), 136);
+        assert_eq!(offset_of!(Crr, ipv4_addr), 160);
+        assert_eq!(offset_of!(Crr, ipv6_addr), 164);
+        assert_eq!(offset_of!(Crr, type_code), 180);
+        assert_eq!(offset_of!(Crr, hinfo_data), 184);
+        assert_eq!(offset_of!(Crr, naptr_data), 216);
+        assert_eq!(offset_of!(Crr, ds_data), 288);
+        assert_eq!(offset_of!(Crr, sshfp_data), 312);
+        assert_eq!(offset_of!(Crr, rrsig_data), 336);
+        assert_eq!(offset_of!(Crr, nsec_data), 392);
+        assert_eq!(offset_of!(Crr, dnskey_data), 424);
+        assert_eq!(offset_of!(Crr, nsec3_data), 448);
+        assert_eq!(offset_of!(Crr, tlsa_data), 504);
+        assert_eq!(offset_of!(Crr, svcb_data), 528);
+        assert_eq!(offset_of!(Crr, uri_data), 640);
+        assert_eq!(offset_of!(Crr, caa_data), 664);
+
+        assert_eq!(size_of::<CEdnsEcs>(), 22);
+        assert_eq!(size_of::<CEdnsEde>(), 24);
+        assert_eq!(size_of::<COptRR>(), 160);
+        assert_eq!(offset_of!(COptRR, flags), 4);
+        assert_eq!(offset_of!(COptRR, rdata_ptr), 8);
+        assert_eq!(offset_of!(COptRR, ipv4_addr), 24);
+        assert_eq!(offset_of!(COptRR, ipv6_addr), 28);
+        assert_eq!(offset_of!(COptRR, dnssec_ok), 44);
+        assert_eq!(offset_of!(COptRR, ecs), 46);
+        assert_eq!(offset_of!(COptRR, nsid), 72);
+        assert_eq!(offset_of!(COptRR, cookie_client), 88);
+        assert_eq!(offset_of!(COptRR, cookie_server), 104);
+        assert_eq!(offset_of!(COptRR, keepalive_present), 120);
+        assert_eq!(offset_of!(COptRR, keepalive_timeout), 122);
+        assert_eq!(offset_of!(COptRR, padding_len), 128);
+        assert_eq!(offset_of!(COptRR, ede), 136);
+    }
+}
@@ -0,0 +1,672 @@
+// HPACK(RFC 7541)解码。每个方向一个解码器，动态表只由该方向的header block更新
+use std::collections::VecDeque;
+use std::sync::OnceLock;
+
+// 对端可以通过SETTINGS调大动态表，这里只设一个上限防止被撑爆
+const MAX_TABLE_SIZE: usize = 1024 * 1024;
+const DEFAULT_TABLE_SIZE: usize = 4096;
+const ENTRY_OVERHEAD: usize = 32;
+const EOS: u16 = 256;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub(crate) enum HpackError {
+    Index,
+    Integer,
+    Huffman,
+    TableSize,
+    Truncated,
+}
+
+pub(crate) type HpackField = (Vec<u8>, Vec<u8>);
+
+pub(crate) struct Hpack {
+    table: VecDeque<HpackField>, // 新的在前面
+    size: usize,
+    max_size: usize,
+}
+
+impl Hpack {
+    pub(crate) fn new() -> Self {
+        Self {
+            table: VecDeque::new(),
+            size: 0,
+            max_size: DEFAULT_TABLE_SIZE,
+        }
+    }
+
+    // 一个完整的header block，HEADERS和CONTINUATION已经拼好
+    pub(crate) fn decode(&mut self, mut block: &[u8]) -> Result<Vec<HpackField>, HpackError> {
+        let mut fields = Vec::new();
+        while let Some(&first) = block.first() {
+            if first & 0x80 != 0 {
+                let index = integer(&mut block, 7)?;
+                fields.push(self.get(index)?.clone());
+            } else if first & 0x40 != 0 {
+                let field = self.literal(&mut block, 6)?;
+                self.insert(field.clone());
+                fields.push(field);
+            } else if first & 0x20 != 0 {
+                let size = integer(&mut block, 5)?;
+                if size > MAX_TABLE_SIZE {
+                    return Err(HpackError::TableSize);
+                }
+                self.max_size = size;
+                self.evict(0);
+            } else {
+                // without indexing和never indexed只是对中间节点的要求不同
+                fields.push(self.literal(&mut block, 4)?);
+            }
+        }
+        Ok(fields)
+    }
+
+    fn literal(&self, block: &mut &[u8], prefix: u8) -> Result<HpackField, HpackError> {
+        let index = integer(block, prefix)?;
+        let name = if index == 0 {
+            string(block)?
+        } else {
+            self.get(index)?.0.clone()
+        };
+        Ok((name, string(block)?))
+    }
+
+    fn get(&self, index: usize) -> Result<&HpackField, HpackError> {
+        static FIELDS: OnceLock<Vec<HpackField>> = OnceLock::new();
+        let fields = FIELDS.get_or_init(|| {
+            STATIC_TABLE
+                .iter()
+                .map(|(name, value)| (name.to_vec(), value.to_vec()))
+                .collect()
+        });
+        match index {
+            0 => Err(HpackError::Index),
+            1..=61 => Ok(&fields[index - 1]),
+            _ => self.table.get(index - 62).ok_or(HpackError::Index),
+        }
+    }
+
+    fn insert(&mut self, field: HpackField) {
+        let size = field.0.len() + field.1.len() + ENTRY_OVERHEAD;
+        self.evict(size);
+        // 比整个表还大的时候只清空表
+        if size <= self.max_size {
+            self.size += size;
+            self.table.push_front(field);
+        }
+    }
+
+    fn evict(&mut self, need: usize) {
+        while self.size + need > self.max_size {
+            let Some((name, value)) = self.table.pop_back() else {
+                break;
+            };
+            self.size -= name.len() + value.len() + ENTRY_OVERHEAD;
+        }
+    }
+}
+
+// 5.1 prefix是第一个字节中可用的位数
+fn integer(block: &mut &[u8], prefix: u8) -> Result<usize, HpackError> {
+    let (&first, mut rest) = block.split_first().ok_or(HpackError::Truncated)?;
+    let mask = (1u16 << prefix) as usize - 1;
+    let mut value = first as usize & mask;
+    if value == mask {
+        let mut shift = 0;
+        loop {
+            let (&byte, next) = rest.split_first().ok_or(HpackError::Truncated)?;
+            rest = next;
+            if shift > 28 {
+                return Err(HpackError::Integer);
+            }
+            value += ((byte & 0x7f) as usize) << shift;
+            shift += 7;
+            if byte & 0x80 == 0 {
+                break;
+            }
+        }
+    }
+    *block = rest;
+    Ok(value)
+}
+
+fn string(block: &mut &[u8]) -> Result<Vec<u8>, HpackError> {
+    let huffman = block.first().ok_or(HpackError::Truncated)? & 0x80 != 0;
+    let len = integer(block, 7)?;
+    if block.len() < len {
+        return Err(HpackError::Truncated);
+    }
+    let (data, rest) = block.split_at(len);
+    *block = rest;
+    if huffman {
+        huffman_decode(data)
+    } else {
+        Ok(data.to_vec())
+    }
+}
+
+// 二叉树，叶子节点保存符号。节点0是根
+#[derive(Clone, Copy)]
+enum Node {
+    Inner([u16; 2]),
+    Leaf(u16),
+}
+
+fn huffman_tree() -> &'static [Node] {
+    static TREE: OnceLock<Vec<Node>> = OnceLock::new();
+    TREE.get_or_init(|| {
+        let mut tree = vec![Node::Inner([0, 0])];
+        for (sym, &(code, len)) in HUFFMAN_CODES.iter().enumerate() {
+            let mut cur = 0;
+            for i in (0..len).rev() {
+                let bit = ((code >> i) & 1) as usize;
+                let Node::Inner(children) = tree[cur] else {
+                    unreachable!();
+                };
+                if children[bit] != 0 {
+                    cur = children[bit] as usize;
+                    continue;
+                }
+                let next = tree.len();
+                tree.push(if i == 0 {
+                    Node::Leaf(sym as u16)
+                } else {
+                    Node::Inner([0, 0])
+                });
+                if let Node::Inner(children) = &mut tree[cur] {
+                    children[bit] = next as u16;
+                }
+                cur = next;
+            }
+        }
+        tree
+    })
+}
+
+// 5.2 结尾的padding是EOS的前缀，不超过7位
+fn huffman_decode(data: &[u8]) -> Result<Vec<u8>, HpackError> {
+    let tree = huffman_tree();
+    let mut out = Vec::with_capacity(data.len() * 8 / 5);
+    let mut cur = 0;
+    let mut pad_bits = 0;
+    let mut pad_ones = true;
+    for &byte in data {
+        for i in (0..8).rev() {
+            let bit = (byte >> i) & 1;
+            let Node::Inner(children) = tree[cur] else {
+                unreachable!();
+            };
+            cur = children[bit as usize] as usize;
+            pad_bits += 1;
+            pad_ones &= bit == 1;
+            if let Node::Leaf(sym) = tree[cur] {
+                if sym == EOS {
+                    return Err(HpackError::Huffman);
+                }
+                out.push(sym as u8);
+                cur = 0;
+                pad_bits = 0;
+                pad_ones = true;
+            }
+        }
+    }
+    if pad_bits > 7 || !pad_ones {
+        return Err(HpackError::Huffman);
+    }
+    Ok(out)
+}
+
+// Appendix A
+const STATIC_TABLE: [(&[u8], &[u8]); 61] = [
+    (b":authority", b""),
+    (b":method", b"GET"),
+    (b":method", b"POST"),
+    (b":path", b"/"),
+    (b":path", b"/index.html"),
+    (b":scheme", b"http"),
+    (b":scheme", b"https"),
+    (b":status", b"200"),
+    (b":status", b"204"),
+    (b":status", b"206"),
+    (b":status", b"304"),
+    (b":status", b"400"),
+    (b":status", b"404"),
+    (b":status", b"500"),
+    (b"accept-charset", b""),
+    (b"accept-encoding", b"gzip, deflate"),
+    (b"accept-language", b""),
+    (b"accept-ranges", b""),
+    (b"accept", b""),
+    (b"access-control-allow-origin", b""),
+    (b"age", b""),
+    (b"allow", b""),
+    (b"authorization", b""),
+    (b"cache-control", b""),
+    (b"content-disposition", b""),
+    (b"content-encoding", b""),
+    (b"content-language", b""),
+    (b"content-length", b""),
+    (b"content-location", b""),
+    (b"content-range", b""),
+    (b"content-type", b""),
+    (b"cookie", b""),
+    (b"date", b""),
+    (b"etag", b""),
+    (b"expect", b""),
+    (b"expires", b""),
+    (b"from", b""),
+    (b"host", b""),
+    (b"if-match", b""),
+    (b"if-modified-since", b""),
+    (b"if-none-match", b""),
+    (b"if-range", b""),
+    (b"if-unmodified-since", b""),
+    (b"last-modified", b""),
+    (b"link", b""),
+    (b"location", b""),
+    (b"max-forwards", b""),
+    (b"proxy-authenticate", b""),
+    (b"proxy-authorization", b""),
+    (b"range", b""),
+    (b"referer", b""),
+    (b"refresh", b""),
+    (b"retry-after", b""),
+    (b"server", b""),
+    (b"set-cookie", b""),
+    (b"strict-transport-security", b""),
+    (b"transfer-encoding", b""),
+    (b"user-agent", b""),
+    (b"vary", b""),
+    (b"via", b""),
+    (b"www-authenticate", b""),
+];
+
+// Appendix B，按符号顺序的(编码, 位数)，最后一个是EOS
+const HUFFMAN_CODES: [(u32, u8); 257] = [
+    (0x1ff8, 13),
+    (0x7fffd8, 23),
+    (0xfffffe2, 28),
+    (0xfffffe3, 28),
+    (0xfffffe4, 28),
+    (0xfffffe5, 28),
+    (0xfffffe6, 28),
+    (0xfffffe7, 28),
+    (0xfffffe8, 28),
+    (0xffffea, 24),
+    (0x3ffffffc, 30),
+    (0xfffffe9, 28),
+    (0xfffffea, 28),
+    (0x3ffffffd, 30),
+    (0xfffffeb, 28),
+    (0xfffffec, 28),
+    (0xfffffed, 28),
+    (0xfffffee, 28),
+    (0xfffffef, 28),
+    (0xffffff0, 28),
+    (0xffffff1, 28),
+    (0xffffff2, 28),
+    (0x3ffffffe, 30),
+    (0xffffff3, 28),
+    (0xffffff4, 28),
+    (0xffffff5, 28),
+    (0xffffff6, 28),
+    (0xffffff7, 28),
+    (0xffffff8, 28),
+    (0xffffff9, 28),
+    (0xffffffa, 28),
+    (0xffffffb, 28),
+    (0x14, 6),
+    (0x3f8, 10),
+    (0x3f9, 10),
+    (0xffa, 12),
+    (0x1ff9, 13),
+    (0x15, 6),
+    (0xf8, 8),
+    (0x7fa, 11),
+    (0x3fa, 10),
+    (0x3fb, 10),
+    (0xf9, 8),
+    (0x7fb, 11),
+    (0xfa, 8),
+    (0x16, 6),
+    (0x17, 6),
+    (0x18, 6),
+    (0x0, 5),
+    (0x1, 5),
+    (0x2, 5),
+    (0x19, 6),
+    (0x1a, 6),
+    (0x1b, 6),
+    (0x1c, 6),
+    (0x1d, 6),
+    (0x1e, 6),
+    (0x1f, 6),
+    (0x5c, 7),
+    (0xfb, 8),
+    (0x7ffc, 15),
+    (0x20, 6),
+    (0xffb, 12),
+    (0x3fc, 10),
+    (0x1ffa, 13),
+    (0x21, 6),
+    (0x5d, 7),
+    (0x5e, 7),
+    (0x5f, 7),
+    (0x60, 7),
+    (0x61, 7),
+    (0x62, 7),
+    (0x63, 7),
+    (0x64, 7),
+    (0x65, 7),
+    (0x66, 7),
+    (0x67, 7),
+    (0x68, 7),
+    (0x69, 7),
+    (0x6a, 7),
+    (0x6b, 7),
+    (0x6c, 7),
+    (0x6d, 7),
+    (0x6e, 7),
+    (0x6f, 7),
+    (0x70, 7),
+    (0x71, 7),
+    (0x72, 7),
+    (0xfc, 8),
+    (0x73, 7),
+    (0xfd, 8),
+    (0x1ffb, 13),
+    (0x7fff0, 19),
+    (0x1ffc, 13),
+    (0x3ffc, 14),
+    (0x22, 6),
+    (0x7ffd, 15),
+    (0x3, 5),
+    (0x23, 6),
+    (0x4, 5),
+    (0x24, 6),
+    (0x5, 5),
+    (0x25, 6),
+    (0x26, 6),
+    (0x27, 6),
+    (0x6, 5),
+    (0x74, 7),
+    (0x75, 7),
+    (0x28, 6),
+    (0x29, 6),
+    (0x2a, 6),
+    (0x7, 5),
+    (0x2b, 6),
+    (0x76, 7),
+    (0x2c, 6),
+    (0x8, 5),
+    (0x9, 5),
+    (0x2d, 6),
+    (0x77, 7),
+    (0x78, 7),
+    (0x79, 7),
+    (0x7a, 7),
+    (0x7b, 7),
+    (0x7ffe, 15),
+    (0x7fc, 11),
+    (0x3ffd, 14),
+    (0x1ffd, 13),
+    (0xffffffc, 28),
+    (0xfffe6, 20),
+    (0x3fffd2, 22),
+    (0xfffe7, 20),
+    (0xfffe8, 20),
+    (0x3fffd3, 22),
+    (0x3fffd4, 22),
+    (0x3fffd5, 22),
+    (0x7fffd9, 23),
+    (0x3fffd6, 22),
+    (0x7fffda, 23),
+    (0x7fffdb, 23),
+    (0x7fffdc, 23),
+    (0x7fffdd, 23),
+    (0x7fffde, 23),
+    (0xffffeb, 24),
+    (0x7fffdf, 23),
+    (0xffffec, 24),
+    (0xffffed, 24),
+    (0x3fffd7, 22),
+    (0x7fffe0, 23),
+    (0xffffee, 24),
+    (0x7fffe1, 23),
+    (0x7fffe2, 23),
+    (0x7fffe3, 23),
+    (0x7fffe4, 23),
+    (0x1fffdc, 21),
+    (0x3fffd8, 22),
+    (0x7fffe5, 23),
+    (0x3fffd9, 22),
+    (0x7fffe6, 23),
+    (0x7fffe7, 23),
+    (0xffffef, 24),
+    (0x3fffda, 22),
+    (0x1fffdd, 21),
+    (0xfffe9, 20),
+    (0x3fffdb, 22),
+    (0x3fffdc, 22),
+    (0x7fffe8, 23),
+    (0x7fffe9, 23),
+    (0x1fffde, 21),
+    (0x7fffea, 23),
+    (0x3fffdd, 22),
+    (0x3fffde, 22),
+    (0xfffff0, 24),
+    (0x1fffdf, 21),
+    (0x3fffdf, 22),
+    (0x7fffeb, 23),
+    (0x7fffec, 23),
+    (0x1fffe0, 21),
+    (0x1fffe1, 21),
+    (0x3fffe0, 22),
+    (0x1fffe2, 21),
+    (0x7fffed, 23),
+    (0x3fffe1, 22),
+    (0x7fffee, 23),
+    (0x7fffef, 23),
+    (0xfffea, 20),
+    (0x3fffe2, 22),
+    (0x3fffe3, 22),
+    (0x3fffe4, 22),
+    (0x7ffff0, 23),
+    (0x3fffe5, 22),
+    (0x3fffe6, 22),
+    (0x7ffff1, 23),
+    (0x3ffffe0, 26),
+    (0x3ffffe1, 26),
+    (0xfffeb, 20),
+    (0x7fff1, 19),
+    (0x3fffe7, 22),
+    (0x7ffff2, 23),
+    (0x3fffe8, 22),
+    (0x1ffffec, 25),
+    (0x3ffffe2, 26),
+    (0x3ffffe3, 26),
+    (0x3ffffe4, 26),
+    (0x7ffffde, 27),
+    (0x7ffffdf, 27),
+    (0x3ffffe5, 26),
+    (0xfffff1, 24),
+    (0x1ffffed, 25),
+    (0x7fff2, 19),
+    (0x1fffe3, 21),
+    (0x3ffffe6, 26),
+    (0x7ffffe0, 27),
+    (0x7ffffe1, 27),
+    (0x3ffffe7, 26),
+    (0x7ffffe2, 27),
+    (0xfffff2, 24),
+    (0x1fffe4, 21),
+    (0x1fffe5, 21),
+    (0x3ffffe8, 26),
+    (0x3ffffe9, 26),
+    (0xffffffd, 28),
+    (0x7ffffe3, 27),
+    (0x7ffffe4, 27),
+    (0x7ffffe5, 27),
+    (0xfffec, 20),
+    (0xfffff3, 24),
+    (0xfffed, 20),
+    (0x1fffe6, 21),
+    (0x3fffe9, 22),
+    (0x1fffe7, 21),
+    (0x1fffe8, 21),
+    (0x7ffff3, 23),
+    (0x3fffea, 22),
+    (0x3fffeb, 22),
+    (0x1ffffee, 25),
+    (0x1ffffef, 25),
+    (0xfffff4, 24),
+    (0xfffff5, 24),
+    (0x3ffffea, 26),
+    (0x7ffff4, 23),
+    (0x3ffffeb, 26),
+    (0x7ffffe6, 27),
+    (0x3ffffec, 26),
+    (0x3ffffed, 26),
+    (0x7ffffe7, 27),
+    (0x7ffffe8, 27),
+    (0x7ffffe9, 27),
+    (0x7ffffea, 27),
+    (0x7ffffeb, 27),
+    (0xffffffe, 28),
+    (0x7ffffec, 27),
+    (0x7ffffed, 27),
+    (0x7ffffee, 27),
+    (0x7ffffef, 27),
+    (0x7fffff0, 27),
+    (0x3ffffee, 26),
+    (0x3fffffff, 30),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields(list: &[(&str, &str)]) -> Vec<HpackField> {
+        list.iter()
+            .map(|(name, value)| (name.as_bytes().to_vec(), value.as_bytes().to_vec()))
+            .collect()
+    }
+
+    #[test]
+    fn test_hpack_integer() {
+        // C.1
+        let mut buf: &[u8] = &[0x0a];
+        assert_eq!(integer(&mut buf, 5), Ok(10));
+        let mut buf: &[u8] = &[0x1f, 0x9a, 0x0a];
+        assert_eq!(integer(&mut buf, 5), Ok(1337));
+        assert!(buf.is_empty());
+        let mut buf: &[u8] = &[0x1f, 0x9a];
+        assert_eq!(integer(&mut buf, 5), Err(HpackError::Truncated));
+    }
+
+    // C.3 不带huffman的连续请求
+    #[test]
+    fn test_hpack_request() {
+        let mut hpack = Hpack::new();
+        let block = [
+            0x82, 0x86, 0x84, 0x41, 0x0f, 0x77, 0x77, 0x77, 0x2e, 0x65, 0x78, 0x61, 0x6d, 0x70,
+            0x6c, 0x65, 0x2e, 0x63, 0x6f, 0x6d,
+        ];
+        assert_eq!(
+            hpack.decode(&block).unwrap(),
+            fields(&[
+                (":method", "GET"),
+                (":scheme", "http"),
+                (":path", "/"),
+                (":authority", "www.example.com"),
+            ])
+        );
+        assert_eq!(hpack.size, 57);
+
+        let block = [
+            0x82, 0x86, 0x84, 0xbe, 0x58, 0x08, 0x6e, 0x6f, 0x2d, 0x63, 0x61, 0x63, 0x68, 0x65,
+        ];
+        assert_eq!(
+            hpack.decode(&block).unwrap(),
+            fields(&[
+                (":method", "GET"),
+                (":scheme", "http"),
+                (":path", "/"),
+                (":authority", "www.example.com"),
+                ("cache-control", "no-cache"),
+            ])
+        );
+        assert_eq!(hpack.size, 110);
+    }
+
+    // C.4 huffman
+    #[test]
+    fn test_hpack_huffman() {
+        let mut hpack = Hpack::new();
+        let block = [
+            0x82, 0x86, 0x84, 0x41, 0x8c, 0xf1, 0xe3, 0xc2, 0xe5, 0xf2, 0x3a, 0x6b, 0xa0, 0xab,
+            0x90, 0xf4, 0xff,
+        ];
+        assert_eq!(
+            hpack.decode(&block).unwrap()[3],
+            fields(&[(":authority", "www.example.com")])[0]
+        );
+        let block = [
+            0x82, 0x86, 0x84, 0xbe, 0x58, 0x86, 0xa8, 0xeb, 0x10, 0x64, 0x9c, 0xbf,
+        ];
+        assert_eq!(
+            hpack.decode(&block).unwrap()[4],
+            fields(&[("cache-control", "no-cache")])[0]
+        );
+        let block = [
+            0x82, 0x87, 0x85, 0xbf, 0x40, 0x88, 0x25, 0xa8, 0x49, 0xe9, 0x5b, 0xa9, 0x7d, 0x7f,
+            0x89, 0x25, 0xa8, 0x49, 0xe9, 0x5b, 0xb8, 0xe8, 0xb4, 0xbf,
+        ];
+        assert_eq!(
+            hpack.decode(&block).unwrap(),
+            fields(&[
+                (":method", "GET"),
+                (":scheme", "https"),
+                (":path", "/index.html"),
+                (":authority", "www.example.com"),
+                ("custom-key", "custom-value"),
+            ])
+        );
+
+        assert_eq!(huffman_decode(&[0xff, 0xff]), Err(HpackError::Huffman));
+    }
+
+    // C.5 动态表只有256字节，需要淘汰
+    #[test]
+    fn test_hpack_evict() {
+        let mut hpack = Hpack::new();
+        assert!(hpack.decode(&[0x3f, 0xe1, 0x01]).unwrap().is_empty());
+        assert_eq!(hpack.max_size, 256);
+
+        let mut block = vec![0x48, 0x03];
+        block.extend_from_slice(b"302");
+        block.extend_from_slice(&[0x58, 0x07]);
+        block.extend_from_slice(b"private");
+        block.extend_from_slice(&[0x61, 0x1d]);
+        block.extend_from_slice(b"Mon, 21 Oct 2013 20:13:21 GMT");
+        block.extend_from_slice(&[0x6e, 0x17]);
+        block.extend_from_slice(b"https://www.example.com");
+        hpack.decode(&block).unwrap();
+        assert_eq!(hpack.size, 222);
+
+        let mut block = vec![0x48, 0x03];
+        block.extend_from_slice(b"307");
+        block.extend_from_slice(&[0xc1, 0xc0, 0xbf]);
+        assert_eq!(
+            hpack.decode(&block).unwrap(),
+            fields(&[
+                (":status", "307"),
+                ("cache-control", "private"),
+                ("date", "Mon, 21 Oct 2013 20:13:21 GMT"),
+                ("location", "https://www.example.com"),
+            ])
+        );
+        assert_eq!(hpack.size, 222);
+        assert_eq!(hpack.table[0].1, b"307");
+        assert_eq!(hpack.decode(&[0xff, 0x00]), Err(HpackError::Index));
+    }
+}
@@ -0,0 +1,240 @@
+// 流式的raw deflate(RFC 1951)解压，基于miniz_oxide。输入可以任意切分，数据不够时保存状态等待下一次输入
+use miniz_oxide::inflate::stream::{InflateState, inflate};
+use miniz_oxide::{DataFormat, MZError, MZFlush, MZStatus};
+
+// 每次调用miniz最多输出的长度
+const OUT_CHUNK: usize = 32 * 1024;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub(crate) struct InflateError;
+
+pub(crate) struct Inflate {
+    state: Box<InflateState>,
+    input: Vec<u8>, // 输出达到上限时还没有被miniz消费的输入
+    done: bool,
+    paused: bool, // 输出达到上限暂停，input或者miniz的窗口中可能还有未输出的数据
+}
+
+impl Inflate {
+    pub(crate) fn new() -> Self {
+        Self {
+            state: InflateState::new_boxed(DataFormat::Raw),
+            input: Vec::new(),
+            done: false,
+            paused: false,
+        }
+    }
+
+    pub(crate) fn is_done(&self) -> bool {
+        self.done
+    }
+
+    pub(crate) fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    // 解压data，结果追加到out。数据不完整时返回Ok，等待下一次输入
+    #[allow(unused)]
+    pub(crate) fn inflate(&mut self, data: &[u8], out: &mut Vec<u8>) -> Result<(), InflateError> {
+        self.inflate_max(data, out, usize::MAX)
+    }
+
+    // 同inflate，但out达到max后暂停。暂停后用空data再次调用继续解压
+    pub(crate) fn inflate_max(
+        &mut self,
+        data: &[u8],
+        out: &mut Vec<u8>,
+        max: usize,
+    ) -> Result<(), InflateError> {
+        // 结束之后是trailer或者垃圾数据，丢弃
+        if self.done {
+            self.input.clear();
+            return Ok(());
+        }
+        self.input.extend_from_slice(data);
+        self.paused = false;
+
+        let mut used = 0;
+        let ret = loop {
+            if out.len() >= max {
+                self.paused = true;
+                break Ok(());
+            }
+            let start = out.len();
+            let chunk = (max - start).min(OUT_CHUNK);
+            out.resize(start + chunk, 0);
+            let ret = inflate(
+                &mut self.state,
+                &self.input[used..],
+                &mut out[start..],
+                MZFlush::None,
+            );
+            out.truncate(start + ret.bytes_written);
+            used += ret.bytes_consumed;
+            match ret.status {
+                Ok(MZStatus::StreamEnd) => {
+                    self.done = true;
+                    break Ok(());
+                }
+                Ok(_) if ret.bytes_written > 0 || ret.bytes_consumed > 0 => {}
+                // 没有进展，需要更多的输入
+                Ok(_) | Err(MZError::Buf) => break Ok(()),
+                Err(_) => break Err(InflateError),
+            }
+        };
+
+        if self.done {
+            self.input.clear();
+        } else {
+            self.input.drain(..used);
+        }
+        ret
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAIL: &[u8] =
+        b"From: alice@example.com\r\nTo: bob@example.org\r\nSubject: quarterly report\r\n\r\n\
+Hi Bob,\r\nPlease find the quarterly numbers below. Revenue grew 12% while costs were flat;\r\n\
+the new IMAP gateway handled 3.4M sessions with COMPRESS=DEFLATE enabled for most clients.\r\n\
+Regards,\r\nAlice\r\n";
+
+    // zlib level 9, raw deflate, dynamic huffman
+    const MAIL_DEFLATE: &[u8] = &[
+        0x4d, 0x90, 0xc1, 0x4a, 0xc4, 0x30, 0x14, 0x45, 0xf7, 0x81, 0xfc, 0xc3, 0xdb, 0xcc, 0x4e,
+        0x0a, 0x3a, 0xae, 0x2a, 0x82, 0x1d, 0xed, 0xa0, 0x60, 0xb1, 0xb4, 0xf3, 0x03, 0x49, 0x7b,
+        0xa7, 0x8d, 0xa4, 0x79, 0xe3, 0x4b, 0x6a, 0x9d, 0xbf, 0xb7, 0xdd, 0x88, 0xdb, 0xcb, 0xb9,
+        0x87, 0xcb, 0x3d, 0x0a, 0x4f, 0x39, 0x19, 0xef, 0x3a, 0x3c, 0xe1, 0xc7, 0x4c, 0x17, 0x8f,
+        0xac, 0xe3, 0x49, 0xab, 0x13, 0xe7, 0x64, 0xd9, 0xfe, 0x85, 0x2c, 0x83, 0x56, 0xed, 0x6c,
+        0x3f, 0xd1, 0xa5, 0x9c, 0xbe, 0x66, 0x23, 0x09, 0xe2, 0xaf, 0x24, 0xb8, 0xb0, 0x24, 0xad,
+        0xb4, 0x7a, 0x75, 0x74, 0x60, 0x7b, 0xa3, 0x55, 0xed, 0x61, 0x22, 0xe8, 0xec, 0x42, 0x4f,
+        0x69, 0xc4, 0x3f, 0x38, 0xcc, 0x93, 0x85, 0x44, 0xb2, 0xf0, 0xbc, 0x64, 0xd4, 0xe0, 0x1b,
+        0x61, 0x06, 0x0d, 0x82, 0x85, 0x6e, 0xef, 0x76, 0xb4, 0x8c, 0xce, 0x83, 0x3a, 0x8e, 0x29,
+        0xd2, 0x02, 0x59, 0x1d, 0xde, 0xa4, 0x07, 0xad, 0x36, 0x4b, 0x58, 0x99, 0xb7, 0xaa, 0xa8,
+        0x69, 0x30, 0x09, 0x8b, 0xb9, 0xd2, 0x68, 0x42, 0xef, 0xd1, 0xd3, 0x3e, 0xbb, 0xaf, 0x28,
+        0x22, 0x46, 0xc7, 0x61, 0xad, 0xb9, 0x34, 0xd2, 0xf3, 0x47, 0x55, 0x37, 0x65, 0xdb, 0x3e,
+        0xbe, 0x94, 0xc7, 0xf7, 0xe2, 0x54, 0x12, 0x82, 0xb1, 0x1b, 0x7b, 0x66, 0xa1, 0x69, 0xd5,
+        0x53, 0xe7, 0x1d, 0x42, 0x8a, 0x99, 0x56, 0x0d, 0x06, 0x23, 0x7d, 0x5c, 0x77, 0x17, 0xdb,
+        0x0b, 0x5a, 0xfd, 0x02,
+    ];
+
+    #[test]
+    fn test_inflate_dynamic() {
+        let mut inflate = Inflate::new();
+        let mut out = Vec::new();
+        assert!(inflate.inflate(MAIL_DEFLATE, &mut out).is_ok());
+        assert!(inflate.is_done());
+        assert_eq!(out, MAIL);
+    }
+
+    #[test]
+    fn test_inflate_byte_by_byte() {
+        let mut inflate = Inflate::new();
+        let mut out = Vec::new();
+        for byte in MAIL_DEFLATE {
+            assert!(inflate.inflate(&[*byte], &mut out).is_ok());
+        }
+        assert!(inflate.is_done());
+        assert_eq!(out, MAIL);
+    }
+
+    #[test]
+    fn test_inflate_fixed_stored() {
+        let mut inflate = Inflate::new();
+        let mut out = Vec::new();
+        let fixed = [0xcb, 0x48, 0xcd, 0xc9, 0xc9, 0x57, 0xc8, 0x40, 0x90, 0x00];
+        assert!(inflate.inflate(&fixed, &mut out).is_ok());
+        assert_eq!(out, b"hello hello hello");
+
+        let mut inflate = Inflate::new();
+        let mut out = Vec::new();
+        let stored = [0x01, 0x03, 0x00, 0xfc, 0xff, 0x61, 0x62, 0x63, 0x0a];
+        assert!(inflate.inflate(&stored, &mut out).is_ok());
+        assert_eq!(out, b"abc");
+        assert!(inflate.is_done());
+    }
+
+    #[test]
+    fn test_inflate_sync_flush() {
+        // 两次Z_SYNC_FLUSH，后一次引用前一次的窗口
+        let first = [
+            0xd2, 0x52, 0xf0, 0xf7, 0x56, 0x88, 0x76, 0x76, 0x0c, 0x70, 0x74, 0xf2, 0xf4, 0xf1,
+            0x0c, 0x89, 0x54, 0xf0, 0xf4, 0x75, 0x0c, 0x30, 0x29, 0x4a, 0x2d, 0x33, 0x8c, 0x55,
+            0x28, 0x4a, 0x4d, 0x4c, 0xa9, 0xe4, 0xe5, 0x02, 0x00, 0x00, 0x00, 0xff, 0xff,
+        ];
+        let second = [
+            0x4a, 0x34, 0xc2, 0xa3, 0x26, 0x25, 0x3f, 0x2f, 0x95, 0x97, 0x0b, 0x00, 0x00, 0x00,
+            0xff, 0xff,
+        ];
+        let mut inflate = Inflate::new();
+        let mut out = Vec::new();
+        assert!(inflate.inflate(&first, &mut out).is_ok());
+        assert_eq!(out, b"* OK [CAPABILITY IMAP4rev1] ready\r\n");
+        out.clear();
+        assert!(inflate.inflate(&second, &mut out).is_ok());
+        assert_eq!(out, b"a2 OK [CAPABILITY IMAP4rev1] done\r\n");
+        assert!(!inflate.is_done());
+    }
+
+    #[test]
+    fn test_inflate_max() {
+        let mut inflate = Inflate::new();
+        let mut out = Vec::new();
+        let mut tot = Vec::new();
+        assert!(inflate.inflate_max(MAIL_DEFLATE, &mut out, 64).is_ok());
+        while inflate.is_paused() {
+            assert!(out.len() < 64 + 258);
+            tot.append(&mut out);
+            assert!(inflate.inflate_max(&[], &mut out, 64).is_ok());
+        }
+        tot.append(&mut out);
+        assert!(inflate.is_done());
+        assert_eq!(tot, MAIL);
+
+        // stored块同样受限
+        let mut inflate = Inflate::new();
+        let mut out = Vec::new();
+        let stored = [0x01, 0x03, 0x00, 0xfc, 0xff, 0x61, 0x62, 0x63, 0x0a];
+        assert!(inflate.inflate_max(&stored, &mut out, 2).is_ok());
+        assert_eq!(out, b"ab");
+        assert!(inflate.is_paused());
+        out.clear();
+        assert!(inflate.inflate_max(&[], &mut out, 2).is_ok());
+        assert_eq!(out, b"c");
+        assert!(!inflate.is_paused());
+        assert!(inflate.is_done());
+    }
+
+    #[test]
+    fn test_inflate_error() {
+        let mut inflate = Inflate::new();
+        let mut out = Vec::new();
+        assert_eq!(inflate.inflate(&[0x07], &mut out), Err(InflateError));
+
+        let mut inflate = Inflate::new();
+        assert_eq!(
+            inflate.inflate(&[0x01, 0x03, 0x00, 0x00, 0x00], &mut out),
+            Err(InflateError)
+        );
+    }
+
+    #[test]
+    fn test_inflate_trailing() {
+        // 结束之后的数据不再缓存
+        let mut inflate = Inflate::new();
+        let mut out = Vec::new();
+        let mut stored = vec![0x01, 0x03, 0x00, 0xfc, 0xff, 0x61, 0x62, 0x63];
+        stored.extend_from_slice(&[0x55; 100]);
+        assert!(inflate.inflate(&stored, &mut out).is_ok());
+        assert!(inflate.is_done());
+        assert_eq!(out, b"abc");
+        for _ in 0..10 {
+            assert!(inflate.inflate(&[0x55; 1000], &mut out).is_ok());
+        }
+        assert_eq!(out, b"abc");
+        assert!(inflate.input.is_empty());
+    }
+}
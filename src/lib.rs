@@ -1,7 +1,11 @@
 mod config;
+mod decode;
 mod enum_map;
+mod expect;
 mod ffi;
 mod heap;
+mod hpack;
+mod inflate;
 mod packet;
 mod parser;
 mod pktdata;
@@ -30,20 +34,34 @@ use crate::readn::*;
 use jemallocator::Jemalloc;
 
 use crate::config::*;
+use crate::decode::*;
+use crate::dnstcp::*;
 use crate::dnsudp::*;
 use crate::enum_map::EnumMap;
+use crate::expect::*;
 use crate::ftpcmd::*;
 use crate::ftpdata::*;
+use crate::ftplist::*;
+use crate::ftpsess::*;
+use crate::grpc::*;
 use crate::heap::*;
+use crate::hpack::*;
 use crate::http::*;
+use crate::http2::*;
+use crate::httpauth::*;
 use crate::imap::*;
 use crate::ordpacket::*;
 use crate::parser::*;
 use crate::pktstrm::*;
 use crate::pop3::*;
+use crate::rtp::*;
+use crate::sdp::*;
 use crate::sip::*;
+use crate::sipdialog::*;
 use crate::smtp::*;
 use crate::stats::*;
+use crate::tls::*;
+use crate::websocket::*;
 use std::cell::RefCell;
 use std::ffi::c_void;
 use std::marker::PhantomData;
@@ -67,6 +85,7 @@ where
     conf: Config,
     stats: Stats,
     parsers: EnumMap<Box<dyn ParserFactory<T>>>,
+    expect: ExpectRef,
     _phantom: PhantomData<T>,
 
     cb_task_c2s: Option<CbStrm>,
@@ -77,7 +96,9 @@ where
     cb_smtp_user: Option<CbUser>,
     cb_smtp_pass: Option<CbPass>,
     cb_smtp_mailfrom: Option<CbMailFrom>,
+    cb_smtp_mailfrom_ext: Option<CbMailFromExt>,
     cb_smtp_rcpt: Option<CbRcpt>,
+    cb_smtp_rcpt_ext: Option<CbRcptExt>,
     cb_smtp_header: Option<CbHeader>,
     cb_smtp_body_start: Option<CbBodyEvt>,
     cb_smtp_body: Option<CbBody>,
@@ -90,6 +111,16 @@ where
     cb_pop3_body_stop: Option<CbBodyEvt>,
     cb_pop3_clt: Option<CbClt>,
     cb_pop3_srv: Option<CbSrv>,
+    cb_pop3_user: Option<CbUser>,
+    cb_pop3_pass: Option<CbPass>,
+    cb_pop3_apop: Option<CbPop3Apop>,
+    cb_pop3_auth: Option<CbPop3Auth>,
+    cb_pop3_reply: Option<CbPop3Reply>,
+    cb_pop3_capa: Option<CbPop3Capa>,
+    cb_pop3_stat: Option<CbPop3Stat>,
+    cb_pop3_list: Option<CbPop3List>,
+    cb_pop3_uidl: Option<CbPop3Uidl>,
+    cb_pop3_msg: Option<CbPop3Msg>,
 
     cb_imap_header: Option<CbHeader>,
     cb_imap_body_start: Option<CbBodyEvt>,
@@ -97,26 +128,71 @@ where
     cb_imap_body_stop: Option<CbBodyEvt>,
     cb_imap_clt: Option<CbClt>,
     cb_imap_srv: Option<CbSrv>,
+    cb_imap_user: Option<CbUser>,
+    cb_imap_pass: Option<CbPass>,
+    cb_imap_auth: Option<CbImapAuth>,
+    cb_imap_reply: Option<CbImapReply>,
+    cb_imap_state: Option<CbImapState>,
+    cb_imap_body_info: Option<CbImapBodyInfo>,
+    cb_imap_fetch: Option<CbImapFetch>,
 
     cb_http_start_line: Option<CbStartLine>,
     cb_http_header: Option<CbHeader>,
     cb_http_body_start: Option<CbBodyEvt>,
     cb_http_body: Option<CbHttpBody>,
     cb_http_body_stop: Option<CbBodyEvt>,
+    cb_http_trans: Option<CbHttpTrans>,
+    cb_http_request: Option<CbHttpRequest>,
+    cb_http_response: Option<CbHttpResponse>,
+    cb_http_part: Option<CbHttpPart>,
+    cb_http_form: Option<CbHttpForm>,
+    cb_http_tunnel: Option<CbHttpTunnel>,
+    cb_http_tunnel_data: Option<CbHttpTunnelData>,
+    cb_ws_frame: Option<CbWsFrame>,
+    cb_tls_hello: Option<CbTlsHello>,
+    cb_http_auth: Option<CbHttpAuth>,
+    cb_http_auth_result: Option<CbHttpAuthResult>,
+
+    cb_http2_start_line: Option<CbHttp2Line>,
+    cb_http2_header: Option<CbHttp2Line>,
+    cb_http2_body_start: Option<CbHttp2BodyEvt>,
+    cb_http2_body: Option<CbHttp2Body>,
+    cb_http2_body_stop: Option<CbHttp2BodyEvt>,
+    cb_grpc_call: Option<CbGrpcCall>,
+    cb_grpc_msg: Option<CbGrpcMsg>,
+    cb_grpc_status: Option<CbGrpcStatus>,
+    cb_http_decode_err: Option<CbHttpDecodeErr>,
 
     cb_ftp_clt: Option<CbClt>,
     cb_ftp_srv: Option<CbSrv>,
     cb_ftp_link: Option<CbFtpLink>,
+    cb_ftp_login: Option<CbFtpLogin>,
+    cb_ftp_file: Option<CbFtpFile>,
+    cb_ftp_transfer: Option<CbFtpTransfer>,
 
     cb_ftp_body_start: Option<CbBodyEvt>,
     cb_ftp_body: Option<CbFtpBody>,
     cb_ftp_body_stop: Option<CbBodyEvt>,
+    cb_ftp_xfer_start: Option<CbFtpXferEvt>,
+    cb_ftp_xfer_body: Option<CbFtpXferBody>,
+    cb_ftp_xfer_stop: Option<CbFtpXferEvt>,
+    cb_ftp_list_entry: Option<CbFtpListEntry>,
 
     cb_sip_start_line: Option<CbStartLine>,
     cb_sip_header: Option<CbHeader>,
     cb_sip_body_start: Option<CbBodyEvt>,
     cb_sip_body: Option<CbSipBody>,
     cb_sip_body_stop: Option<CbBodyEvt>,
+    cb_sip_call: Option<CbSipCall>,
+    cb_sip_sdp: Option<CbSipSdp>,
+    cb_sip_media_link: Option<CbSipMediaLink>,
+    sip_dialogs: SipDialogsRef,
+
+    cb_rtp_pkt: Option<CbRtpPkt>,
+    cb_rtp_stats: Option<CbRtpStats>,
+    cb_rtcp_report: Option<CbRtcpReport>,
+    cb_rtcp_sdes: Option<CbRtcpSdes>,
+    cb_rtcp_bye: Option<CbRtcpBye>,
 
     cb_dns_header: Option<CbDnsHeader>,
     cb_dns_query: Option<CbDnsQuery>,
@@ -151,6 +227,7 @@ where
             conf,
             stats: Stats::new(),
             parsers: EnumMap::new(),
+            expect: Rc::new(RefCell::new(ExpectTable::default())),
             _phantom: PhantomData,
 
             cb_task_c2s: None,
@@ -161,7 +238,9 @@ where
             cb_smtp_user: None,
             cb_smtp_pass: None,
             cb_smtp_mailfrom: None,
+            cb_smtp_mailfrom_ext: None,
             cb_smtp_rcpt: None,
+            cb_smtp_rcpt_ext: None,
             cb_smtp_header: None,
             cb_smtp_body_start: None,
             cb_smtp_body: None,
@@ -174,6 +253,16 @@ where
             cb_pop3_body_stop: None,
             cb_pop3_clt: None,
             cb_pop3_srv: None,
+            cb_pop3_user: None,
+            cb_pop3_pass: None,
+            cb_pop3_apop: None,
+            cb_pop3_auth: None,
+            cb_pop3_reply: None,
+            cb_pop3_capa: None,
+            cb_pop3_stat: None,
+            cb_pop3_list: None,
+            cb_pop3_uidl: None,
+            cb_pop3_msg: None,
 
             cb_imap_header: None,
             cb_imap_body_start: None,
@@ -181,25 +270,70 @@ where
             cb_imap_body_stop: None,
             cb_imap_clt: None,
             cb_imap_srv: None,
+            cb_imap_user: None,
+            cb_imap_pass: None,
+            cb_imap_auth: None,
+            cb_imap_reply: None,
+            cb_imap_state: None,
+            cb_imap_body_info: None,
+            cb_imap_fetch: None,
 
             cb_http_start_line: None,
             cb_http_header: None,
             cb_http_body_start: None,
             cb_http_body: None,
             cb_http_body_stop: None,
+            cb_http_trans: None,
+            cb_http_request: None,
+            cb_http_response: None,
+            cb_http_part: None,
+            cb_http_form: None,
+            cb_http_tunnel: None,
+            cb_http_tunnel_data: None,
+            cb_ws_frame: None,
+            cb_tls_hello: None,
+            cb_http_auth: None,
+            cb_http_auth_result: None,
+
+            cb_http2_start_line: None,
+            cb_http2_header: None,
+            cb_http2_body_start: None,
+            cb_http2_body: None,
+            cb_http2_body_stop: None,
+            cb_grpc_call: None,
+            cb_grpc_msg: None,
+            cb_grpc_status: None,
+            cb_http_decode_err: None,
 
             cb_ftp_clt: None,
             cb_ftp_srv: None,
             cb_ftp_link: None,
+            cb_ftp_login: None,
+            cb_ftp_file: None,
+            cb_ftp_transfer: None,
             cb_ftp_body_start: None,
             cb_ftp_body: None,
             cb_ftp_body_stop: None,
+            cb_ftp_xfer_start: None,
+            cb_ftp_xfer_body: None,
+            cb_ftp_xfer_stop: None,
+            cb_ftp_list_entry: None,
 
             cb_sip_start_line: None,
             cb_sip_header: None,
             cb_sip_body_start: None,
             cb_sip_body: None,
             cb_sip_body_stop: None,
+            cb_sip_call: None,
+            cb_sip_sdp: None,
+            cb_sip_media_link: None,
+            sip_dialogs: Rc::new(RefCell::new(SipDialogs::default())),
+
+            cb_rtp_pkt: None,
+            cb_rtp_stats: None,
+            cb_rtcp_report: None,
+            cb_rtcp_sdes: None,
+            cb_rtcp_bye: None,
 
             cb_dns_header: None,
             cb_dns_query: None,
@@ -243,14 +377,22 @@ where
             .insert(L7Proto::Imap, Box::new(ImapFactory::<T>::new()));
         self.parsers
             .insert(L7Proto::Http, Box::new(HttpFactory::<T>::new()));
+        self.parsers
+            .insert(L7Proto::Http2, Box::new(Http2Factory::<T>::new()));
         self.parsers
             .insert(L7Proto::FtpCmd, Box::new(FtpCmdFactory::<T>::new()));
         self.parsers
             .insert(L7Proto::FtpData, Box::new(FtpDataFactory::<T>::new()));
         self.parsers
             .insert(L7Proto::Sip, Box::new(SipFactory::<T>::new()));
+        self.parsers
+            .insert(L7Proto::SipTcp, Box::new(SipFactory::<T>::new()));
+        self.parsers
+            .insert(L7Proto::Rtp, Box::new(RtpFactory::<T>::new()));
         self.parsers
             .insert(L7Proto::DnsUdp, Box::new(DnsUdpFactory::<T>::new()));
+        self.parsers
+            .insert(L7Proto::DnsTcp, Box::new(DnsTcpFactory::<T>::new()));
 
         #[cfg(test)]
         {
@@ -313,6 +455,18 @@ where
 
     pub fn run_task(&mut self, task: &mut Task<T>, pkt: T) -> Option<Result<(), ()>> {
         self.stats.packet_count += 1;
+        if !task.parser_set() {
+            let expected = self.expect.borrow_mut().take(&pkt);
+            match expected {
+                Some(Expected::FtpData(xfer)) => {
+                    task.set_parser(FtpDataFactory::create_xfer(self, Some(xfer)));
+                }
+                Some(Expected::Rtp(media)) => {
+                    task.set_parser(RtpFactory::create_media(self, Some(media)));
+                }
+                None => {}
+            }
+        }
         task.run(pkt)
     }
 
@@ -414,6 +568,13 @@ where
         self.cb_smtp_mailfrom = Some(Rc::new(RefCell::new(callback)) as CbMailFrom);
     }
 
+    pub fn set_cb_smtp_mailfrom_ext<F>(&mut self, callback: F)
+    where
+        F: SmtpAddrCbFn + 'static,
+    {
+        self.cb_smtp_mailfrom_ext = Some(Rc::new(RefCell::new(callback)) as CbMailFromExt);
+    }
+
     pub fn set_cb_smtp_rcpt<F>(&mut self, callback: F)
     where
         F: DataCbFn + 'static,
@@ -421,6 +582,13 @@ where
         self.cb_smtp_rcpt = Some(Rc::new(RefCell::new(callback)) as CbRcpt);
     }
 
+    pub fn set_cb_smtp_rcpt_ext<F>(&mut self, callback: F)
+    where
+        F: SmtpAddrCbFn + 'static,
+    {
+        self.cb_smtp_rcpt_ext = Some(Rc::new(RefCell::new(callback)) as CbRcptExt);
+    }
+
     pub fn set_cb_smtp_header<F>(&mut self, callback: F)
     where
         F: DataCbDirFn + 'static,
@@ -498,6 +666,76 @@ where
         self.cb_pop3_srv = Some(Rc::new(RefCell::new(callback)) as CbSrv);
     }
 
+    pub fn set_cb_pop3_user<F>(&mut self, callback: F)
+    where
+        F: DataCbFn + 'static,
+    {
+        self.cb_pop3_user = Some(Rc::new(RefCell::new(callback)) as CbUser);
+    }
+
+    pub fn set_cb_pop3_pass<F>(&mut self, callback: F)
+    where
+        F: DataCbFn + 'static,
+    {
+        self.cb_pop3_pass = Some(Rc::new(RefCell::new(callback)) as CbPass);
+    }
+
+    pub fn set_cb_pop3_apop<F>(&mut self, callback: F)
+    where
+        F: Pop3ApopCbFn + 'static,
+    {
+        self.cb_pop3_apop = Some(Rc::new(RefCell::new(callback)) as CbPop3Apop);
+    }
+
+    pub fn set_cb_pop3_auth<F>(&mut self, callback: F)
+    where
+        F: Pop3AuthCbFn + 'static,
+    {
+        self.cb_pop3_auth = Some(Rc::new(RefCell::new(callback)) as CbPop3Auth);
+    }
+
+    pub fn set_cb_pop3_reply<F>(&mut self, callback: F)
+    where
+        F: Pop3ReplyCbFn + 'static,
+    {
+        self.cb_pop3_reply = Some(Rc::new(RefCell::new(callback)) as CbPop3Reply);
+    }
+
+    pub fn set_cb_pop3_capa<F>(&mut self, callback: F)
+    where
+        F: DataCbFn + 'static,
+    {
+        self.cb_pop3_capa = Some(Rc::new(RefCell::new(callback)) as CbPop3Capa);
+    }
+
+    pub fn set_cb_pop3_stat<F>(&mut self, callback: F)
+    where
+        F: Pop3StatCbFn + 'static,
+    {
+        self.cb_pop3_stat = Some(Rc::new(RefCell::new(callback)) as CbPop3Stat);
+    }
+
+    pub fn set_cb_pop3_list<F>(&mut self, callback: F)
+    where
+        F: Pop3ListCbFn + 'static,
+    {
+        self.cb_pop3_list = Some(Rc::new(RefCell::new(callback)) as CbPop3List);
+    }
+
+    pub fn set_cb_pop3_uidl<F>(&mut self, callback: F)
+    where
+        F: Pop3UidlCbFn + 'static,
+    {
+        self.cb_pop3_uidl = Some(Rc::new(RefCell::new(callback)) as CbPop3Uidl);
+    }
+
+    pub fn set_cb_pop3_msg<F>(&mut self, callback: F)
+    where
+        F: Pop3MsgCbFn + 'static,
+    {
+        self.cb_pop3_msg = Some(Rc::new(RefCell::new(callback)) as CbPop3Msg);
+    }
+
     pub fn set_cb_imap_header<F>(&mut self, callback: F)
     where
         F: DataCbDirFn + 'static,
@@ -540,6 +778,55 @@ where
         self.cb_imap_srv = Some(Rc::new(RefCell::new(callback)) as CbSrv);
     }
 
+    pub fn set_cb_imap_user<F>(&mut self, callback: F)
+    where
+        F: DataCbFn + 'static,
+    {
+        self.cb_imap_user = Some(Rc::new(RefCell::new(callback)) as CbUser);
+    }
+
+    pub fn set_cb_imap_pass<F>(&mut self, callback: F)
+    where
+        F: DataCbFn + 'static,
+    {
+        self.cb_imap_pass = Some(Rc::new(RefCell::new(callback)) as CbPass);
+    }
+
+    pub fn set_cb_imap_auth<F>(&mut self, callback: F)
+    where
+        F: ImapAuthCbFn + 'static,
+    {
+        self.cb_imap_auth = Some(Rc::new(RefCell::new(callback)) as CbImapAuth);
+    }
+
+    pub fn set_cb_imap_reply<F>(&mut self, callback: F)
+    where
+        F: ImapReplyCbFn + 'static,
+    {
+        self.cb_imap_reply = Some(Rc::new(RefCell::new(callback)) as CbImapReply);
+    }
+
+    pub fn set_cb_imap_state<F>(&mut self, callback: F)
+    where
+        F: ImapStateCbFn + 'static,
+    {
+        self.cb_imap_state = Some(Rc::new(RefCell::new(callback)) as CbImapState);
+    }
+
+    pub fn set_cb_imap_body_info<F>(&mut self, callback: F)
+    where
+        F: ImapBodyInfoCbFn + 'static,
+    {
+        self.cb_imap_body_info = Some(Rc::new(RefCell::new(callback)) as CbImapBodyInfo);
+    }
+
+    pub fn set_cb_imap_fetch<F>(&mut self, callback: F)
+    where
+        F: ImapFetchCbFn + 'static,
+    {
+        self.cb_imap_fetch = Some(Rc::new(RefCell::new(callback)) as CbImapFetch);
+    }
+
     pub fn set_cb_http_start_line<F>(&mut self, callback: F)
     where
         F: DataCbDirFn + 'static,
@@ -575,6 +862,146 @@ where
         self.cb_http_body_stop = Some(Rc::new(RefCell::new(callback)) as CbBodyEvt);
     }
 
+    pub fn set_cb_http_trans<F>(&mut self, callback: F)
+    where
+        F: HttpTransCbFn + 'static,
+    {
+        self.cb_http_trans = Some(Rc::new(RefCell::new(callback)) as CbHttpTrans);
+    }
+
+    pub fn set_cb_http_request<F>(&mut self, callback: F)
+    where
+        F: HttpRequestCbFn + 'static,
+    {
+        self.cb_http_request = Some(Rc::new(RefCell::new(callback)) as CbHttpRequest);
+    }
+
+    pub fn set_cb_http_response<F>(&mut self, callback: F)
+    where
+        F: HttpResponseCbFn + 'static,
+    {
+        self.cb_http_response = Some(Rc::new(RefCell::new(callback)) as CbHttpResponse);
+    }
+
+    pub fn set_cb_http_part<F>(&mut self, callback: F)
+    where
+        F: HttpPartCbFn + 'static,
+    {
+        self.cb_http_part = Some(Rc::new(RefCell::new(callback)) as CbHttpPart);
+    }
+
+    pub fn set_cb_http_form<F>(&mut self, callback: F)
+    where
+        F: HttpFormCbFn + 'static,
+    {
+        self.cb_http_form = Some(Rc::new(RefCell::new(callback)) as CbHttpForm);
+    }
+
+    pub fn set_cb_http_tunnel<F>(&mut self, callback: F)
+    where
+        F: HttpTunnelCbFn + 'static,
+    {
+        self.cb_http_tunnel = Some(Rc::new(RefCell::new(callback)) as CbHttpTunnel);
+    }
+
+    pub fn set_cb_http_tunnel_data<F>(&mut self, callback: F)
+    where
+        F: DataCbDirFn + 'static,
+    {
+        self.cb_http_tunnel_data = Some(Rc::new(RefCell::new(callback)) as CbHttpTunnelData);
+    }
+
+    pub fn set_cb_ws_frame<F>(&mut self, callback: F)
+    where
+        F: WsFrameCbFn + 'static,
+    {
+        self.cb_ws_frame = Some(Rc::new(RefCell::new(callback)) as CbWsFrame);
+    }
+
+    pub fn set_cb_tls_hello<F>(&mut self, callback: F)
+    where
+        F: TlsHelloCbFn + 'static,
+    {
+        self.cb_tls_hello = Some(Rc::new(RefCell::new(callback)) as CbTlsHello);
+    }
+
+    pub fn set_cb_http_auth<F>(&mut self, callback: F)
+    where
+        F: HttpAuthCbFn + 'static,
+    {
+        self.cb_http_auth = Some(Rc::new(RefCell::new(callback)) as CbHttpAuth);
+    }
+
+    pub fn set_cb_http_auth_result<F>(&mut self, callback: F)
+    where
+        F: HttpAuthResultCbFn + 'static,
+    {
+        self.cb_http_auth_result = Some(Rc::new(RefCell::new(callback)) as CbHttpAuthResult);
+    }
+
+    pub fn set_cb_http_decode_err<F>(&mut self, callback: F)
+    where
+        F: HttpDecodeErrCbFn + 'static,
+    {
+        self.cb_http_decode_err = Some(Rc::new(RefCell::new(callback)) as CbHttpDecodeErr);
+    }
+
+    pub fn set_cb_http2_start_line<F>(&mut self, callback: F)
+    where
+        F: Http2LineCbFn + 'static,
+    {
+        self.cb_http2_start_line = Some(Rc::new(RefCell::new(callback)) as CbHttp2Line);
+    }
+
+    pub fn set_cb_http2_header<F>(&mut self, callback: F)
+    where
+        F: Http2LineCbFn + 'static,
+    {
+        self.cb_http2_header = Some(Rc::new(RefCell::new(callback)) as CbHttp2Line);
+    }
+
+    pub fn set_cb_http2_body_start<F>(&mut self, callback: F)
+    where
+        F: Http2BodyEvtCbFn + 'static,
+    {
+        self.cb_http2_body_start = Some(Rc::new(RefCell::new(callback)) as CbHttp2BodyEvt);
+    }
+
+    pub fn set_cb_http2_body<F>(&mut self, callback: F)
+    where
+        F: Http2BodyCbFn + 'static,
+    {
+        self.cb_http2_body = Some(Rc::new(RefCell::new(callback)) as CbHttp2Body);
+    }
+
+    pub fn set_cb_http2_body_stop<F>(&mut self, callback: F)
+    where
+        F: Http2BodyEvtCbFn + 'static,
+    {
+        self.cb_http2_body_stop = Some(Rc::new(RefCell::new(callback)) as CbHttp2BodyEvt);
+    }
+
+    pub fn set_cb_grpc_call<F>(&mut self, callback: F)
+    where
+        F: GrpcCallCbFn + 'static,
+    {
+        self.cb_grpc_call = Some(Rc::new(RefCell::new(callback)) as CbGrpcCall);
+    }
+
+    pub fn set_cb_grpc_msg<F>(&mut self, callback: F)
+    where
+        F: GrpcMsgCbFn + 'static,
+    {
+        self.cb_grpc_msg = Some(Rc::new(RefCell::new(callback)) as CbGrpcMsg);
+    }
+
+    pub fn set_cb_grpc_status<F>(&mut self, callback: F)
+    where
+        F: GrpcStatusCbFn + 'static,
+    {
+        self.cb_grpc_status = Some(Rc::new(RefCell::new(callback)) as CbGrpcStatus);
+    }
+
     pub fn set_cb_ftp_clt<F>(&mut self, callback: F)
     where
         F: DataCbFn + 'static,
@@ -596,6 +1023,27 @@ where
         self.cb_ftp_link = Some(Rc::new(RefCell::new(callback)) as CbFtpLink);
     }
 
+    pub fn set_cb_ftp_login<F>(&mut self, callback: F)
+    where
+        F: FtpLoginCbFn + 'static,
+    {
+        self.cb_ftp_login = Some(Rc::new(RefCell::new(callback)) as CbFtpLogin);
+    }
+
+    pub fn set_cb_ftp_file<F>(&mut self, callback: F)
+    where
+        F: FtpFileCbFn + 'static,
+    {
+        self.cb_ftp_file = Some(Rc::new(RefCell::new(callback)) as CbFtpFile);
+    }
+
+    pub fn set_cb_ftp_transfer<F>(&mut self, callback: F)
+    where
+        F: FtpTransferCbFn + 'static,
+    {
+        self.cb_ftp_transfer = Some(Rc::new(RefCell::new(callback)) as CbFtpTransfer);
+    }
+
     pub fn set_cb_ftp_body_start<F>(&mut self, callback: F)
     where
         F: EvtCbFn + 'static,
@@ -617,6 +1065,34 @@ where
         self.cb_ftp_body_stop = Some(Rc::new(RefCell::new(callback)) as CbBodyEvt);
     }
 
+    pub fn set_cb_ftp_xfer_start<F>(&mut self, callback: F)
+    where
+        F: FtpXferEvtCbFn + 'static,
+    {
+        self.cb_ftp_xfer_start = Some(Rc::new(RefCell::new(callback)) as CbFtpXferEvt);
+    }
+
+    pub fn set_cb_ftp_xfer_body<F>(&mut self, callback: F)
+    where
+        F: FtpXferBodyCbFn + 'static,
+    {
+        self.cb_ftp_xfer_body = Some(Rc::new(RefCell::new(callback)) as CbFtpXferBody);
+    }
+
+    pub fn set_cb_ftp_xfer_stop<F>(&mut self, callback: F)
+    where
+        F: FtpXferEvtCbFn + 'static,
+    {
+        self.cb_ftp_xfer_stop = Some(Rc::new(RefCell::new(callback)) as CbFtpXferEvt);
+    }
+
+    pub fn set_cb_ftp_list_entry<F>(&mut self, callback: F)
+    where
+        F: FtpListEntryCbFn + 'static,
+    {
+        self.cb_ftp_list_entry = Some(Rc::new(RefCell::new(callback)) as CbFtpListEntry);
+    }
+
     pub fn set_cb_sip_start_line<F>(&mut self, callback: F)
     where
         F: DataCbDirFn + 'static,
@@ -652,6 +1128,71 @@ where
         self.cb_sip_body_stop = Some(Rc::new(RefCell::new(callback)) as CbBodyEvt);
     }
 
+    pub fn set_cb_sip_call<F>(&mut self, callback: F)
+    where
+        F: SipCallCbFn + 'static,
+    {
+        self.cb_sip_call = Some(Rc::new(RefCell::new(callback)) as CbSipCall);
+    }
+
+    pub fn set_cb_sip_sdp<F>(&mut self, callback: F)
+    where
+        F: SipSdpCbFn + 'static,
+    {
+        self.cb_sip_sdp = Some(Rc::new(RefCell::new(callback)) as CbSipSdp);
+    }
+
+    pub fn set_cb_sip_media_link<F>(&mut self, callback: F)
+    where
+        F: SipMediaLinkCbFn + 'static,
+    {
+        self.cb_sip_media_link = Some(Rc::new(RefCell::new(callback)) as CbSipMediaLink);
+    }
+
+    pub fn set_cb_rtp_pkt<F>(&mut self, callback: F)
+    where
+        F: RtpPktCbFn + 'static,
+    {
+        self.cb_rtp_pkt = Some(Rc::new(RefCell::new(callback)) as CbRtpPkt);
+    }
+
+    pub fn set_cb_rtp_stats<F>(&mut self, callback: F)
+    where
+        F: RtpStatsCbFn + 'static,
+    {
+        self.cb_rtp_stats = Some(Rc::new(RefCell::new(callback)) as CbRtpStats);
+    }
+
+    pub fn set_cb_rtcp_report<F>(&mut self, callback: F)
+    where
+        F: RtcpReportCbFn + 'static,
+    {
+        self.cb_rtcp_report = Some(Rc::new(RefCell::new(callback)) as CbRtcpReport);
+    }
+
+    pub fn set_cb_rtcp_sdes<F>(&mut self, callback: F)
+    where
+        F: RtcpSdesCbFn + 'static,
+    {
+        self.cb_rtcp_sdes = Some(Rc::new(RefCell::new(callback)) as CbRtcpSdes);
+    }
+
+    pub fn set_cb_rtcp_bye<F>(&mut self, callback: F)
+    where
+        F: RtcpByeCbFn + 'static,
+    {
+        self.cb_rtcp_bye = Some(Rc::new(RefCell::new(callback)) as CbRtcpBye);
+    }
+
+    // 设置了rtp的回调，sip才登记sdp中的媒体地址
+    fn rtp_enabled(&self) -> bool {
+        self.cb_rtp_pkt.is_some()
+            || self.cb_rtp_stats.is_some()
+            || self.cb_rtcp_report.is_some()
+            || self.cb_rtcp_sdes.is_some()
+            || self.cb_rtcp_bye.is_some()
+    }
+
     pub fn set_cb_dns_header<F>(&mut self, callback: F)
     where
         F: DnsHeaderCbFn + 'static,
@@ -14,6 +14,10 @@ pub enum L7Proto {
     FtpData,
     Sip,
     DnsUdp,
+    Http2,
+    SipTcp, // 和Sip相同，Sip用在tcp的task上也是流模式
+    Rtp,
+    DnsTcp,
 
     #[cfg(test)]
     RawPacket,
@@ -67,6 +71,10 @@ pub trait Packet: Clone {
     fn fin(&self) -> bool;
     fn payload_len(&self) -> usize;
     fn payload(&self) -> &[u8];
+    // 毫秒。不提供时为0，依赖时间的统计(比如SIP通话时长)也为0
+    fn timestamp(&self) -> u64 {
+        0
+    }
 }
 
 #[derive(Clone, Debug)]
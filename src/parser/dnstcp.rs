@@ -0,0 +1,215 @@
+// DNS over TCP(RFC 7766)。每个消息前面有2字节长度，一个连接上可以有多个查询，
+// AXFR/IXFR的应答是连续的多个消息。消息本身和udp一样交给dns_parser
+use crate::DnsCallbacks;
+use crate::Parser;
+use crate::ParserFactory;
+use crate::ParserFuture;
+use crate::PktStrm;
+use crate::Prolens;
+use crate::dns_parser;
+use crate::packet::*;
+use byteorder::{BigEndian, ByteOrder};
+use std::ffi::c_void;
+use std::marker::PhantomData;
+
+pub struct DnsTcpParser<T>
+where
+    T: Packet,
+{
+    callbacks: Option<DnsCallbacks>,
+    _phantom_t: PhantomData<T>,
+}
+
+impl<T> DnsTcpParser<T>
+where
+    T: Packet + 'static,
+{
+    pub(crate) fn new() -> Self {
+        Self {
+            callbacks: None,
+            _phantom_t: PhantomData,
+        }
+    }
+
+    async fn parser_inner(
+        strm: *mut PktStrm<T>,
+        callbacks: Option<DnsCallbacks>,
+        cb_ctx: *mut c_void,
+    ) -> Result<(), ()> {
+        let stm = unsafe { &mut *strm };
+        let mut msg = Vec::new();
+
+        loop {
+            let (len, _) = stm.readn(2).await?;
+            let mut remain = BigEndian::read_u16(len) as usize;
+
+            // 消息可能比读缓冲大，名字压缩又要用到整个消息，只能攒起来
+            msg.clear();
+            while remain > 0 {
+                let (bytes, _) = stm.read(remain).await?;
+                remain -= bytes.len();
+                msg.extend_from_slice(bytes);
+            }
+
+            // 有长度分帧，一个消息解析失败不影响后面的
+            if let Some(ref callbacks) = callbacks
+                && !msg.is_empty()
+            {
+                let _ = dns_parser(&msg, callbacks.clone(), cb_ctx);
+            }
+        }
+    }
+}
+
+impl<T> Parser for DnsTcpParser<T>
+where
+    T: Packet + 'static,
+{
+    type T = T;
+
+    fn c2s_parser(&self, strm: *mut PktStrm<T>, cb_ctx: *mut c_void) -> Option<ParserFuture> {
+        Some(Box::pin(Self::parser_inner(
+            strm,
+            self.callbacks.clone(),
+            cb_ctx,
+        )))
+    }
+
+    fn s2c_parser(&self, strm: *mut PktStrm<T>, cb_ctx: *mut c_void) -> Option<ParserFuture> {
+        Some(Box::pin(Self::parser_inner(
+            strm,
+            self.callbacks.clone(),
+            cb_ctx,
+        )))
+    }
+}
+
+pub(crate) struct DnsTcpFactory<T> {
+    _phantom_t: PhantomData<T>,
+}
+
+impl<T> ParserFactory<T> for DnsTcpFactory<T>
+where
+    T: Packet + 'static,
+{
+    fn new() -> Self {
+        Self {
+            _phantom_t: PhantomData,
+        }
+    }
+
+    fn create(&self, prolens: &Prolens<T>) -> Box<dyn Parser<T = T>> {
+        let mut parser = Box::new(DnsTcpParser::new());
+        parser.callbacks = Some(DnsCallbacks::new(prolens));
+        parser
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use crate::{Header, RR, Rdata};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    // 带2字节长度的消息，answers是(名字, A记录或TXT长度)
+    fn dns_msg(id: u16, qr: bool, answers: &[(&str, Result<[u8; 4], usize>)]) -> Vec<u8> {
+        let mut msg = id.to_be_bytes().to_vec();
+        msg.extend_from_slice(&[if qr { 0x84 } else { 0x00 }, 0x00, 0, 1]);
+        msg.extend_from_slice(&(answers.len() as u16).to_be_bytes());
+        msg.extend_from_slice(&[0, 0, 0, 0]);
+        msg.extend_from_slice(b"\x01a\x03com\x00\x00\xfc\x00\x01");
+        for (name, rdata) in answers {
+            for label in name.split('.') {
+                msg.push(label.len() as u8);
+                msg.extend_from_slice(label.as_bytes());
+            }
+            msg.push(0);
+            match rdata {
+                Ok(ip) => {
+                    msg.extend_from_slice(&[0, 1, 0, 1, 0, 0, 0, 60, 0, 4]);
+                    msg.extend_from_slice(ip);
+                }
+                Err(len) => {
+                    msg.extend_from_slice(&[0, 16, 0, 1, 0, 0, 0, 60]);
+                    msg.extend_from_slice(&(*len as u16).to_be_bytes());
+                    msg.extend(std::iter::repeat_n(b'x', *len));
+                }
+            }
+        }
+        let mut framed = (msg.len() as u16).to_be_bytes().to_vec();
+        framed.extend_from_slice(&msg);
+        framed
+    }
+
+    #[test]
+    fn test_dns_tcp() {
+        let headers = Rc::new(RefCell::new(Vec::new()));
+        let answers = Rc::new(RefCell::new(Vec::new()));
+        let ends = Rc::new(RefCell::new(0));
+
+        let mut protolens = Prolens::<CapPacket>::default();
+        let h = headers.clone();
+        protolens.set_cb_dns_header(move |header: Header, _offset: usize, _ctx: *mut c_void| {
+            h.borrow_mut().push((header.id, header.qr));
+        });
+        let a = answers.clone();
+        protolens.set_cb_dns_answer(move |rr: RR, _offset: usize, _ctx: *mut c_void| {
+            let rdata = match rr.rdata {
+                Rdata::A(ip) => ip.to_string(),
+                Rdata::Txt(txt) => format!("txt {}", txt.len()),
+                _ => "other".to_string(),
+            };
+            let name = String::from_utf8_lossy(rr.name);
+            a.borrow_mut()
+                .push((name.trim_end_matches('\0').to_string(), rdata));
+        });
+        let e = ends.clone();
+        protolens.set_cb_dns_end(move |_ctx: *mut c_void| {
+            *e.borrow_mut() += 1;
+        });
+
+        let query = dns_msg(7, false, &[]);
+        // AXFR的应答分成三个消息，第二个比读缓冲大
+        let mut rsp = dns_msg(7, true, &[("a.com", Ok([10, 0, 0, 1]))]);
+        rsp.extend(dns_msg(7, true, &[("big.a.com", Err(3000))]));
+        rsp.extend(dns_msg(
+            7,
+            true,
+            &[
+                ("x.a.com", Ok([10, 0, 0, 2])),
+                ("y.a.com", Ok([10, 0, 0, 3])),
+            ],
+        ));
+
+        let mut task = protolens.new_task(TransProto::Tcp);
+        protolens.set_task_parser(&mut task, L7Proto::DnsTcp);
+        let pkt = build_pkt_payload2(1, &query, 40000, 53, false);
+        let _ = pkt.decode();
+        protolens.run_task(&mut task, pkt);
+
+        let mut seq = 1;
+        for chunk in rsp.chunks(1000) {
+            let pkt = build_pkt_payload2_rev(seq, chunk, 53, 40000, false);
+            let _ = pkt.decode();
+            protolens.run_task(&mut task, pkt);
+            seq += chunk.len() as u32;
+        }
+
+        assert_eq!(
+            *headers.borrow(),
+            vec![(7, true), (7, false), (7, false), (7, false)]
+        );
+        assert_eq!(
+            *answers.borrow(),
+            vec![
+                ("a.com".to_string(), "10.0.0.1".to_string()),
+                ("big.a.com".to_string(), "txt 3000".to_string()),
+                ("x.a.com".to_string(), "10.0.0.2".to_string()),
+                ("y.a.com".to_string(), "10.0.0.3".to_string()),
+            ]
+        );
+        assert_eq!(*ends.borrow(), 4);
+    }
+}
@@ -14,6 +14,7 @@ use crate::packet::*;
 use byteorder::{BigEndian, ByteOrder};
 use std::ffi::c_void;
 use std::marker::PhantomData;
+use std::net::IpAddr;
 use std::net::Ipv4Addr;
 use std::net::Ipv6Addr;
 
@@ -313,35 +314,13 @@ fn name_parser(data: &[u8], pkt: &[u8], name: &mut [u8]) -> Result<usize, ()> {
     }
 }
 
+// 不认识的类型用Other保留类型值，rdata按Unknown交给上层
 fn type_parser(data: &[u8]) -> Result<Type, ()> {
     if data.len() < 2 {
         return Err(());
     }
 
-    let val = BigEndian::read_u16(&data[..2]);
-    match val {
-        1 => Ok(Type::A),
-        2 => Ok(Type::NS),
-        3 => Ok(Type::MD),
-        4 => Ok(Type::MF),
-        5 => Ok(Type::Cname),
-        6 => Ok(Type::Soa),
-        7 => Ok(Type::MB),
-        8 => Ok(Type::MG),
-        9 => Ok(Type::MR),
-        10 => Ok(Type::Null),
-        11 => Ok(Type::Wks),
-        12 => Ok(Type::Ptr),
-        13 => Ok(Type::Hinfo),
-        14 => Ok(Type::Minfo),
-        15 => Ok(Type::MX),
-        16 => Ok(Type::Txt),
-        28 => Ok(Type::Aaaa),
-        33 => Ok(Type::Srv),
-        41 => Ok(Type::Opt),
-        47 => Ok(Type::Nsec),
-        _ => Err(()),
-    }
+    Ok(BigEndian::read_u16(&data[..2]).into())
 }
 
 fn qtype_parser(data: &[u8]) -> Result<Qtype, ()> {
@@ -349,32 +328,7 @@ fn qtype_parser(data: &[u8]) -> Result<Qtype, ()> {
         return Err(());
     }
 
-    let val = BigEndian::read_u16(&data[..2]);
-    match val {
-        1 => Ok(Qtype::A),
-        2 => Ok(Qtype::NS),
-        3 => Ok(Qtype::MD),
-        4 => Ok(Qtype::MF),
-        5 => Ok(Qtype::Cname),
-        6 => Ok(Qtype::Soa),
-        7 => Ok(Qtype::MB),
-        8 => Ok(Qtype::MG),
-        9 => Ok(Qtype::MR),
-        10 => Ok(Qtype::Null),
-        11 => Ok(Qtype::Wks),
-        12 => Ok(Qtype::Ptr),
-        13 => Ok(Qtype::Hinfo),
-        14 => Ok(Qtype::Minfo),
-        15 => Ok(Qtype::MX),
-        16 => Ok(Qtype::Txt),
-        28 => Ok(Qtype::Aaaa),
-        33 => Ok(Qtype::Srv),
-        252 => Ok(Qtype::Axfr),
-        253 => Ok(Qtype::Mailb),
-        254 => Ok(Qtype::Maila),
-        255 => Ok(Qtype::All),
-        _ => Err(()),
-    }
+    Ok(BigEndian::read_u16(&data[..2]).into())
 }
 
 fn qclass_parser(data: &[u8]) -> Result<(bool, Qclass), ()> {
@@ -551,11 +505,23 @@ fn rdata_parser<'a>(
         Type::Soa => soa_parser(data, pkt, rd_name, soa_name),
         Type::Srv => srv_parser(data, pkt, rd_name),
         Type::Txt => Ok(Rdata::Txt(data)),
+        Type::Hinfo => hinfo_parser(data),
+        Type::Naptr => naptr_parser(data, pkt, rd_name),
+        Type::Ds => ds_parser(data),
+        Type::Sshfp => sshfp_parser(data),
+        Type::Rrsig => rrsig_parser(data, pkt, rd_name),
+        Type::Nsec => nsec_parser(data, pkt, rd_name),
+        Type::Dnskey => dnskey_parser(data),
+        Type::Nsec3 => nsec3_parser(data),
+        Type::Tlsa => tlsa_parser(data),
+        Type::Svcb | Type::Https => svcb_parser(data, pkt, rd_name),
+        Type::Uri => uri_parser(data),
+        Type::Caa => caa_parser(data),
         _ => Ok(Rdata::Unknown(data)),
     }
 }
 
-fn a_parser(rdata: &[u8]) -> Result<Rdata, ()> {
+fn a_parser(rdata: &[u8]) -> Result<Rdata<'_>, ()> {
     if rdata.len() != 4 {
         return Err(());
     }
@@ -564,7 +530,7 @@ fn a_parser(rdata: &[u8]) -> Result<Rdata, ()> {
     Ok(Rdata::A(ip))
 }
 
-fn aaaa_parser(rdata: &[u8]) -> Result<Rdata, ()> {
+fn aaaa_parser(rdata: &[u8]) -> Result<Rdata<'_>, ()> {
     if rdata.len() != 16 {
         return Err(());
     }
@@ -670,6 +636,217 @@ fn srv_parser<'a>(rdata: &[u8], pkt: &[u8], rd_name: &'a mut [u8]) -> Result<Rda
     Ok(Rdata::Srv(record))
 }
 
+// <character-string>，返回(内容, 剩余部分)
+fn char_string(data: &[u8]) -> Result<(&[u8], &[u8]), ()> {
+    let (&len, rest) = data.split_first().ok_or(())?;
+    if rest.len() < len as usize {
+        return Err(());
+    }
+    Ok(rest.split_at(len as usize))
+}
+
+fn hinfo_parser(rdata: &[u8]) -> Result<Rdata<'_>, ()> {
+    let (cpu, rest) = char_string(rdata)?;
+    let (os, _) = char_string(rest)?;
+    Ok(Rdata::Hinfo(RdHinfo { cpu, os }))
+}
+
+fn naptr_parser<'a>(rdata: &'a [u8], pkt: &[u8], rd_name: &'a mut [u8]) -> Result<Rdata<'a>, ()> {
+    if rdata.len() < 4 {
+        return Err(());
+    }
+
+    let order = BigEndian::read_u16(&rdata[0..2]);
+    let preference = BigEndian::read_u16(&rdata[2..4]);
+    let (flags, rest) = char_string(&rdata[4..])?;
+    let (services, rest) = char_string(rest)?;
+    let (regexp, rest) = char_string(rest)?;
+    name_parser(rest, pkt, rd_name)?;
+
+    let record = RdNaptr {
+        order,
+        preference,
+        flags,
+        services,
+        regexp,
+        replacement: rd_name,
+    };
+    Ok(Rdata::Naptr(record))
+}
+
+fn ds_parser(rdata: &[u8]) -> Result<Rdata<'_>, ()> {
+    if rdata.len() < 4 {
+        return Err(());
+    }
+
+    let record = RdDs {
+        key_tag: BigEndian::read_u16(&rdata[0..2]),
+        algorithm: rdata[2],
+        digest_type: rdata[3],
+        digest: &rdata[4..],
+    };
+    Ok(Rdata::Ds(record))
+}
+
+fn sshfp_parser(rdata: &[u8]) -> Result<Rdata<'_>, ()> {
+    if rdata.len() < 2 {
+        return Err(());
+    }
+
+    let record = RdSshfp {
+        algorithm: rdata[0],
+        fp_type: rdata[1],
+        fingerprint: &rdata[2..],
+    };
+    Ok(Rdata::Sshfp(record))
+}
+
+fn rrsig_parser<'a>(rdata: &'a [u8], pkt: &[u8], rd_name: &'a mut [u8]) -> Result<Rdata<'a>, ()> {
+    if rdata.len() < 19 {
+        return Err(());
+    }
+
+    let signer_len = name_parser(&rdata[18..], pkt, rd_name)?;
+    let record = RdRrsig {
+        type_covered: BigEndian::read_u16(&rdata[0..2]).into(),
+        algorithm: rdata[2],
+        labels: rdata[3],
+        original_ttl: BigEndian::read_u32(&rdata[4..8]),
+        expiration: BigEndian::read_u32(&rdata[8..12]),
+        inception: BigEndian::read_u32(&rdata[12..16]),
+        key_tag: BigEndian::read_u16(&rdata[16..18]),
+        signer: rd_name,
+        signature: &rdata[18 + signer_len..],
+    };
+    Ok(Rdata::Rrsig(record))
+}
+
+fn nsec_parser<'a>(rdata: &'a [u8], pkt: &[u8], rd_name: &'a mut [u8]) -> Result<Rdata<'a>, ()> {
+    let next_len = name_parser(rdata, pkt, rd_name)?;
+    let record = RdNsec {
+        next: rd_name,
+        type_bitmap: &rdata[next_len..],
+    };
+    Ok(Rdata::Nsec(record))
+}
+
+fn dnskey_parser(rdata: &[u8]) -> Result<Rdata<'_>, ()> {
+    if rdata.len() < 4 {
+        return Err(());
+    }
+
+    let record = RdDnskey {
+        flags: BigEndian::read_u16(&rdata[0..2]),
+        protocol: rdata[2],
+        algorithm: rdata[3],
+        public_key: &rdata[4..],
+    };
+    Ok(Rdata::Dnskey(record))
+}
+
+fn nsec3_parser(rdata: &[u8]) -> Result<Rdata<'_>, ()> {
+    if rdata.len() < 4 {
+        return Err(());
+    }
+
+    let (salt, rest) = char_string(&rdata[4..])?;
+    let (next_hashed, type_bitmap) = char_string(rest)?;
+    let record = RdNsec3 {
+        hash_algorithm: rdata[0],
+        flags: rdata[1],
+        iterations: BigEndian::read_u16(&rdata[2..4]),
+        salt,
+        next_hashed,
+        type_bitmap,
+    };
+    Ok(Rdata::Nsec3(record))
+}
+
+fn tlsa_parser(rdata: &[u8]) -> Result<Rdata<'_>, ()> {
+    if rdata.len() < 3 {
+        return Err(());
+    }
+
+    let record = RdTlsa {
+        usage: rdata[0],
+        selector: rdata[1],
+        matching_type: rdata[2],
+        data: &rdata[3..],
+    };
+    Ok(Rdata::Tlsa(record))
+}
+
+fn svcb_parser<'a>(rdata: &'a [u8], pkt: &[u8], rd_name: &'a mut [u8]) -> Result<Rdata<'a>, ()> {
+    if rdata.len() < 3 {
+        return Err(());
+    }
+
+    let priority = BigEndian::read_u16(&rdata[0..2]);
+    let target_len = name_parser(&rdata[2..], pkt, rd_name)?;
+    let params = &rdata[2 + target_len..];
+
+    let mut record = RdSvcb {
+        priority,
+        target: rd_name,
+        alpn: &[],
+        no_default_alpn: false,
+        port: None,
+        ipv4hint: &[],
+        ech: &[],
+        ipv6hint: &[],
+        params,
+    };
+
+    let mut rest = params;
+    while !rest.is_empty() {
+        if rest.len() < 4 {
+            return Err(());
+        }
+        let key = BigEndian::read_u16(&rest[..2]);
+        let len = BigEndian::read_u16(&rest[2..4]) as usize;
+        let value = rest.get(4..4 + len).ok_or(())?;
+        rest = &rest[4 + len..];
+
+        match key {
+            svc_key::ALPN => record.alpn = value,
+            svc_key::NO_DEFAULT_ALPN => record.no_default_alpn = true,
+            svc_key::PORT if len == 2 => record.port = Some(BigEndian::read_u16(value)),
+            svc_key::IPV4HINT => record.ipv4hint = value,
+            svc_key::ECH => record.ech = value,
+            svc_key::IPV6HINT => record.ipv6hint = value,
+            _ => {}
+        }
+    }
+    Ok(Rdata::Svcb(record))
+}
+
+fn uri_parser(rdata: &[u8]) -> Result<Rdata<'_>, ()> {
+    if rdata.len() < 4 {
+        return Err(());
+    }
+
+    let record = RdUri {
+        priority: BigEndian::read_u16(&rdata[0..2]),
+        weight: BigEndian::read_u16(&rdata[2..4]),
+        target: &rdata[4..],
+    };
+    Ok(Rdata::Uri(record))
+}
+
+fn caa_parser(rdata: &[u8]) -> Result<Rdata<'_>, ()> {
+    if rdata.len() < 2 {
+        return Err(());
+    }
+
+    let (tag, value) = char_string(&rdata[1..])?;
+    let record = RdCaa {
+        flags: rdata[0],
+        tag,
+        value,
+    };
+    Ok(Rdata::Caa(record))
+}
+
 #[derive(Clone)]
 pub(crate) struct DnsCallbacks {
     header: Option<CbDnsHeader>,
@@ -681,6 +858,20 @@ pub(crate) struct DnsCallbacks {
     end: Option<CbDnsEnd>,
 }
 
+impl DnsCallbacks {
+    pub(crate) fn new<T: Packet>(prolens: &Prolens<T>) -> Self {
+        DnsCallbacks {
+            header: prolens.cb_dns_header.clone(),
+            query: prolens.cb_dns_query.clone(),
+            answer: prolens.cb_dns_answer.clone(),
+            auth: prolens.cb_dns_auth.clone(),
+            add: prolens.cb_dns_add.clone(),
+            opt_add: prolens.cb_dns_opt_add.clone(),
+            end: prolens.cb_dns_end.clone(),
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub struct Header {
     pub id: u16,
@@ -808,10 +999,115 @@ pub enum Type {
     Aaaa,
     // service record (RFC 2782)
     Srv,
+    // naming authority pointer (RFC 3403)
+    Naptr,
     // EDNS0 options (RFC 6891)
     Opt,
+    // delegation signer (RFC 4034)
+    Ds,
+    // ssh key fingerprint (RFC 4255)
+    Sshfp,
+    // DNSSEC signature (RFC 4034)
+    Rrsig,
     // next secure record (RFC 4034, RFC 6762)
     Nsec,
+    // DNS key record (RFC 4034)
+    Dnskey,
+    // hashed next secure record (RFC 5155)
+    Nsec3,
+    Nsec3param,
+    // DANE (RFC 6698)
+    Tlsa,
+    // service binding (RFC 9460)
+    Svcb,
+    Https,
+    // RFC 7553
+    Uri,
+    // certification authority authorization (RFC 8659)
+    Caa,
+    Other(u16),
+}
+
+impl From<u16> for Type {
+    fn from(code: u16) -> Type {
+        use self::Type::*;
+        match code {
+            1 => A,
+            2 => NS,
+            3 => MD,
+            4 => MF,
+            5 => Cname,
+            6 => Soa,
+            7 => MB,
+            8 => MG,
+            9 => MR,
+            10 => Null,
+            11 => Wks,
+            12 => Ptr,
+            13 => Hinfo,
+            14 => Minfo,
+            15 => MX,
+            16 => Txt,
+            28 => Aaaa,
+            33 => Srv,
+            35 => Naptr,
+            41 => Opt,
+            43 => Ds,
+            44 => Sshfp,
+            46 => Rrsig,
+            47 => Nsec,
+            48 => Dnskey,
+            50 => Nsec3,
+            51 => Nsec3param,
+            52 => Tlsa,
+            64 => Svcb,
+            65 => Https,
+            256 => Uri,
+            257 => Caa,
+            x => Other(x),
+        }
+    }
+}
+
+impl From<Type> for u16 {
+    fn from(typ: Type) -> Self {
+        use self::Type::*;
+        match typ {
+            A => 1,
+            NS => 2,
+            MD => 3,
+            MF => 4,
+            Cname => 5,
+            Soa => 6,
+            MB => 7,
+            MG => 8,
+            MR => 9,
+            Null => 10,
+            Wks => 11,
+            Ptr => 12,
+            Hinfo => 13,
+            Minfo => 14,
+            MX => 15,
+            Txt => 16,
+            Aaaa => 28,
+            Srv => 33,
+            Naptr => 35,
+            Opt => 41,
+            Ds => 43,
+            Sshfp => 44,
+            Rrsig => 46,
+            Nsec => 47,
+            Dnskey => 48,
+            Nsec3 => 50,
+            Nsec3param => 51,
+            Tlsa => 52,
+            Svcb => 64,
+            Https => 65,
+            Uri => 256,
+            Caa => 257,
+            Other(x) => x,
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
@@ -836,10 +1132,66 @@ pub enum Qtype {
     Aaaa,
     // service record (RFC 2782)
     Srv,
+    Naptr,
+    Ds,
+    Sshfp,
+    Rrsig,
+    Nsec,
+    Dnskey,
+    Nsec3,
+    Tlsa,
+    Svcb,
+    Https,
     Axfr,
     Mailb,
     Maila,
     All,
+    Uri,
+    Caa,
+    Other(u16),
+}
+
+impl From<u16> for Qtype {
+    fn from(code: u16) -> Qtype {
+        use self::Qtype::*;
+        match code {
+            1 => A,
+            2 => NS,
+            3 => MD,
+            4 => MF,
+            5 => Cname,
+            6 => Soa,
+            7 => MB,
+            8 => MG,
+            9 => MR,
+            10 => Null,
+            11 => Wks,
+            12 => Ptr,
+            13 => Hinfo,
+            14 => Minfo,
+            15 => MX,
+            16 => Txt,
+            28 => Aaaa,
+            33 => Srv,
+            35 => Naptr,
+            43 => Ds,
+            44 => Sshfp,
+            46 => Rrsig,
+            47 => Nsec,
+            48 => Dnskey,
+            50 => Nsec3,
+            52 => Tlsa,
+            64 => Svcb,
+            65 => Https,
+            252 => Axfr,
+            253 => Mailb,
+            254 => Maila,
+            255 => All,
+            256 => Uri,
+            257 => Caa,
+            x => Other(x),
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
@@ -876,6 +1228,135 @@ pub struct OptRR<'a> {
     pub rdata: Rdata<'a>,
 }
 
+impl<'a> OptRR<'a> {
+    pub fn dnssec_ok(&self) -> bool {
+        self.flags & 0x8000 != 0
+    }
+
+    pub fn options(&self) -> EdnsOptions<'a> {
+        let data = match self.rdata {
+            Rdata::Unknown(data) => data,
+            _ => &[],
+        };
+        EdnsOptions { data }
+    }
+}
+
+pub mod edns_code {
+    pub const NSID: u16 = 3;
+    pub const CLIENT_SUBNET: u16 = 8;
+    pub const COOKIE: u16 = 10;
+    pub const TCP_KEEPALIVE: u16 = 11;
+    pub const PADDING: u16 = 12;
+    pub const EXTENDED_ERROR: u16 = 15;
+}
+
+// EDNS(0)选项(RFC 6891)。长度不对的已知选项按Other返回
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EdnsOpt<'a> {
+    Nsid(&'a [u8]),
+    ClientSubnet(EdnsClientSubnet),
+    // server在查询中为空
+    Cookie {
+        client: &'a [u8],
+        server: &'a [u8],
+    },
+    // 单位100ms，查询中没有值
+    TcpKeepalive(Option<u16>),
+    Padding(usize),
+    // RFC 8914，extra_text是utf8
+    ExtendedError {
+        info_code: u16,
+        extra_text: &'a [u8],
+    },
+    Other(u16, &'a [u8]),
+}
+
+// RFC 7871，addr按source_prefix截断后补0
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EdnsClientSubnet {
+    pub family: u16,
+    pub source_prefix: u8,
+    pub scope_prefix: u8,
+    pub addr: IpAddr,
+}
+
+// 选项头不完整时结束
+pub struct EdnsOptions<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Iterator for EdnsOptions<'a> {
+    type Item = EdnsOpt<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.data.len() < 4 {
+            return None;
+        }
+        let code = BigEndian::read_u16(&self.data[..2]);
+        let len = BigEndian::read_u16(&self.data[2..4]) as usize;
+        let value = self.data.get(4..4 + len)?;
+        self.data = &self.data[4 + len..];
+        Some(edns_opt(code, value).unwrap_or(EdnsOpt::Other(code, value)))
+    }
+}
+
+fn edns_opt(code: u16, value: &[u8]) -> Option<EdnsOpt<'_>> {
+    match code {
+        edns_code::NSID => Some(EdnsOpt::Nsid(value)),
+        edns_code::CLIENT_SUBNET => client_subnet(value).map(EdnsOpt::ClientSubnet),
+        edns_code::COOKIE => {
+            if value.len() != 8 && !(16..=40).contains(&value.len()) {
+                return None;
+            }
+            let (client, server) = value.split_at(8);
+            Some(EdnsOpt::Cookie { client, server })
+        }
+        edns_code::TCP_KEEPALIVE => match value.len() {
+            0 => Some(EdnsOpt::TcpKeepalive(None)),
+            2 => Some(EdnsOpt::TcpKeepalive(Some(BigEndian::read_u16(value)))),
+            _ => None,
+        },
+        edns_code::PADDING => Some(EdnsOpt::Padding(value.len())),
+        edns_code::EXTENDED_ERROR if value.len() >= 2 => Some(EdnsOpt::ExtendedError {
+            info_code: BigEndian::read_u16(&value[..2]),
+            extra_text: &value[2..],
+        }),
+        _ => None,
+    }
+}
+
+fn client_subnet(value: &[u8]) -> Option<EdnsClientSubnet> {
+    if value.len() < 4 {
+        return None;
+    }
+    let family = BigEndian::read_u16(&value[..2]);
+    let source_prefix = value[2];
+    let addr = &value[4..];
+    if addr.len() != (source_prefix as usize).div_ceil(8) {
+        return None;
+    }
+    let addr = match family {
+        1 if addr.len() <= 4 => {
+            let mut octets = [0u8; 4];
+            octets[..addr.len()].copy_from_slice(addr);
+            IpAddr::from(octets)
+        }
+        2 if addr.len() <= 16 => {
+            let mut octets = [0u8; 16];
+            octets[..addr.len()].copy_from_slice(addr);
+            IpAddr::from(octets)
+        }
+        _ => return None,
+    };
+    Some(EdnsClientSubnet {
+        family,
+        source_prefix,
+        scope_prefix: value[3],
+        addr,
+    })
+}
+
 #[derive(Debug)]
 pub enum Rdata<'a> {
     A(Ipv4Addr),
@@ -887,6 +1368,18 @@ pub enum Rdata<'a> {
     Soa(RdSoa<'a>),
     Srv(RdSrv<'a>),
     Txt(&'a [u8]),
+    Hinfo(RdHinfo<'a>),
+    Naptr(RdNaptr<'a>),
+    Ds(RdDs<'a>),
+    Sshfp(RdSshfp<'a>),
+    Rrsig(RdRrsig<'a>),
+    Nsec(RdNsec<'a>),
+    Dnskey(RdDnskey<'a>),
+    Nsec3(RdNsec3<'a>),
+    Tlsa(RdTlsa<'a>),
+    Svcb(RdSvcb<'a>),
+    Uri(RdUri<'a>),
+    Caa(RdCaa<'a>),
     Unknown(&'a [u8]),
 }
 
@@ -915,6 +1408,238 @@ pub struct RdSrv<'a> {
     pub target: &'a [u8],
 }
 
+#[derive(Debug, Clone, Copy)]
+pub struct RdHinfo<'a> {
+    pub cpu: &'a [u8],
+    pub os: &'a [u8],
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RdNaptr<'a> {
+    pub order: u16,
+    pub preference: u16,
+    pub flags: &'a [u8],
+    pub services: &'a [u8],
+    pub regexp: &'a [u8],
+    pub replacement: &'a [u8],
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RdDs<'a> {
+    pub key_tag: u16,
+    pub algorithm: u8,
+    pub digest_type: u8,
+    pub digest: &'a [u8],
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RdSshfp<'a> {
+    pub algorithm: u8,
+    pub fp_type: u8,
+    pub fingerprint: &'a [u8],
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RdRrsig<'a> {
+    pub type_covered: Type,
+    pub algorithm: u8,
+    pub labels: u8,
+    pub original_ttl: u32,
+    pub expiration: u32,
+    pub inception: u32,
+    pub key_tag: u16,
+    pub signer: &'a [u8],
+    pub signature: &'a [u8],
+}
+
+// type_bitmap是原始的窗口格式，用types()展开
+#[derive(Debug, Clone, Copy)]
+pub struct RdNsec<'a> {
+    pub next: &'a [u8],
+    pub type_bitmap: &'a [u8],
+}
+
+impl<'a> RdNsec<'a> {
+    pub fn types(&self) -> TypeBitmap<'a> {
+        TypeBitmap::new(self.type_bitmap)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RdDnskey<'a> {
+    pub flags: u16,
+    pub protocol: u8,
+    pub algorithm: u8,
+    pub public_key: &'a [u8],
+}
+
+// next_hashed是二进制的hash，不是名字
+#[derive(Debug, Clone, Copy)]
+pub struct RdNsec3<'a> {
+    pub hash_algorithm: u8,
+    pub flags: u8,
+    pub iterations: u16,
+    pub salt: &'a [u8],
+    pub next_hashed: &'a [u8],
+    pub type_bitmap: &'a [u8],
+}
+
+impl<'a> RdNsec3<'a> {
+    pub fn types(&self) -> TypeBitmap<'a> {
+        TypeBitmap::new(self.type_bitmap)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RdTlsa<'a> {
+    pub usage: u8,
+    pub selector: u8,
+    pub matching_type: u8,
+    pub data: &'a [u8],
+}
+
+// SVCB和HTTPS格式相同。常用的参数直接取出来，都是线上格式：
+// alpn是长度前缀的协议列表，ipv4hint/ipv6hint是连续的地址。其他参数用params()遍历
+#[derive(Debug, Clone, Copy)]
+pub struct RdSvcb<'a> {
+    pub priority: u16,
+    pub target: &'a [u8],
+    pub alpn: &'a [u8],
+    pub no_default_alpn: bool,
+    pub port: Option<u16>,
+    pub ipv4hint: &'a [u8],
+    pub ech: &'a [u8],
+    pub ipv6hint: &'a [u8],
+    pub params: &'a [u8],
+}
+
+impl<'a> RdSvcb<'a> {
+    pub fn params(&self) -> SvcParams<'a> {
+        SvcParams { data: self.params }
+    }
+
+    pub fn alpn_ids(&self) -> impl Iterator<Item = &'a [u8]> {
+        CharStrings { data: self.alpn }
+    }
+
+    pub fn ipv4_hints(&self) -> impl Iterator<Item = Ipv4Addr> + 'a {
+        self.ipv4hint
+            .chunks_exact(4)
+            .map(|ip| Ipv4Addr::new(ip[0], ip[1], ip[2], ip[3]))
+    }
+
+    pub fn ipv6_hints(&self) -> impl Iterator<Item = Ipv6Addr> + 'a {
+        self.ipv6hint.chunks_exact(16).map(|ip| {
+            let mut octets = [0u8; 16];
+            octets.copy_from_slice(ip);
+            Ipv6Addr::from(octets)
+        })
+    }
+}
+
+pub mod svc_key {
+    pub const ALPN: u16 = 1;
+    pub const NO_DEFAULT_ALPN: u16 = 2;
+    pub const PORT: u16 = 3;
+    pub const IPV4HINT: u16 = 4;
+    pub const ECH: u16 = 5;
+    pub const IPV6HINT: u16 = 6;
+}
+
+// (key, value)，rdata解析时已经检查过长度
+pub struct SvcParams<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Iterator for SvcParams<'a> {
+    type Item = (u16, &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.data.len() < 4 {
+            return None;
+        }
+        let key = BigEndian::read_u16(&self.data[..2]);
+        let len = BigEndian::read_u16(&self.data[2..4]) as usize;
+        let value = self.data.get(4..4 + len)?;
+        self.data = &self.data[4 + len..];
+        Some((key, value))
+    }
+}
+
+struct CharStrings<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Iterator for CharStrings<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (&len, rest) = self.data.split_first()?;
+        let value = rest.get(..len as usize)?;
+        self.data = &rest[len as usize..];
+        Some(value)
+    }
+}
+
+pub struct TypeBitmap<'a> {
+    data: &'a [u8],
+    window: u16,
+    bits: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> TypeBitmap<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        TypeBitmap {
+            data,
+            window: 0,
+            bits: &[],
+            pos: 0,
+        }
+    }
+}
+
+impl Iterator for TypeBitmap<'_> {
+    type Item = Type;
+
+    fn next(&mut self) -> Option<Type> {
+        loop {
+            while self.pos < self.bits.len() * 8 {
+                let pos = self.pos;
+                self.pos += 1;
+                if self.bits[pos / 8] & (0x80 >> (pos % 8)) != 0 {
+                    return Some((self.window << 8 | pos as u16).into());
+                }
+            }
+            if self.data.len() < 2 {
+                return None;
+            }
+            let len = self.data[1] as usize;
+            if self.data.len() < 2 + len {
+                return None;
+            }
+            self.window = self.data[0] as u16;
+            self.bits = &self.data[2..2 + len];
+            self.data = &self.data[2 + len..];
+            self.pos = 0;
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RdUri<'a> {
+    pub priority: u16,
+    pub weight: u16,
+    pub target: &'a [u8],
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RdCaa<'a> {
+    pub flags: u8,
+    pub tag: &'a [u8],
+    pub value: &'a [u8],
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1181,19 +1906,21 @@ mod tests {
         assert_eq!(type_parser(&[0x00, 0x29]), Ok(Type::Opt));
         assert_eq!(type_parser(&[0x00, 0x2F]), Ok(Type::Nsec));
 
-        // 测试不支持的记录类型
-        assert_eq!(type_parser(&[0x00, 0x00]), Err(())); // 类型0
-        assert_eq!(type_parser(&[0x00, 0x11]), Err(())); // 类型17
-        assert_eq!(type_parser(&[0x00, 0x1B]), Err(())); // 类型27
-        assert_eq!(type_parser(&[0x00, 0x1D]), Err(())); // 类型29
-        assert_eq!(type_parser(&[0x00, 0x20]), Err(())); // 类型32
-        assert_eq!(type_parser(&[0x00, 0x22]), Err(())); // 类型34
-        assert_eq!(type_parser(&[0x00, 0x28]), Err(())); // 类型40
-        assert_eq!(type_parser(&[0x00, 0x2A]), Err(())); // 类型42
-        assert_eq!(type_parser(&[0x00, 0x2E]), Err(())); // 类型46
-        assert_eq!(type_parser(&[0x00, 0x30]), Err(())); // 类型48
-        assert_eq!(type_parser(&[0x00, 0xFF]), Err(())); // 类型255
-        assert_eq!(type_parser(&[0x01, 0xFF]), Err(()));
+        assert_eq!(type_parser(&[0x00, 0x23]), Ok(Type::Naptr));
+        assert_eq!(type_parser(&[0x00, 0x2B]), Ok(Type::Ds));
+        assert_eq!(type_parser(&[0x00, 0x2E]), Ok(Type::Rrsig));
+        assert_eq!(type_parser(&[0x00, 0x30]), Ok(Type::Dnskey));
+        assert_eq!(type_parser(&[0x00, 0x41]), Ok(Type::Https));
+        assert_eq!(type_parser(&[0x01, 0x01]), Ok(Type::Caa));
+
+        // 不认识的记录类型保留类型值
+        assert_eq!(type_parser(&[0x00, 0x00]), Ok(Type::Other(0)));
+        assert_eq!(type_parser(&[0x00, 0x11]), Ok(Type::Other(17)));
+        assert_eq!(type_parser(&[0x00, 0xFF]), Ok(Type::Other(255)));
+        assert_eq!(type_parser(&[0x01, 0xFF]), Ok(Type::Other(511)));
+        for code in [1, 16, 41, 65, 257, 999] {
+            assert_eq!(u16::from(Type::from(code)), code);
+        }
 
         // 测试数据长度不足
         assert_eq!(type_parser(&[]), Err(())); // 空数据
@@ -1219,9 +1946,11 @@ mod tests {
         let data_all = [0x00, 0xFF];
         assert_eq!(qtype_parser(&data_all), Ok(Qtype::All));
 
+        assert_eq!(qtype_parser(&[0x00, 0x41]), Ok(Qtype::Https));
+
         // 测试未知类型
         let data_unknown = [0x12, 0x34];
-        assert_eq!(qtype_parser(&data_unknown), Err(()));
+        assert_eq!(qtype_parser(&data_unknown), Ok(Qtype::Other(0x1234)));
 
         // 测试数据不足
         let data_short = [0x00];
@@ -1713,17 +2442,6 @@ mod tests {
         let result = record_parser(&invalid_rdata_len, &mut name, &mut rd_name, &mut soa_name);
         assert!(result.is_err());
 
-        // 测试无效的记录类型
-        let mut invalid_type = Vec::new();
-        invalid_type.extend_from_slice(&[4, b't', b'e', b's', b't', 0]); // 域名：test
-        invalid_type.extend_from_slice(&[0x00, 0x00]); // 类型：0（无效）
-        invalid_type.extend_from_slice(&[0x00, 0x01]); // 类：IN
-        invalid_type.extend_from_slice(&[0x00, 0x00, 0x0E, 0x10]); // TTL
-        invalid_type.extend_from_slice(&[0x00, 0x04]); // RDATA长度
-        invalid_type.extend_from_slice(&[1, 2, 3, 4]); // RDATA
-        let result = record_parser(&invalid_type, &mut name, &mut rd_name, &mut soa_name);
-        assert!(result.is_err());
-
         // 测试无效的类
         let mut invalid_class = Vec::new();
         invalid_class.extend_from_slice(&[4, b't', b'e', b's', b't', 0]); // 域名：test
@@ -1738,7 +2456,7 @@ mod tests {
 
     #[test]
     fn test_record_parser_unknown_type() {
-        // 测试未知记录类型（应该返回Unknown类型的Rdata）
+        // 测试未知记录类型（应该返回Other类型和Unknown的Rdata）
         let mut data = Vec::new();
 
         // 域名：test.com
@@ -1759,8 +2477,13 @@ mod tests {
         let mut rd_name = [0u8; MAX_NAME];
         let mut soa_name = [0u8; MAX_NAME];
 
-        let result = record_parser(&data, &mut name, &mut rd_name, &mut soa_name);
-        assert!(result.is_err());
+        let (len, rr) = record_parser(&data, &mut name, &mut rd_name, &mut soa_name).unwrap();
+        assert_eq!(len, data.len());
+        assert_eq!(rr.rtype, Type::Other(99));
+        assert!(matches!(
+            rr.rdata,
+            Rdata::Unknown(&[1, 2, 3, 4, 5, 6, 7, 8])
+        ));
     }
 
     // OPT 记录解析 - 正常情况
@@ -2204,6 +2927,7 @@ mod tests {
                         String::from_utf8_lossy(srv.target)
                     ),
                     Rdata::Unknown(data) => format!("Unknown: {} bytes", data.len()),
+                    other => format!("{:?}", other),
                 };
                 let record_info = format!(
                     "{} {} {:?} {} {}",
@@ -2325,4 +3049,336 @@ mod tests {
             .any(|answer| answer.contains("60.1.1.15"));
         assert!(has_target_ip, "应答中应该包含目标IP地址60.1.1.15");
     }
+
+    fn rdata_of(typ: Type, rdata: &[u8], check: impl FnOnce(Rdata)) {
+        let mut rd_name = [0u8; MAX_NAME];
+        let mut soa_name = [0u8; MAX_NAME];
+        check(rdata_parser(typ, rdata, rdata, &mut rd_name, &mut soa_name).unwrap());
+    }
+
+    fn name_str(name: &[u8]) -> String {
+        String::from_utf8_lossy(name)
+            .trim_end_matches('\0')
+            .to_string()
+    }
+
+    #[test]
+    fn test_rdata_svcb() {
+        // 1 . alpn=h2,h3 port=8443 ipv4hint=1.2.3.4,5.6.7.8 ech=abc 未知参数99
+        let mut data = vec![0x00, 0x01, 0x00];
+        data.extend_from_slice(&[0, 1, 0, 6, 2, b'h', b'2', 2, b'h', b'3']);
+        data.extend_from_slice(&[0, 3, 0, 2, 0x20, 0xFB]);
+        data.extend_from_slice(&[0, 4, 0, 8, 1, 2, 3, 4, 5, 6, 7, 8]);
+        data.extend_from_slice(&[0, 5, 0, 3, b'a', b'b', b'c']);
+        data.extend_from_slice(&[0, 99, 0, 1, 0xFF]);
+        rdata_of(Type::Https, &data, |rdata| {
+            let Rdata::Svcb(svcb) = rdata else {
+                panic!("expect svcb");
+            };
+            assert_eq!(svcb.priority, 1);
+            assert_eq!(name_str(svcb.target), "");
+            assert_eq!(svcb.alpn_ids().collect::<Vec<_>>(), vec![b"h2", b"h3"]);
+            assert!(!svcb.no_default_alpn);
+            assert_eq!(svcb.port, Some(8443));
+            assert_eq!(
+                svcb.ipv4_hints().collect::<Vec<_>>(),
+                vec![Ipv4Addr::new(1, 2, 3, 4), Ipv4Addr::new(5, 6, 7, 8)]
+            );
+            assert_eq!(svcb.ech, b"abc");
+            assert_eq!(svcb.ipv6_hints().count(), 0);
+            let keys: Vec<u16> = svcb.params().map(|(key, _)| key).collect();
+            assert_eq!(keys, vec![1, 3, 4, 5, 99]);
+        });
+
+        // 别名模式，target是名字
+        let data = [0, 0, 3, b's', b'v', b'c', 0];
+        rdata_of(Type::Svcb, &data, |rdata| {
+            let Rdata::Svcb(svcb) = rdata else {
+                panic!("expect svcb");
+            };
+            assert_eq!(svcb.priority, 0);
+            assert_eq!(name_str(svcb.target), "svc");
+            assert!(svcb.params.is_empty());
+        });
+
+        // 参数长度越界
+        let mut rd_name = [0u8; MAX_NAME];
+        let mut soa_name = [0u8; MAX_NAME];
+        let data = [0, 1, 0, 0, 1, 0, 9, 2, b'h', b'2'];
+        assert!(rdata_parser(Type::Https, &data, &data, &mut rd_name, &mut soa_name).is_err());
+    }
+
+    #[test]
+    fn test_rdata_dnssec() {
+        let data = [0x30, 0x39, 8, 2, 0xAA, 0xBB];
+        rdata_of(Type::Ds, &data, |rdata| {
+            let Rdata::Ds(ds) = rdata else {
+                panic!("expect ds");
+            };
+            assert_eq!((ds.key_tag, ds.algorithm, ds.digest_type), (12345, 8, 2));
+            assert_eq!(ds.digest, &[0xAA, 0xBB]);
+        });
+
+        let data = [0x01, 0x01, 3, 13, 1, 2, 3];
+        rdata_of(Type::Dnskey, &data, |rdata| {
+            let Rdata::Dnskey(key) = rdata else {
+                panic!("expect dnskey");
+            };
+            assert_eq!((key.flags, key.protocol, key.algorithm), (257, 3, 13));
+            assert_eq!(key.public_key, &[1, 2, 3]);
+        });
+
+        let mut data = vec![0, 1, 13, 2, 0, 0, 0x0E, 0x10];
+        data.extend_from_slice(&[0x65, 0, 0, 0, 0x64, 0, 0, 0, 0x30, 0x39]);
+        data.extend_from_slice(b"\x01a\x03com\x00");
+        data.extend_from_slice(&[9, 9, 9]);
+        rdata_of(Type::Rrsig, &data, |rdata| {
+            let Rdata::Rrsig(sig) = rdata else {
+                panic!("expect rrsig");
+            };
+            assert_eq!(sig.type_covered, Type::A);
+            assert_eq!((sig.algorithm, sig.labels, sig.original_ttl), (13, 2, 3600));
+            assert_eq!((sig.expiration, sig.inception), (0x6500_0000, 0x6400_0000));
+            assert_eq!(sig.key_tag, 12345);
+            assert_eq!(name_str(sig.signer), "a.com");
+            assert_eq!(sig.signature, &[9, 9, 9]);
+        });
+
+        // 下一个名字b.com，类型A NS RRSIG NSEC CAA
+        let mut data = b"\x01b\x03com\x00".to_vec();
+        data.extend_from_slice(&[0, 6, 0x60, 0, 0, 0, 0, 0x03, 1, 1, 0x40]);
+        rdata_of(Type::Nsec, &data, |rdata| {
+            let Rdata::Nsec(nsec) = rdata else {
+                panic!("expect nsec");
+            };
+            assert_eq!(name_str(nsec.next), "b.com");
+            assert_eq!(
+                nsec.types().collect::<Vec<_>>(),
+                vec![Type::A, Type::NS, Type::Rrsig, Type::Nsec, Type::Caa]
+            );
+        });
+
+        let data = [1, 0, 0, 10, 2, 0xAB, 0xCD, 3, 1, 2, 3, 0, 1, 0x40];
+        rdata_of(Type::Nsec3, &data, |rdata| {
+            let Rdata::Nsec3(nsec3) = rdata else {
+                panic!("expect nsec3");
+            };
+            assert_eq!(
+                (nsec3.hash_algorithm, nsec3.flags, nsec3.iterations),
+                (1, 0, 10)
+            );
+            assert_eq!(nsec3.salt, &[0xAB, 0xCD]);
+            assert_eq!(nsec3.next_hashed, &[1, 2, 3]);
+            assert_eq!(nsec3.types().collect::<Vec<_>>(), vec![Type::A]);
+        });
+    }
+
+    #[test]
+    fn test_rdata_misc() {
+        let mut data = vec![0, 5];
+        data.extend_from_slice(b"issueletsencrypt.org");
+        rdata_of(Type::Caa, &data, |rdata| {
+            let Rdata::Caa(caa) = rdata else {
+                panic!("expect caa");
+            };
+            assert_eq!(caa.flags, 0);
+            assert_eq!(caa.tag, b"issue");
+            assert_eq!(caa.value, b"letsencrypt.org");
+        });
+
+        let data = [3, 1, 1, 0xDE, 0xAD];
+        rdata_of(Type::Tlsa, &data, |rdata| {
+            let Rdata::Tlsa(tlsa) = rdata else {
+                panic!("expect tlsa");
+            };
+            assert_eq!((tlsa.usage, tlsa.selector, tlsa.matching_type), (3, 1, 1));
+            assert_eq!(tlsa.data, &[0xDE, 0xAD]);
+        });
+
+        let data = [4, 2, 0xBE, 0xEF];
+        rdata_of(Type::Sshfp, &data, |rdata| {
+            let Rdata::Sshfp(fp) = rdata else {
+                panic!("expect sshfp");
+            };
+            assert_eq!((fp.algorithm, fp.fp_type), (4, 2));
+            assert_eq!(fp.fingerprint, &[0xBE, 0xEF]);
+        });
+
+        let mut data = vec![0, 100, 0, 10, 1, b'S', 7];
+        data.extend_from_slice(b"SIP+D2U");
+        data.extend_from_slice(&[0]);
+        data.extend_from_slice(b"\x04_sip\x04_udp\x01a\x03com\x00");
+        rdata_of(Type::Naptr, &data, |rdata| {
+            let Rdata::Naptr(naptr) = rdata else {
+                panic!("expect naptr");
+            };
+            assert_eq!((naptr.order, naptr.preference), (100, 10));
+            assert_eq!(naptr.flags, b"S");
+            assert_eq!(naptr.services, b"SIP+D2U");
+            assert_eq!(naptr.regexp, b"");
+            assert_eq!(name_str(naptr.replacement), "_sip._udp.a.com");
+        });
+
+        let data = [3, b'x', b'8', b'6', 5, b'L', b'i', b'n', b'u', b'x'];
+        rdata_of(Type::Hinfo, &data, |rdata| {
+            let Rdata::Hinfo(hinfo) = rdata else {
+                panic!("expect hinfo");
+            };
+            assert_eq!(hinfo.cpu, b"x86");
+            assert_eq!(hinfo.os, b"Linux");
+        });
+
+        let mut data = vec![0, 10, 0, 1];
+        data.extend_from_slice(b"ftp://a.com/");
+        rdata_of(Type::Uri, &data, |rdata| {
+            let Rdata::Uri(uri) = rdata else {
+                panic!("expect uri");
+            };
+            assert_eq!((uri.priority, uri.weight), (10, 1));
+            assert_eq!(uri.target, b"ftp://a.com/");
+        });
+
+        let mut rd_name = [0u8; MAX_NAME];
+        let mut soa_name = [0u8; MAX_NAME];
+        let data = [3, b'x', b'8'];
+        assert!(rdata_parser(Type::Hinfo, &data, &data, &mut rd_name, &mut soa_name).is_err());
+    }
+
+    // HTTPS和不认识的类型不影响后面的记录
+    #[test]
+    fn test_dns_parser_modern_types() {
+        let mut msg = vec![0x12, 0x34, 0x81, 0x80, 0, 1, 0, 3, 0, 0, 0, 0];
+        msg.extend_from_slice(b"\x01a\x03com\x00\x00\x41\x00\x01");
+        // HTTPS 1 . alpn=h2
+        msg.extend_from_slice(b"\x01a\x03com\x00");
+        msg.extend_from_slice(&[0, 65, 0, 1, 0, 0, 0, 60, 0, 10]);
+        msg.extend_from_slice(&[0, 1, 0, 0, 1, 0, 3, 2, b'h', b'2']);
+        // 类型65280
+        msg.extend_from_slice(b"\x01a\x03com\x00");
+        msg.extend_from_slice(&[0xFF, 0x00, 0, 1, 0, 0, 0, 60, 0, 2, 7, 7]);
+        msg.extend_from_slice(b"\x01a\x03com\x00");
+        msg.extend_from_slice(&[0, 1, 0, 1, 0, 0, 0, 60, 0, 4, 1, 2, 3, 4]);
+
+        let qtypes = Rc::new(RefCell::new(Vec::new()));
+        let answers = Rc::new(RefCell::new(Vec::new()));
+        let q = qtypes.clone();
+        let query: CbDnsQuery = Rc::new(RefCell::new(
+            move |_name: &[u8],
+                  qtype: Qtype,
+                  _qclass: Qclass,
+                  _unicast: bool,
+                  _offset: usize,
+                  _ctx: *mut c_void| {
+                q.borrow_mut().push(qtype);
+            },
+        ));
+        let a = answers.clone();
+        let answer: CbDnsAnswer = Rc::new(RefCell::new(
+            move |rr: RR, _offset: usize, _ctx: *mut c_void| {
+                let alpn = match rr.rdata {
+                    Rdata::Svcb(svcb) => svcb.alpn_ids().count(),
+                    _ => 0,
+                };
+                a.borrow_mut().push((rr.rtype, alpn));
+            },
+        ));
+        let callbacks = DnsCallbacks {
+            header: None,
+            query: Some(query),
+            answer: Some(answer),
+            auth: None,
+            add: None,
+            opt_add: None,
+            end: None,
+        };
+        assert!(dns_parser(&msg, callbacks, std::ptr::null_mut()).is_ok());
+        assert_eq!(*qtypes.borrow(), vec![Qtype::Https]);
+        assert_eq!(
+            *answers.borrow(),
+            vec![(Type::Https, 1), (Type::Other(0xFF00), 0), (Type::A, 0)]
+        );
+    }
+
+    #[test]
+    fn test_opt_record_edns_options() {
+        let mut rd_name = [0u8; MAX_NAME];
+        let mut soa_name = [0u8; MAX_NAME];
+
+        let mut options = Vec::new();
+        // ECS 192.168.1.0/24 scope 0
+        options.extend_from_slice(&[0, 8, 0, 7, 0, 1, 24, 0, 192, 168, 1]);
+        // COOKIE 8字节client + 8字节server
+        options.extend_from_slice(&[0, 10, 0, 16]);
+        options.extend_from_slice(&[1; 8]);
+        options.extend_from_slice(&[2; 8]);
+        options.extend_from_slice(&[0, 3, 0, 2, b'n', b'1']);
+        options.extend_from_slice(&[0, 11, 0, 2, 0x01, 0x2C]);
+        options.extend_from_slice(&[0, 12, 0, 3, 0, 0, 0]);
+        // EDE 18 Prohibited
+        options.extend_from_slice(&[0, 15, 0, 6, 0, 18, b'b', b'l', b'o', b'c']);
+        // 长度不对的ECS
+        options.extend_from_slice(&[0, 8, 0, 5, 0, 1, 24, 0, 10]);
+        options.extend_from_slice(&[0xFD, 0xE9, 0, 1, 7]);
+        // 不完整的选项头
+        options.extend_from_slice(&[0, 3]);
+
+        let mut data = vec![0x00, 0x00, 0x29, 0x04, 0xD0, 0x00, 0x00, 0x80, 0x00];
+        data.extend_from_slice(&(options.len() as u16).to_be_bytes());
+        data.extend_from_slice(&options);
+
+        let (_, opt_rr) = opt_record_parser(&data, &mut rd_name, &mut soa_name).unwrap();
+        assert!(opt_rr.dnssec_ok());
+        let opts: Vec<_> = opt_rr.options().collect();
+        assert_eq!(
+            opts,
+            vec![
+                EdnsOpt::ClientSubnet(EdnsClientSubnet {
+                    family: 1,
+                    source_prefix: 24,
+                    scope_prefix: 0,
+                    addr: IpAddr::V4(Ipv4Addr::new(192, 168, 1, 0)),
+                }),
+                EdnsOpt::Cookie {
+                    client: &[1; 8],
+                    server: &[2; 8],
+                },
+                EdnsOpt::Nsid(b"n1"),
+                EdnsOpt::TcpKeepalive(Some(300)),
+                EdnsOpt::Padding(3),
+                EdnsOpt::ExtendedError {
+                    info_code: 18,
+                    extra_text: b"bloc",
+                },
+                EdnsOpt::Other(8, &[0, 1, 24, 0, 10]),
+                EdnsOpt::Other(65001, &[7]),
+            ]
+        );
+
+        // 查询中的cookie和keepalive，IPv6的ECS
+        let options = [
+            0, 10, 0, 8, 1, 2, 3, 4, 5, 6, 7, 8, 0, 11, 0, 0, 0, 8, 0, 6, 0, 2, 16, 0, 0x20, 0x01,
+        ];
+        let mut data = vec![0x00, 0x00, 0x29, 0x04, 0xD0, 0x00, 0x00, 0x00, 0x00];
+        data.extend_from_slice(&(options.len() as u16).to_be_bytes());
+        data.extend_from_slice(&options);
+        let (_, opt_rr) = opt_record_parser(&data, &mut rd_name, &mut soa_name).unwrap();
+        assert!(!opt_rr.dnssec_ok());
+        let opts: Vec<_> = opt_rr.options().collect();
+        assert_eq!(
+            opts,
+            vec![
+                EdnsOpt::Cookie {
+                    client: &[1, 2, 3, 4, 5, 6, 7, 8],
+                    server: &[],
+                },
+                EdnsOpt::TcpKeepalive(None),
+                EdnsOpt::ClientSubnet(EdnsClientSubnet {
+                    family: 2,
+                    source_prefix: 16,
+                    scope_prefix: 0,
+                    addr: "2001::".parse().unwrap(),
+                }),
+            ]
+        );
+    }
 }
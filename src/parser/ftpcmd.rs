@@ -2,13 +2,16 @@ use crate::CbClt;
 use crate::CbFtpLink;
 use crate::CbSrv;
 use crate::DirConfirmFn;
+use crate::ExpectRef;
 use crate::FTP_PORT;
+use crate::FtpSession;
 use crate::Parser;
 use crate::ParserFactory;
 use crate::ParserFuture;
 use crate::PktStrm;
 use crate::Prolens;
 use crate::packet::*;
+use crate::reply_line;
 use nom::{
     IResult,
     bytes::complete::{tag, take_until, take_while},
@@ -17,11 +20,23 @@ use nom::{
     sequence::{preceded, tuple},
 };
 use phf::phf_set;
+use std::cell::RefCell;
 use std::ffi::c_void;
 use std::marker::PhantomData;
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::rc::Rc;
 use std::str::FromStr;
 
+// 一次数据连接传输。cmd和path来自触发它的RETR/STOR/LIST等命令，数据连接建立时可能还没有
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct FtpXfer {
+    pub session: u64, // 控制连接的id
+    pub cmd: Option<String>,
+    pub path: Option<String>,
+}
+
+pub(crate) type FtpXferRef = Rc<RefCell<FtpXfer>>;
+
 pub struct FtpCmdParser<T>
 where
     T: Packet,
@@ -29,6 +44,8 @@ where
     cb_clt: Option<CbClt>,
     cb_srv: Option<CbSrv>,
     cb_link: Option<CbFtpLink>,
+    expect: Option<ExpectRef>,
+    session: Rc<RefCell<FtpSession>>,
     _phantom_t: PhantomData<T>,
 }
 
@@ -41,6 +58,8 @@ where
             cb_clt: None,
             cb_srv: None,
             cb_link: None,
+            expect: None,
+            session: Rc::new(RefCell::new(FtpSession::default())),
             _phantom_t: PhantomData,
         }
     }
@@ -49,6 +68,8 @@ where
         strm: *mut PktStrm<T>,
         cb_clt: Option<CbClt>,
         cb_link: Option<CbFtpLink>,
+        expect: Option<ExpectRef>,
+        session: Rc<RefCell<FtpSession>>,
         cb_ctx: *mut c_void,
     ) -> Result<(), ()> {
         let stm = unsafe { &mut *strm };
@@ -59,8 +80,12 @@ where
             if let Some(ref cb) = cb_clt {
                 cb.borrow_mut()(line.as_bytes(), seq, cb_ctx);
             }
+            session.borrow_mut().command(line);
 
             if let Some((ip, port)) = port_cmd(line) {
+                session
+                    .borrow_mut()
+                    .link(expect.as_ref(), IpAddr::V4(ip), port, stm.dip());
                 if let Some(ref cb) = cb_link {
                     cb.borrow_mut()(Some(IpAddr::V4(ip)), port, cb_ctx, Direction::C2s);
                 }
@@ -68,6 +93,9 @@ where
             }
 
             if let Some((ip, port)) = eprt_cmd(line) {
+                session
+                    .borrow_mut()
+                    .link(expect.as_ref(), ip, port, stm.dip());
                 if let Some(ref cb) = cb_link {
                     cb.borrow_mut()(Some(ip), port, cb_ctx, Direction::C2s);
                 }
@@ -80,9 +108,12 @@ where
         strm: *mut PktStrm<T>,
         cb_srv: Option<CbSrv>,
         cb_link: Option<CbFtpLink>,
+        expect: Option<ExpectRef>,
+        session: Rc<RefCell<FtpSession>>,
         cb_ctx: *mut c_void,
     ) -> Result<(), ()> {
         let stm = unsafe { &mut *strm };
+        let mut multi = None;
 
         loop {
             let (line, seq) = stm.readline_str().await?;
@@ -90,8 +121,19 @@ where
             if let Some(ref cb) = cb_srv {
                 cb.borrow_mut()(line.as_bytes(), seq, cb_ctx);
             }
+            if let Some((code, text)) = reply_line(line, &mut multi) {
+                session.borrow_mut().reply(code, text, cb_ctx);
+            }
 
             if let Some((ip, port)) = pasv_rsp(line) {
+                // 有的服务器回0.0.0.0，这时用控制连接的服务端地址
+                let link_ip = match stm.sip() {
+                    Some(sip) if ip.is_unspecified() => sip,
+                    _ => IpAddr::V4(ip),
+                };
+                session
+                    .borrow_mut()
+                    .link(expect.as_ref(), link_ip, port, stm.dip());
                 if let Some(ref cb) = cb_link {
                     cb.borrow_mut()(Some(IpAddr::V4(ip)), port, cb_ctx, Direction::S2c);
                 }
@@ -99,6 +141,12 @@ where
             }
 
             if let Some(port) = epsv_rsp(line) {
+                // EPSV不带地址，数据连接的目的地址就是控制连接的服务端
+                if let Some(sip) = stm.sip() {
+                    session
+                        .borrow_mut()
+                        .link(expect.as_ref(), sip, port, stm.dip());
+                }
                 if let Some(ref cb) = cb_link {
                     cb.borrow_mut()(None, port, cb_ctx, Direction::S2c);
                 }
@@ -144,11 +192,11 @@ where
                 }
             }
 
-            if let Ok(payload) = payload_c2s {
-                if payload.len() >= 4 && srv_cmd(unsafe { std::str::from_utf8_unchecked(payload) })
-                {
-                    return Some(false);
-                }
+            if let Ok(payload) = payload_c2s
+                && payload.len() >= 4
+                && srv_cmd(unsafe { std::str::from_utf8_unchecked(payload) })
+            {
+                return Some(false);
             }
 
             Some(true)
@@ -160,6 +208,8 @@ where
             strm,
             self.cb_clt.clone(),
             self.cb_link.clone(),
+            self.expect.clone(),
+            self.session.clone(),
             cb_ctx,
         )))
     }
@@ -169,6 +219,8 @@ where
             strm,
             self.cb_srv.clone(),
             self.cb_link.clone(),
+            self.expect.clone(),
+            self.session.clone(),
             cb_ctx,
         )))
     }
@@ -193,6 +245,14 @@ where
         parser.cb_clt = prolens.cb_ftp_clt.clone();
         parser.cb_srv = prolens.cb_ftp_srv.clone();
         parser.cb_link = prolens.cb_ftp_link.clone();
+        {
+            let mut session = parser.session.borrow_mut();
+            session.id = prolens.expect.borrow_mut().session();
+            session.cb_login = prolens.cb_ftp_login.clone();
+            session.cb_file = prolens.cb_ftp_file.clone();
+            session.cb_transfer = prolens.cb_ftp_transfer.clone();
+        }
+        parser.expect = Some(prolens.expect.clone());
         parser
     }
 }
@@ -391,6 +451,7 @@ fn clt_cmd(input: &str) -> bool {
 mod tests {
     use super::*;
     use crate::test_utils::*;
+    use crate::{FtpFile, FtpLogin, FtpTransfer};
     use std::cell::RefCell;
     use std::env;
     use std::rc::Rc;
@@ -791,4 +852,219 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_ftp_expect() {
+        let xfers = Rc::new(RefCell::new(Vec::new()));
+        let bodies = Rc::new(RefCell::new(Vec::new()));
+
+        let mut protolens = Prolens::<CapPacket>::default();
+        let x = xfers.clone();
+        protolens.set_cb_ftp_xfer_start(move |xfer: &FtpXfer, _ctx: *mut c_void, dir| {
+            x.borrow_mut().push((xfer.clone(), dir));
+        });
+        let b = bodies.clone();
+        protolens.set_cb_ftp_xfer_body(
+            move |xfer: &FtpXfer, data: &[u8], _seq: u32, _ctx: *mut c_void, _dir| {
+                b.borrow_mut().push((xfer.session, data.to_vec()));
+            },
+        );
+
+        // 控制连接 192.168.1.1:1234 -> 192.168.1.2:21
+        let mut ctrl = protolens.new_task(TransProto::Tcp);
+        protolens.set_task_parser(&mut ctrl, L7Proto::FtpCmd);
+        let mut clt_seq = 1;
+        let mut srv_seq = 1;
+        let mut ctrl_run = |protolens: &mut Prolens<CapPacket>, line: &[u8], c2s: bool| {
+            let pkt = if c2s {
+                let pkt = build_pkt_payload2(clt_seq, line, 1234, 21, false);
+                clt_seq += line.len() as u32;
+                pkt
+            } else {
+                let pkt = build_pkt_payload2_rev(srv_seq, line, 21, 1234, false);
+                srv_seq += line.len() as u32;
+                pkt
+            };
+            let _ = pkt.decode();
+            protolens.run_task(&mut ctrl, pkt);
+        };
+
+        ctrl_run(&mut protolens, b"EPSV\r\n", true);
+        ctrl_run(
+            &mut protolens,
+            b"229 Entering Extended Passive Mode (|||5000|)\r\n",
+            false,
+        );
+        assert_eq!(protolens.expect.borrow().len(), 1);
+
+        // 别的客户端连同一个服务端端口，不是这个控制连接的数据连接
+        let mut stranger = protolens.new_task(TransProto::Tcp);
+        let pkt = build_pkt_payload_inner(
+            1,
+            b"",
+            [192, 168, 1, 9],
+            [192, 168, 1, 2],
+            1240,
+            5000,
+            false,
+        );
+        let _ = pkt.decode();
+        protolens.run_task(&mut stranger, pkt);
+        assert!(!stranger.parser_set());
+        assert_eq!(protolens.expect.borrow().len(), 1);
+
+        // 数据连接先建立，RETR随后才到
+        let mut data = protolens.new_task(TransProto::Tcp);
+        let pkt = build_pkt_payload2(1, b"", 1235, 5000, false);
+        let _ = pkt.decode();
+        protolens.run_task(&mut data, pkt);
+        assert!(data.parser_set());
+        assert_eq!(protolens.expect.borrow().len(), 0);
+
+        ctrl_run(&mut protolens, b"RETR pub/file.txt\r\n", true);
+        let pkt = build_pkt_payload2_rev(1, b"hello", 5000, 1235, true);
+        let _ = pkt.decode();
+        protolens.run_task(&mut data, pkt);
+
+        // 主动模式，服务端从20端口连过来
+        ctrl_run(&mut protolens, b"PORT 192,168,1,1,4,1\r\n", true);
+        ctrl_run(&mut protolens, b"200 PORT command successful.\r\n", false);
+        ctrl_run(&mut protolens, b"LIST\r\n", true);
+        let mut data2 = protolens.new_task(TransProto::Tcp);
+        let pkt = build_pkt_payload2_rev(1, b"a.txt\r\n", 20, 1025, true);
+        let _ = pkt.decode();
+        protolens.run_task(&mut data2, pkt);
+
+        // 没有预测的流不受影响
+        let mut other = protolens.new_task(TransProto::Tcp);
+        let pkt = build_pkt_payload2(1, b"x", 1236, 5001, false);
+        let _ = pkt.decode();
+        protolens.run_task(&mut other, pkt);
+        assert!(!other.parser_set());
+
+        let xfers = xfers.borrow();
+        assert_eq!(xfers.len(), 2);
+        assert_eq!(xfers[0].0.session, 1);
+        assert_eq!(xfers[0].0.cmd.as_deref(), Some("RETR"));
+        assert_eq!(xfers[0].0.path.as_deref(), Some("pub/file.txt"));
+        assert_eq!(xfers[0].1, Direction::S2c);
+        assert_eq!(xfers[1].0.session, 1);
+        assert_eq!(xfers[1].0.cmd.as_deref(), Some("LIST"));
+        assert_eq!(xfers[1].0.path, None);
+        assert_eq!(
+            *bodies.borrow(),
+            vec![(1, b"hello".to_vec()), (1, b"a.txt\r\n".to_vec())]
+        );
+    }
+
+    #[test]
+    fn test_ftp_session() {
+        let events = Rc::new(RefCell::new(Vec::new()));
+
+        let mut protolens = Prolens::<CapPacket>::default();
+        let e = events.clone();
+        protolens.set_cb_ftp_login(move |login: &FtpLogin, _ctx: *mut c_void| {
+            e.borrow_mut().push(format!(
+                "login {} {:?} {}",
+                login.user, login.pass, login.code
+            ));
+        });
+        let e = events.clone();
+        protolens.set_cb_ftp_file(move |file: &FtpFile, _ctx: *mut c_void| {
+            e.borrow_mut().push(format!(
+                "file {} {} {:?} {:?} {}",
+                file.cmd, file.path, file.rename_to, file.cwd, file.code
+            ));
+        });
+        let e = events.clone();
+        protolens.set_cb_ftp_transfer(move |xfer: &FtpTransfer, _ctx: *mut c_void| {
+            e.borrow_mut().push(format!(
+                "transfer {} {:?} {:?} {} {:?} {:?} {}",
+                xfer.cmd, xfer.path, xfer.cwd, xfer.code, xfer.data_type, xfer.mode, xfer.rest
+            ));
+        });
+
+        let dialog: &[(bool, &[u8])] = &[
+            (false, b"220 ready\r\n"),
+            (true, b"USER bob\r\n"),
+            (false, b"331 Password required\r\n"),
+            (true, b"PASS wrong\r\n"),
+            (false, b"530 Login incorrect\r\n"),
+            (true, b"USER bob\r\n"),
+            (false, b"331 Password required\r\n"),
+            (true, b"PASS secret\r\n"),
+            (
+                false,
+                b"230-Welcome\r\n230-to the server\r\n230 Login successful\r\n",
+            ),
+            (true, b"FEAT\r\n"),
+            (
+                false,
+                b"211-Features:\r\n EPSV\r\n REST STREAM\r\n211 End\r\n",
+            ),
+            (true, b"PWD\r\n"),
+            (false, b"257 \"/home/bob\" is current directory\r\n"),
+            (true, b"CWD pub/../docs\r\n"),
+            (false, b"250 OK\r\n"),
+            (true, b"TYPE I\r\n"),
+            (false, b"200 Switching to Binary mode\r\n"),
+            (true, b"REST 100\r\n"),
+            (false, b"350 Restarting at 100\r\n"),
+            (true, b"PASV\r\n"),
+            (false, b"227 Entering Passive Mode (192,168,1,2,19,136)\r\n"),
+            (true, b"RETR a.iso\r\n"),
+            (false, b"150 Opening BINARY mode data connection\r\n"),
+            (false, b"226 Transfer complete\r\n"),
+            (true, b"CDUP\r\n"),
+            (false, b"250 OK\r\n"),
+            (true, b"PASV\r\n"),
+            (false, b"227 Entering Passive Mode (192,168,1,2,19,137)\r\n"),
+            (true, b"STOR up.txt\r\n"),
+            (false, b"150 Ok to send data\r\n"),
+            (true, b"ABOR\r\n"),
+            (false, b"426 Connection closed; transfer aborted\r\n"),
+            (false, b"226 ABOR successful\r\n"),
+            (true, b"RNFR up.txt\r\n"),
+            (false, b"350 Ready for RNTO\r\n"),
+            (true, b"RNTO done.txt\r\n"),
+            (false, b"250 Rename successful\r\n"),
+            (true, b"DELE missing.txt\r\n"),
+            (false, b"550 Delete operation failed\r\n"),
+            (true, b"MKD new\r\n"),
+            (false, b"257 \"/home/new\" created\r\n"),
+        ];
+
+        let mut task = protolens.new_task(TransProto::Tcp);
+        protolens.set_task_parser(&mut task, L7Proto::FtpCmd);
+        let mut clt_seq = 1;
+        let mut srv_seq = 1;
+        for (c2s, line) in dialog {
+            let pkt = if *c2s {
+                let pkt = build_pkt_payload2(clt_seq, line, 1234, 21, false);
+                clt_seq += line.len() as u32;
+                pkt
+            } else {
+                let pkt = build_pkt_payload2_rev(srv_seq, line, 21, 1234, false);
+                srv_seq += line.len() as u32;
+                pkt
+            };
+            let _ = pkt.decode();
+            protolens.run_task(&mut task, pkt);
+        }
+
+        assert_eq!(
+            *events.borrow(),
+            vec![
+                "login bob Some(\"wrong\") 530",
+                "login bob Some(\"secret\") 230",
+                "file RETR a.iso None Some(\"/home/bob/docs\") 226",
+                "transfer RETR Some(\"a.iso\") Some(\"/home/bob/docs\") 226 Image Stream 100",
+                "file STOR up.txt None Some(\"/home/bob\") 426",
+                "transfer STOR Some(\"up.txt\") Some(\"/home/bob\") 426 Image Stream 0",
+                "file RNTO up.txt Some(\"done.txt\") Some(\"/home/bob\") 250",
+                "file DELE missing.txt None Some(\"/home/bob\") 550",
+                "file MKD new None Some(\"/home/bob\") 257",
+            ]
+        );
+    }
 }
@@ -1,5 +1,11 @@
 use crate::CbBodyEvt;
 use crate::CbFtpBody;
+use crate::CbFtpListEntry;
+use crate::CbFtpXferBody;
+use crate::CbFtpXferEvt;
+use crate::FtpList;
+use crate::FtpXferRef;
+use crate::ListFormat;
 use crate::Parser;
 use crate::ParserFactory;
 use crate::ParserFuture;
@@ -10,13 +16,46 @@ use crate::packet::*;
 use std::ffi::c_void;
 use std::marker::PhantomData;
 
+#[derive(Clone)]
+struct FtpDataCallbacks {
+    body_start: Option<CbBodyEvt>,
+    body: Option<CbFtpBody>,
+    body_stop: Option<CbBodyEvt>,
+    xfer_start: Option<CbFtpXferEvt>,
+    xfer_body: Option<CbFtpXferBody>,
+    xfer_stop: Option<CbFtpXferEvt>,
+    list_entry: Option<CbFtpListEntry>,
+    xfer: Option<FtpXferRef>, // 由控制连接预测出来的数据连接才有
+}
+
+impl FtpDataCallbacks {
+    // 数据连接经常先于RETR/STOR建立，xfer_start推迟到第一块数据，保证能带上命令
+    fn xfer_start(&self, started: &mut bool, cb_ctx: *mut c_void, dir: Direction) {
+        if *started {
+            return;
+        }
+        *started = true;
+        if let Some(ref xfer) = self.xfer
+            && let Some(ref cb) = self.xfer_start
+        {
+            cb.borrow_mut()(&xfer.borrow(), cb_ctx, dir);
+        }
+    }
+
+    // 触发命令是LIST/MLSD/NLST时按目录列表解析
+    fn list(&self) -> Option<FtpList> {
+        self.list_entry.as_ref()?;
+        let xfer = self.xfer.as_ref()?.borrow();
+        let format = ListFormat::from_cmd(xfer.cmd.as_deref()?)?;
+        Some(FtpList::new(format, xfer.session))
+    }
+}
+
 pub struct FtpDataParser<T>
 where
     T: Packet,
 {
-    cb_body_start: Option<CbBodyEvt>,
-    cb_body: Option<CbFtpBody>,
-    cb_body_stop: Option<CbBodyEvt>,
+    cb: FtpDataCallbacks,
     _phantom_t: PhantomData<T>,
 }
 
@@ -26,32 +65,51 @@ where
 {
     pub(crate) fn new() -> Self {
         Self {
-            cb_body_start: None,
-            cb_body: None,
-            cb_body_stop: None,
+            cb: FtpDataCallbacks {
+                body_start: None,
+                body: None,
+                body_stop: None,
+                xfer_start: None,
+                xfer_body: None,
+                xfer_stop: None,
+                list_entry: None,
+                xfer: None,
+            },
             _phantom_t: PhantomData,
         }
     }
 
     async fn parser_inner(
         strm: *mut PktStrm<T>,
-        cb_body_start: Option<CbBodyEvt>,
-        cb_body: Option<CbFtpBody>,
-        cb_body_stop: Option<CbBodyEvt>,
+        cb: FtpDataCallbacks,
         dir: Direction,
         cb_ctx: *mut c_void,
     ) -> Result<(), ()> {
         let stm = unsafe { &mut *strm };
 
-        if let Some(cb) = cb_body_start {
+        if let Some(ref cb) = cb.body_start {
             cb.borrow_mut()(cb_ctx, dir);
         }
+        let mut started = false;
+        let mut list = None;
         loop {
             match stm.read2eof().await {
                 Ok((bytes, seq)) => {
-                    if let Some(ref cb) = cb_body {
+                    if let Some(ref cb) = cb.body {
                         cb.borrow_mut()(bytes, seq, cb_ctx, dir);
                     }
+                    if !started {
+                        list = cb.list();
+                    }
+                    cb.xfer_start(&mut started, cb_ctx, dir);
+                    if let Some(ref xfer) = cb.xfer
+                        && let Some(ref cb) = cb.xfer_body
+                    {
+                        cb.borrow_mut()(&xfer.borrow(), bytes, seq, cb_ctx, dir);
+                    }
+                    if let Some(ref mut list) = list {
+                        list.feed(bytes, cb.list_entry.as_ref(), cb_ctx, dir);
+                    }
                 }
                 Err(ReadError::Eof) => {
                     break;
@@ -61,9 +119,18 @@ where
                 }
             }
         }
-        if let Some(cb) = cb_body_stop {
+        if let Some(ref mut list) = list {
+            list.flush(cb.list_entry.as_ref(), cb_ctx, dir);
+        }
+        if let Some(ref cb) = cb.body_stop {
             cb.borrow_mut()(cb_ctx, dir);
         }
+        cb.xfer_start(&mut started, cb_ctx, dir);
+        if let Some(ref xfer) = cb.xfer
+            && let Some(ref cb) = cb.xfer_stop
+        {
+            cb.borrow_mut()(&xfer.borrow(), cb_ctx, dir);
+        }
         Ok(())
     }
 }
@@ -77,9 +144,7 @@ where
     fn c2s_parser(&self, strm: *mut PktStrm<T>, cb_ctx: *mut c_void) -> Option<ParserFuture> {
         Some(Box::pin(Self::parser_inner(
             strm,
-            self.cb_body_start.clone(),
-            self.cb_body.clone(),
-            self.cb_body_stop.clone(),
+            self.cb.clone(),
             Direction::C2s,
             cb_ctx,
         )))
@@ -88,9 +153,7 @@ where
     fn s2c_parser(&self, strm: *mut PktStrm<T>, cb_ctx: *mut c_void) -> Option<ParserFuture> {
         Some(Box::pin(Self::parser_inner(
             strm,
-            self.cb_body_start.clone(),
-            self.cb_body.clone(),
-            self.cb_body_stop.clone(),
+            self.cb.clone(),
             Direction::S2c,
             cb_ctx,
         )))
@@ -101,6 +164,28 @@ pub(crate) struct FtpDataFactory<T> {
     _phantom_t: PhantomData<T>,
 }
 
+impl<T> FtpDataFactory<T>
+where
+    T: Packet + 'static,
+{
+    // 命中控制连接预测的流，带上对应的传输
+    pub(crate) fn create_xfer(
+        prolens: &Prolens<T>,
+        xfer: Option<FtpXferRef>,
+    ) -> Box<dyn Parser<T = T>> {
+        let mut parser = Box::new(FtpDataParser::new());
+        parser.cb.body_start = prolens.cb_ftp_body_start.clone();
+        parser.cb.body = prolens.cb_ftp_body.clone();
+        parser.cb.body_stop = prolens.cb_ftp_body_stop.clone();
+        parser.cb.xfer_start = prolens.cb_ftp_xfer_start.clone();
+        parser.cb.xfer_body = prolens.cb_ftp_xfer_body.clone();
+        parser.cb.xfer_stop = prolens.cb_ftp_xfer_stop.clone();
+        parser.cb.list_entry = prolens.cb_ftp_list_entry.clone();
+        parser.cb.xfer = xfer;
+        parser
+    }
+}
+
 impl<T> ParserFactory<T> for FtpDataFactory<T>
 where
     T: Packet + 'static,
@@ -112,11 +197,7 @@ where
     }
 
     fn create(&self, prolens: &Prolens<T>) -> Box<dyn Parser<T = T>> {
-        let mut parser = Box::new(FtpDataParser::new());
-        parser.cb_body_start = prolens.cb_ftp_body_start.clone();
-        parser.cb_body = prolens.cb_ftp_body.clone();
-        parser.cb_body_stop = prolens.cb_ftp_body_stop.clone();
-        parser
+        Self::create_xfer(prolens, None)
     }
 }
 
@@ -124,6 +205,7 @@ where
 mod tests {
     use super::*;
     use crate::test_utils::*;
+    use crate::{FtpEntry, FtpEntryType};
     use std::cell::RefCell;
     use std::env;
     use std::rc::Rc;
@@ -212,4 +294,49 @@ mod tests {
         assert!(body0_str.contains("wget-log.1"));
         assert!(body0_str.contains(" 4096 May 05  2010 音乐\r\n"));
     }
+
+    #[test]
+    fn test_ftp_list_entry() {
+        let entries = Rc::new(RefCell::new(Vec::new()));
+
+        let mut protolens = Prolens::<CapPacket>::default();
+        let e = entries.clone();
+        protolens.set_cb_ftp_list_entry(move |entry: &FtpEntry, _ctx: *mut c_void, _dir| {
+            e.borrow_mut()
+                .push((entry.name.to_string(), entry.entry_type, entry.size));
+        });
+
+        let mut ctrl = protolens.new_task(TransProto::Tcp);
+        protolens.set_task_parser(&mut ctrl, L7Proto::FtpCmd);
+        let line = b"229 Entering Extended Passive Mode (|||5000|)\r\n";
+        let pkt = build_pkt_payload2(1, b"EPSV\r\n", 1234, 21, false);
+        let _ = pkt.decode();
+        protolens.run_task(&mut ctrl, pkt);
+        let pkt = build_pkt_payload2_rev(1, line, 21, 1234, false);
+        let _ = pkt.decode();
+        protolens.run_task(&mut ctrl, pkt);
+        let pkt = build_pkt_payload2(7, b"LIST -la\r\n", 1234, 21, false);
+        let _ = pkt.decode();
+        protolens.run_task(&mut ctrl, pkt);
+
+        let listing = b"total 8\r\ndrwxr-xr-x 2 0 0 4096 May 05  2010 pub\r\n-rw-r--r-- 1 0 0 68 Feb 29 12:01 read me.txt";
+        let mut data = protolens.new_task(TransProto::Tcp);
+        let pkt = build_pkt_payload2(1, b"", 1235, 5000, false);
+        let _ = pkt.decode();
+        protolens.run_task(&mut data, pkt);
+        let pkt = build_pkt_payload2_rev(1, &listing[..40], 5000, 1235, false);
+        let _ = pkt.decode();
+        protolens.run_task(&mut data, pkt);
+        let pkt = build_pkt_payload2_rev(41, &listing[40..], 5000, 1235, true);
+        let _ = pkt.decode();
+        protolens.run_task(&mut data, pkt);
+
+        assert_eq!(
+            *entries.borrow(),
+            vec![
+                ("pub".to_string(), FtpEntryType::Dir, Some(4096)),
+                ("read me.txt".to_string(), FtpEntryType::File, Some(68)),
+            ]
+        );
+    }
 }
@@ -0,0 +1,343 @@
+// LIST/MLSD/NLST的数据连接内容按行解析成目录项。LIST支持unix ls -l和dos(IIS)两种格式
+use crate::CbFtpListEntry;
+use crate::Direction;
+use std::ffi::c_void;
+
+const MAX_LINE: usize = 4096;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FtpEntryType {
+    File,
+    Dir,
+    Link,
+    Other,
+    Unknown, // NLST只有名字
+}
+
+// mtime是原始文本，unix是"May 05  2010"，dos是"03-05-20 10:30AM"，MLSD是modify的值
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FtpEntry<'a> {
+    pub session: u64,
+    pub name: &'a str,
+    pub entry_type: FtpEntryType,
+    pub size: Option<u64>,
+    pub perm: Option<&'a str>,
+    pub mtime: Option<&'a str>,
+    pub link: Option<&'a str>, // 符号链接的目标
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ListFormat {
+    List,
+    Mlsd,
+    Nlst,
+}
+
+impl ListFormat {
+    pub(crate) fn from_cmd(cmd: &str) -> Option<Self> {
+        match cmd {
+            "LIST" => Some(ListFormat::List),
+            "MLSD" => Some(ListFormat::Mlsd),
+            "NLST" => Some(ListFormat::Nlst),
+            _ => None,
+        }
+    }
+}
+
+pub(crate) struct FtpList {
+    format: ListFormat,
+    session: u64,
+    line: Vec<u8>,
+}
+
+impl FtpList {
+    pub(crate) fn new(format: ListFormat, session: u64) -> Self {
+        FtpList {
+            format,
+            session,
+            line: Vec::new(),
+        }
+    }
+
+    pub(crate) fn feed(
+        &mut self,
+        data: &[u8],
+        cb: Option<&CbFtpListEntry>,
+        cb_ctx: *mut c_void,
+        dir: Direction,
+    ) {
+        for &byte in data {
+            if byte == b'\n' {
+                self.flush(cb, cb_ctx, dir);
+            } else if self.line.len() < MAX_LINE {
+                self.line.push(byte);
+            }
+        }
+    }
+
+    // 最后一行可能没有换行
+    pub(crate) fn flush(
+        &mut self,
+        cb: Option<&CbFtpListEntry>,
+        cb_ctx: *mut c_void,
+        dir: Direction,
+    ) {
+        let line = String::from_utf8_lossy(&self.line);
+        let line = line.trim_end_matches('\r');
+        let entry = match self.format {
+            ListFormat::List => unix_entry(line).or_else(|| dos_entry(line)),
+            ListFormat::Mlsd => mlsd_entry(line),
+            ListFormat::Nlst => nlst_entry(line),
+        };
+        if let Some(mut entry) = entry
+            && let Some(cb) = cb
+        {
+            entry.session = self.session;
+            cb.borrow_mut()(&entry, cb_ctx, dir);
+        }
+        self.line.clear();
+    }
+}
+
+fn entry(name: &str, entry_type: FtpEntryType) -> FtpEntry<'_> {
+    FtpEntry {
+        session: 0,
+        name,
+        entry_type,
+        size: None,
+        perm: None,
+        mtime: None,
+        link: None,
+    }
+}
+
+// 按空白切分，返回(起始位置, token, 剩余部分)，文件名里可能有空格
+fn tokens(line: &str) -> Vec<(usize, &str, &str)> {
+    let mut out = Vec::new();
+    let mut rest = line.trim_start();
+    while !rest.is_empty() {
+        let start = line.len() - rest.len();
+        let end = rest.find([' ', '\t']).unwrap_or(rest.len());
+        let token = &rest[..end];
+        rest = rest[end..].trim_start();
+        out.push((start, token, rest));
+    }
+    out
+}
+
+static MONTHS: [&str; 12] = [
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+
+fn is_month(token: &str) -> bool {
+    token.len() == 3 && MONTHS.iter().any(|m| token.eq_ignore_ascii_case(m))
+}
+
+// drwxr-xr-x 2 user group 4096 May 05  2010 name。有的服务器没有group或者links
+fn unix_entry(line: &str) -> Option<FtpEntry<'_>> {
+    let toks = tokens(line);
+    let (_, perm, _) = *toks.first()?;
+    if perm.len() < 10 || !perm.is_char_boundary(10) {
+        return None;
+    }
+    let entry_type = match perm.as_bytes()[0] {
+        b'-' => FtpEntryType::File,
+        b'd' => FtpEntryType::Dir,
+        b'l' => FtpEntryType::Link,
+        b'b' | b'c' | b'p' | b's' => FtpEntryType::Other,
+        _ => return None,
+    };
+    if !perm.as_bytes()[1..10]
+        .iter()
+        .all(|b| b"rwxsStTl-".contains(b))
+    {
+        return None;
+    }
+
+    // 月份前面是size，后面是日和年或时间，再后面是名字
+    let month = (2..toks.len().saturating_sub(2))
+        .find(|&i| is_month(toks[i].1) && toks[i + 1].1.bytes().all(|b| b.is_ascii_digit()))?;
+    let size = toks[month - 1].1.parse().ok()?;
+    let (_, _, name) = toks[month + 2];
+    if name.is_empty() {
+        return None;
+    }
+    let mtime = line[toks[month].0..line.len() - name.len()].trim_end();
+
+    let (name, link) = match (entry_type, name.split_once(" -> ")) {
+        (FtpEntryType::Link, Some((name, link))) => (name, Some(link)),
+        _ => (name, None),
+    };
+    Some(FtpEntry {
+        size: Some(size),
+        perm: Some(perm),
+        mtime: Some(mtime),
+        link,
+        ..entry(name, entry_type)
+    })
+}
+
+// 03-05-20  10:30AM  <DIR>  name 或者 03-05-20  10:30AM  1234 name
+fn dos_entry(line: &str) -> Option<FtpEntry<'_>> {
+    let toks = tokens(line);
+    if toks.len() < 4 {
+        return None;
+    }
+    let (_, date, _) = toks[0];
+    let (_, time, _) = toks[1];
+    if !date
+        .bytes()
+        .all(|b| b.is_ascii_digit() || b == b'-' || b == b'/')
+        || !date.bytes().any(|b| b == b'-' || b == b'/')
+        || !time.contains(':')
+    {
+        return None;
+    }
+    let (kind_start, kind, name) = toks[2];
+    if name.is_empty() {
+        return None;
+    }
+    let (entry_type, size) = if kind.eq_ignore_ascii_case("<DIR>") {
+        (FtpEntryType::Dir, None)
+    } else {
+        (FtpEntryType::File, Some(kind.parse().ok()?))
+    };
+    Some(FtpEntry {
+        size,
+        mtime: Some(line[toks[0].0..kind_start].trim_end()),
+        ..entry(name, entry_type)
+    })
+}
+
+// type=file;size=123;modify=20200101120000;perm=r; name
+fn mlsd_entry(line: &str) -> Option<FtpEntry<'_>> {
+    let (facts, name) = line.split_once(' ')?;
+    if name.is_empty() {
+        return None;
+    }
+    let mut ret = entry(name, FtpEntryType::Other);
+    let mut unix_mode = None;
+    for fact in facts.split(';') {
+        let Some((key, value)) = fact.split_once('=') else {
+            continue;
+        };
+        match key.to_ascii_lowercase().as_str() {
+            "type" => {
+                ret.entry_type = match value.to_ascii_lowercase().as_str() {
+                    "file" => FtpEntryType::File,
+                    "dir" => FtpEntryType::Dir,
+                    // 当前目录和父目录不是目录项
+                    "cdir" | "pdir" => return None,
+                    v if v.starts_with("os.unix=slink") || v.starts_with("os.unix=symlink") => {
+                        FtpEntryType::Link
+                    }
+                    _ => FtpEntryType::Other,
+                }
+            }
+            "size" | "sizd" => ret.size = value.parse().ok(),
+            "modify" => ret.mtime = Some(value),
+            "perm" => ret.perm = Some(value),
+            "unix.mode" => unix_mode = Some(value),
+            _ => {}
+        }
+    }
+    if ret.perm.is_none() {
+        ret.perm = unix_mode;
+    }
+    Some(ret)
+}
+
+fn nlst_entry(line: &str) -> Option<FtpEntry<'_>> {
+    (!line.is_empty()).then(|| entry(line, FtpEntryType::Unknown))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_unix_entry() {
+        let entry =
+            unix_entry("drwxr-xr-x    2 0        0            4096 May 05  2010 音乐").unwrap();
+        assert_eq!(entry.name, "音乐");
+        assert_eq!(entry.entry_type, FtpEntryType::Dir);
+        assert_eq!(entry.size, Some(4096));
+        assert_eq!(entry.perm, Some("drwxr-xr-x"));
+        assert_eq!(entry.mtime, Some("May 05  2010"));
+
+        let entry = unix_entry("-rw-r--r-- 1 ftp ftp 68 Feb 29 12:01 my file.txt").unwrap();
+        assert_eq!(entry.name, "my file.txt");
+        assert_eq!(entry.size, Some(68));
+        assert_eq!(entry.mtime, Some("Feb 29 12:01"));
+
+        // 没有group
+        let entry = unix_entry("lrwxrwxrwx 1 owner 7 Jan  1  2020 link -> target").unwrap();
+        assert_eq!(entry.entry_type, FtpEntryType::Link);
+        assert_eq!(entry.name, "link");
+        assert_eq!(entry.link, Some("target"));
+        assert_eq!(entry.size, Some(7));
+
+        assert_eq!(unix_entry("total 24"), None);
+        assert_eq!(unix_entry("03-05-20  10:30AM  <DIR>  docs"), None);
+    }
+
+    #[test]
+    fn test_dos_entry() {
+        let entry = dos_entry("03-05-20  10:30AM       <DIR>          my docs").unwrap();
+        assert_eq!(entry.name, "my docs");
+        assert_eq!(entry.entry_type, FtpEntryType::Dir);
+        assert_eq!(entry.size, None);
+        assert_eq!(entry.mtime, Some("03-05-20  10:30AM"));
+
+        let entry = dos_entry("11/21/2019  09:15PM              1234 a.txt").unwrap();
+        assert_eq!(entry.entry_type, FtpEntryType::File);
+        assert_eq!(entry.size, Some(1234));
+        assert_eq!(entry.name, "a.txt");
+        assert_eq!(dos_entry("total 24"), None);
+    }
+
+    #[test]
+    fn test_mlsd_entry() {
+        let entry =
+            mlsd_entry("type=file;size=1024;modify=20200217180100;perm=adfrw; a b.iso").unwrap();
+        assert_eq!(entry.name, "a b.iso");
+        assert_eq!(entry.entry_type, FtpEntryType::File);
+        assert_eq!(entry.size, Some(1024));
+        assert_eq!(entry.mtime, Some("20200217180100"));
+        assert_eq!(entry.perm, Some("adfrw"));
+
+        let entry = mlsd_entry("Type=dir;Modify=20200101000000;UNIX.mode=0755; pub").unwrap();
+        assert_eq!(entry.entry_type, FtpEntryType::Dir);
+        assert_eq!(entry.perm, Some("0755"));
+        assert_eq!(mlsd_entry("type=cdir;modify=20200101000000; ."), None);
+    }
+
+    #[test]
+    fn test_ftp_list_feed() {
+        let names = Rc::new(RefCell::new(Vec::new()));
+        let n = names.clone();
+        let cb: CbFtpListEntry = Rc::new(RefCell::new(
+            move |entry: &FtpEntry, _ctx: *mut c_void, _dir: Direction| {
+                n.borrow_mut()
+                    .push((entry.session, entry.name.to_string(), entry.entry_type));
+            },
+        ));
+
+        let mut list = FtpList::new(ListFormat::Nlst, 3);
+        let ctx = std::ptr::null_mut();
+        list.feed(b"a.txt\r\nsub", Some(&cb), ctx, Direction::S2c);
+        list.feed(b"dir\r\n\r\nlast", Some(&cb), ctx, Direction::S2c);
+        list.flush(Some(&cb), ctx, Direction::S2c);
+        assert_eq!(
+            *names.borrow(),
+            vec![
+                (3, "a.txt".to_string(), FtpEntryType::Unknown),
+                (3, "subdir".to_string(), FtpEntryType::Unknown),
+                (3, "last".to_string(), FtpEntryType::Unknown),
+            ]
+        );
+    }
+}
@@ -0,0 +1,355 @@
+// ftp控制连接的会话状态。命令和应答按顺序一一对应，1xx是中间应答，不消耗命令
+use crate::CbFtpFile;
+use crate::CbFtpLogin;
+use crate::CbFtpTransfer;
+use crate::ExpectKey;
+use crate::ExpectRef;
+use crate::Expected;
+use crate::FtpXfer;
+use crate::FtpXferRef;
+use crate::TransProto;
+use phf::phf_set;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::ffi::c_void;
+use std::net::IpAddr;
+use std::rc::Rc;
+
+const MAX_PENDING_CMD: usize = 64;
+
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum FtpType {
+    #[default]
+    Ascii,
+    Ebcdic,
+    Image,
+    Local,
+}
+
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum FtpMode {
+    #[default]
+    Stream,
+    Block,
+    Compressed,
+}
+
+// USER之后直接230/530，或者PASS的应答。code是230表示登录成功
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct FtpLogin<'a> {
+    pub session: u64,
+    pub user: &'a str,
+    pub pass: Option<&'a str>,
+    pub code: u16,
+}
+
+// RETR/STOR/STOU/APPE/DELE/MKD/RMD和RNFR-RNTO，code是最终应答。
+// path是命令中的原始参数，cwd是当时跟踪到的工作目录
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct FtpFile<'a> {
+    pub session: u64,
+    pub cmd: &'a str,
+    pub path: &'a str,
+    pub rename_to: Option<&'a str>, // 只有RNTO有，这时path是RNFR的参数
+    pub cwd: Option<&'a str>,
+    pub code: u16,
+}
+
+// 数据连接传输结束，226或426
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct FtpTransfer<'a> {
+    pub session: u64,
+    pub cmd: &'a str,
+    pub path: Option<&'a str>,
+    pub cwd: Option<&'a str>,
+    pub code: u16,
+    pub data_type: FtpType,
+    pub mode: FtpMode,
+    pub rest: u64,
+}
+
+static XFER_COMMANDS: phf::Set<&'static str> = phf_set! {
+    "RETR", "STOR", "STOU", "APPE", "LIST", "NLST", "MLSD"
+};
+
+// c2s和s2c共享
+#[derive(Default)]
+pub(crate) struct FtpSession {
+    pub(crate) id: u64,
+    pub(crate) cb_login: Option<CbFtpLogin>,
+    pub(crate) cb_file: Option<CbFtpFile>,
+    pub(crate) cb_transfer: Option<CbFtpTransfer>,
+
+    xfer: Option<FtpXferRef>, // 最近一次PORT/PASV建立的传输
+    cmds: VecDeque<(String, String)>,
+    user: Option<String>,
+    pass: Option<String>,
+    cwd: Option<String>,
+    rename: Option<String>,
+    data_type: FtpType,
+    mode: FtpMode,
+    rest: u64, // 只对下一次传输有效
+}
+
+impl FtpSession {
+    // ip/port是等待数据连接的一端，peer是会连过来的另一端
+    pub(crate) fn link(
+        &mut self,
+        expect: Option<&ExpectRef>,
+        ip: IpAddr,
+        port: u16,
+        peer: Option<IpAddr>,
+    ) {
+        let xfer = Rc::new(RefCell::new(FtpXfer {
+            session: self.id,
+            ..Default::default()
+        }));
+        if let Some(expect) = expect {
+            let key = ExpectKey {
+                proto: TransProto::Tcp,
+                ip,
+                port,
+                peer,
+            };
+            expect
+                .borrow_mut()
+                .add(key, Expected::FtpData(xfer.clone()));
+        }
+        self.xfer = Some(xfer);
+    }
+
+    pub(crate) fn command(&mut self, line: &str) {
+        let line = line.trim_end_matches(['\r', '\n']);
+        let (cmd, arg) = line.split_once(' ').unwrap_or((line, ""));
+        let cmd = cmd.to_ascii_uppercase();
+        let arg = arg.trim();
+
+        if XFER_COMMANDS.contains(cmd.as_str())
+            && let Some(ref xfer) = self.xfer
+        {
+            let mut xfer = xfer.borrow_mut();
+            xfer.cmd = Some(cmd.clone());
+            xfer.path = (!arg.is_empty()).then(|| arg.to_string());
+        }
+
+        if self.cmds.len() >= MAX_PENDING_CMD {
+            self.cmds.pop_front();
+        }
+        self.cmds.push_back((cmd, arg.to_string()));
+    }
+
+    pub(crate) fn reply(&mut self, code: u16, text: &str, cb_ctx: *mut c_void) {
+        if code < 200 {
+            return;
+        }
+        let Some((cmd, arg)) = self.cmds.pop_front() else {
+            return;
+        };
+        let ok = (200..300).contains(&code);
+
+        match cmd.as_str() {
+            "USER" => {
+                self.user = Some(arg);
+                self.pass = None;
+                if code != 331 && code != 332 {
+                    self.login(code, cb_ctx);
+                }
+            }
+            "PASS" => {
+                self.pass = Some(arg);
+                if code != 332 {
+                    self.login(code, cb_ctx);
+                }
+            }
+            "CWD" | "XCWD" if ok => self.cwd = join_path(self.cwd.as_deref(), &arg),
+            "CDUP" | "XCUP" if ok => self.cwd = join_path(self.cwd.as_deref(), ".."),
+            "PWD" | "XPWD" if code == 257 => {
+                if let Some(cwd) = quoted(text) {
+                    self.cwd = Some(cwd);
+                }
+            }
+            "TYPE" if ok => {
+                self.data_type = match arg.bytes().next().map(|b| b.to_ascii_uppercase()) {
+                    Some(b'E') => FtpType::Ebcdic,
+                    Some(b'I') => FtpType::Image,
+                    Some(b'L') => FtpType::Local,
+                    _ => FtpType::Ascii,
+                }
+            }
+            "MODE" if ok => {
+                self.mode = match arg.bytes().next().map(|b| b.to_ascii_uppercase()) {
+                    Some(b'B') => FtpMode::Block,
+                    Some(b'C') => FtpMode::Compressed,
+                    _ => FtpMode::Stream,
+                }
+            }
+            "REST" if code == 350 => self.rest = arg.parse().unwrap_or(0),
+            "RNFR" => self.rename = (code == 350).then_some(arg),
+            "RNTO" => {
+                if let Some(from) = self.rename.take() {
+                    self.file(&cmd, &from, Some(&arg), code, cb_ctx);
+                }
+            }
+            "DELE" | "MKD" | "XMKD" | "RMD" | "XRMD" => self.file(&cmd, &arg, None, code, cb_ctx),
+            "RETR" | "STOR" | "STOU" | "APPE" => {
+                self.file(&cmd, &arg, None, code, cb_ctx);
+                self.transfer(&cmd, &arg, code, cb_ctx);
+            }
+            "LIST" | "NLST" | "MLSD" => self.transfer(&cmd, &arg, code, cb_ctx),
+            _ => {}
+        }
+    }
+
+    fn login(&self, code: u16, cb_ctx: *mut c_void) {
+        let (Some(cb), Some(user)) = (&self.cb_login, &self.user) else {
+            return;
+        };
+        let login = FtpLogin {
+            session: self.id,
+            user,
+            pass: self.pass.as_deref(),
+            code,
+        };
+        cb.borrow_mut()(&login, cb_ctx);
+    }
+
+    fn file(&self, cmd: &str, path: &str, to: Option<&str>, code: u16, cb_ctx: *mut c_void) {
+        let Some(ref cb) = self.cb_file else {
+            return;
+        };
+        let file = FtpFile {
+            session: self.id,
+            cmd,
+            path,
+            rename_to: to,
+            cwd: self.cwd.as_deref(),
+            code,
+        };
+        cb.borrow_mut()(&file, cb_ctx);
+    }
+
+    fn transfer(&mut self, cmd: &str, path: &str, code: u16, cb_ctx: *mut c_void) {
+        let rest = std::mem::take(&mut self.rest);
+        if code != 226 && code != 426 {
+            return;
+        }
+        let Some(ref cb) = self.cb_transfer else {
+            return;
+        };
+        let transfer = FtpTransfer {
+            session: self.id,
+            cmd,
+            path: (!path.is_empty()).then_some(path),
+            cwd: self.cwd.as_deref(),
+            code,
+            data_type: self.data_type,
+            mode: self.mode,
+            rest,
+        };
+        cb.borrow_mut()(&transfer, cb_ctx);
+    }
+}
+
+// 多行应答以"211-"开始，到相同code加空格的行结束。返回(code, 结束行的文本)
+pub(crate) fn reply_line<'a>(line: &'a str, multi: &mut Option<u16>) -> Option<(u16, &'a str)> {
+    let bytes = line.as_bytes();
+    let code = (bytes.len() >= 3 && bytes[..3].iter().all(u8::is_ascii_digit))
+        .then(|| line[..3].parse::<u16>().ok())
+        .flatten();
+    let sep = bytes.get(3).copied();
+    let text = line.get(4..).unwrap_or("").trim_end_matches(['\r', '\n']);
+
+    if let Some(start) = *multi {
+        if code == Some(start) && sep == Some(b' ') {
+            *multi = None;
+            return Some((start, text));
+        }
+        return None;
+    }
+    let code = code?;
+    match sep {
+        Some(b'-') => {
+            *multi = Some(code);
+            None
+        }
+        Some(b' ') | Some(b'\r') | Some(b'\n') | None => Some((code, text)),
+        _ => None,
+    }
+}
+
+// 257 "/a ""b""" is current directory，引号内的""表示一个引号
+fn quoted(text: &str) -> Option<String> {
+    let mut rest = text.trim_start().strip_prefix('"')?;
+    let mut out = String::new();
+    loop {
+        let end = rest.find('"')?;
+        out.push_str(&rest[..end]);
+        rest = &rest[end + 1..];
+        match rest.strip_prefix('"') {
+            Some(next) => {
+                out.push('"');
+                rest = next;
+            }
+            None => return Some(out),
+        }
+    }
+}
+
+// 相对路径需要已知的cwd
+fn join_path(cwd: Option<&str>, path: &str) -> Option<String> {
+    let mut parts: Vec<&str> = if path.starts_with('/') {
+        Vec::new()
+    } else {
+        cwd?.split('/').filter(|part| !part.is_empty()).collect()
+    };
+    for part in path.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            _ => parts.push(part),
+        }
+    }
+    Some(format!("/{}", parts.join("/")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reply_line() {
+        let mut multi = None;
+        assert_eq!(
+            reply_line("230 Login ok.\r\n", &mut multi),
+            Some((230, "Login ok."))
+        );
+        assert_eq!(reply_line("211-Features:\r\n", &mut multi), None);
+        assert_eq!(multi, Some(211));
+        assert_eq!(reply_line(" EPSV\r\n", &mut multi), None);
+        assert_eq!(reply_line("211-more\r\n", &mut multi), None);
+        assert_eq!(reply_line("211 End\r\n", &mut multi), Some((211, "End")));
+        assert_eq!(multi, None);
+        assert_eq!(reply_line("226\r\n", &mut multi), Some((226, "")));
+        assert_eq!(reply_line("+12 x\r\n", &mut multi), None);
+    }
+
+    #[test]
+    fn test_path() {
+        assert_eq!(quoted("\"/root\" is current"), Some("/root".to_string()));
+        assert_eq!(quoted("\"/a \"\"b\"\"\" ok"), Some("/a \"b\"".to_string()));
+        assert_eq!(quoted("no quote"), None);
+
+        assert_eq!(join_path(None, "pub"), None);
+        assert_eq!(join_path(None, "/pub/../etc"), Some("/etc".to_string()));
+        assert_eq!(
+            join_path(Some("/root"), "a/./b"),
+            Some("/root/a/b".to_string())
+        );
+        assert_eq!(join_path(Some("/root"), ".."), Some("/".to_string()));
+        assert_eq!(join_path(Some("/"), ".."), Some("/".to_string()));
+    }
+}
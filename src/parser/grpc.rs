@@ -0,0 +1,245 @@
+// http/2之上的gRPC。content-type是application/grpc*的stream按length-prefixed message切分
+use crate::CbGrpcMsg;
+use crate::Direction;
+use crate::HpackField;
+use std::ffi::c_void;
+
+const GRPC_HEAD_LEN: usize = 5;
+
+// :path是/package.Service/Method
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct GrpcCall<'a> {
+    pub stream: u32,
+    pub service: &'a str,
+    pub method: &'a str,
+    pub content_type: &'a str,
+    pub encoding: Option<&'a str>, // grpc-encoding，压缩标志为1时使用
+}
+
+// 大的消息分多次回调，offset是data在消息中的偏移
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct GrpcMsg<'a> {
+    pub stream: u32,
+    pub compressed: bool,
+    pub len: u32,
+    pub offset: u32,
+    pub data: &'a [u8],
+}
+
+// 来自trailer，或者只有header的应答(trailers-only)。message已经做了百分号解码
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct GrpcStatus<'a> {
+    pub stream: u32,
+    pub status: Option<u32>,
+    pub message: Option<&'a str>,
+}
+
+#[derive(Default)]
+pub(crate) struct GrpcStream {
+    head: Vec<u8>,
+    msg: Option<(bool, u32, u32)>, // compressed, len, offset
+}
+
+impl GrpcStream {
+    pub(crate) fn feed(
+        &mut self,
+        stream: u32,
+        mut data: &[u8],
+        cb_msg: Option<&CbGrpcMsg>,
+        cb_ctx: *mut c_void,
+        dir: Direction,
+    ) {
+        while !data.is_empty() {
+            let Some((compressed, len, offset)) = self.msg else {
+                let take = (GRPC_HEAD_LEN - self.head.len()).min(data.len());
+                self.head.extend_from_slice(&data[..take]);
+                data = &data[take..];
+                if self.head.len() == GRPC_HEAD_LEN {
+                    let len = u32::from_be_bytes([
+                        self.head[1],
+                        self.head[2],
+                        self.head[3],
+                        self.head[4],
+                    ]);
+                    self.msg = Some((self.head[0] & 0x1 != 0, len, 0));
+                    self.head.clear();
+                }
+                // 空消息也通知一次
+                if let Some((compressed, 0, _)) = self.msg {
+                    self.msg = None;
+                    let msg = GrpcMsg {
+                        stream,
+                        compressed,
+                        len: 0,
+                        offset: 0,
+                        data: &[],
+                    };
+                    emit(cb_msg, &msg, cb_ctx, dir);
+                }
+                continue;
+            };
+            let take = ((len - offset) as usize).min(data.len());
+            let msg = GrpcMsg {
+                stream,
+                compressed,
+                len,
+                offset,
+                data: &data[..take],
+            };
+            emit(cb_msg, &msg, cb_ctx, dir);
+            data = &data[take..];
+            let offset = offset + take as u32;
+            self.msg = (offset < len).then_some((compressed, len, offset));
+        }
+    }
+}
+
+fn emit(cb_msg: Option<&CbGrpcMsg>, msg: &GrpcMsg, cb_ctx: *mut c_void, dir: Direction) {
+    if let Some(cb) = cb_msg {
+        cb.borrow_mut()(msg, cb_ctx, dir);
+    }
+}
+
+fn field<'a>(fields: &'a [HpackField], name: &[u8]) -> Option<&'a str> {
+    fields
+        .iter()
+        .find(|(key, _)| key == name)
+        .and_then(|(_, value)| std::str::from_utf8(value).ok())
+}
+
+pub(crate) fn is_grpc(fields: &[HpackField]) -> bool {
+    field(fields, b"content-type").is_some_and(|ty| {
+        ty.get(..16)
+            .is_some_and(|prefix| prefix.eq_ignore_ascii_case("application/grpc"))
+    })
+}
+
+pub(crate) fn grpc_call(stream: u32, fields: &[HpackField]) -> Option<GrpcCall<'_>> {
+    let path = field(fields, b":path")?;
+    let (service, method) = path.strip_prefix('/')?.rsplit_once('/')?;
+    Some(GrpcCall {
+        stream,
+        service,
+        method,
+        content_type: field(fields, b"content-type")?,
+        encoding: field(fields, b"grpc-encoding"),
+    })
+}
+
+// 没有grpc-status和grpc-message时返回None。plain存放解码之后的message
+pub(crate) fn grpc_status<'a>(
+    stream: u32,
+    fields: &[HpackField],
+    plain: &'a mut String,
+) -> Option<GrpcStatus<'a>> {
+    let status = field(fields, b"grpc-status");
+    let message = field(fields, b"grpc-message");
+    if status.is_none() && message.is_none() {
+        return None;
+    }
+    if let Some(message) = message {
+        *plain = percent_decode(message);
+    }
+    Some(GrpcStatus {
+        stream,
+        status: status.and_then(|status| status.trim().parse().ok()),
+        message: message.map(|_| plain.as_str()),
+    })
+}
+
+// grpc-message只用%XX，不把+当空格
+fn percent_decode(input: &str) -> String {
+    let bytes = input.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%'
+            && let Some(hex) = input.get(i + 1..i + 3)
+            && let Ok(byte) = u8::from_str_radix(hex, 16)
+        {
+            out.push(byte);
+            i += 3;
+            continue;
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    fn fields(list: &[(&str, &str)]) -> Vec<HpackField> {
+        list.iter()
+            .map(|(name, value)| (name.as_bytes().to_vec(), value.as_bytes().to_vec()))
+            .collect()
+    }
+
+    #[test]
+    fn test_grpc_split() {
+        let msgs = Rc::new(RefCell::new(Vec::new()));
+        let m = msgs.clone();
+        let cb: CbGrpcMsg = Rc::new(RefCell::new(
+            move |msg: &GrpcMsg, _ctx: *mut c_void, _dir: Direction| {
+                m.borrow_mut()
+                    .push((msg.compressed, msg.len, msg.offset, msg.data.to_vec()));
+            },
+        ));
+
+        let mut data = vec![
+            0, 0, 0, 0, 3, b'a', b'b', b'c', 0, 0, 0, 0, 0, 1, 0, 0, 0, 2,
+        ];
+        data.extend_from_slice(b"xy");
+        let mut grpc = GrpcStream::default();
+        // 前缀和消息都被切开
+        for chunk in [
+            &data[..2],
+            &data[2..6],
+            &data[6..15],
+            &data[15..19],
+            &data[19..],
+        ] {
+            grpc.feed(1, chunk, Some(&cb), std::ptr::null_mut(), Direction::C2s);
+        }
+        assert_eq!(
+            *msgs.borrow(),
+            vec![
+                (false, 3, 0, b"a".to_vec()),
+                (false, 3, 1, b"bc".to_vec()),
+                (false, 0, 0, Vec::new()),
+                (true, 2, 0, b"x".to_vec()),
+                (true, 2, 1, b"y".to_vec()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_grpc_headers() {
+        let req = fields(&[
+            (":method", "POST"),
+            (":path", "/helloworld.Greeter/SayHello"),
+            ("content-type", "application/grpc+proto"),
+            ("grpc-encoding", "gzip"),
+        ]);
+        assert!(is_grpc(&req));
+        let call = grpc_call(1, &req).unwrap();
+        assert_eq!(call.service, "helloworld.Greeter");
+        assert_eq!(call.method, "SayHello");
+        assert_eq!(call.encoding, Some("gzip"));
+        assert!(!is_grpc(&fields(&[("content-type", "application/json")])));
+
+        let mut plain = String::new();
+        let trailer = fields(&[
+            ("grpc-status", "5"),
+            ("grpc-message", "not%20found+%E4%BD%A0"),
+        ]);
+        let status = grpc_status(1, &trailer, &mut plain).unwrap();
+        assert_eq!(status.status, Some(5));
+        assert_eq!(status.message, Some("not found+你"));
+        assert_eq!(grpc_status(1, &req, &mut plain), None);
+    }
+}
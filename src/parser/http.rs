@@ -1,21 +1,46 @@
+use crate::BodyDecoder;
 use crate::CbBodyEvt;
 use crate::CbHeader;
+use crate::CbHttpAuth;
+use crate::CbHttpAuthResult;
 use crate::CbHttpBody;
+use crate::CbHttpDecodeErr;
+use crate::CbHttpForm;
+use crate::CbHttpPart;
+use crate::CbHttpRequest;
+use crate::CbHttpResponse;
+use crate::CbHttpTrans;
+use crate::CbHttpTunnel;
+use crate::CbHttpTunnelData;
 use crate::CbStartLine;
+use crate::CbTlsHello;
+use crate::CbWsFrame;
+use crate::DecodeError;
+use crate::DecodeLimit;
 use crate::DirConfirmFn;
 use crate::Direction;
 use crate::Encoding;
+use crate::H2_PREFACE;
 use crate::HTTP_PORT;
+use crate::Http2Callbacks;
+use crate::HttpAuth;
+use crate::HttpCred;
 use crate::Parser;
 use crate::ParserFactory;
 use crate::ParserFuture;
 use crate::PktStrm;
 use crate::Prolens;
 use crate::ReadRet;
+use crate::auth_header;
 use crate::content_length;
 use crate::content_type;
 use crate::content_type_ext;
+use crate::h2_frames;
+use crate::http_cred;
+use crate::is_tls_record;
 use crate::packet::*;
+use crate::tls_records;
+use crate::ws_frames;
 use memchr::memmem::Finder;
 use nom::{
     IResult,
@@ -25,8 +50,183 @@ use nom::{
     sequence::terminated,
 };
 use phf::phf_set;
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::VecDeque;
 use std::ffi::c_void;
+use std::future::poll_fn;
 use std::marker::PhantomData;
+use std::rc::Rc;
+use std::task::Poll;
+use std::task::Waker;
+
+// c2s解析出的请求，等待s2c的应答
+#[derive(Clone)]
+struct HttpReq {
+    id: u64,
+    method: String,
+    target: String,
+    tunnel: Option<TunnelKind>, // CONNECT或者带Upgrade头
+    auth: Vec<bool>,            // 已经通知的凭证的proxy标记，等应答状态码
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TunnelKind {
+    Connect,
+    WebSocket,
+    H2c,
+    Other,
+}
+
+// c2s发出CONNECT/Upgrade请求之后等s2c的应答决定是否切换
+#[derive(Default, Clone, Copy, PartialEq, Eq)]
+enum TunnelState {
+    #[default]
+    Idle,
+    Wait,
+    Open(TunnelKind),
+    Refused,
+}
+
+// 同一个连接上两个方向共享。事务id从1开始
+#[derive(Default)]
+struct HttpTrans {
+    last_id: u64,
+    reqs: VecDeque<HttpReq>,
+    tunnel: TunnelState,
+    tunnel_waker: Option<Waker>, // c2s在Wait时挂起，应答决定之后唤醒
+    req_waker: Option<Waker>,    // s2c先到时等c2s解析出对应的请求
+    c2s_end: bool,               // c2s结束之后应答不再等请求
+    ntlm_flags: Option<u32>,     // type 1/2协商的flags，老的type 3没有flags时用
+}
+
+impl HttpTrans {
+    fn new_id(&mut self) -> u64 {
+        self.last_id += 1;
+        self.last_id
+    }
+
+    fn wake_s2c(&mut self) {
+        if let Some(waker) = self.req_waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+type HttpShared = Rc<RefCell<HttpTrans>>;
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct HttpHeader<'a> {
+    pub name: &'a str,
+    pub value: Cow<'a, str>, // obs-fold的续行用一个空格拼接
+}
+
+// 保持原始顺序，重复的头都保留
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct HttpHeaders<'a>(pub Vec<HttpHeader<'a>>);
+
+impl<'a> HttpHeaders<'a> {
+    // 名字不区分大小写，有多个时返回第一个
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|hdr| hdr.name.eq_ignore_ascii_case(name))
+            .map(|hdr| hdr.value.as_ref())
+    }
+
+    pub fn get_all<'b>(&'b self, name: &'b str) -> impl Iterator<Item = &'b str> + 'b {
+        self.0
+            .iter()
+            .filter(move |hdr| hdr.name.eq_ignore_ascii_case(name))
+            .map(|hdr| hdr.value.as_ref())
+    }
+
+    fn parse(lines: &'a [String]) -> Self {
+        let mut headers: Vec<HttpHeader> = Vec::new();
+        for line in lines {
+            let line = line.trim_end_matches(['\r', '\n']);
+            if line.starts_with([' ', '\t']) {
+                if let Some(last) = headers.last_mut() {
+                    let value = last.value.to_mut();
+                    value.push(' ');
+                    value.push_str(line.trim());
+                }
+                continue;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                headers.push(HttpHeader {
+                    name: name.trim(),
+                    value: Cow::Borrowed(value.trim()),
+                });
+            }
+        }
+        HttpHeaders(headers)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct HttpRequest<'a> {
+    pub id: u64,
+    pub method: &'a str,
+    pub target: &'a str,
+    pub path: &'a str,
+    pub query: Option<&'a str>,
+    pub version: &'a str,
+    pub headers: HttpHeaders<'a>,
+}
+
+impl HttpRequest<'_> {
+    pub fn host(&self) -> Option<&str> {
+        self.headers.get("Host")
+    }
+
+    pub fn user_agent(&self) -> Option<&str> {
+        self.headers.get("User-Agent")
+    }
+
+    pub fn content_type(&self) -> Option<&str> {
+        self.headers.get("Content-Type")
+    }
+
+    pub fn cookie(&self) -> Option<&str> {
+        self.headers.get("Cookie")
+    }
+
+    pub fn authorization(&self) -> Option<&str> {
+        self.headers.get("Authorization")
+    }
+}
+
+// multipart中的一个part，在这个part的body_start之前通知
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct HttpPart<'a> {
+    pub id: u64,
+    pub index: usize, // 在所属multipart中的序号，从0开始
+    pub depth: usize, // 嵌套深度，最外层为0
+    pub name: Option<&'a str>,
+    pub filename: Option<Cow<'a, str>>, // filename*优先，已经解码
+    pub content_type: Option<&'a str>,
+    pub headers: HttpHeaders<'a>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct HttpResponse<'a> {
+    pub id: u64,
+    pub version: &'a str,
+    pub status: u16,
+    pub reason: &'a str,
+    pub headers: HttpHeaders<'a>,
+}
+
+impl HttpResponse<'_> {
+    pub fn content_type(&self) -> Option<&str> {
+        self.headers.get("Content-Type")
+    }
+
+    pub fn set_cookie(&self) -> Vec<&str> {
+        self.headers.get_all("Set-Cookie").collect()
+    }
+}
 
 pub struct HttpParser<T>
 where
@@ -37,6 +237,21 @@ where
     cb_body_start: Option<CbBodyEvt>,
     cb_body: Option<CbHttpBody>,
     cb_body_stop: Option<CbBodyEvt>,
+    cb_trans: Option<CbHttpTrans>,
+    cb_request: Option<CbHttpRequest>,
+    cb_response: Option<CbHttpResponse>,
+    cb_part: Option<CbHttpPart>,
+    cb_form: Option<CbHttpForm>,
+    cb_tunnel: Option<CbHttpTunnel>,
+    cb_tunnel_data: Option<CbHttpTunnelData>,
+    cb_ws_frame: Option<CbWsFrame>,
+    cb_tls_hello: Option<CbTlsHello>,
+    cb_auth: Option<CbHttpAuth>,
+    cb_auth_result: Option<CbHttpAuthResult>,
+    cb_h2: Option<Http2Callbacks>,
+    cb_decode_err: Option<CbHttpDecodeErr>,
+    decode: Option<DecodeLimit>,
+    trans: HttpShared,
     _phantom_t: PhantomData<T>,
 }
 
@@ -51,6 +266,21 @@ where
             cb_body_start: None,
             cb_body: None,
             cb_body_stop: None,
+            cb_trans: None,
+            cb_request: None,
+            cb_response: None,
+            cb_part: None,
+            cb_form: None,
+            cb_tunnel: None,
+            cb_tunnel_data: None,
+            cb_ws_frame: None,
+            cb_tls_hello: None,
+            cb_auth: None,
+            cb_auth_result: None,
+            cb_h2: None,
+            cb_decode_err: None,
+            decode: None,
+            trans: Rc::new(RefCell::new(HttpTrans::default())),
             _phantom_t: PhantomData,
         }
     }
@@ -58,45 +288,167 @@ where
     async fn parser_inner(
         strm: *mut PktStrm<T>,
         cb_http: HttpCallbacks,
+        trans: HttpShared,
         cb_ctx: *mut c_void,
-        start_line_parser: fn(&[u8]) -> HttpVersion,
     ) -> Result<(), ()> {
         let stm = unsafe { &mut *strm };
 
         loop {
+            // prior knowledge的http/2，c2s已经读到了preface
+            if cb_http.dir == Direction::S2c
+                && trans.borrow().tunnel == TunnelState::Open(TunnelKind::H2c)
+            {
+                return Self::tunnel(stm, TunnelKind::H2c, &cb_http, cb_ctx).await;
+            }
+
             let (line, seq) = stm.readline_str().await?;
 
-            match start_line_parser(line.as_bytes()) {
+            let version = if cb_http.dir == Direction::C2s {
+                req_version(line.as_bytes())
+            } else {
+                rsp_version(line.as_bytes())
+            };
+            match version {
+                HttpVersion::Http20
+                    if cb_http.dir == Direction::C2s && line.as_bytes() == &H2_PREFACE[..16] =>
+                {
+                    let (rest, _seq) = stm.readn(H2_PREFACE.len() - 16).await?;
+                    if rest != &H2_PREFACE[16..] {
+                        return Ok(());
+                    }
+                    trans.borrow_mut().tunnel = TunnelState::Open(TunnelKind::H2c);
+                    return Self::h2(stm, &cb_http, cb_ctx, false).await;
+                }
                 HttpVersion::Http20 | HttpVersion::Unknown => {
                     return Ok(());
                 }
                 _ => {}
             }
+
+            let mut status = 0;
+            // s2c时是对应的请求
+            let (id, req) = if cb_http.dir == Direction::C2s {
+                let mut parts = line.split(' ');
+                let mut trans = trans.borrow_mut();
+                let id = trans.new_id();
+                trans.reqs.push_back(HttpReq {
+                    id,
+                    method: parts.next().unwrap_or_default().to_string(),
+                    target: parts.next().unwrap_or_default().to_string(),
+                    tunnel: None,
+                    auth: Vec::new(),
+                });
+                trans.wake_s2c();
+                (id, None)
+            } else {
+                status = rsp_status(line).unwrap_or(0);
+                // 1xx是中间应答，之后还有同一个请求的最终应答
+                let interim = (100..200).contains(&status) && status != 101;
+                match next_req(&trans, interim).await {
+                    Some(req) => (req.id, Some(req)),
+                    None => (trans.borrow_mut().new_id(), None),
+                }
+            };
+            let method = req.as_ref().map(|req| req.method.as_str());
+
+            if let Some(ref cb) = cb_http.trans {
+                cb.borrow_mut()(id, cb_ctx, cb_http.dir);
+            }
             if let Some(ref cb) = cb_http.start_line {
                 cb.borrow_mut()(line.as_bytes(), seq, cb_ctx, cb_http.dir);
             }
 
-            let header_ret =
-                Self::header(stm, cb_http.header.as_ref(), cb_ctx, cb_http.dir).await?;
+            // 只有注册了结构化回调才保留请求行和头
+            let typed = match cb_http.dir {
+                Direction::C2s => cb_http.request.is_some(),
+                _ => cb_http.response.is_some(),
+            };
+            let start_line = typed.then(|| line.to_string());
+            let mut lines = Vec::new();
+            let header_ret = Self::header(
+                stm,
+                cb_http.header.as_ref(),
+                cb_ctx,
+                cb_http.dir,
+                typed.then_some(&mut lines),
+            )
+            .await?;
+            if let Some(start_line) = start_line {
+                http_msg(&cb_http, id, &start_line, &lines, cb_ctx);
+            }
 
-            if header_ret.boundary.is_some() {
-                let bdry = header_ret.boundary.unwrap();
-                Self::multi_body(stm, &bdry, &bdry, &cb_http, cb_ctx).await?;
-            } else if header_ret.is_chunked() {
-                Self::chunk_body(stm, &cb_http, cb_ctx, &header_ret.ce, &header_ret.te).await?;
-            } else if header_ret.content_len.is_some() {
-                let params = HttpBodyParams {
-                    bdry: "",
-                    bdry_finder: None,
-                    cb_body_start: cb_http.body_start.as_ref(),
-                    cb_body: cb_http.body.as_ref(),
-                    cb_body_stop: cb_http.body_stop.as_ref(),
-                    cb_ctx,
-                    dir: cb_http.dir,
-                    ce: &header_ret.ce,
-                    te: &header_ret.te,
-                };
-                Self::size_body(stm, header_ret.content_len.unwrap(), params).await?;
+            let body_len = if cb_http.dir == Direction::C2s {
+                req_body_len(&header_ret)
+            } else {
+                rsp_body_len(&header_ret, method, status)
+            };
+            let wait_tunnel = match cb_http.dir {
+                Direction::C2s => {
+                    req_auth(&cb_http, &trans, id, &header_ret, cb_ctx);
+                    req_tunnel(&trans, id, &header_ret)
+                }
+                _ => {
+                    let req = req.as_ref();
+                    rsp_auth(&cb_http, &trans, id, req, status, &header_ret, cb_ctx);
+                    rsp_tunnel(&trans, req, status, &header_ret, &body_len);
+                    false
+                }
+            };
+
+            // urlencoded表单需要收齐body之后再解析
+            let form = (header_ret.form && cb_http.form.is_some())
+                .then(|| Rc::new(RefCell::new(FormBuf::default())));
+            let msg_cb = body_callbacks(&cb_http, form.clone());
+            let cb_body = msg_cb.as_ref().unwrap_or(&cb_http);
+
+            match body_len {
+                BodyLen::None => {}
+                BodyLen::Chunked => {
+                    Self::chunk_body(stm, cb_body, cb_ctx, &header_ret.ce, &header_ret.te).await?;
+                }
+                BodyLen::Multipart(bdry) => {
+                    Self::multi_body(stm, &bdry, &bdry, cb_body, cb_ctx, id, 0).await?;
+                }
+                BodyLen::Size(content_len) => {
+                    let params = HttpBodyParams {
+                        bdry: "",
+                        bdry_finder: None,
+                        cb_body_start: cb_body.body_start.as_ref(),
+                        cb_body: cb_body.body.as_ref(),
+                        cb_body_stop: cb_body.body_stop.as_ref(),
+                        cb_ctx,
+                        dir: cb_http.dir,
+                        ce: &header_ret.ce,
+                        te: &header_ret.te,
+                    };
+                    Self::size_body(stm, content_len, params).await?;
+                }
+                BodyLen::Close => {
+                    Self::close_body(stm, cb_body, cb_ctx, &header_ret.ce, &header_ret.te).await;
+                    if let Some(buf) = form {
+                        http_form(&cb_http, id, &buf.borrow(), cb_ctx);
+                    }
+                    return Ok(());
+                }
+                // 协议切换或者隧道，之后不再是http。只有应答会走到这里
+                BodyLen::Tunnel => {
+                    let kind = match trans.borrow().tunnel {
+                        TunnelState::Open(kind) => kind,
+                        _ => TunnelKind::Other,
+                    };
+                    if let Some(ref cb) = cb_http.tunnel {
+                        let target = req.as_ref().map_or("", |req| req.target.as_str());
+                        cb.borrow_mut()(id, kind, target.as_bytes(), cb_ctx);
+                    }
+                    return Self::tunnel(stm, kind, &cb_http, cb_ctx).await;
+                }
+                BodyLen::Invalid => return Err(()),
+            }
+            if let Some(buf) = form {
+                http_form(&cb_http, id, &buf.borrow(), cb_ctx);
+            }
+            if wait_tunnel && let Some(kind) = tunnel_decided(&trans).await {
+                return Self::tunnel(stm, kind, &cb_http, cb_ctx).await;
             }
         }
     }
@@ -106,12 +458,16 @@ where
         cb_header: Option<&CbHeader>,
         cb_ctx: *mut c_void,
         dir: Direction,
+        mut lines: Option<&mut Vec<String>>,
     ) -> Result<HeaderRet, ()> {
         let mut content_len = None;
         let mut boundary = None;
         let mut te = None;
         let mut ce = None;
         let mut cont_type = false;
+        let mut form = false;
+        let mut upgrade = None;
+        let mut auth = Vec::new();
 
         loop {
             let (line, seq) = stm.readline_str().await?;
@@ -126,10 +482,17 @@ where
                     boundary,
                     ce,
                     te,
+                    form,
+                    upgrade,
+                    auth,
                 };
                 return Ok(ret);
             }
 
+            if let Some(lines) = lines.as_mut() {
+                lines.push(line.to_string());
+            }
+
             if ce.is_none() {
                 ce = content_encoding(line);
             }
@@ -142,6 +505,18 @@ where
                 content_len = content_length(line);
             }
 
+            if !form {
+                form = form_urlencoded(line);
+            }
+
+            if upgrade.is_none() {
+                upgrade = upgrade_proto(line);
+            }
+
+            if let Some(value) = auth_header(line, dir) {
+                auth.push(value);
+            }
+
             // content-type ext
             // 放在content-type前面是因为。只有content-type结束之后才能作这个判断。
             // 放在前面，cont_type 肯定为false
@@ -176,16 +551,40 @@ where
         bdry: &str,
         cb_http: &HttpCallbacks,
         cb_ctx: *mut c_void,
+        id: u64,
+        depth: usize,
     ) -> Result<(), ()> {
         let bdry_finder = Finder::new(bdry);
 
         let _ = stm.readline_str().await?;
+        let mut index = 0;
         loop {
-            let header_ret =
-                Self::header(stm, cb_http.header.as_ref(), cb_ctx, cb_http.dir).await?;
-            if header_ret.boundary.is_some() {
-                let bdry = header_ret.boundary.unwrap();
-                Box::pin(Self::multi_body(stm, out_bdry, &bdry, cb_http, cb_ctx)).await?;
+            let mut lines = Vec::new();
+            let header_ret = Self::header(
+                stm,
+                cb_http.header.as_ref(),
+                cb_ctx,
+                cb_http.dir,
+                cb_http.part.is_some().then_some(&mut lines),
+            )
+            .await?;
+            if let Some(ref cb) = cb_http.part {
+                let part = http_part(id, index, depth, &lines);
+                cb.borrow_mut()(&part, cb_ctx, cb_http.dir);
+            }
+            index += 1;
+
+            if let Some(bdry) = header_ret.boundary {
+                Box::pin(Self::multi_body(
+                    stm,
+                    out_bdry,
+                    &bdry,
+                    cb_http,
+                    cb_ctx,
+                    id,
+                    depth + 1,
+                ))
+                .await?;
                 continue;
             } else {
                 let params = HttpBodyParams {
@@ -315,6 +714,82 @@ where
         Ok(())
     }
 
+    // 没有长度的应答，一直读到连接关闭
+    async fn close_body(
+        stm: &mut PktStrm<T>,
+        cb_http: &HttpCallbacks,
+        cb_ctx: *mut c_void,
+        ce: &Option<Vec<Encoding>>,
+        te: &Option<Vec<Encoding>>,
+    ) {
+        if let Some(cb) = &cb_http.body_start {
+            cb.borrow_mut()(cb_ctx, cb_http.dir);
+        }
+        while let Ok((bytes, seq)) = stm.read2eof().await {
+            if let Some(cb) = &cb_http.body {
+                cb.borrow_mut()(bytes, seq, cb_ctx, cb_http.dir, ce, te);
+            }
+        }
+        if let Some(cb) = &cb_http.body_stop {
+            cb.borrow_mut()(cb_ctx, cb_http.dir);
+        }
+    }
+
+    async fn tunnel(
+        stm: &mut PktStrm<T>,
+        kind: TunnelKind,
+        cb_http: &HttpCallbacks,
+        cb_ctx: *mut c_void,
+    ) -> Result<(), ()> {
+        if kind == TunnelKind::WebSocket {
+            return ws_frames(stm, cb_http.ws_frame.as_ref(), cb_ctx, cb_http.dir).await;
+        }
+        // 101之后客户端先发preface，服务端直接发SETTINGS
+        if kind == TunnelKind::H2c && cb_http.h2.is_some() {
+            return Self::h2(stm, cb_http, cb_ctx, cb_http.dir == Direction::C2s).await;
+        }
+        // CONNECT之后是TLS时解析握手，record仍然原样交给tunnel_data
+        if kind == TunnelKind::Connect && cb_http.tls_hello.is_some() {
+            let (head, seq) = stm.readn(5).await?;
+            let head: [u8; 5] = head.try_into().map_err(|_| ())?;
+            if is_tls_record(&head) {
+                return tls_records(
+                    stm,
+                    head,
+                    seq,
+                    cb_http.tls_hello.as_ref(),
+                    cb_http.tunnel_data.as_ref(),
+                    cb_ctx,
+                    cb_http.dir,
+                )
+                .await;
+            }
+            if let Some(ref cb) = cb_http.tunnel_data {
+                cb.borrow_mut()(&head, seq, cb_ctx, cb_http.dir);
+            }
+        }
+        // 其他的原样交给用户
+        let Some(ref cb) = cb_http.tunnel_data else {
+            return Ok(());
+        };
+        while let Ok((bytes, seq)) = stm.read2eof().await {
+            cb.borrow_mut()(bytes, seq, cb_ctx, cb_http.dir);
+        }
+        Ok(())
+    }
+
+    async fn h2(
+        stm: &mut PktStrm<T>,
+        cb_http: &HttpCallbacks,
+        cb_ctx: *mut c_void,
+        preface: bool,
+    ) -> Result<(), ()> {
+        match cb_http.h2 {
+            Some(ref cb) => h2_frames(stm, cb, cb_ctx, preface).await,
+            None => Ok(()),
+        }
+    }
+
     async fn tailer(stm: &mut PktStrm<T>) -> Result<(), ()> {
         loop {
             let (line, _seq) = stm.readline_str().await?;
@@ -359,10 +834,10 @@ where
                 }
             }
 
-            if let Ok(payload) = payload_s2c {
-                if req(unsafe { std::str::from_utf8_unchecked(payload) }) {
-                    return Some(false);
-                }
+            if let Ok(payload) = payload_s2c
+                && req(unsafe { std::str::from_utf8_unchecked(payload) })
+            {
+                return Some(false);
             }
 
             Some(true)
@@ -376,14 +851,30 @@ where
             body_start: self.cb_body_start.clone(),
             body: self.cb_body.clone(),
             body_stop: self.cb_body_stop.clone(),
+            trans: self.cb_trans.clone(),
+            request: self.cb_request.clone(),
+            response: self.cb_response.clone(),
+            part: self.cb_part.clone(),
+            form: self.cb_form.clone(),
+            tunnel: self.cb_tunnel.clone(),
+            tunnel_data: self.cb_tunnel_data.clone(),
+            ws_frame: self.cb_ws_frame.clone(),
+            tls_hello: self.cb_tls_hello.clone(),
+            auth: self.cb_auth.clone(),
+            auth_result: self.cb_auth_result.clone(),
+            h2: self.cb_h2.as_ref().map(|cb| cb.with_dir(Direction::C2s)),
+            decode: self.decode,
+            decode_err: self.cb_decode_err.clone(),
             dir: Direction::C2s,
         };
-        Some(Box::pin(Self::parser_inner(
-            strm,
-            cb_http,
-            cb_ctx,
-            req_version,
-        )))
+        let trans = self.trans.clone();
+        Some(Box::pin(async move {
+            let ret = Self::parser_inner(strm, cb_http, trans.clone(), cb_ctx).await;
+            let mut trans = trans.borrow_mut();
+            trans.c2s_end = true;
+            trans.wake_s2c();
+            ret
+        }))
     }
 
     fn s2c_parser(&self, strm: *mut PktStrm<T>, cb_ctx: *mut c_void) -> Option<ParserFuture> {
@@ -393,13 +884,27 @@ where
             body_start: self.cb_body_start.clone(),
             body: self.cb_body.clone(),
             body_stop: self.cb_body_stop.clone(),
+            trans: self.cb_trans.clone(),
+            request: self.cb_request.clone(),
+            response: self.cb_response.clone(),
+            part: self.cb_part.clone(),
+            form: self.cb_form.clone(),
+            tunnel: self.cb_tunnel.clone(),
+            tunnel_data: self.cb_tunnel_data.clone(),
+            ws_frame: self.cb_ws_frame.clone(),
+            tls_hello: self.cb_tls_hello.clone(),
+            auth: self.cb_auth.clone(),
+            auth_result: self.cb_auth_result.clone(),
+            h2: self.cb_h2.as_ref().map(|cb| cb.with_dir(Direction::S2c)),
+            decode: self.decode,
+            decode_err: self.cb_decode_err.clone(),
             dir: Direction::S2c,
         };
         Some(Box::pin(Self::parser_inner(
             strm,
             cb_http,
+            self.trans.clone(),
             cb_ctx,
-            rsp_version,
         )))
     }
 }
@@ -425,48 +930,695 @@ where
         parser.cb_body_start = prolens.cb_http_body_start.clone();
         parser.cb_body = prolens.cb_http_body.clone();
         parser.cb_body_stop = prolens.cb_http_body_stop.clone();
+        parser.cb_trans = prolens.cb_http_trans.clone();
+        parser.cb_request = prolens.cb_http_request.clone();
+        parser.cb_response = prolens.cb_http_response.clone();
+        parser.cb_part = prolens.cb_http_part.clone();
+        parser.cb_form = prolens.cb_http_form.clone();
+        parser.cb_tunnel = prolens.cb_http_tunnel.clone();
+        parser.cb_tunnel_data = prolens.cb_http_tunnel_data.clone();
+        parser.cb_ws_frame = prolens.cb_ws_frame.clone();
+        parser.cb_tls_hello = prolens.cb_tls_hello.clone();
+        parser.cb_auth = prolens.cb_http_auth.clone();
+        parser.cb_auth_result = prolens.cb_http_auth_result.clone();
+        parser.cb_h2 = Some(Http2Callbacks::new(prolens));
+        parser.cb_decode_err = prolens.cb_http_decode_err.clone();
+        let conf = prolens.config();
+        if conf.http_decode {
+            parser.decode = Some(DecodeLimit {
+                max_size: conf.http_decode_max,
+                max_ratio: conf.http_decode_ratio,
+            });
+        }
         parser
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone)]
-struct HeaderRet {
-    content_len: Option<usize>,
-    boundary: Option<String>,
-    ce: Option<Vec<Encoding>>,
-    te: Option<Vec<Encoding>>,
+#[derive(Debug, PartialEq, Eq, Clone)]
+struct HeaderRet {
+    content_len: Option<usize>,
+    boundary: Option<String>,
+    ce: Option<Vec<Encoding>>,
+    te: Option<Vec<Encoding>>,
+    form: bool, // application/x-www-form-urlencoded
+    upgrade: Option<TunnelKind>,
+    auth: Vec<(bool, String)>,
+}
+
+impl HeaderRet {
+    fn is_chunked(&self) -> bool {
+        if let Some(encodings) = &self.te {
+            encodings.iter().rev().any(|enc| *enc == Encoding::Chunked)
+        } else {
+            false
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+enum BodyLen {
+    None,
+    Chunked,
+    Multipart(String),
+    Size(usize),
+    Close,
+    Tunnel,
+    Invalid,
+}
+
+// RFC 9112 6.3
+fn req_body_len(header: &HeaderRet) -> BodyLen {
+    if header.te.is_some() {
+        // 请求中chunked不是最后一个编码时长度无法确定
+        return if header.is_chunked() {
+            BodyLen::Chunked
+        } else {
+            BodyLen::Invalid
+        };
+    }
+    if let Some(bdry) = &header.boundary {
+        return BodyLen::Multipart(bdry.clone());
+    }
+    match header.content_len {
+        Some(len) => BodyLen::Size(len),
+        None => BodyLen::None,
+    }
+}
+
+fn rsp_body_len(header: &HeaderRet, method: Option<&str>, status: u16) -> BodyLen {
+    if (method == Some("CONNECT") && (200..300).contains(&status)) || status == 101 {
+        return BodyLen::Tunnel;
+    }
+    if method == Some("HEAD") || (100..200).contains(&status) || status == 204 || status == 304 {
+        return BodyLen::None;
+    }
+    if header.te.is_some() {
+        return if header.is_chunked() {
+            BodyLen::Chunked
+        } else {
+            BodyLen::Close
+        };
+    }
+    if let Some(bdry) = &header.boundary {
+        return BodyLen::Multipart(bdry.clone());
+    }
+    match header.content_len {
+        Some(len) => BodyLen::Size(len),
+        None => BodyLen::Close,
+    }
+}
+
+struct HttpBodyParams<'a> {
+    bdry: &'a str,
+    bdry_finder: Option<&'a Finder<'a>>,
+    cb_body_start: Option<&'a CbBodyEvt>,
+    cb_body: Option<&'a CbHttpBody>,
+    cb_body_stop: Option<&'a CbBodyEvt>,
+    cb_ctx: *mut c_void,
+    dir: Direction,
+    ce: &'a Option<Vec<Encoding>>,
+    te: &'a Option<Vec<Encoding>>,
+}
+
+#[derive(Clone)]
+struct HttpCallbacks {
+    pub(crate) start_line: Option<CbStartLine>,
+    pub(crate) header: Option<CbHeader>,
+    pub(crate) body_start: Option<CbBodyEvt>,
+    pub(crate) body: Option<CbHttpBody>,
+    pub(crate) body_stop: Option<CbBodyEvt>,
+    pub(crate) trans: Option<CbHttpTrans>,
+    pub(crate) request: Option<CbHttpRequest>,
+    pub(crate) response: Option<CbHttpResponse>,
+    pub(crate) part: Option<CbHttpPart>,
+    pub(crate) form: Option<CbHttpForm>,
+    pub(crate) tunnel: Option<CbHttpTunnel>,
+    pub(crate) tunnel_data: Option<CbHttpTunnelData>,
+    pub(crate) ws_frame: Option<CbWsFrame>,
+    pub(crate) tls_hello: Option<CbTlsHello>,
+    pub(crate) auth: Option<CbHttpAuth>,
+    pub(crate) auth_result: Option<CbHttpAuthResult>,
+    pub(crate) h2: Option<Http2Callbacks>,
+    pub(crate) decode: Option<DecodeLimit>, // 每个消息的body回调外面包一层解码
+    pub(crate) decode_err: Option<CbHttpDecodeErr>,
+    pub(crate) dir: Direction,
+}
+
+// 凭证在请求时就通知，status为0。proxy标记记在请求上，应答到达之后通知结果
+fn req_auth(
+    cb_http: &HttpCallbacks,
+    trans: &HttpShared,
+    id: u64,
+    header: &HeaderRet,
+    cb_ctx: *mut c_void,
+) {
+    if header.auth.is_empty() || (cb_http.auth.is_none() && cb_http.auth_result.is_none()) {
+        return;
+    }
+    let mut negotiated = trans.borrow().ntlm_flags;
+    let mut proxies = Vec::new();
+    let mut plain = Vec::new();
+    for (proxy, value) in &header.auth {
+        let Some(cred) = http_cred(value, &mut plain, negotiated) else {
+            continue;
+        };
+        if let HttpCred::Ntlm(ref msg) = cred
+            && msg.msg_type == 1
+            && negotiated.is_none()
+        {
+            negotiated = Some(msg.flags);
+        }
+        proxies.push(*proxy);
+        if let Some(ref cb) = cb_http.auth {
+            let auth = HttpAuth {
+                id,
+                proxy: *proxy,
+                status: 0,
+                cred,
+            };
+            cb.borrow_mut()(&auth, cb_ctx, Direction::C2s);
+        }
+    }
+    let mut trans = trans.borrow_mut();
+    trans.ntlm_flags = negotiated;
+    if let Some(req) = trans.reqs.back_mut().filter(|req| req.id == id) {
+        req.auth = proxies;
+    }
+}
+
+// 1xx之后还有最终应答，不通知。应答里只有NTLM challenge有意义
+fn rsp_auth(
+    cb_http: &HttpCallbacks,
+    trans: &HttpShared,
+    id: u64,
+    req: Option<&HttpReq>,
+    status: u16,
+    header: &HeaderRet,
+    cb_ctx: *mut c_void,
+) {
+    if (100..200).contains(&status) && status != 101 {
+        return;
+    }
+    if let Some(ref cb) = cb_http.auth_result {
+        for proxy in req.iter().flat_map(|req| req.auth.iter()) {
+            cb.borrow_mut()(id, *proxy, status, cb_ctx, Direction::S2c);
+        }
+    }
+    let Some(ref cb) = cb_http.auth else {
+        return;
+    };
+    let mut plain = Vec::new();
+    for (proxy, value) in &header.auth {
+        if let Some(HttpCred::Ntlm(msg)) = http_cred(value, &mut plain, None) {
+            // 之后的type 3按challenge的flags解码
+            if msg.msg_type == 2 {
+                trans.borrow_mut().ntlm_flags = Some(msg.flags);
+            }
+            let auth = HttpAuth {
+                id,
+                proxy: *proxy,
+                status,
+                cred: HttpCred::Ntlm(msg),
+            };
+            cb.borrow_mut()(&auth, cb_ctx, Direction::S2c);
+        }
+    }
+}
+
+// 请求是CONNECT或者带Upgrade时，c2s需要等应答
+fn req_tunnel(trans: &HttpShared, id: u64, header: &HeaderRet) -> bool {
+    let mut trans = trans.borrow_mut();
+    let trans = &mut *trans;
+    let Some(req) = trans.reqs.back_mut().filter(|req| req.id == id) else {
+        return false;
+    };
+    req.tunnel = if req.method == "CONNECT" {
+        Some(TunnelKind::Connect)
+    } else {
+        header.upgrade
+    };
+    if req.tunnel.is_some() {
+        trans.tunnel = TunnelState::Wait;
+    }
+    req.tunnel.is_some()
+}
+
+fn rsp_tunnel(
+    trans: &HttpShared,
+    req: Option<&HttpReq>,
+    status: u16,
+    header: &HeaderRet,
+    body_len: &BodyLen,
+) {
+    let req_kind = req.and_then(|req| req.tunnel);
+    let state = if *body_len == BodyLen::Tunnel {
+        let kind = if req.is_some_and(|req| req.method == "CONNECT") {
+            TunnelKind::Connect
+        } else {
+            header.upgrade.or(req_kind).unwrap_or(TunnelKind::Other)
+        };
+        TunnelState::Open(kind)
+    } else if req_kind.is_some() && !(100..200).contains(&status) {
+        TunnelState::Refused
+    } else {
+        return;
+    };
+    let mut trans = trans.borrow_mut();
+    trans.tunnel = state;
+    if let Some(waker) = trans.tunnel_waker.take() {
+        waker.wake();
+    }
+}
+
+// 请求还没有解析出来时挂起，c2s结束之后返回None
+async fn next_req(trans: &HttpShared, interim: bool) -> Option<HttpReq> {
+    poll_fn(|ctx| {
+        let mut trans = trans.borrow_mut();
+        let req = if interim {
+            trans.reqs.front().cloned()
+        } else {
+            trans.reqs.pop_front()
+        };
+        if req.is_some() || trans.c2s_end {
+            Poll::Ready(req)
+        } else {
+            trans.req_waker = Some(ctx.waker().clone());
+            Poll::Pending
+        }
+    })
+    .await
+}
+
+async fn tunnel_decided(trans: &HttpShared) -> Option<TunnelKind> {
+    poll_fn(|ctx| {
+        let mut trans = trans.borrow_mut();
+        match trans.tunnel {
+            TunnelState::Wait => {
+                trans.tunnel_waker = Some(ctx.waker().clone());
+                Poll::Pending
+            }
+            TunnelState::Open(kind) => Poll::Ready(Some(kind)),
+            TunnelState::Refused | TunnelState::Idle => {
+                trans.tunnel = TunnelState::Idle;
+                Poll::Ready(None)
+            }
+        }
+    })
+    .await
+}
+
+fn http_request<'a>(id: u64, line: &'a str, lines: &'a [String]) -> Option<HttpRequest<'a>> {
+    let mut parts = line.trim_end_matches(['\r', '\n']).splitn(3, ' ');
+    let method = parts.next()?;
+    let target = parts.next()?;
+    let version = parts.next()?;
+    let (path, query) = match target.split_once('?') {
+        Some((path, query)) => (path, Some(query)),
+        None => (target, None),
+    };
+    Some(HttpRequest {
+        id,
+        method,
+        target,
+        path,
+        query,
+        version,
+        headers: HttpHeaders::parse(lines),
+    })
+}
+
+fn http_response<'a>(id: u64, line: &'a str, lines: &'a [String]) -> Option<HttpResponse<'a>> {
+    let mut parts = line.trim_end_matches(['\r', '\n']).splitn(3, ' ');
+    let version = parts.next()?;
+    let status = parts.next()?.parse().ok()?;
+    let reason = parts.next().unwrap_or_default();
+    Some(HttpResponse {
+        id,
+        version,
+        status,
+        reason,
+        headers: HttpHeaders::parse(lines),
+    })
+}
+
+fn http_part<'a>(id: u64, index: usize, depth: usize, lines: &'a [String]) -> HttpPart<'a> {
+    let headers = HttpHeaders::parse(lines);
+    let mut name = None;
+    let mut filename = None;
+    let mut content_type = None;
+    for hdr in &headers.0 {
+        // 值都是借用原始行，不会有obs-fold
+        let Cow::Borrowed(value) = hdr.value else {
+            continue;
+        };
+        if hdr.name.eq_ignore_ascii_case("Content-Disposition") {
+            (name, filename) = disposition(value);
+        } else if hdr.name.eq_ignore_ascii_case("Content-Type") && content_type.is_none() {
+            content_type = Some(value);
+        }
+    }
+    HttpPart {
+        id,
+        index,
+        depth,
+        name,
+        filename,
+        content_type,
+        headers,
+    }
+}
+
+// form-data; name="file"; filename="a;b.txt"; filename*=UTF-8''%E4%B8%AD.txt
+// 返回name和filename。filename*(RFC 6266/5987)能解码时优先
+fn disposition(value: &str) -> (Option<&str>, Option<Cow<'_, str>>) {
+    let mut name = None;
+    let mut filename = None;
+    let mut filename_ext = None;
+    for (key, val) in disposition_params(value) {
+        if key.eq_ignore_ascii_case("name") {
+            name = Some(val);
+        } else if key.eq_ignore_ascii_case("filename") {
+            filename = Some(unescape_quoted(val));
+        } else if key.eq_ignore_ascii_case("filename*") {
+            filename_ext = ext_value(val);
+        }
+    }
+    (name, filename_ext.map(Cow::Owned).or(filename))
+}
+
+// 跳过第一个disposition type，按;切分参数。引号内的;和\"不切分，返回的值去掉了外层引号
+fn disposition_params(value: &str) -> Vec<(&str, &str)> {
+    let mut params = Vec::new();
+    let Some(pos) = value.find(';') else {
+        return params;
+    };
+    let mut rest = &value[pos..];
+    loop {
+        rest = rest.trim_start_matches([' ', '\t', ';']);
+        let Some(pos) = rest.find(['=', ';']) else {
+            break;
+        };
+        if rest.as_bytes()[pos] == b';' {
+            rest = &rest[pos..];
+            continue;
+        }
+        let key = rest[..pos].trim();
+        let after = rest[pos + 1..].trim_start();
+        let (val, next) = if let Some(quoted) = after.strip_prefix('"') {
+            let end = quoted_end(quoted);
+            (&quoted[..end], quoted.get(end + 1..).unwrap_or_default())
+        } else {
+            match after.find(';') {
+                Some(end) => (after[..end].trim_end(), &after[end..]),
+                None => (after.trim_end(), ""),
+            }
+        };
+        params.push((key, val));
+        rest = next;
+    }
+    params
+}
+
+// 引号字符串结束的位置，没有结束引号时返回整个长度
+fn quoted_end(quoted: &str) -> usize {
+    let bytes = quoted.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 2,
+            b'"' => return i,
+            _ => i += 1,
+        }
+    }
+    quoted.len()
+}
+
+// 只处理\"和\\，windows路径中的其他\保留
+fn unescape_quoted(val: &str) -> Cow<'_, str> {
+    if !val.contains("\\\"") && !val.contains("\\\\") {
+        return Cow::Borrowed(val);
+    }
+    let mut out = String::with_capacity(val.len());
+    let mut chars = val.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\\'
+            && let Some(&next) = chars.peek()
+            && (next == '"' || next == '\\')
+        {
+            out.push(next);
+            chars.next();
+            continue;
+        }
+        out.push(c);
+    }
+    Cow::Owned(out)
+}
+
+// RFC 5987 ext-value: charset'language'pct-encoded
+fn ext_value(val: &str) -> Option<String> {
+    fn hex(b: u8) -> Option<u8> {
+        (b as char).to_digit(16).map(|d| d as u8)
+    }
+
+    let (charset, rest) = val.split_once('\'')?;
+    let (_language, encoded) = rest.split_once('\'')?;
+    let input = encoded.as_bytes();
+    let mut bytes = Vec::with_capacity(input.len());
+    let mut i = 0;
+    while i < input.len() {
+        if input[i] == b'%' {
+            let hi = hex(*input.get(i + 1)?)?;
+            let lo = hex(*input.get(i + 2)?)?;
+            bytes.push(hi << 4 | lo);
+            i += 3;
+        } else {
+            bytes.push(input[i]);
+            i += 1;
+        }
+    }
+    if charset.eq_ignore_ascii_case("utf-8") {
+        String::from_utf8(bytes).ok()
+    } else if charset.eq_ignore_ascii_case("iso-8859-1") {
+        Some(bytes.iter().map(|&b| b as char).collect())
+    } else {
+        None
+    }
+}
+
+// 表单体最多缓存这么多
+const MAX_FORM_BUF: usize = 64 * 1024;
+
+// truncated表示body超过了MAX_FORM_BUF，最后一个字段不完整
+#[derive(Default)]
+struct FormBuf {
+    data: Vec<u8>,
+    truncated: bool,
+}
+
+// 当前消息body的回调。表单在内层，看到的是解码之后的数据。都不需要时返回None
+fn body_callbacks(
+    cb_http: &HttpCallbacks,
+    form: Option<Rc<RefCell<FormBuf>>>,
+) -> Option<HttpCallbacks> {
+    if form.is_none() && cb_http.decode.is_none() {
+        return None;
+    }
+    let mut cb_body = match form {
+        Some(buf) => form_callbacks(cb_http, buf),
+        None => cb_http.clone(),
+    };
+    if let Some(limit) = cb_body.decode {
+        let cb_err = cb_body.decode_err.clone();
+        decode_callbacks(&mut cb_body, limit, cb_err);
+    }
+    Some(cb_body)
+}
+
+// 在body回调外面包一层，把数据另存一份
+fn form_callbacks(cb_http: &HttpCallbacks, buf: Rc<RefCell<FormBuf>>) -> HttpCallbacks {
+    let mut cb_form = cb_http.clone();
+    let cb_body = cb_http.body.clone();
+    let body = move |data: &[u8],
+                     seq: u32,
+                     cb_ctx: *mut c_void,
+                     dir: Direction,
+                     ce: &Option<Vec<Encoding>>,
+                     te: &Option<Vec<Encoding>>| {
+        let mut buf = buf.borrow_mut();
+        let len = data.len().min(MAX_FORM_BUF.saturating_sub(buf.data.len()));
+        buf.data.extend_from_slice(&data[..len]);
+        buf.truncated |= len < data.len();
+        if let Some(ref cb) = cb_body {
+            cb.borrow_mut()(data, seq, cb_ctx, dir, ce, te);
+        }
+    };
+    cb_form.body = Some(Rc::new(RefCell::new(body)) as CbHttpBody);
+    cb_form
+}
+
+// 截断的表单不通知，避免把不完整的字段当成完整的
+fn http_form(cb_http: &HttpCallbacks, id: u64, body: &FormBuf, cb_ctx: *mut c_void) {
+    let Some(ref cb) = cb_http.form else {
+        return;
+    };
+    if body.truncated {
+        return;
+    }
+    for pair in body.data.split(|&b| b == b'&') {
+        if pair.is_empty() {
+            continue;
+        }
+        let (key, value) = match pair.iter().position(|&b| b == b'=') {
+            Some(pos) => (&pair[..pos], &pair[pos + 1..]),
+            None => (pair, &b""[..]),
+        };
+        cb.borrow_mut()(
+            id,
+            &url_decode(key),
+            &url_decode(value),
+            cb_ctx,
+            cb_http.dir,
+        );
+    }
+}
+
+// application/x-www-form-urlencoded的解码，+为空格
+fn url_decode(input: &[u8]) -> Vec<u8> {
+    fn hex(b: u8) -> Option<u8> {
+        (b as char).to_digit(16).map(|d| d as u8)
+    }
+
+    let mut out = Vec::with_capacity(input.len());
+    let mut i = 0;
+    while i < input.len() {
+        match input[i] {
+            b'+' => out.push(b' '),
+            b'%' if i + 2 < input.len() => match (hex(input[i + 1]), hex(input[i + 2])) {
+                (Some(hi), Some(lo)) => {
+                    out.push(hi << 4 | lo);
+                    i += 2;
+                }
+                _ => out.push(b'%'),
+            },
+            b => out.push(b),
+        }
+        i += 1;
+    }
+    out
+}
+
+// Upgrade: websocket。有多个时只看第一个
+fn upgrade_proto(line: &str) -> Option<TunnelKind> {
+    let (name, value) = line.split_once(':')?;
+    if !name.eq_ignore_ascii_case("upgrade") {
+        return None;
+    }
+    let proto = value.split(',').next()?.trim();
+    Some(if proto.eq_ignore_ascii_case("websocket") {
+        TunnelKind::WebSocket
+    } else if proto.eq_ignore_ascii_case("h2c") {
+        TunnelKind::H2c
+    } else {
+        TunnelKind::Other
+    })
 }
 
-impl HeaderRet {
-    fn is_chunked(&self) -> bool {
-        if let Some(encodings) = &self.te {
-            encodings.iter().rev().any(|enc| *enc == Encoding::Chunked)
-        } else {
-            false
+fn form_urlencoded(line: &str) -> bool {
+    line.get(..13)
+        .is_some_and(|name| name.eq_ignore_ascii_case("content-type:"))
+        && line[13..]
+            .trim_start()
+            .get(..33)
+            .is_some_and(|ty| ty.eq_ignore_ascii_case("application/x-www-form-urlencoded"))
+}
+
+fn http_msg(
+    cb_http: &HttpCallbacks,
+    id: u64,
+    start_line: &str,
+    lines: &[String],
+    cb_ctx: *mut c_void,
+) {
+    if cb_http.dir == Direction::C2s {
+        if let Some(ref cb) = cb_http.request
+            && let Some(req) = http_request(id, start_line, lines)
+        {
+            cb.borrow_mut()(&req, cb_ctx);
         }
+    } else if let Some(ref cb) = cb_http.response
+        && let Some(rsp) = http_response(id, start_line, lines)
+    {
+        cb.borrow_mut()(&rsp, cb_ctx);
     }
 }
 
-struct HttpBodyParams<'a> {
-    bdry: &'a str,
-    bdry_finder: Option<&'a Finder<'a>>,
-    cb_body_start: Option<&'a CbBodyEvt>,
-    cb_body: Option<&'a CbHttpBody>,
-    cb_body_stop: Option<&'a CbBodyEvt>,
-    cb_ctx: *mut c_void,
-    dir: Direction,
-    ce: &'a Option<Vec<Encoding>>,
-    te: &'a Option<Vec<Encoding>>,
+// 每个body开始时重置
+enum DecodeState {
+    Init,
+    Decode(BodyDecoder),
+    Raw,    // 不支持的编码，原样交给用户
+    Failed, // 解码出错或者超过限制，丢弃剩下的数据
 }
 
-#[derive(Clone)]
-struct HttpCallbacks {
-    pub(crate) start_line: Option<CbStartLine>,
-    pub(crate) header: Option<CbHeader>,
-    pub(crate) body_start: Option<CbBodyEvt>,
-    pub(crate) body: Option<CbHttpBody>,
-    pub(crate) body_stop: Option<CbBodyEvt>,
-    pub(crate) dir: Direction,
+// 把body回调包一层，用户收到的是解码之后的数据，ce和te为None
+fn decode_callbacks(
+    cb_http: &mut HttpCallbacks,
+    limit: DecodeLimit,
+    cb_err: Option<CbHttpDecodeErr>,
+) {
+    let Some(cb_body) = cb_http.body.take() else {
+        return;
+    };
+    let state = Rc::new(RefCell::new(DecodeState::Init));
+
+    let start_state = state.clone();
+    let cb_start = cb_http.body_start.take();
+    let body_start = move |cb_ctx: *mut c_void, dir: Direction| {
+        *start_state.borrow_mut() = DecodeState::Init;
+        if let Some(ref cb) = cb_start {
+            cb.borrow_mut()(cb_ctx, dir);
+        }
+    };
+    cb_http.body_start = Some(Rc::new(RefCell::new(body_start)) as CbBodyEvt);
+
+    let report = move |err: DecodeError, cb_ctx: *mut c_void, dir: Direction| {
+        if let Some(ref cb) = cb_err {
+            cb.borrow_mut()(err, cb_ctx, dir);
+        }
+    };
+    let body = move |data: &[u8],
+                     seq: u32,
+                     cb_ctx: *mut c_void,
+                     dir: Direction,
+                     ce: &Option<Vec<Encoding>>,
+                     te: &Option<Vec<Encoding>>| {
+        let mut state = state.borrow_mut();
+        if matches!(*state, DecodeState::Init) {
+            *state = match BodyDecoder::new(ce, te, limit) {
+                Ok(dec) => DecodeState::Decode(dec),
+                Err(err) => {
+                    report(err, cb_ctx, dir);
+                    DecodeState::Raw
+                }
+            };
+        }
+        match &mut *state {
+            DecodeState::Decode(dec) => {
+                let mut out = Vec::new();
+                match dec.decode(data, &mut out) {
+                    Ok(()) if out.is_empty() => {}
+                    Ok(()) => cb_body.borrow_mut()(&out, seq, cb_ctx, dir, &None, &None),
+                    Err(err) => {
+                        report(err, cb_ctx, dir);
+                        *state = DecodeState::Failed;
+                    }
+                }
+            }
+            DecodeState::Raw => cb_body.borrow_mut()(data, seq, cb_ctx, dir, ce, te),
+            DecodeState::Init | DecodeState::Failed => {}
+        }
+    };
+    cb_http.body = Some(Rc::new(RefCell::new(body)) as CbHttpBody);
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
@@ -527,6 +1679,15 @@ fn rsp_version(line: &[u8]) -> HttpVersion {
     }
 }
 
+// HTTP/1.1 200 OK
+fn rsp_status(line: &str) -> Option<u16> {
+    let code = line.split(' ').nth(1)?;
+    if code.len() != 3 {
+        return None;
+    }
+    code.parse().ok()
+}
+
 fn transfer_encoding(line: &str) -> Option<Vec<Encoding>> {
     fn parse_transfer_encoding(input: &str) -> IResult<&str, Vec<Encoding>> {
         let (input, _) = tag_no_case("Transfer-Encoding:")(input)?;
@@ -564,7 +1725,7 @@ fn transfer_encoding(line: &str) -> Option<Vec<Encoding>> {
     }
 }
 
-fn content_encoding(line: &str) -> Option<Vec<Encoding>> {
+pub(crate) fn content_encoding(line: &str) -> Option<Vec<Encoding>> {
     fn parse_content_encoding(input: &str) -> IResult<&str, Vec<Encoding>> {
         let (input, _) = tag_no_case("Content-Encoding:")(input)?;
         let (input, _) = take_while(|c| c == ' ')(input)?;
@@ -651,6 +1812,11 @@ fn rsp(input: &str) -> bool {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::Config;
+    use crate::HttpAuth;
+    use crate::HttpCred;
+    use crate::TlsHello;
+    use crate::WsFrame;
     use crate::test_utils::*;
     use std::cell::RefCell;
     use std::env;
@@ -1275,6 +2441,19 @@ mod tests {
         let mut task = protolens.new_task(TransProto::Tcp);
         protolens.set_task_parser(&mut task, L7Proto::Http);
 
+        // 应答要等对应的请求，先发请求
+        let pkt = build_pkt_payload_inner(
+            1,
+            b"GET / HTTP/1.1\r\n\r\n",
+            [192, 168, 1, 2],
+            [192, 168, 1, 1],
+            TEST_UTILS_DPORT,
+            TEST_UTILS_SPORT,
+            false,
+        );
+        let _ = pkt.decode();
+        protolens.run_task(&mut task, pkt);
+
         let mut seq = 1000;
         for line in lines.iter() {
             let line_bytes = line.as_bytes();
@@ -1309,4 +2488,1019 @@ mod tests {
         let body0 = &bodies_guard[0];
         assert_eq!(*body0, b"12345678123456789a123456789ab");
     }
+
+    #[derive(Default)]
+    struct Record {
+        trans: Vec<(u64, Direction)>,
+        start_lines: Vec<(String, Direction)>,
+        bodies: Vec<(Vec<u8>, Direction)>,
+        body_stops: Vec<Direction>,
+        decode_errs: Vec<DecodeError>,
+    }
+
+    fn new_prolens(rec: &Rc<RefCell<Record>>) -> Prolens<CapPacket> {
+        new_prolens_conf(rec, Config::default())
+    }
+
+    fn new_prolens_conf(rec: &Rc<RefCell<Record>>, conf: Config) -> Prolens<CapPacket> {
+        let mut protolens = Prolens::<CapPacket>::new(conf);
+
+        let r = rec.clone();
+        protolens.set_cb_http_trans(move |id, _ctx: *mut c_void, dir| {
+            r.borrow_mut().trans.push((id, dir));
+        });
+        let r = rec.clone();
+        protolens.set_cb_http_start_line(move |line: &[u8], _seq, _ctx: *mut c_void, dir| {
+            let line = std::str::from_utf8(line).unwrap().trim_end().to_string();
+            r.borrow_mut().start_lines.push((line, dir));
+        });
+        let r = rec.clone();
+        protolens.set_cb_http_body_start(move |_ctx: *mut c_void, dir| {
+            r.borrow_mut().bodies.push((Vec::new(), dir));
+        });
+        let r = rec.clone();
+        protolens.set_cb_http_body(
+            move |data: &[u8],
+                  _seq,
+                  _ctx: *mut c_void,
+                  _dir,
+                  _ce: &Option<Vec<Encoding>>,
+                  _te: &Option<Vec<Encoding>>| {
+                if let Some(body) = r.borrow_mut().bodies.last_mut() {
+                    body.0.extend_from_slice(data);
+                }
+            },
+        );
+        let r = rec.clone();
+        protolens.set_cb_http_body_stop(move |_ctx: *mut c_void, dir| {
+            r.borrow_mut().body_stops.push(dir);
+        });
+        let r = rec.clone();
+        protolens.set_cb_http_decode_err(move |err, _ctx: *mut c_void, _dir| {
+            r.borrow_mut().decode_errs.push(err);
+        });
+        protolens
+    }
+
+    #[test]
+    fn test_http_pipeline_head() {
+        let session = [
+            (true, "HEAD /a HTTP/1.1\r\nHost: x\r\n\r\n"),
+            (true, "GET /b HTTP/1.1\r\nHost: x\r\n\r\n"),
+            (false, "HTTP/1.1 200 OK\r\nContent-Length: 100\r\n\r\n"),
+            (false, "HTTP/1.1 200 OK\r\nContent-Length: 3\r\n\r\nabc"),
+        ];
+        let rec = Rc::new(RefCell::new(Record::default()));
+        let mut protolens = new_prolens(&rec);
+        run_session(&mut protolens, L7Proto::Http, HTTP_PORT, &session, false);
+
+        let rec = rec.borrow();
+        assert_eq!(
+            rec.trans,
+            vec![
+                (1, Direction::C2s),
+                (2, Direction::C2s),
+                (1, Direction::S2c),
+                (2, Direction::S2c),
+            ]
+        );
+        // HEAD的应答没有body，Content-Length不生效
+        assert_eq!(rec.bodies, vec![(b"abc".to_vec(), Direction::S2c)]);
+    }
+
+    #[test]
+    fn test_http_rsp_first() {
+        let rec = Rc::new(RefCell::new(Record::default()));
+        let mut protolens = new_prolens(&rec);
+        let mut sess = TestSession::new(&mut protolens, L7Proto::Http, HTTP_PORT);
+        // 应答先于请求被调度，等请求解析出来之后再配对
+        sess.send_all(
+            &mut protolens,
+            &[
+                (false, "HTTP/1.1 200 OK\r\nContent-Length: 100\r\n\r\n"),
+                (false, "HTTP/1.1 200 OK\r\nContent-Length: 3\r\n\r\nabc"),
+                (false, "HTTP/1.1 204 No Content\r\n\r\n"),
+                (true, "HEAD /a HTTP/1.1\r\nHost: x\r\n\r\n"),
+            ],
+        );
+        assert_eq!(
+            rec.borrow().trans,
+            vec![(1, Direction::C2s), (1, Direction::S2c)]
+        );
+        // c2s结束之后多出来的应答不再等
+        sess.send(
+            &mut protolens,
+            true,
+            b"GET /b HTTP/1.1\r\nHost: x\r\n\r\n",
+            true,
+        );
+
+        let rec = rec.borrow();
+        assert_eq!(
+            rec.trans,
+            vec![
+                (1, Direction::C2s),
+                (1, Direction::S2c),
+                (2, Direction::C2s),
+                (2, Direction::S2c),
+                (3, Direction::S2c),
+            ]
+        );
+        assert_eq!(rec.bodies, vec![(b"abc".to_vec(), Direction::S2c)]);
+    }
+
+    #[test]
+    fn test_http_continue() {
+        let session = [
+            (
+                true,
+                "POST /up HTTP/1.1\r\nExpect: 100-continue\r\nContent-Length: 4\r\n\r\n",
+            ),
+            (false, "HTTP/1.1 100 Continue\r\n\r\n"),
+            (true, "data"),
+            (false, "HTTP/1.1 201 Created\r\nContent-Length: 2\r\n\r\nok"),
+            (true, "GET /next HTTP/1.1\r\n\r\n"),
+            (
+                false,
+                "HTTP/1.1 304 Not Modified\r\nContent-Length: 10\r\n\r\n",
+            ),
+            (true, "DELETE /x HTTP/1.1\r\n\r\n"),
+            (
+                false,
+                "HTTP/1.1 204 No Content\r\nContent-Length: 10\r\n\r\n",
+            ),
+        ];
+        let rec = Rc::new(RefCell::new(Record::default()));
+        let mut protolens = new_prolens(&rec);
+        run_session(&mut protolens, L7Proto::Http, HTTP_PORT, &session, false);
+
+        let rec = rec.borrow();
+        let s2c: Vec<u64> = rec
+            .trans
+            .iter()
+            .filter(|(_, dir)| *dir == Direction::S2c)
+            .map(|(id, _)| *id)
+            .collect();
+        assert_eq!(s2c, vec![1, 1, 2, 3]);
+        assert_eq!(rec.start_lines.len(), 7);
+        assert_eq!(
+            rec.bodies,
+            vec![
+                (b"data".to_vec(), Direction::C2s),
+                (b"ok".to_vec(), Direction::S2c)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_http_close_body() {
+        let session = [
+            (true, "GET / HTTP/1.0\r\n\r\n"),
+            (false, "HTTP/1.0 200 OK\r\nContent-Type: text/plain\r\n\r\n"),
+            (false, "hello "),
+            (false, "world"),
+        ];
+        let rec = Rc::new(RefCell::new(Record::default()));
+        let mut protolens = new_prolens(&rec);
+        run_session(&mut protolens, L7Proto::Http, HTTP_PORT, &session, true);
+
+        let rec = rec.borrow();
+        assert_eq!(rec.bodies, vec![(b"hello world".to_vec(), Direction::S2c)]);
+        assert_eq!(rec.body_stops, vec![Direction::S2c]);
+    }
+
+    #[test]
+    fn test_http_decode() {
+        let gzip: &[u8] = &[
+            0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x03, 0xcb, 0x48, 0xcd, 0xc9,
+            0xc9, 0x57, 0x48, 0xaf, 0xca, 0x2c, 0x50, 0x48, 0xca, 0x4f, 0xa9, 0xd4, 0x51, 0xc8,
+            0x20, 0x20, 0xc0, 0x05, 0x00, 0xb0, 0x06, 0xc5, 0x66, 0x32, 0x00, 0x00, 0x00,
+        ];
+        let brotli: &[u8] = &[
+            0x1b, 0x37, 0x00, 0xf8, 0x45, 0x77, 0xf8, 0xf7, 0x5c, 0xf3, 0xf7, 0x5e, 0x29, 0x8a,
+            0x31, 0xd2, 0x53, 0x29, 0x52, 0x55, 0x88, 0x50, 0xed, 0x22, 0x85, 0x58, 0x1d, 0x07,
+            0xa1, 0x7c, 0x47, 0x33,
+        ];
+        let session: [(bool, &[u8]); 12] = [
+            (true, b"GET /a HTTP/1.1\r\n\r\n"),
+            (
+                false,
+                b"HTTP/1.1 200 OK\r\nContent-Encoding: gzip\r\nTransfer-Encoding: chunked\r\n\r\n",
+            ),
+            (false, b"14\r\n"),
+            (false, &gzip[..20]),
+            (false, b"\r\n15\r\n"),
+            (false, &gzip[20..]),
+            (false, b"\r\n0\r\n\r\n"),
+            (true, b"GET /b HTTP/1.1\r\n\r\n"),
+            (
+                false,
+                b"HTTP/1.1 200 OK\r\nContent-Encoding: br\r\nContent-Length: 32\r\n\r\n",
+            ),
+            (false, brotli),
+            (true, b"GET /c HTTP/1.1\r\n\r\n"),
+            (
+                false,
+                b"HTTP/1.1 200 OK\r\nContent-Encoding: compress\r\nContent-Length: 3\r\n\r\nxyz",
+            ),
+        ];
+        let rec = Rc::new(RefCell::new(Record::default()));
+        let conf = Config {
+            http_decode: true,
+            ..Default::default()
+        };
+        let mut protolens = new_prolens_conf(&rec, conf);
+        run_session(&mut protolens, L7Proto::Http, HTTP_PORT, &session, false);
+
+        let rec = rec.borrow();
+        assert_eq!(
+            rec.bodies,
+            vec![
+                (
+                    b"hello gzip body, hello gzip body, hello gzip body\n".to_vec(),
+                    Direction::S2c
+                ),
+                (
+                    b"hello brotli body, hello brotli body, hello brotli body\n".to_vec(),
+                    Direction::S2c
+                ),
+                // compress不支持，原样交付
+                (b"xyz".to_vec(), Direction::S2c),
+            ]
+        );
+        assert_eq!(rec.decode_errs, vec![DecodeError::Unsupported]);
+        assert_eq!(
+            rec.body_stops,
+            vec![Direction::S2c, Direction::S2c, Direction::S2c]
+        );
+    }
+
+    #[test]
+    fn test_http_decode_limit() {
+        let gzip: &[u8] = &[
+            0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x03, 0xcb, 0x48, 0xcd, 0xc9,
+            0xc9, 0x57, 0x48, 0xaf, 0xca, 0x2c, 0x50, 0x48, 0xca, 0x4f, 0xa9, 0xd4, 0x51, 0xc8,
+            0x20, 0x20, 0xc0, 0x05, 0x00, 0xb0, 0x06, 0xc5, 0x66, 0x32, 0x00, 0x00, 0x00,
+        ];
+        let session: [(bool, &[u8]); 3] = [
+            (true, b"GET /a HTTP/1.1\r\n\r\n"),
+            (
+                false,
+                b"HTTP/1.1 200 OK\r\nContent-Encoding: gzip\r\nContent-Length: 41\r\n\r\n",
+            ),
+            (false, gzip),
+        ];
+        let rec = Rc::new(RefCell::new(Record::default()));
+        let conf = Config {
+            http_decode: true,
+            http_decode_max: 16,
+            ..Default::default()
+        };
+        let mut protolens = new_prolens_conf(&rec, conf);
+        run_session(&mut protolens, L7Proto::Http, HTTP_PORT, &session, false);
+
+        let rec = rec.borrow();
+        assert_eq!(rec.bodies, vec![(Vec::new(), Direction::S2c)]);
+        assert_eq!(rec.decode_errs, vec![DecodeError::Limit]);
+        assert_eq!(rec.body_stops, vec![Direction::S2c]);
+    }
+
+    #[test]
+    fn test_http_typed_msg() {
+        let session = [
+            (
+                true,
+                "GET /search?q=rust&page=2 HTTP/1.1\r\nhost: example.com\r\nUser-Agent: curl/8.0\r\nX-Long: a\r\n  b\r\n\tc\r\nCookie: sid=1\r\nAuthorization: Basic dXNlcjpwYXNz\r\n\r\n",
+            ),
+            (
+                false,
+                "HTTP/1.1 404 Not Found\r\nContent-Type: text/html\r\nSet-Cookie: a=1\r\nset-cookie: b=2\r\nContent-Length: 0\r\n\r\n",
+            ),
+        ];
+        let reqs = Rc::new(RefCell::new(Vec::new()));
+        let rsps = Rc::new(RefCell::new(Vec::new()));
+        let mut protolens = Prolens::<CapPacket>::default();
+        let r = reqs.clone();
+        protolens.set_cb_http_request(move |req: &HttpRequest, _ctx: *mut c_void| {
+            r.borrow_mut().push((
+                req.id,
+                req.method.to_string(),
+                req.path.to_string(),
+                req.query.map(|q| q.to_string()),
+                req.version.to_string(),
+                req.host().map(|v| v.to_string()),
+                req.user_agent().map(|v| v.to_string()),
+                req.headers.get("x-long").map(|v| v.to_string()),
+                req.cookie().map(|v| v.to_string()),
+                req.authorization().map(|v| v.to_string()),
+            ));
+        });
+        let r = rsps.clone();
+        protolens.set_cb_http_response(move |rsp: &HttpResponse, _ctx: *mut c_void| {
+            r.borrow_mut().push((
+                rsp.id,
+                rsp.status,
+                rsp.reason.to_string(),
+                rsp.content_type().map(|v| v.to_string()),
+                rsp.set_cookie().join(";"),
+                rsp.headers.0.len(),
+            ));
+        });
+        run_session(&mut protolens, L7Proto::Http, HTTP_PORT, &session, false);
+
+        assert_eq!(
+            *reqs.borrow(),
+            vec![(
+                1,
+                "GET".to_string(),
+                "/search".to_string(),
+                Some("q=rust&page=2".to_string()),
+                "HTTP/1.1".to_string(),
+                Some("example.com".to_string()),
+                Some("curl/8.0".to_string()),
+                Some("a b c".to_string()),
+                Some("sid=1".to_string()),
+                Some("Basic dXNlcjpwYXNz".to_string()),
+            )]
+        );
+        assert_eq!(
+            *rsps.borrow(),
+            vec![(
+                1,
+                404,
+                "Not Found".to_string(),
+                Some("text/html".to_string()),
+                "a=1;b=2".to_string(),
+                4
+            )]
+        );
+    }
+
+    #[test]
+    fn test_http_headers() {
+        let lines = vec![
+            "Accept: */*\r\n".to_string(),
+            "Via:1.1 a\r\n".to_string(),
+            "VIA: 1.1 b\r\n".to_string(),
+            "bad line\r\n".to_string(),
+        ];
+        let headers = HttpHeaders::parse(&lines);
+        assert_eq!(headers.0.len(), 3);
+        assert_eq!(headers.get("via"), Some("1.1 a"));
+        assert_eq!(
+            headers.get_all("Via").collect::<Vec<_>>(),
+            vec!["1.1 a", "1.1 b"]
+        );
+        assert_eq!(headers.get("Host"), None);
+
+        let rsp = http_response(3, "HTTP/1.1 204\r\n", &[]).unwrap();
+        assert_eq!((rsp.status, rsp.reason), (204, ""));
+        assert!(http_request(1, "GET\r\n", &[]).is_none());
+    }
+
+    #[test]
+    fn test_http_part() {
+        let session = [
+            (
+                true,
+                "POST /upload HTTP/1.1\r\nContent-Type: multipart/form-data; boundary=XyZ\r\n\r\n",
+            ),
+            (
+                true,
+                "--XyZ\r\nContent-Disposition: form-data; name=\"title\"\r\n\r\nhello\r\n",
+            ),
+            (
+                true,
+                "--XyZ\r\nContent-Disposition: form-data; name=\"file\"; filename=\"a b.txt\"\r\nContent-Type: text/plain\r\n\r\nfile data\r\n",
+            ),
+            (
+                true,
+                "--XyZ\r\nContent-Disposition: form-data; name=\"doc\"; filename=\"a;b.txt\"; filename*=UTF-8''%E4%B8%AD%E6%96%87.txt\r\n\r\ndoc\r\n",
+            ),
+            (true, "--XyZ--\r\n"),
+        ];
+        let parts = Rc::new(RefCell::new(Vec::new()));
+        let rec = Rc::new(RefCell::new(Record::default()));
+        let mut protolens = new_prolens(&rec);
+        let p = parts.clone();
+        protolens.set_cb_http_part(move |part: &HttpPart, _ctx: *mut c_void, _dir| {
+            p.borrow_mut().push((
+                part.id,
+                part.index,
+                part.depth,
+                part.name.map(|v| v.to_string()),
+                part.filename.as_deref().map(|v| v.to_string()),
+                part.content_type.map(|v| v.to_string()),
+            ));
+        });
+        run_session(&mut protolens, L7Proto::Http, HTTP_PORT, &session, false);
+
+        assert_eq!(
+            *parts.borrow(),
+            vec![
+                (1, 0, 0, Some("title".to_string()), None, None),
+                (
+                    1,
+                    1,
+                    0,
+                    Some("file".to_string()),
+                    Some("a b.txt".to_string()),
+                    Some("text/plain".to_string())
+                ),
+                (
+                    1,
+                    2,
+                    0,
+                    Some("doc".to_string()),
+                    Some("中文.txt".to_string()),
+                    None
+                ),
+            ]
+        );
+        let rec = rec.borrow();
+        assert_eq!(
+            rec.bodies,
+            vec![
+                (b"hello".to_vec(), Direction::C2s),
+                (b"file data".to_vec(), Direction::C2s),
+                (b"doc".to_vec(), Direction::C2s)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_http_disposition() {
+        let (name, filename) = disposition("form-data; name=\"f\"; filename=\"a;b=c.txt\"");
+        assert_eq!(name, Some("f"));
+        assert_eq!(filename.as_deref(), Some("a;b=c.txt"));
+
+        // 转义的引号，没有引号的值，没有值的参数
+        let (name, filename) =
+            disposition("form-data; x; name=plain ; filename=\"say \\\"hi\\\";.txt\"");
+        assert_eq!(name, Some("plain"));
+        assert_eq!(filename.as_deref(), Some("say \"hi\";.txt"));
+        assert!(matches!(filename, Some(Cow::Owned(_))));
+
+        // windows路径中的\保留
+        let (_, filename) = disposition("form-data; filename=\"C:\\dir\\a.txt\"");
+        assert_eq!(filename.as_deref(), Some("C:\\dir\\a.txt"));
+
+        // filename*优先，不管先后顺序
+        let (_, filename) =
+            disposition("attachment; filename*=UTF-8'en'a%20%E2%82%AC.txt; filename=\"a.txt\"");
+        assert_eq!(filename.as_deref(), Some("a €.txt"));
+        let (_, filename) = disposition("attachment; filename*=iso-8859-1''caf%E9.txt");
+        assert_eq!(filename.as_deref(), Some("café.txt"));
+
+        // filename*无法解码时退回filename
+        let (_, filename) = disposition("attachment; filename=\"b.txt\"; filename*=UTF-8''%FF%zz");
+        assert_eq!(filename.as_deref(), Some("b.txt"));
+        let (_, filename) = disposition("attachment; filename*=koi8-r''abc; filename=c.txt");
+        assert_eq!(filename.as_deref(), Some("c.txt"));
+
+        assert_eq!(disposition("inline"), (None, None));
+    }
+
+    #[test]
+    fn test_http_form() {
+        let body = "user=tim+lee&pass=a%26b%3D&empty&bad=%zz";
+        let req = format!(
+            "POST /login HTTP/1.1\r\nContent-Type: application/x-www-form-urlencoded; charset=utf-8\r\nContent-Length: {}\r\n\r\n",
+            body.len()
+        );
+        let session = [
+            (true, req.as_str()),
+            (true, &body[..10]),
+            (true, &body[10..]),
+            (false, "HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n"),
+        ];
+        let fields = Rc::new(RefCell::new(Vec::new()));
+        let rec = Rc::new(RefCell::new(Record::default()));
+        let mut protolens = new_prolens(&rec);
+        let f = fields.clone();
+        protolens.set_cb_http_form(
+            move |id, key: &[u8], value: &[u8], _ctx: *mut c_void, dir| {
+                assert_eq!(dir, Direction::C2s);
+                f.borrow_mut().push((id, key.to_vec(), value.to_vec()));
+            },
+        );
+        run_session(&mut protolens, L7Proto::Http, HTTP_PORT, &session, false);
+
+        assert_eq!(
+            *fields.borrow(),
+            vec![
+                (1, b"user".to_vec(), b"tim lee".to_vec()),
+                (1, b"pass".to_vec(), b"a&b=".to_vec()),
+                (1, b"empty".to_vec(), b"".to_vec()),
+                (1, b"bad".to_vec(), b"%zz".to_vec()),
+            ]
+        );
+        // 原始body照常交付
+        assert_eq!(
+            rec.borrow().bodies,
+            vec![
+                (body.as_bytes().to_vec(), Direction::C2s),
+                (Vec::new(), Direction::S2c)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_http_form_truncated() {
+        let body = format!("user=tim&note={}", "x".repeat(MAX_FORM_BUF));
+        let req = format!(
+            "POST /note HTTP/1.1\r\nContent-Type: application/x-www-form-urlencoded\r\nContent-Length: {}\r\n\r\n",
+            body.len()
+        );
+        let fields = Rc::new(RefCell::new(Vec::new()));
+        let rec = Rc::new(RefCell::new(Record::default()));
+        let mut protolens = new_prolens(&rec);
+        let f = fields.clone();
+        protolens.set_cb_http_form(move |_id, key: &[u8], _value: &[u8], _ctx, _dir| {
+            f.borrow_mut().push(key.to_vec());
+        });
+        let mut sess = TestSession::new(&mut protolens, L7Proto::Http, HTTP_PORT).chunk(1400);
+        sess.send_all(&mut protolens, &[(true, req), (true, body.clone())]);
+
+        // 超过缓存上限的表单不通知字段，body照常交付
+        assert!(fields.borrow().is_empty());
+        let len: usize = rec.borrow().bodies.iter().map(|(data, _)| data.len()).sum();
+        assert_eq!(len, body.len());
+    }
+
+    #[test]
+    fn test_http_form_decode() {
+        let gzip: &[u8] = &[
+            0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x03, 0x2b, 0x2d, 0x4e, 0x2d,
+            0xb2, 0x2d, 0xc9, 0xcc, 0xd5, 0xce, 0x49, 0x4d, 0x55, 0x2b, 0x48, 0x2c, 0x2e, 0xb6,
+            0xad, 0x00, 0x00, 0xea, 0x62, 0x51, 0x64, 0x13, 0x00, 0x00, 0x00,
+        ];
+        let req = format!(
+            "POST /login HTTP/1.1\r\nContent-Type: application/x-www-form-urlencoded\r\nContent-Encoding: gzip\r\nContent-Length: {}\r\n\r\n",
+            gzip.len()
+        );
+        let session: [(bool, &[u8]); 2] = [(true, req.as_bytes()), (true, gzip)];
+        let conf = Config {
+            http_decode: true,
+            ..Default::default()
+        };
+        // 没有注册body回调，表单同样拿到解码之后的数据
+        let mut protolens = Prolens::<CapPacket>::new(conf);
+        let fields = Rc::new(RefCell::new(Vec::new()));
+        let f = fields.clone();
+        protolens.set_cb_http_form(move |_id, key: &[u8], value: &[u8], _ctx, _dir| {
+            f.borrow_mut().push((key.to_vec(), value.to_vec()));
+        });
+        run_session(&mut protolens, L7Proto::Http, HTTP_PORT, &session, false);
+
+        assert_eq!(
+            *fields.borrow(),
+            vec![
+                (b"user".to_vec(), b"tim lee".to_vec()),
+                (b"pass".to_vec(), b"x".to_vec()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_http_connect() {
+        let session: [(bool, &[u8]); 4] = [
+            (
+                true,
+                b"CONNECT example.com:443 HTTP/1.1\r\nHost: example.com:443\r\n\r\n",
+            ),
+            (false, b"HTTP/1.1 200 Connection established\r\n\r\n"),
+            (true, b"\x16\x03\x01\x00\x05hello"),
+            (false, b"\x16\x03\x03\x00\x02hi"),
+        ];
+        let tunnels = Rc::new(RefCell::new(Vec::new()));
+        let data = Rc::new(RefCell::new(Vec::new()));
+        let rec = Rc::new(RefCell::new(Record::default()));
+        let mut protolens = new_prolens(&rec);
+        let t = tunnels.clone();
+        protolens.set_cb_http_tunnel(move |id, kind, target: &[u8], _ctx: *mut c_void| {
+            t.borrow_mut().push((id, kind, target.to_vec()));
+        });
+        let d = data.clone();
+        protolens.set_cb_http_tunnel_data(move |bytes: &[u8], _seq, _ctx: *mut c_void, dir| {
+            d.borrow_mut().push((bytes.to_vec(), dir));
+        });
+        run_session(&mut protolens, L7Proto::Http, HTTP_PORT, &session, false);
+
+        assert_eq!(
+            *tunnels.borrow(),
+            vec![(1, TunnelKind::Connect, b"example.com:443".to_vec())]
+        );
+        assert_eq!(
+            *data.borrow(),
+            vec![
+                (b"\x16\x03\x01\x00\x05hello".to_vec(), Direction::C2s),
+                (b"\x16\x03\x03\x00\x02hi".to_vec(), Direction::S2c),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_http_connect_tls() {
+        // 客户端不等200就发了ClientHello，s2c的应答要唤醒等待中的c2s
+        let mut req = b"CONNECT example.com:443 HTTP/1.1\r\nHost: example.com:443\r\n\r\n".to_vec();
+        let client_hello = tls_client_hello();
+        req.extend_from_slice(&client_hello);
+        let mut rsp = b"HTTP/1.1 200 Connection established\r\n\r\n".to_vec();
+        let server_hello = tls_server_hello();
+        rsp.extend_from_slice(&server_hello[..20]);
+        let app_data = b"\x17\x03\x03\x00\x03abc";
+        let session: [(bool, &[u8]); 4] = [
+            (true, &req),
+            (false, &rsp),
+            (false, &server_hello[20..]),
+            (true, app_data),
+        ];
+        let hellos = Rc::new(RefCell::new(Vec::new()));
+        let data = Rc::new(RefCell::new(Vec::new()));
+        let rec = Rc::new(RefCell::new(Record::default()));
+        let mut protolens = new_prolens(&rec);
+        let h = hellos.clone();
+        protolens.set_cb_tls_hello(move |hello: &TlsHello, _ctx: *mut c_void, dir| {
+            h.borrow_mut().push((
+                hello.client,
+                hello.sni.map(|v| v.to_string()),
+                hello.alpn.iter().map(|v| v.to_vec()).collect::<Vec<_>>(),
+                hello.versions.clone(),
+                dir,
+            ));
+        });
+        let d = data.clone();
+        protolens.set_cb_http_tunnel_data(move |bytes: &[u8], _seq, _ctx: *mut c_void, dir| {
+            d.borrow_mut().push((bytes.to_vec(), dir));
+        });
+
+        let mut sess = TestSession::new(&mut protolens, L7Proto::Http, HTTP_PORT);
+        sess.send_all(&mut protolens, &session[..2]);
+        // 还没有新的c2s包，ClientHello已经解析
+        assert_eq!(
+            *hellos.borrow(),
+            vec![(
+                true,
+                Some("example.com".to_string()),
+                vec![b"h2".to_vec(), b"http/1.1".to_vec()],
+                vec![0x0304, 0x0303],
+                Direction::C2s
+            )]
+        );
+        sess.send_all(&mut protolens, &session[2..]);
+
+        assert_eq!(hellos.borrow().len(), 2);
+        assert_eq!(
+            hellos.borrow()[1],
+            (false, None, Vec::new(), vec![0x0304], Direction::S2c)
+        );
+        // record原样交给tunnel_data
+        let data = data.borrow();
+        let c2s: Vec<u8> = data
+            .iter()
+            .filter(|(_, dir)| *dir == Direction::C2s)
+            .flat_map(|(bytes, _)| bytes.clone())
+            .collect();
+        let mut expect = client_hello.clone();
+        expect.extend_from_slice(app_data);
+        assert_eq!(c2s, expect);
+        let s2c: Vec<u8> = data
+            .iter()
+            .filter(|(_, dir)| *dir == Direction::S2c)
+            .flat_map(|(bytes, _)| bytes.clone())
+            .collect();
+        assert_eq!(s2c, server_hello);
+    }
+
+    #[test]
+    fn test_http_websocket() {
+        let mask = [1u8, 2, 3, 4];
+        let mut c2s_frame = vec![0x81, 0x85];
+        c2s_frame.extend_from_slice(&mask);
+        c2s_frame.extend(b"hello".iter().enumerate().map(|(i, b)| b ^ mask[i % 4]));
+        let session: [(bool, &[u8]); 6] = [
+            (
+                true,
+                b"GET /chat HTTP/1.1\r\nHost: x\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\r\n",
+            ),
+            (
+                false,
+                b"HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\r\n",
+            ),
+            (true, &c2s_frame),
+            (false, b"\x01\x02hi"),
+            (false, b"\x80\x01!"),
+            (true, b"\x88\x80\x00\x00\x00\x00"),
+        ];
+        let tunnels = Rc::new(RefCell::new(Vec::new()));
+        let frames = Rc::new(RefCell::new(Vec::new()));
+        let rec = Rc::new(RefCell::new(Record::default()));
+        let mut protolens = new_prolens(&rec);
+        let t = tunnels.clone();
+        protolens.set_cb_http_tunnel(move |id, kind, target: &[u8], _ctx: *mut c_void| {
+            t.borrow_mut().push((id, kind, target.to_vec()));
+        });
+        let f = frames.clone();
+        protolens.set_cb_ws_frame(move |frame: &WsFrame, _ctx: *mut c_void, dir| {
+            f.borrow_mut()
+                .push((frame.fin, frame.opcode, frame.payload.to_vec(), dir));
+        });
+        run_session(&mut protolens, L7Proto::Http, HTTP_PORT, &session, false);
+
+        assert_eq!(
+            *tunnels.borrow(),
+            vec![(1, TunnelKind::WebSocket, b"/chat".to_vec())]
+        );
+        assert_eq!(
+            *frames.borrow(),
+            vec![
+                (true, 1, b"hello".to_vec(), Direction::C2s),
+                (false, 1, b"hi".to_vec(), Direction::S2c),
+                (true, 0, b"!".to_vec(), Direction::S2c),
+                (true, 8, Vec::new(), Direction::C2s),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_http_websocket_ext_len() {
+        let payload: Vec<u8> = (0..300u32).map(|i| i as u8).collect();
+        let mut frame = vec![0x82, 126, 0x01, 0x2c];
+        frame.extend_from_slice(&payload);
+        let session: [(bool, &[u8]); 5] = [
+            (true, b"GET / HTTP/1.1\r\nUpgrade: websocket\r\n\r\n"),
+            (false, b"HTTP/1.1 101 Switching Protocols\r\n\r\n"),
+            (false, &frame[..3]),
+            (false, &frame[3..100]),
+            (false, &frame[100..]),
+        ];
+        let frames = Rc::new(RefCell::new(Vec::new()));
+        let rec = Rc::new(RefCell::new(Record::default()));
+        let mut protolens = new_prolens(&rec);
+        let f = frames.clone();
+        protolens.set_cb_ws_frame(move |frame: &WsFrame, _ctx: *mut c_void, _dir| {
+            assert_eq!((frame.opcode, frame.len), (2, 300));
+            f.borrow_mut().push((frame.offset, frame.payload.to_vec()));
+        });
+        run_session(&mut protolens, L7Proto::Http, HTTP_PORT, &session, false);
+
+        let frames = frames.borrow();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].0, 0);
+        assert_eq!(frames[1].0, frames[0].1.len() as u64);
+        let all: Vec<u8> = frames.iter().flat_map(|(_, data)| data.clone()).collect();
+        assert_eq!(all, payload);
+    }
+
+    #[test]
+    fn test_http_auth() {
+        let session = [
+            (
+                true,
+                "GET /a HTTP/1.1\r\nAuthorization: Basic dXNlcjpiYWQ=\r\n\r\n",
+            ),
+            (
+                false,
+                "HTTP/1.1 401 Unauthorized\r\nWWW-Authenticate: Basic realm=\"x\"\r\nContent-Length: 0\r\n\r\n",
+            ),
+            (
+                true,
+                "GET /a HTTP/1.1\r\nExpect: 100-continue\r\nProxy-Authorization: Bearer tok\r\nAuthorization: Basic dXNlcjpnb29k\r\n\r\n",
+            ),
+            (false, "HTTP/1.1 100 Continue\r\n\r\n"),
+            (false, "HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n"),
+        ];
+        let rec = Rc::new(RefCell::new(Record::default()));
+        let mut protolens = new_prolens(&rec);
+        let auths = Rc::new(RefCell::new(Vec::new()));
+        let a = auths.clone();
+        protolens.set_cb_http_auth(move |auth: &HttpAuth, _ctx: *mut c_void, dir| {
+            let cred = match &auth.cred {
+                HttpCred::Basic { user, pass } => format!(
+                    "{}:{}",
+                    String::from_utf8_lossy(user),
+                    String::from_utf8_lossy(pass)
+                ),
+                HttpCred::Bearer(token) => token.to_string(),
+                other => format!("{:?}", other),
+            };
+            a.borrow_mut()
+                .push((auth.id, auth.proxy, auth.status, cred, dir));
+        });
+        let results = Rc::new(RefCell::new(Vec::new()));
+        let r = results.clone();
+        protolens.set_cb_http_auth_result(move |id, proxy, status, _ctx: *mut c_void, dir| {
+            r.borrow_mut().push((id, proxy, status, dir));
+        });
+        run_session(&mut protolens, L7Proto::Http, HTTP_PORT, &session, false);
+
+        // 凭证在请求时通知，结果未知
+        assert_eq!(
+            *auths.borrow(),
+            vec![
+                (1, false, 0, "user:bad".to_string(), Direction::C2s),
+                (2, true, 0, "tok".to_string(), Direction::C2s),
+                (2, false, 0, "user:good".to_string(), Direction::C2s),
+            ]
+        );
+        // 1xx不通知结果
+        assert_eq!(
+            *results.borrow(),
+            vec![
+                (1, false, 401, Direction::S2c),
+                (2, true, 200, Direction::S2c),
+                (2, false, 200, Direction::S2c),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_http_auth_ntlm() {
+        // type 2协商OEM编码，之后老格式的type 3没有flags字段
+        let session = [
+            (
+                true,
+                "GET / HTTP/1.1\r\nAuthorization: NTLM TlRMTVNTUAABAAAAB4IIAAAAAAAAAAAAAAAAAAAAAAA=\r\n\r\n",
+            ),
+            (
+                false,
+                "HTTP/1.1 401 Unauthorized\r\nWWW-Authenticate: NTLM TlRMTVNTUAACAAAAAAAAACAAAAACAAAAAQIDBAUGBwg=\r\nContent-Length: 0\r\n\r\n",
+            ),
+            (
+                true,
+                "GET / HTTP/1.1\r\nAuthorization: NTLM TlRMTVNTUAADAAAABgAGADQAAAAAAAAAOgAAAAQABAA6AAAAAwADAD4AAAAEAAQAQQAAAGxtcmVzcENPUlBib2JXUzAy\r\n\r\n",
+            ),
+            (false, "HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n"),
+        ];
+        let rec = Rc::new(RefCell::new(Record::default()));
+        let mut protolens = new_prolens(&rec);
+        let msgs = Rc::new(RefCell::new(Vec::new()));
+        let m = msgs.clone();
+        protolens.set_cb_http_auth(move |auth: &HttpAuth, _ctx: *mut c_void, dir| {
+            if let HttpCred::Ntlm(msg) = &auth.cred {
+                m.borrow_mut()
+                    .push((auth.id, auth.status, msg.clone(), dir));
+            }
+        });
+        run_session(&mut protolens, L7Proto::Http, HTTP_PORT, &session, false);
+
+        let msgs = msgs.borrow();
+        assert_eq!(msgs.len(), 3);
+        assert_eq!((msgs[0].0, msgs[0].1, msgs[0].2.msg_type), (1, 0, 1));
+        assert_eq!(
+            (msgs[1].0, msgs[1].1, msgs[1].2.msg_type, msgs[1].3),
+            (1, 401, 2, Direction::S2c)
+        );
+        let type3 = &msgs[2].2;
+        assert_eq!((msgs[2].0, msgs[2].1, type3.msg_type), (2, 0, 3));
+        assert_eq!(type3.flags, 0x0000_0002);
+        assert_eq!(type3.domain.as_deref(), Some("CORP"));
+        assert_eq!(type3.user.as_deref(), Some("bob"));
+        assert_eq!(type3.workstation.as_deref(), Some("WS02"));
+    }
+
+    #[test]
+    fn test_http_h2c_upgrade() {
+        // :status 200，静态表索引8
+        let mut s2c =
+            b"HTTP/1.1 101 Switching Protocols\r\nConnection: Upgrade\r\nUpgrade: h2c\r\n\r\n"
+                .to_vec();
+        s2c.extend(h2_frame(0x4, 0, 0, &[]));
+        s2c.extend(h2_frame(0x1, 0x4, 1, &[0x88]));
+        s2c.extend(h2_frame(0x0, 0x1, 1, b"hi"));
+        // :method GET, :path /, :scheme http
+        let mut c2s2 = H2_PREFACE.to_vec();
+        c2s2.extend(h2_frame(0x4, 0, 0, &[]));
+        c2s2.extend(h2_frame(0x1, 0x5, 3, &[0x82, 0x84, 0x86]));
+        let session: [(bool, &[u8]); 3] = [
+            (
+                true,
+                b"GET / HTTP/1.1\r\nHost: x\r\nConnection: Upgrade, HTTP2-Settings\r\nUpgrade: h2c\r\nHTTP2-Settings: AAMAAABkAAQAAP__\r\n\r\n",
+            ),
+            (false, &s2c),
+            (true, &c2s2),
+        ];
+        let rec = Rc::new(RefCell::new(Record::default()));
+        let mut protolens = new_prolens(&rec);
+        let h2 = Rc::new(RefCell::new(H2Record::default()));
+        record_h2(&mut protolens, &h2);
+        run_session(&mut protolens, L7Proto::Http, HTTP_PORT, &session, false);
+
+        let h2 = h2.borrow();
+        assert_eq!(
+            h2.lines,
+            vec![
+                (1, "HTTP/2 200".to_string(), Direction::S2c),
+                (3, "GET / HTTP/2".to_string(), Direction::C2s),
+            ]
+        );
+        assert_eq!(h2.bodies, vec![(1, b"hi".to_vec(), Direction::S2c)]);
+        assert_eq!(rec.borrow().start_lines.len(), 2);
+    }
+
+    #[test]
+    fn test_http_h2_prior_knowledge() {
+        let mut c2s = H2_PREFACE.to_vec();
+        c2s.extend(h2_frame(0x1, 0x5, 1, &[0x82, 0x84, 0x86]));
+        let mut s2c = h2_frame(0x4, 0, 0, &[]);
+        s2c.extend(h2_frame(0x1, 0x5, 1, &[0x8d]));
+        let session: [(bool, &[u8]); 2] = [(true, &c2s), (false, &s2c)];
+        let rec = Rc::new(RefCell::new(Record::default()));
+        let mut protolens = new_prolens(&rec);
+        let h2 = Rc::new(RefCell::new(H2Record::default()));
+        record_h2(&mut protolens, &h2);
+        run_session(&mut protolens, L7Proto::Http, HTTP_PORT, &session, false);
+
+        assert_eq!(
+            h2.borrow().lines,
+            vec![
+                (1, "GET / HTTP/2".to_string(), Direction::C2s),
+                (1, "HTTP/2 404".to_string(), Direction::S2c),
+            ]
+        );
+        assert!(rec.borrow().start_lines.is_empty());
+    }
+
+    #[test]
+    fn test_http_upgrade_refused() {
+        let session = [
+            (true, "GET /a HTTP/1.1\r\nUpgrade: h2c\r\n\r\n"),
+            (false, "HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok"),
+            (true, "GET /b HTTP/1.1\r\n\r\n"),
+            (false, "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n"),
+        ];
+        let rec = Rc::new(RefCell::new(Record::default()));
+        let mut protolens = new_prolens(&rec);
+        run_session(&mut protolens, L7Proto::Http, HTTP_PORT, &session, false);
+
+        let rec = rec.borrow();
+        assert_eq!(
+            rec.trans,
+            vec![
+                (1, Direction::C2s),
+                (1, Direction::S2c),
+                (2, Direction::C2s),
+                (2, Direction::S2c),
+            ]
+        );
+        assert_eq!(upgrade_proto("upgrade: H2C\r\n"), Some(TunnelKind::H2c));
+        assert_eq!(
+            upgrade_proto("Upgrade: foo/1, bar\r\n"),
+            Some(TunnelKind::Other)
+        );
+        assert_eq!(upgrade_proto("Host: x\r\n"), None);
+    }
+
+    #[test]
+    fn test_http_body_len() {
+        let header = |content_len, te: Option<Vec<Encoding>>| HeaderRet {
+            content_len,
+            boundary: None,
+            ce: None,
+            te,
+            form: false,
+            upgrade: None,
+            auth: Vec::new(),
+        };
+
+        assert_eq!(req_body_len(&header(None, None)), BodyLen::None);
+        assert_eq!(req_body_len(&header(Some(5), None)), BodyLen::Size(5));
+        // te优先于content-length
+        assert_eq!(
+            req_body_len(&header(Some(5), Some(vec![Encoding::Chunked]))),
+            BodyLen::Chunked
+        );
+        assert_eq!(
+            req_body_len(&header(None, Some(vec![Encoding::Gzip]))),
+            BodyLen::Invalid
+        );
+
+        assert_eq!(
+            rsp_body_len(&header(None, None), Some("GET"), 200),
+            BodyLen::Close
+        );
+        assert_eq!(
+            rsp_body_len(&header(Some(5), None), Some("HEAD"), 200),
+            BodyLen::None
+        );
+        assert_eq!(
+            rsp_body_len(&header(Some(5), None), Some("GET"), 304),
+            BodyLen::None
+        );
+        assert_eq!(
+            rsp_body_len(&header(None, None), Some("CONNECT"), 200),
+            BodyLen::Tunnel
+        );
+        assert_eq!(
+            rsp_body_len(&header(None, None), Some("GET"), 101),
+            BodyLen::Tunnel
+        );
+        assert_eq!(
+            rsp_body_len(&header(None, Some(vec![Encoding::Gzip])), None, 200),
+            BodyLen::Close
+        );
+        assert_eq!(rsp_status("HTTP/1.1 404 Not Found\r\n"), Some(404));
+        assert_eq!(rsp_status("HTTP/1.1 xx\r\n"), None);
+    }
 }
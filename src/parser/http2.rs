@@ -0,0 +1,702 @@
+// HTTP/2明文(RFC 9113)。prior knowledge直接用L7Proto::Http2，h2c升级由http parser切换过来
+use crate::CbGrpcCall;
+use crate::CbGrpcMsg;
+use crate::CbGrpcStatus;
+use crate::CbHttp2Body;
+use crate::CbHttp2BodyEvt;
+use crate::CbHttp2Line;
+use crate::DirConfirmFn;
+use crate::Direction;
+use crate::Encoding;
+use crate::GrpcStream;
+use crate::Hpack;
+use crate::HpackField;
+use crate::Parser;
+use crate::ParserFactory;
+use crate::ParserFuture;
+use crate::PktStrm;
+use crate::Prolens;
+use crate::content_encoding;
+use crate::grpc_call;
+use crate::grpc_status;
+use crate::is_grpc;
+use crate::packet::*;
+use std::collections::HashMap;
+use std::ffi::c_void;
+use std::marker::PhantomData;
+
+pub(crate) const H2_PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+
+const FRAME_HEAD_LEN: usize = 9;
+const DATA: u8 = 0x0;
+const HEADERS: u8 = 0x1;
+const PUSH_PROMISE: u8 = 0x5;
+const CONTINUATION: u8 = 0x9;
+
+const FLAG_END_STREAM: u8 = 0x1;
+const FLAG_END_HEADERS: u8 = 0x4;
+const FLAG_PADDED: u8 = 0x8;
+const FLAG_PRIORITY: u8 = 0x20;
+
+// 一个header block(包括CONTINUATION)的上限
+const MAX_HEADER_BLOCK: usize = 256 * 1024;
+// 同时打开的stream上限，超过之后新的stream不再通知
+const MAX_STREAMS: usize = 1024;
+
+#[derive(Clone)]
+pub(crate) struct Http2Callbacks {
+    pub(crate) start_line: Option<CbHttp2Line>,
+    pub(crate) header: Option<CbHttp2Line>,
+    pub(crate) body_start: Option<CbHttp2BodyEvt>,
+    pub(crate) body: Option<CbHttp2Body>,
+    pub(crate) body_stop: Option<CbHttp2BodyEvt>,
+    pub(crate) grpc_call: Option<CbGrpcCall>,
+    pub(crate) grpc_msg: Option<CbGrpcMsg>,
+    pub(crate) grpc_status: Option<CbGrpcStatus>,
+    pub(crate) dir: Direction,
+}
+
+impl Http2Callbacks {
+    // 方向在创建parser future的时候再设置
+    pub(crate) fn new<T: Packet>(prolens: &Prolens<T>) -> Self {
+        Self {
+            start_line: prolens.cb_http2_start_line.clone(),
+            header: prolens.cb_http2_header.clone(),
+            body_start: prolens.cb_http2_body_start.clone(),
+            body: prolens.cb_http2_body.clone(),
+            body_stop: prolens.cb_http2_body_stop.clone(),
+            grpc_call: prolens.cb_grpc_call.clone(),
+            grpc_msg: prolens.cb_grpc_msg.clone(),
+            grpc_status: prolens.cb_grpc_status.clone(),
+            dir: Direction::Unknown,
+        }
+    }
+
+    pub(crate) fn with_dir(&self, dir: Direction) -> Self {
+        Self {
+            dir,
+            ..self.clone()
+        }
+    }
+}
+
+#[derive(Default)]
+struct H2Stream {
+    body: bool, // 已经通知了body_start
+    ce: Option<Vec<Encoding>>,
+    grpc: Option<GrpcStream>,
+}
+
+// 正在拼接的header block
+struct H2Block {
+    stream: u32,
+    end_stream: bool,
+    push: bool, // PUSH_PROMISE只用来更新动态表
+    data: Vec<u8>,
+}
+
+// 一个方向的帧解析。每个方向有自己的hpack动态表和stream状态
+pub(crate) async fn h2_frames<T: Packet>(
+    stm: &mut PktStrm<T>,
+    cb: &Http2Callbacks,
+    cb_ctx: *mut c_void,
+    preface: bool,
+) -> Result<(), ()> {
+    if preface {
+        let (data, _seq) = stm.readn(H2_PREFACE.len()).await?;
+        if data != H2_PREFACE {
+            return Err(());
+        }
+    }
+
+    let mut hpack = Hpack::new();
+    let mut streams: HashMap<u32, H2Stream> = HashMap::new();
+    let mut block: Option<H2Block> = None;
+    loop {
+        let (head, _seq) = stm.readn(FRAME_HEAD_LEN).await?;
+        let len = u32::from_be_bytes([0, head[0], head[1], head[2]]) as usize;
+        let ftype = head[3];
+        let flags = head[4];
+        let id = u32::from_be_bytes([head[5], head[6], head[7], head[8]]) & 0x7fff_ffff;
+
+        // header block中间不能插入其他帧
+        if block.is_some() && (ftype != CONTINUATION || block.as_ref().unwrap().stream != id) {
+            return Err(());
+        }
+
+        match ftype {
+            DATA => {
+                let stream = streams.get_mut(&id);
+                data_frame(stm, len, flags, id, stream, cb, cb_ctx).await?;
+                if flags & FLAG_END_STREAM != 0 {
+                    streams.remove(&id);
+                }
+            }
+            HEADERS | PUSH_PROMISE => {
+                let mut payload = Vec::new();
+                read_payload(stm, len, &mut payload).await?;
+                let mut frag = unpad(&payload, flags)?;
+                if ftype == HEADERS && flags & FLAG_PRIORITY != 0 {
+                    frag = frag.get(5..).ok_or(())?;
+                }
+                if ftype == PUSH_PROMISE {
+                    frag = frag.get(4..).ok_or(())?;
+                }
+                block = Some(H2Block {
+                    stream: id,
+                    end_stream: flags & FLAG_END_STREAM != 0,
+                    push: ftype == PUSH_PROMISE,
+                    data: frag.to_vec(),
+                });
+            }
+            CONTINUATION => {
+                let Some(ref mut cur) = block else {
+                    return Err(());
+                };
+                if cur.data.len() + len > MAX_HEADER_BLOCK {
+                    return Err(());
+                }
+                read_payload(stm, len, &mut cur.data).await?;
+            }
+            _ => skip(stm, len).await?,
+        }
+
+        if matches!(ftype, HEADERS | PUSH_PROMISE | CONTINUATION) && flags & FLAG_END_HEADERS != 0 {
+            let Some(cur) = block.take() else {
+                return Err(());
+            };
+            // 解码失败之后动态表已经不同步，后面的都没法解
+            let fields = hpack.decode(&cur.data).map_err(|_| ())?;
+            if cur.push {
+                continue;
+            }
+            if !streams.contains_key(&cur.stream) && streams.len() >= MAX_STREAMS {
+                continue;
+            }
+            let stream = streams.entry(cur.stream).or_default();
+            header_block(cur.stream, stream, &fields, cb, cb_ctx);
+            if cur.end_stream {
+                if stream.body
+                    && let Some(ref cb_stop) = cb.body_stop
+                {
+                    cb_stop.borrow_mut()(cur.stream, cb_ctx, cb.dir);
+                }
+                streams.remove(&cur.stream);
+            }
+        }
+    }
+}
+
+async fn data_frame<T: Packet>(
+    stm: &mut PktStrm<T>,
+    len: usize,
+    flags: u8,
+    id: u32,
+    mut stream: Option<&mut H2Stream>,
+    cb: &Http2Callbacks,
+    cb_ctx: *mut c_void,
+) -> Result<(), ()> {
+    let mut pad = 0;
+    let mut remain = len;
+    if flags & FLAG_PADDED != 0 {
+        let (byte, _seq) = stm.readn(1).await?;
+        pad = byte[0] as usize;
+        remain = len.checked_sub(1 + pad).ok_or(())?;
+    }
+
+    // 没有HEADERS的stream(比如超过MAX_STREAMS)数据直接丢掉
+    if let Some(ref mut stream) = stream
+        && !stream.body
+        && remain > 0
+    {
+        stream.body = true;
+        if let Some(ref cb_start) = cb.body_start {
+            cb_start.borrow_mut()(id, cb_ctx, cb.dir);
+        }
+    }
+    while remain > 0 {
+        let (data, _seq) = stm.read(remain).await?;
+        remain -= data.len();
+        let Some(ref mut stream) = stream else {
+            continue;
+        };
+        if let Some(ref cb_body) = cb.body {
+            cb_body.borrow_mut()(id, data, cb_ctx, cb.dir, &stream.ce);
+        }
+        if let Some(ref mut grpc) = stream.grpc {
+            grpc.feed(id, data, cb.grpc_msg.as_ref(), cb_ctx, cb.dir);
+        }
+    }
+    skip(stm, pad).await?;
+
+    if flags & FLAG_END_STREAM != 0
+        && stream.is_some_and(|stream| stream.body)
+        && let Some(ref cb_stop) = cb.body_stop
+    {
+        cb_stop.borrow_mut()(id, cb_ctx, cb.dir);
+    }
+    Ok(())
+}
+
+// 带:method或者:status的是消息头，否则是trailer。
+// 转换成http/1.1的形式通知：起始行和"name: value\r\n"，最后是空行
+fn header_block(
+    id: u32,
+    stream: &mut H2Stream,
+    fields: &[HpackField],
+    cb: &Http2Callbacks,
+    cb_ctx: *mut c_void,
+) {
+    let pseudo = |name: &[u8]| {
+        fields
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_slice())
+    };
+
+    let start_line = if let Some(method) = pseudo(b":method") {
+        // CONNECT没有:path
+        let target = pseudo(b":path")
+            .or(pseudo(b":authority"))
+            .unwrap_or_default();
+        Some([method, b" ", target, b" HTTP/2\r\n"].concat())
+    } else {
+        pseudo(b":status").map(|status| [b"HTTP/2 ", status, b"\r\n"].concat())
+    };
+    if let Some(ref line) = start_line
+        && let Some(ref cb_line) = cb.start_line
+    {
+        cb_line.borrow_mut()(id, line, cb_ctx, cb.dir);
+    }
+
+    let mut line = Vec::new();
+    for (name, value) in fields {
+        // :authority相当于host，其他伪头已经在起始行里
+        let name = match name.as_slice() {
+            b":authority" => b"host".as_slice(),
+            name if name.starts_with(b":") => continue,
+            name => name,
+        };
+        line.clear();
+        line.extend_from_slice(name);
+        line.extend_from_slice(b": ");
+        line.extend_from_slice(value);
+        line.extend_from_slice(b"\r\n");
+
+        if start_line.is_some()
+            && stream.ce.is_none()
+            && let Ok(text) = std::str::from_utf8(&line)
+        {
+            stream.ce = content_encoding(text);
+        }
+        if let Some(ref cb_header) = cb.header {
+            cb_header.borrow_mut()(id, &line, cb_ctx, cb.dir);
+        }
+    }
+    if let Some(ref cb_header) = cb.header {
+        cb_header.borrow_mut()(id, b"\r\n", cb_ctx, cb.dir);
+    }
+
+    if start_line.is_some() && is_grpc(fields) {
+        stream.grpc = Some(GrpcStream::default());
+        if let Some(ref cb_call) = cb.grpc_call
+            && let Some(call) = grpc_call(id, fields)
+        {
+            cb_call.borrow_mut()(&call, cb_ctx, cb.dir);
+        }
+    }
+    let mut plain = String::new();
+    if stream.grpc.is_some()
+        && let Some(ref cb_status) = cb.grpc_status
+        && let Some(status) = grpc_status(id, fields, &mut plain)
+    {
+        cb_status.borrow_mut()(&status, cb_ctx, cb.dir);
+    }
+}
+
+async fn read_payload<T: Packet>(
+    stm: &mut PktStrm<T>,
+    len: usize,
+    buf: &mut Vec<u8>,
+) -> Result<(), ()> {
+    if len > MAX_HEADER_BLOCK {
+        return Err(());
+    }
+    let mut remain = len;
+    while remain > 0 {
+        let (data, _seq) = stm.read(remain).await?;
+        remain -= data.len();
+        buf.extend_from_slice(data);
+    }
+    Ok(())
+}
+
+async fn skip<T: Packet>(stm: &mut PktStrm<T>, len: usize) -> Result<(), ()> {
+    let mut remain = len;
+    while remain > 0 {
+        let (data, _seq) = stm.read(remain).await?;
+        remain -= data.len();
+    }
+    Ok(())
+}
+
+fn unpad(payload: &[u8], flags: u8) -> Result<&[u8], ()> {
+    if flags & FLAG_PADDED == 0 {
+        return Ok(payload);
+    }
+    let (&pad, rest) = payload.split_first().ok_or(())?;
+    let end = rest.len().checked_sub(pad as usize).ok_or(())?;
+    Ok(&rest[..end])
+}
+
+pub struct Http2Parser<T>
+where
+    T: Packet,
+{
+    cb_h2: Option<Http2Callbacks>,
+    _phantom_t: PhantomData<T>,
+}
+
+impl<T> Http2Parser<T>
+where
+    T: Packet,
+{
+    pub(crate) fn new() -> Self {
+        Self {
+            cb_h2: None,
+            _phantom_t: PhantomData,
+        }
+    }
+
+    async fn parser_inner(
+        strm: *mut PktStrm<T>,
+        cb: Http2Callbacks,
+        cb_ctx: *mut c_void,
+    ) -> Result<(), ()> {
+        let stm = unsafe { &mut *strm };
+        // 只有客户端发送preface
+        let preface = cb.dir == Direction::C2s;
+        h2_frames(stm, &cb, cb_ctx, preface).await
+    }
+}
+
+impl<T> Parser for Http2Parser<T>
+where
+    T: Packet + 'static,
+{
+    type T = T;
+
+    fn dir_confirm(&self) -> DirConfirmFn<Self::T> {
+        |c2s_strm, s2c_strm, _c2s_port, _s2c_port| {
+            let stm_c2s = unsafe { &mut *c2s_strm };
+            let stm_s2c = unsafe { &mut *s2c_strm };
+
+            if let Ok(payload) = stm_c2s.peek_payload()
+                && payload.starts_with(&H2_PREFACE[..4])
+            {
+                return Some(true);
+            }
+            if let Ok(payload) = stm_s2c.peek_payload()
+                && payload.starts_with(&H2_PREFACE[..4])
+            {
+                return Some(false);
+            }
+            Some(true)
+        }
+    }
+
+    fn c2s_parser(&self, strm: *mut PktStrm<T>, cb_ctx: *mut c_void) -> Option<ParserFuture> {
+        let cb = self.cb_h2.as_ref()?.with_dir(Direction::C2s);
+        Some(Box::pin(Self::parser_inner(strm, cb, cb_ctx)))
+    }
+
+    fn s2c_parser(&self, strm: *mut PktStrm<T>, cb_ctx: *mut c_void) -> Option<ParserFuture> {
+        let cb = self.cb_h2.as_ref()?.with_dir(Direction::S2c);
+        Some(Box::pin(Self::parser_inner(strm, cb, cb_ctx)))
+    }
+}
+
+pub(crate) struct Http2Factory<T> {
+    _phantom_t: PhantomData<T>,
+}
+
+impl<T> ParserFactory<T> for Http2Factory<T>
+where
+    T: Packet + 'static,
+{
+    fn new() -> Self {
+        Self {
+            _phantom_t: PhantomData,
+        }
+    }
+
+    fn create(&self, prolens: &Prolens<T>) -> Box<dyn Parser<T = T>> {
+        let mut parser = Box::new(Http2Parser::new());
+        parser.cb_h2 = Some(Http2Callbacks::new(prolens));
+        parser
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Config;
+    use crate::GrpcCall;
+    use crate::GrpcMsg;
+    use crate::GrpcStatus;
+    use crate::test_utils::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    // 全部用literal with incremental indexing，新名字
+    fn block(fields: &[(&str, &str)]) -> Vec<u8> {
+        let mut buf = Vec::new();
+        for (name, value) in fields {
+            buf.push(0x40);
+            buf.push(name.len() as u8);
+            buf.extend_from_slice(name.as_bytes());
+            buf.push(value.len() as u8);
+            buf.extend_from_slice(value.as_bytes());
+        }
+        buf
+    }
+
+    #[test]
+    fn test_http2_prior_knowledge() {
+        let mut c2s = H2_PREFACE.to_vec();
+        c2s.extend(h2_frame(0x4, 0, 0, &[0, 3, 0, 0, 0, 100]));
+        c2s.extend(h2_frame(
+            HEADERS,
+            FLAG_END_HEADERS | FLAG_END_STREAM,
+            1,
+            &block(&[(":method", "GET"), (":path", "/a"), (":authority", "h")]),
+        ));
+        // 第二个请求引用动态表。插入:path之后63是:authority，65是:method GET
+        let mut req = block(&[(":path", "/b")]);
+        req.extend_from_slice(&[0xbf, 0xc1]);
+        let mut headers = vec![0x08]; // pad 8
+        headers.extend_from_slice(&[0x00, 0x00, 0x00, 0x00, 0x10]); // priority
+        headers.extend_from_slice(&req);
+        headers.extend_from_slice(&[0; 8]);
+        c2s.extend(h2_frame(
+            HEADERS,
+            FLAG_END_HEADERS | FLAG_PADDED | FLAG_PRIORITY,
+            3,
+            &headers,
+        ));
+        c2s.extend(h2_frame(DATA, 0, 3, b"hel"));
+        c2s.extend(h2_frame(
+            DATA,
+            FLAG_END_STREAM | FLAG_PADDED,
+            3,
+            b"\x02lo\0\0",
+        ));
+
+        let mut s2c = h2_frame(0x4, 0, 0, &[]);
+        s2c.extend(h2_frame(
+            HEADERS,
+            FLAG_END_HEADERS,
+            3,
+            &block(&[(":status", "200")]),
+        ));
+        // header block分到CONTINUATION
+        let rsp = block(&[(":status", "404"), ("content-encoding", "gzip")]);
+        s2c.extend(h2_frame(HEADERS, 0, 1, &rsp[..10]));
+        s2c.extend(h2_frame(CONTINUATION, FLAG_END_HEADERS, 1, &rsp[10..]));
+        s2c.extend(h2_frame(DATA, 0, 1, b"x"));
+        s2c.extend(h2_frame(DATA, FLAG_END_STREAM, 3, b"ok"));
+        s2c.extend(h2_frame(0x8, 0, 0, &[0, 0, 1, 0])); // WINDOW_UPDATE
+        s2c.extend(h2_frame(DATA, FLAG_END_STREAM, 1, b"y"));
+
+        let rec = Rc::new(RefCell::new(H2Record::default()));
+        let mut protolens = Prolens::<CapPacket>::new(Config::default());
+        record_h2(&mut protolens, &rec);
+        let session = [(true, c2s), (false, s2c)];
+        TestSession::new(&mut protolens, L7Proto::Http2, 8080)
+            .chunk(7)
+            .send_all(&mut protolens, &session);
+
+        let rec = rec.borrow();
+        assert_eq!(
+            rec.lines,
+            vec![
+                (1, "GET /a HTTP/2".to_string(), Direction::C2s),
+                (3, "GET /b HTTP/2".to_string(), Direction::C2s),
+                (3, "HTTP/2 200".to_string(), Direction::S2c),
+                (1, "HTTP/2 404".to_string(), Direction::S2c),
+            ]
+        );
+        let headers: Vec<_> = rec
+            .headers
+            .iter()
+            .map(|(id, line, _)| (*id, line.as_str()))
+            .collect();
+        assert_eq!(
+            headers,
+            vec![
+                (1, "host: h"),
+                (1, ""),
+                (3, "host: h"),
+                (3, ""),
+                (3, ""),
+                (1, "content-encoding: gzip"),
+                (1, ""),
+            ]
+        );
+        assert_eq!(
+            rec.bodies,
+            vec![
+                (3, b"hello".to_vec(), Direction::C2s),
+                (1, b"xy".to_vec(), Direction::S2c),
+                (3, b"ok".to_vec(), Direction::S2c),
+            ]
+        );
+        assert_eq!(
+            rec.body_stops,
+            vec![
+                (3, Direction::C2s),
+                (3, Direction::S2c),
+                (1, Direction::S2c),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_http2_trailer() {
+        let mut c2s = H2_PREFACE.to_vec();
+        c2s.extend(h2_frame(
+            HEADERS,
+            FLAG_END_HEADERS,
+            1,
+            &block(&[(":method", "POST"), (":path", "/svc/M")]),
+        ));
+        c2s.extend(h2_frame(DATA, FLAG_END_STREAM, 1, b"req"));
+
+        let mut s2c = h2_frame(HEADERS, FLAG_END_HEADERS, 1, &block(&[(":status", "200")]));
+        s2c.extend(h2_frame(DATA, 0, 1, b"rsp"));
+        s2c.extend(h2_frame(
+            HEADERS,
+            FLAG_END_HEADERS | FLAG_END_STREAM,
+            1,
+            &block(&[("grpc-status", "0")]),
+        ));
+
+        let rec = Rc::new(RefCell::new(H2Record::default()));
+        let mut protolens = Prolens::<CapPacket>::new(Config::default());
+        record_h2(&mut protolens, &rec);
+        let session = [(true, c2s), (false, s2c)];
+        TestSession::new(&mut protolens, L7Proto::Http2, 8080)
+            .chunk(1500)
+            .send_all(&mut protolens, &session);
+
+        let rec = rec.borrow();
+        assert_eq!(rec.lines.len(), 2);
+        assert!(
+            rec.headers
+                .contains(&(1, "grpc-status: 0".to_string(), Direction::S2c))
+        );
+        assert_eq!(
+            rec.body_stops,
+            vec![(1, Direction::C2s), (1, Direction::S2c)]
+        );
+    }
+
+    #[test]
+    fn test_http2_grpc() {
+        let mut c2s = H2_PREFACE.to_vec();
+        c2s.extend(h2_frame(
+            HEADERS,
+            FLAG_END_HEADERS,
+            1,
+            &block(&[
+                (":method", "POST"),
+                (":path", "/helloworld.Greeter/SayHello"),
+                ("content-type", "application/grpc"),
+            ]),
+        ));
+        // 一个消息跨两个DATA帧
+        c2s.extend(h2_frame(DATA, 0, 1, &[0, 0, 0, 0, 4, b'a', b'b']));
+        c2s.extend(h2_frame(DATA, FLAG_END_STREAM, 1, b"cd"));
+
+        let mut s2c = h2_frame(
+            HEADERS,
+            FLAG_END_HEADERS,
+            1,
+            &block(&[(":status", "200"), ("content-type", "application/grpc")]),
+        );
+        s2c.extend(h2_frame(DATA, 0, 1, &[1, 0, 0, 0, 1, b'z', 0, 0, 0, 0, 0]));
+        s2c.extend(h2_frame(
+            HEADERS,
+            FLAG_END_HEADERS | FLAG_END_STREAM,
+            1,
+            &block(&[("grpc-status", "3"), ("grpc-message", "bad%20arg")]),
+        ));
+
+        let rec = Rc::new(RefCell::new(H2Record::default()));
+        let mut protolens = Prolens::<CapPacket>::new(Config::default());
+        record_h2(&mut protolens, &rec);
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let e = events.clone();
+        protolens.set_cb_grpc_call(move |call: &GrpcCall, _ctx: *mut c_void, _dir| {
+            e.borrow_mut().push(format!(
+                "call {} {} {}",
+                call.stream, call.service, call.method
+            ));
+        });
+        let e = events.clone();
+        protolens.set_cb_grpc_msg(move |msg: &GrpcMsg, _ctx: *mut c_void, dir| {
+            e.borrow_mut().push(format!(
+                "msg {:?} {} {} {} {}",
+                dir,
+                msg.compressed,
+                msg.len,
+                msg.offset,
+                String::from_utf8_lossy(msg.data)
+            ));
+        });
+        let e = events.clone();
+        protolens.set_cb_grpc_status(move |status: &GrpcStatus, _ctx: *mut c_void, _dir| {
+            e.borrow_mut()
+                .push(format!("status {:?} {:?}", status.status, status.message));
+        });
+        let session = [(true, c2s), (false, s2c)];
+        TestSession::new(&mut protolens, L7Proto::Http2, 8080)
+            .chunk(1500)
+            .send_all(&mut protolens, &session);
+
+        assert_eq!(
+            *events.borrow(),
+            vec![
+                "call 1 helloworld.Greeter SayHello",
+                "msg C2s false 4 0 ab",
+                "msg C2s false 4 2 cd",
+                "msg S2c true 1 0 z",
+                "msg S2c false 0 0 ",
+                "status Some(3) Some(\"bad arg\")",
+            ]
+        );
+        // body事件照常
+        assert_eq!(rec.borrow().bodies.len(), 2);
+    }
+
+    #[test]
+    fn test_http2_bad_frame() {
+        // CONTINUATION之前插入了其他帧
+        let mut c2s = H2_PREFACE.to_vec();
+        let req = block(&[(":method", "GET"), (":path", "/")]);
+        c2s.extend(h2_frame(HEADERS, 0, 1, &req[..5]));
+        c2s.extend(h2_frame(DATA, 0, 1, b"x"));
+        c2s.extend(h2_frame(CONTINUATION, FLAG_END_HEADERS, 1, &req[5..]));
+
+        let rec = Rc::new(RefCell::new(H2Record::default()));
+        let mut protolens = Prolens::<CapPacket>::new(Config::default());
+        record_h2(&mut protolens, &rec);
+        TestSession::new(&mut protolens, L7Proto::Http2, 8080)
+            .chunk(1500)
+            .send_all(&mut protolens, &[(true, c2s)]);
+        assert!(rec.borrow().lines.is_empty());
+
+        assert_eq!(unpad(&[4, b'a', 0, 0], FLAG_PADDED), Err(()));
+        assert_eq!(unpad(&[2, b'a', 0, 0], FLAG_PADDED), Ok(&b"a"[..]));
+    }
+}
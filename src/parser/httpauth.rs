@@ -0,0 +1,404 @@
+// Authorization/Proxy-Authorization以及NTLM challenge的解析
+use crate::Direction;
+use crate::base64_decode;
+
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct HttpDigest<'a> {
+    pub username: Option<&'a str>,
+    pub realm: Option<&'a str>,
+    pub nonce: Option<&'a str>,
+    pub uri: Option<&'a str>,
+    pub response: Option<&'a str>,
+    pub algorithm: Option<&'a str>,
+    pub qop: Option<&'a str>,
+    pub nc: Option<&'a str>,
+    pub cnonce: Option<&'a str>,
+    pub opaque: Option<&'a str>,
+}
+
+// NTLMSSP消息。type 1/3的字符串是否unicode由flags决定，这里统一转成String
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct NtlmMsg {
+    pub msg_type: u32,
+    pub flags: u32,
+    pub domain: Option<String>,
+    pub user: Option<String>,
+    pub workstation: Option<String>,
+    pub target: Option<String>,     // type 2
+    pub challenge: Option<[u8; 8]>, // type 2
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum HttpCred<'a> {
+    Basic {
+        user: &'a [u8],
+        pass: &'a [u8],
+    },
+    Digest(HttpDigest<'a>),
+    Bearer(&'a str),
+    Ntlm(NtlmMsg),
+    Other {
+        scheme: &'a str,
+        credentials: &'a str,
+    },
+}
+
+// 请求中的凭证在请求头解析完就通知，这时status为0表示结果未知，
+// 最终应答的状态码通过auth result回调通知。服务端的NTLM challenge在应答中通知
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct HttpAuth<'a> {
+    pub id: u64,
+    pub proxy: bool,
+    pub status: u16,
+    pub cred: HttpCred<'a>,
+}
+
+const NTLM_SIG: &[u8] = b"NTLMSSP\0";
+const NTLM_UNICODE: u32 = 0x0000_0001;
+const NTLM3_HEADER: usize = 64; // 带flags的type 3头长度
+const NTLM_DOMAIN_SUPPLIED: u32 = 0x0000_1000;
+const NTLM_WORKSTATION_SUPPLIED: u32 = 0x0000_2000;
+
+// 请求取Authorization，应答取WWW-Authenticate。返回(proxy, 头的值)
+pub(crate) fn auth_header(line: &str, dir: Direction) -> Option<(bool, String)> {
+    let (name, value) = line.split_once(':')?;
+    let (plain, proxy) = match dir {
+        Direction::C2s => ("authorization", "proxy-authorization"),
+        _ => ("www-authenticate", "proxy-authenticate"),
+    };
+    let proxy = if name.eq_ignore_ascii_case(plain) {
+        false
+    } else if name.eq_ignore_ascii_case(proxy) {
+        true
+    } else {
+        return None;
+    };
+    Some((proxy, value.trim().to_string()))
+}
+
+// value是去掉头名字之后的部分，比如"Basic dXNlcjpwYXNz"。plain用来存放base64解码的结果。
+// negotiated是之前type 1/2协商的NTLM flags，type 3没有flags字段时使用
+pub(crate) fn http_cred<'a>(
+    value: &'a str,
+    plain: &'a mut Vec<u8>,
+    negotiated: Option<u32>,
+) -> Option<HttpCred<'a>> {
+    let value = value.trim();
+    let (scheme, rest) = value
+        .split_once([' ', '\t'])
+        .map_or((value, ""), |(scheme, rest)| (scheme, rest.trim()));
+
+    if scheme.eq_ignore_ascii_case("Basic") {
+        *plain = base64_decode(rest.as_bytes())?;
+        let pos = plain.iter().position(|&c| c == b':')?;
+        let (user, pass) = plain.split_at(pos);
+        Some(HttpCred::Basic {
+            user,
+            pass: &pass[1..],
+        })
+    } else if scheme.eq_ignore_ascii_case("Digest") {
+        Some(HttpCred::Digest(digest(rest)))
+    } else if scheme.eq_ignore_ascii_case("Bearer") {
+        Some(HttpCred::Bearer(rest))
+    } else if scheme.eq_ignore_ascii_case("NTLM") || scheme.eq_ignore_ascii_case("Negotiate") {
+        // Negotiate可能是SPNEGO包着的kerberos，不是NTLMSSP时按Other处理
+        match base64_decode(rest.as_bytes()).and_then(|msg| ntlm(&msg, negotiated)) {
+            Some(msg) => Some(HttpCred::Ntlm(msg)),
+            None => Some(HttpCred::Other {
+                scheme,
+                credentials: rest,
+            }),
+        }
+    } else if scheme.is_empty() {
+        None
+    } else {
+        Some(HttpCred::Other {
+            scheme,
+            credentials: rest,
+        })
+    }
+}
+
+fn digest(input: &str) -> HttpDigest<'_> {
+    let mut digest = HttpDigest::default();
+    for (key, value) in auth_params(input) {
+        let field = match key.to_ascii_lowercase().as_str() {
+            "username" => &mut digest.username,
+            "realm" => &mut digest.realm,
+            "nonce" => &mut digest.nonce,
+            "uri" => &mut digest.uri,
+            "response" => &mut digest.response,
+            "algorithm" => &mut digest.algorithm,
+            "qop" => &mut digest.qop,
+            "nc" => &mut digest.nc,
+            "cnonce" => &mut digest.cnonce,
+            "opaque" => &mut digest.opaque,
+            _ => continue,
+        };
+        *field = Some(value);
+    }
+    digest
+}
+
+// key=value, key="value, with comma"。引号内的转义不处理
+fn auth_params(input: &str) -> Vec<(&str, &str)> {
+    let mut params = Vec::new();
+    let mut rest = input;
+    loop {
+        rest = rest.trim_start_matches([' ', '\t', ',']);
+        let Some((key, after)) = rest.split_once('=') else {
+            break;
+        };
+        let after = after.trim_start();
+        let (value, next) = if let Some(quoted) = after.strip_prefix('"') {
+            match quoted.find('"') {
+                Some(end) => (&quoted[..end], &quoted[end + 1..]),
+                None => (quoted, ""),
+            }
+        } else {
+            match after.find(',') {
+                Some(end) => (after[..end].trim_end(), &after[end..]),
+                None => (after.trim_end(), ""),
+            }
+        };
+        params.push((key.trim(), value));
+        rest = next;
+    }
+    params
+}
+
+pub(crate) fn ntlm(msg: &[u8], negotiated: Option<u32>) -> Option<NtlmMsg> {
+    if msg.len() < 12 || !msg.starts_with(NTLM_SIG) {
+        return None;
+    }
+    let msg_type = le32(msg, 8)?;
+    let mut ret = NtlmMsg {
+        msg_type,
+        ..Default::default()
+    };
+    match msg_type {
+        1 => {
+            ret.flags = le32(msg, 12)?;
+            if ret.flags & NTLM_DOMAIN_SUPPLIED != 0 {
+                ret.domain = sec_buf(msg, 16).map(oem_str);
+            }
+            if ret.flags & NTLM_WORKSTATION_SUPPLIED != 0 {
+                ret.workstation = sec_buf(msg, 24).map(oem_str);
+            }
+        }
+        2 => {
+            ret.flags = le32(msg, 20)?;
+            let challenge = msg.get(24..32)?;
+            ret.challenge = Some(challenge.try_into().ok()?);
+            ret.target = sec_buf(msg, 12).map(|buf| ntlm_str(buf, ret.flags));
+        }
+        3 => {
+            ret.flags = ntlm3_flags(msg).or(negotiated).unwrap_or(NTLM_UNICODE);
+            ret.domain = sec_buf(msg, 28).map(|buf| ntlm_str(buf, ret.flags));
+            ret.user = sec_buf(msg, 36).map(|buf| ntlm_str(buf, ret.flags));
+            ret.workstation = sec_buf(msg, 44).map(|buf| ntlm_str(buf, ret.flags));
+        }
+        _ => return None,
+    }
+    Some(ret)
+}
+
+// type 3的flags在60，但老的实现没有这个字段，payload直接从52开始。
+// 只有消息够长，并且非空的security buffer都在64之后，60处才是flags
+fn ntlm3_flags(msg: &[u8]) -> Option<u32> {
+    if msg.len() < NTLM3_HEADER {
+        return None;
+    }
+    for off in [12, 20, 28, 36, 44] {
+        let len = le16(msg, off)?;
+        let start = le32(msg, off + 4)? as usize;
+        if len != 0 && start < NTLM3_HEADER {
+            return None;
+        }
+    }
+    le32(msg, 60)
+}
+
+fn le16(buf: &[u8], off: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(buf.get(off..off + 2)?.try_into().ok()?))
+}
+
+fn le32(buf: &[u8], off: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(buf.get(off..off + 4)?.try_into().ok()?))
+}
+
+// len(2) maxlen(2) offset(4)
+fn sec_buf(msg: &[u8], off: usize) -> Option<&[u8]> {
+    let len = le16(msg, off)? as usize;
+    let start = le32(msg, off + 4)? as usize;
+    msg.get(start..start.checked_add(len)?)
+}
+
+fn ntlm_str(buf: &[u8], flags: u32) -> String {
+    if flags & NTLM_UNICODE == 0 {
+        return oem_str(buf);
+    }
+    let units: Vec<u16> = buf
+        .chunks_exact(2)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .collect();
+    String::from_utf16_lossy(&units)
+}
+
+fn oem_str(buf: &[u8]) -> String {
+    String::from_utf8_lossy(buf).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cred(value: &str) -> Option<HttpCred<'_>> {
+        // 测试里plain泄漏无所谓
+        let plain = Box::leak(Box::new(Vec::new()));
+        http_cred(value, plain, None)
+    }
+
+    #[test]
+    fn test_basic_bearer() {
+        assert_eq!(
+            cred("Basic dXNlcjpwYTpzcw=="),
+            Some(HttpCred::Basic {
+                user: b"user",
+                pass: b"pa:ss"
+            })
+        );
+        assert_eq!(cred("basic bm9jb2xvbg=="), None);
+        assert_eq!(
+            cred("Bearer abc.def.ghi"),
+            Some(HttpCred::Bearer("abc.def.ghi"))
+        );
+        assert_eq!(
+            cred("AWS4-HMAC-SHA256 Credential=x"),
+            Some(HttpCred::Other {
+                scheme: "AWS4-HMAC-SHA256",
+                credentials: "Credential=x"
+            })
+        );
+    }
+
+    #[test]
+    fn test_digest() {
+        let value = "Digest username=\"Mufasa\", realm=\"http-auth@example.org\", uri=\"/dir/index.html\", algorithm=MD5, nonce=\"7ypf/xlj9XXwfDPEoM4URrv/xwf94BcCAzFZH4GiTo0v\", nc=00000001, cnonce=\"f2/wE4q74E6zIJEtWaHKaf5wv/H5QzzpXusqGemxURZJ\", qop=auth, response=\"8ca523f5e9506fed4657c9700eebdbec\", opaque=\"FQhe/qaU925kfnzjCev0ciny7QMkPqMAFRtzCUYo5tdS\"";
+        let Some(HttpCred::Digest(digest)) = cred(value) else {
+            panic!("not digest");
+        };
+        assert_eq!(digest.username, Some("Mufasa"));
+        assert_eq!(digest.realm, Some("http-auth@example.org"));
+        assert_eq!(digest.uri, Some("/dir/index.html"));
+        assert_eq!(digest.algorithm, Some("MD5"));
+        assert_eq!(digest.nc, Some("00000001"));
+        assert_eq!(digest.qop, Some("auth"));
+        assert_eq!(digest.response, Some("8ca523f5e9506fed4657c9700eebdbec"));
+
+        let params = auth_params("qop=\"auth,auth-int\", x=1");
+        assert_eq!(params, vec![("qop", "auth,auth-int"), ("x", "1")]);
+    }
+
+    fn ntlm_type3(domain: &str, user: &str, ws: &str) -> Vec<u8> {
+        let enc = |s: &str| -> Vec<u8> { s.encode_utf16().flat_map(|u| u.to_le_bytes()).collect() };
+        let fields = [
+            Vec::new(),
+            Vec::new(),
+            enc(domain),
+            enc(user),
+            enc(ws),
+            Vec::new(),
+        ];
+        ntlm_type3_raw(&fields, Some(NTLM_UNICODE))
+    }
+
+    // flags为None时是没有session key和flags的老格式，payload从52开始
+    fn ntlm_type3_raw(fields: &[Vec<u8>], flags: Option<u32>) -> Vec<u8> {
+        let mut msg = NTLM_SIG.to_vec();
+        msg.extend_from_slice(&3u32.to_le_bytes());
+        let mut off = if flags.is_some() { 64u32 } else { 52 };
+        let mut payload = Vec::new();
+        for field in fields {
+            msg.extend_from_slice(&(field.len() as u16).to_le_bytes());
+            msg.extend_from_slice(&(field.len() as u16).to_le_bytes());
+            msg.extend_from_slice(&off.to_le_bytes());
+            off += field.len() as u32;
+            payload.extend_from_slice(field);
+        }
+        if let Some(flags) = flags {
+            msg.extend_from_slice(&flags.to_le_bytes());
+        }
+        msg.extend_from_slice(&payload);
+        msg
+    }
+
+    #[test]
+    fn test_ntlm() {
+        let msg = ntlm(&ntlm_type3("CORP", "alice", "WS01"), None).unwrap();
+        assert_eq!(msg.msg_type, 3);
+        assert_eq!(msg.domain.as_deref(), Some("CORP"));
+        assert_eq!(msg.user.as_deref(), Some("alice"));
+        assert_eq!(msg.workstation.as_deref(), Some("WS01"));
+
+        // type 1，没有domain和workstation
+        let msg = cred("NTLM TlRMTVNTUAABAAAAB4IIAAAAAAAAAAAAAAAAAAAAAAA=").unwrap();
+        let HttpCred::Ntlm(msg) = msg else {
+            panic!("not ntlm");
+        };
+        assert_eq!((msg.msg_type, msg.flags), (1, 0x00088207));
+        assert_eq!(msg.domain, None);
+
+        // type 2
+        let mut challenge = NTLM_SIG.to_vec();
+        challenge.extend_from_slice(&2u32.to_le_bytes());
+        challenge.extend_from_slice(&[6, 0, 6, 0, 32, 0, 0, 0]);
+        challenge.extend_from_slice(&0x0000_0001u32.to_le_bytes());
+        challenge.extend_from_slice(&[1, 2, 3, 4, 5, 6, 7, 8]);
+        challenge.extend_from_slice(&[b'D', 0, b'O', 0, b'M', 0]);
+        let msg = ntlm(&challenge, None).unwrap();
+        assert_eq!(msg.challenge, Some([1, 2, 3, 4, 5, 6, 7, 8]));
+        assert_eq!(msg.target.as_deref(), Some("DOM"));
+
+        assert_eq!(ntlm(b"NTLMSSP\0\x09\0\0\0", None), None);
+        assert_eq!(ntlm(b"short", None), None);
+    }
+
+    #[test]
+    fn test_ntlm_legacy_type3() {
+        // 老格式，60处是payload，不能当成flags。用协商的OEM编码
+        let fields = [
+            b"lmresp".to_vec(),
+            Vec::new(),
+            b"CORP".to_vec(),
+            b"bob".to_vec(),
+            b"WS02".to_vec(),
+        ];
+        let raw = ntlm_type3_raw(&fields, None);
+        assert!(raw.len() >= NTLM3_HEADER);
+        assert_eq!(ntlm3_flags(&raw), None);
+        let msg = ntlm(&raw, Some(0x0000_0002)).unwrap();
+        assert_eq!(msg.flags, 0x0000_0002);
+        assert_eq!(msg.domain.as_deref(), Some("CORP"));
+        assert_eq!(msg.user.as_deref(), Some("bob"));
+        assert_eq!(msg.workstation.as_deref(), Some("WS02"));
+
+        // 没有协商结果时默认unicode
+        assert_eq!(ntlm(&raw, None).unwrap().flags, NTLM_UNICODE);
+
+        // 短消息不读60
+        let raw = ntlm_type3_raw(
+            &[
+                Vec::new(),
+                Vec::new(),
+                b"D".to_vec(),
+                Vec::new(),
+                Vec::new(),
+            ],
+            None,
+        );
+        assert!(raw.len() < NTLM3_HEADER);
+        let msg = ntlm(&raw, Some(0x0000_0002)).unwrap();
+        assert_eq!(msg.domain.as_deref(), Some("D"));
+    }
+}
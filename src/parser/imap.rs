@@ -3,7 +3,14 @@ use crate::CbBody;
 use crate::CbBodyEvt;
 use crate::CbClt;
 use crate::CbHeader;
+use crate::CbImapAuth;
+use crate::CbImapBodyInfo;
+use crate::CbImapFetch;
+use crate::CbImapReply;
+use crate::CbImapState;
+use crate::CbPass;
 use crate::CbSrv;
+use crate::CbUser;
 use crate::DirConfirmFn;
 use crate::Direction;
 use crate::IMAP_PORT;
@@ -19,19 +26,212 @@ use crate::header;
 use crate::mime_body;
 use crate::packet::*;
 use crate::preamble;
+use crate::sasl_cred;
 use imapprotolens::{
-    AttributeValue2, BodyStructParser2, ContentEncoding, follow_rsp_fetch, rsp_fetch,
+    Address, AttributeValue, AttributeValue2, BodyStructParser2, ContentEncoding, Envelope,
+    FetchRet, MessageSection, Response, SectionPath, follow_rsp_fetch, parse_response, rsp_fetch,
 };
 use memchr::memmem::Finder;
 use nom::{
     IResult,
     bytes::complete::{tag, take_while1},
-    character::complete::{char, digit1, space1},
-    combinator::map_res,
-    sequence::{delimited, tuple},
+    character::complete::space1,
+    sequence::tuple,
 };
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
 use std::ffi::c_void;
 use std::marker::PhantomData;
+use std::rc::Rc;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ImapState {
+    NotAuth,
+    Auth,
+    Selected,
+    Logout,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ImapStatus {
+    Ok,
+    No,
+    Bad,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct ImapAddr<'a> {
+    pub name: Option<&'a [u8]>,
+    pub mailbox: Option<&'a [u8]>,
+    pub host: Option<&'a [u8]>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct ImapEnvelope<'a> {
+    pub date: Option<&'a [u8]>,
+    pub subject: Option<&'a [u8]>,
+    pub from: Vec<ImapAddr<'a>>,
+    pub sender: Vec<ImapAddr<'a>>,
+    pub reply_to: Vec<ImapAddr<'a>>,
+    pub to: Vec<ImapAddr<'a>>,
+    pub cc: Vec<ImapAddr<'a>>,
+    pub bcc: Vec<ImapAddr<'a>>,
+    pub in_reply_to: Option<&'a [u8]>,
+    pub message_id: Option<&'a [u8]>,
+}
+
+// 一个FETCH应答中的元数据。body的内容走body回调，这里不带
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct ImapFetch<'a> {
+    pub mailbox: Option<&'a [u8]>,
+    pub num: u32,
+    pub uid: Option<u32>,
+    pub flags: Vec<&'a str>,
+    pub internal_date: Option<&'a str>,
+    pub size: Option<u32>,
+    pub envelope: Option<ImapEnvelope<'a>>,
+}
+
+// 命令参数中的literal最多保留这么多
+const MAX_LITERAL_ARG: usize = 1024;
+
+// FETCH应答最多缓存这么多，超过就放弃
+const MAX_FETCH_BUF: usize = 64 * 1024;
+
+// c2s解析出的带tag的命令，等待s2c的tagged应答
+struct ImapReq {
+    cmd: String,
+    mailbox: Option<String>,
+}
+
+type ImapPending = Rc<RefCell<HashMap<String, ImapReq>>>;
+
+pub(crate) struct ImapCmdCallbacks {
+    user: Option<CbUser>,
+    pass: Option<CbPass>,
+    auth: Option<CbImapAuth>,
+}
+
+pub(crate) struct ImapRspCallbacks {
+    reply: Option<CbImapReply>,
+    state: Option<CbImapState>,
+    body_info: Option<CbImapBodyInfo>,
+    fetch: Option<CbImapFetch>,
+}
+
+// s2c侧的会话状态
+struct ImapSession {
+    state: ImapState,
+    mailbox: Option<String>,
+    read_only: bool,
+    // 当前FETCH应答的序号和uid，一个FETCH里可能有多个literal
+    fetch_num: u32,
+    fetch_uid: Option<u32>,
+    // 拼接中的FETCH应答，body literal替换成NIL
+    fetch_buf: Option<String>,
+}
+
+impl ImapSession {
+    fn new() -> Self {
+        Self {
+            state: ImapState::NotAuth,
+            mailbox: None,
+            read_only: false,
+            fetch_num: 0,
+            fetch_uid: None,
+            fetch_buf: None,
+        }
+    }
+
+    fn set_state(
+        &mut self,
+        state: ImapState,
+        mailbox: Option<String>,
+        read_only: bool,
+        cb_rsp: &ImapRspCallbacks,
+        cb_ctx: *mut c_void,
+    ) {
+        if self.state == state && self.mailbox == mailbox && self.read_only == read_only {
+            return;
+        }
+        self.state = state;
+        self.mailbox = mailbox;
+        self.read_only = read_only;
+
+        if let Some(ref cb) = cb_rsp.state {
+            let mailbox = self.mailbox.as_ref().map(|m| m.as_bytes());
+            cb.borrow_mut()(self.state, mailbox, self.read_only, cb_ctx);
+        }
+    }
+
+    fn tagged(
+        &mut self,
+        req: &ImapReq,
+        status: ImapStatus,
+        text: &str,
+        cb_rsp: &ImapRspCallbacks,
+        cb_ctx: *mut c_void,
+    ) {
+        match (req.cmd.as_str(), status) {
+            ("LOGIN" | "AUTHENTICATE", ImapStatus::Ok) => {
+                self.set_state(ImapState::Auth, None, false, cb_rsp, cb_ctx);
+            }
+            ("SELECT" | "EXAMINE", ImapStatus::Ok) => {
+                let read_only = req.cmd == "EXAMINE" || text.contains("[READ-ONLY]");
+                self.set_state(
+                    ImapState::Selected,
+                    req.mailbox.clone(),
+                    read_only,
+                    cb_rsp,
+                    cb_ctx,
+                );
+            }
+            // select失败之后之前选中的邮箱也不再是选中状态
+            ("SELECT" | "EXAMINE", _) | ("CLOSE" | "UNSELECT", ImapStatus::Ok)
+                if self.state == ImapState::Selected =>
+            {
+                self.set_state(ImapState::Auth, None, false, cb_rsp, cb_ctx);
+            }
+            ("LOGOUT", ImapStatus::Ok) => {
+                self.set_state(ImapState::Logout, None, false, cb_rsp, cb_ctx);
+            }
+            _ => {}
+        }
+    }
+
+    // body_literal表示行尾的literal会被当作body读走
+    fn fetch_line(
+        &mut self,
+        line: &str,
+        body_literal: bool,
+        cb_rsp: &ImapRspCallbacks,
+        cb_ctx: *mut c_void,
+    ) {
+        let Some(buf) = self.fetch_buf.as_mut() else {
+            return;
+        };
+        if body_literal && let Some((prefix, _, _)) = literal(line) {
+            buf.push_str(prefix);
+            buf.push_str("NIL");
+            return;
+        }
+        buf.push_str(line);
+
+        match parse_response(buf.as_bytes()) {
+            Ok((_, Response::Fetch(num, attrs))) => {
+                if let Some(ref cb) = cb_rsp.fetch {
+                    let mut fetch = imap_fetch(num, &attrs);
+                    fetch.mailbox = self.mailbox.as_ref().map(|m| m.as_bytes());
+                    cb.borrow_mut()(&fetch, cb_ctx);
+                }
+            }
+            // envelope里的literal不会被当作body读走，要继续拼接
+            Err(nom::Err::Incomplete(_)) if buf.len() < MAX_FETCH_BUF => return,
+            _ => {}
+        }
+        self.fetch_buf = None;
+    }
+}
 
 pub struct ImapParser<T>
 where
@@ -43,6 +243,16 @@ where
     cb_body_stop: Option<CbBodyEvt>,
     cb_clt: Option<CbClt>,
     cb_srv: Option<CbSrv>,
+    cb_user: Option<CbUser>,
+    cb_pass: Option<CbPass>,
+    cb_auth: Option<CbImapAuth>,
+    cb_reply: Option<CbImapReply>,
+    cb_state: Option<CbImapState>,
+    cb_body_info: Option<CbImapBodyInfo>,
+    cb_fetch: Option<CbImapFetch>,
+    pending: ImapPending,
+    // COMPRESS成功之后s2c要同时切换c2s的stream
+    c2s_strm: Rc<Cell<*mut PktStrm<T>>>,
     _phantom_t: PhantomData<T>,
 }
 
@@ -58,6 +268,15 @@ where
             cb_body_stop: None,
             cb_clt: None,
             cb_srv: None,
+            cb_user: None,
+            cb_pass: None,
+            cb_auth: None,
+            cb_reply: None,
+            cb_state: None,
+            cb_body_info: None,
+            cb_fetch: None,
+            pending: Rc::new(RefCell::new(HashMap::new())),
+            c2s_strm: Rc::new(Cell::new(std::ptr::null_mut())),
             _phantom_t: PhantomData,
         }
     }
@@ -65,9 +284,13 @@ where
     async fn c2s_parser_inner(
         strm: *mut PktStrm<T>,
         cb_imap: Callbacks,
+        cb_cmd: ImapCmdCallbacks,
+        pending: ImapPending,
         cb_ctx: *mut c_void,
     ) -> Result<(), ()> {
         let stm = unsafe { &mut *strm };
+        // AUTHENTICATE之后的sasl交互: 机制名和已收到的应答数
+        let mut sasl: Option<(String, usize)> = None;
 
         loop {
             let (line, seq) = stm.read_clean_line_str().await?;
@@ -76,27 +299,169 @@ where
                 cb.borrow_mut()(line.as_bytes(), seq, cb_ctx);
             }
 
-            let (is_append, mail_size) = append(line);
-            if is_append && append_ok(stm).await? {
-                Self::append_mail(stm, mail_size, &cb_imap, cb_ctx).await?;
+            // sasl应答是一行base64，没有空格
+            if let Some((mech, cnt)) = sasl.as_mut()
+                && !line.contains(' ')
+            {
+                if line == "*" {
+                    sasl = None;
+                } else {
+                    sasl_resp(mech, *cnt, line.as_bytes(), seq, &cb_cmd, cb_ctx);
+                    *cnt += 1;
+                }
+                continue;
+            }
+            sasl = None;
+
+            // append的literal是邮件，其他命令的literal拼进命令行里
+            let (is_append, _) = append(line);
+            // 等literal的时候server可能已经应答了，先登记tag
+            let mut early = false;
+            if !is_append
+                && literal(line).is_some()
+                && let Some((tag, cmd, _)) = imap_cmd(line)
+            {
+                let req = ImapReq { cmd, mailbox: None };
+                pending.borrow_mut().insert(tag.to_string(), req);
+                early = true;
+            }
+            let line = line.to_string();
+            let line = if is_append {
+                line
+            } else {
+                Self::cmd_literals(stm, line, &cb_imap, cb_ctx).await?
+            };
+
+            if let Some((tag, cmd, args)) = imap_cmd(&line) {
+                let mut mailbox = None;
+                match cmd.as_str() {
+                    "LOGIN" => {
+                        if let Some((user, rest)) = astring(args)
+                            && let Some((pass, _)) = astring(rest.trim_start())
+                        {
+                            if let Some(ref cb) = cb_cmd.user {
+                                cb.borrow_mut()(user.as_bytes(), seq, cb_ctx);
+                            }
+                            if let Some(ref cb) = cb_cmd.pass {
+                                cb.borrow_mut()(pass.as_bytes(), seq, cb_ctx);
+                            }
+                        }
+                    }
+                    "AUTHENTICATE" => {
+                        // SASL-IR的初始应答跟在机制名后面
+                        let (mech, ir) = match args.split_once(' ') {
+                            Some((mech, ir)) => (mech, Some(ir.trim())),
+                            None => (args, None),
+                        };
+                        let mech = mech.to_ascii_uppercase();
+                        let mut cnt = 0;
+                        if let Some(ir) = ir {
+                            let ir = if ir == "=" { "" } else { ir };
+                            sasl_resp(&mech, cnt, ir.as_bytes(), seq, &cb_cmd, cb_ctx);
+                            cnt += 1;
+                        }
+                        sasl = Some((mech, cnt));
+                    }
+                    "SELECT" | "EXAMINE" => {
+                        mailbox = astring(args).map(|(mailbox, _)| mailbox);
+                    }
+                    _ => {}
+                }
+                let mut pending = pending.borrow_mut();
+                if !early {
+                    pending.insert(tag.to_string(), ImapReq { cmd, mailbox });
+                } else if let Some(req) = pending.get_mut(tag) {
+                    req.mailbox = mailbox;
+                }
+            }
+
+            if is_append {
+                Self::append_cmd(stm, line, &cb_imap, cb_ctx).await?;
+            }
+        }
+    }
 
-                // command = tag SP (command-any / command-auth / command-nonauth /
-                //           command-select) CRLF
-                let (byte, _seq) = stm.readn(2).await?;
-                if byte != b"\r\n" {
-                    return Err(());
+    // 读完命令中所有的literal，literal的内容转成quoted string拼到命令行中。
+    // 同步literal被server拒绝时命令到此为止
+    async fn cmd_literals(
+        stm: &mut PktStrm<T>,
+        mut line: String,
+        cb_imap: &Callbacks,
+        cb_ctx: *mut c_void,
+    ) -> Result<String, ()> {
+        let mut cmd = String::new();
+        while let Some((prefix, size, non_sync)) = literal(&line) {
+            if !non_sync && !literal_ok(stm).await? {
+                break;
+            }
+            cmd.push_str(prefix);
+
+            let mut value = Vec::new();
+            let mut remain = size;
+            while remain > 0 {
+                let (data, _seq) = stm.read(remain).await?;
+                remain -= data.len();
+                if value.len() < MAX_LITERAL_ARG {
+                    let len = data.len().min(MAX_LITERAL_ARG - value.len());
+                    value.extend_from_slice(&data[..len]);
+                }
+            }
+            cmd.push('"');
+            for c in String::from_utf8_lossy(&value).chars() {
+                if c == '"' || c == '\\' {
+                    cmd.push('\\');
                 }
+                cmd.push(c);
+            }
+            cmd.push('"');
+
+            // command = tag SP (command-any / command-auth / command-nonauth /
+            //           command-select) CRLF
+            let (next, seq) = stm.read_clean_line_str().await?;
+            if let Some(ref cb) = cb_imap.clt {
+                cb.borrow_mut()(next.as_bytes(), seq, cb_ctx);
+            }
+            line = next.to_string();
+        }
+        cmd.push_str(&line);
+        Ok(cmd)
+    }
+
+    // MULTIAPPEND的一条命令里可以有多个邮件
+    async fn append_cmd(
+        stm: &mut PktStrm<T>,
+        mut line: String,
+        cb_imap: &Callbacks,
+        cb_ctx: *mut c_void,
+    ) -> Result<(), ()> {
+        while let Some((_, mail_size, non_sync)) = literal(&line) {
+            if !non_sync && !literal_ok(stm).await? {
+                break;
+            }
+            Self::append_mail(stm, mail_size, cb_imap, cb_ctx).await?;
+
+            let (next, seq) = stm.read_clean_line_str().await?;
+            if !next.is_empty()
+                && let Some(ref cb) = cb_imap.clt
+            {
+                cb.borrow_mut()(next.as_bytes(), seq, cb_ctx);
             }
+            line = next.to_string();
         }
+        Ok(())
     }
 
     async fn s2c_parser_inner(
         strm: *mut PktStrm<T>,
         cb_imap: Callbacks,
+        cb_rsp: ImapRspCallbacks,
+        pending: ImapPending,
+        c2s_strm: Rc<Cell<*mut PktStrm<T>>>,
         cb_ctx: *mut c_void,
     ) -> Result<(), ()> {
         let stm = unsafe { &mut *strm };
         let mut bds_parser = None;
+        let mut session = ImapSession::new();
 
         loop {
             let (line, seq) = stm.readline_str().await?;
@@ -105,8 +470,67 @@ where
                 cb.borrow_mut()(line.as_bytes(), seq, cb_ctx);
             }
 
-            let fetch_ret = rsp_fetch(line).or_else(|| follow_rsp_fetch(line));
+            if let Some((tag, status, text)) = tagged_rsp(line) {
+                let req = pending.borrow_mut().remove(tag);
+                if let Some(req) = req {
+                    if let Some(ref cb) = cb_rsp.reply {
+                        cb.borrow_mut()(
+                            tag.as_bytes(),
+                            req.cmd.as_bytes(),
+                            status,
+                            text.as_bytes(),
+                            seq,
+                            cb_ctx,
+                        );
+                    }
+                    session.tagged(&req, status, text, &cb_rsp, cb_ctx);
+
+                    // RFC 4978: tagged OK之后两个方向都是deflate流
+                    if req.cmd == "COMPRESS" && status == ImapStatus::Ok {
+                        stm.set_inflate()?;
+                        let c2s = c2s_strm.get();
+                        if !c2s.is_null() {
+                            unsafe { &mut *c2s }.set_inflate()?;
+                        }
+                    }
+                }
+                continue;
+            }
+            if line.starts_with("* PREAUTH") {
+                session.set_state(ImapState::Auth, None, false, &cb_rsp, cb_ctx);
+                continue;
+            }
+            if line.starts_with("* BYE") {
+                session.set_state(ImapState::Logout, None, false, &cb_rsp, cb_ctx);
+                continue;
+            }
+
+            // rsp_fetch只认识body相关的属性，FETCH应答的开始要自己判断
+            if let Some(num) = fetch_num(line) {
+                session.fetch_num = num;
+                session.fetch_uid = fetch_uid(line);
+                session.fetch_buf = cb_rsp.fetch.as_ref().map(|_| String::new());
+            }
+            let fetch_ret = match rsp_fetch(line) {
+                Some(fetch_ret) => Some(fetch_ret),
+                None => {
+                    let fetch_ret = follow_rsp_fetch(line);
+                    if fetch_ret.is_some() && session.fetch_uid.is_none() {
+                        session.fetch_uid = fetch_uid(line);
+                    }
+                    fetch_ret
+                }
+            };
+            let body_literal = fetch_ret
+                .as_ref()
+                .is_some_and(|fetch_ret| fetch_ret.literal_size().is_some());
+            session.fetch_line(line, body_literal, &cb_rsp, cb_ctx);
+
             if let Some(fetch_ret) = fetch_ret {
+                if fetch_ret.data().is_some() || fetch_ret.literal_size().is_some() {
+                    Self::body_info(&session, &fetch_ret, &cb_rsp, cb_ctx);
+                }
+
                 if let Some(data) = fetch_ret.data() {
                     let tail_seq = seq + line.len() as u32;
                     Self::quoted_body(data, tail_seq - data.len() as u32, &cb_imap, cb_ctx)?;
@@ -140,12 +564,37 @@ where
                         _ => None,
                     });
 
-                    Self::handle_fetch_ret(stm, size, header, te, &cb_imap, cb_ctx).await?;
+                    let tail =
+                        Self::handle_fetch_ret(stm, size, header, te, &cb_imap, cb_ctx).await?;
+                    if let Some(tail) = tail
+                        && let Some(buf) = session.fetch_buf.as_mut()
+                    {
+                        buf.push(tail as char);
+                    }
                 }
             }
         }
     }
 
+    fn body_info(
+        session: &ImapSession,
+        fetch_ret: &FetchRet,
+        cb_rsp: &ImapRspCallbacks,
+        cb_ctx: *mut c_void,
+    ) {
+        if let Some(ref cb) = cb_rsp.body_info {
+            let mailbox = session.mailbox.as_ref().map(|m| m.as_bytes());
+            let section = fetch_section(fetch_ret.attrs.last());
+            cb.borrow_mut()(
+                mailbox,
+                session.fetch_num,
+                session.fetch_uid,
+                section.as_bytes(),
+                cb_ctx,
+            );
+        }
+    }
+
     async fn append_mail(
         stm: &mut PktStrm<T>,
         mail_size: usize,
@@ -195,6 +644,7 @@ where
                     cb_body_stop: cb.body_stop.as_ref(),
                     cb_ctx,
                     dir: cb.dir,
+                    dot_unstuff: false,
                 };
                 mime_body(stm, params).await?;
             }
@@ -263,9 +713,9 @@ where
         te: Option<TransferEncoding>,
         cb_imap: &Callbacks,
         cb_ctx: *mut c_void,
-    ) -> Result<(), ()> {
+    ) -> Result<Option<u8>, ()> {
         if size.is_none() {
-            return Ok(());
+            return Ok(None);
         }
         let literal_size = size.unwrap();
 
@@ -275,11 +725,9 @@ where
             Self::size_body(stm, literal_size, te, cb_imap, cb_ctx).await?;
         }
 
+        // literal后面紧跟的一个字节，通常是)
         let (byte, _seq) = stm.readn(1).await?;
-        if byte == b")" {
-            return Ok(());
-        }
-        Ok(())
+        Ok(Some(byte[0]))
     }
 
     async fn fetch_header(
@@ -357,10 +805,11 @@ where
                 }
             }
 
-            if let Ok(payload) = payload_c2s {
-                if payload.len() >= 5 && (payload.starts_with(b"* OK ")) {
-                    return Some(false);
-                }
+            if let Ok(payload) = payload_c2s
+                && payload.len() >= 5
+                && (payload.starts_with(b"* OK "))
+            {
+                return Some(false);
             }
 
             Some(true)
@@ -376,8 +825,21 @@ where
             clt: self.cb_clt.clone(),
             srv: None,
             dir: Direction::C2s,
+            dot_unstuff: false,
         };
-        Some(Box::pin(Self::c2s_parser_inner(stream, cb_imap, cb_ctx)))
+        let cb_cmd = ImapCmdCallbacks {
+            user: self.cb_user.clone(),
+            pass: self.cb_pass.clone(),
+            auth: self.cb_auth.clone(),
+        };
+        self.c2s_strm.set(stream);
+        Some(Box::pin(Self::c2s_parser_inner(
+            stream,
+            cb_imap,
+            cb_cmd,
+            self.pending.clone(),
+            cb_ctx,
+        )))
     }
 
     fn s2c_parser(&self, stream: *mut PktStrm<T>, cb_ctx: *mut c_void) -> Option<ParserFuture> {
@@ -389,8 +851,22 @@ where
             clt: None,
             srv: self.cb_srv.clone(),
             dir: Direction::S2c,
+            dot_unstuff: false,
+        };
+        let cb_rsp = ImapRspCallbacks {
+            reply: self.cb_reply.clone(),
+            state: self.cb_state.clone(),
+            body_info: self.cb_body_info.clone(),
+            fetch: self.cb_fetch.clone(),
         };
-        Some(Box::pin(Self::s2c_parser_inner(stream, cb_imap, cb_ctx)))
+        Some(Box::pin(Self::s2c_parser_inner(
+            stream,
+            cb_imap,
+            cb_rsp,
+            self.pending.clone(),
+            self.c2s_strm.clone(),
+            cb_ctx,
+        )))
     }
 }
 
@@ -416,41 +892,236 @@ where
         parser.cb_body_stop = prolens.cb_imap_body_stop.clone();
         parser.cb_clt = prolens.cb_imap_clt.clone();
         parser.cb_srv = prolens.cb_imap_srv.clone();
+        parser.cb_user = prolens.cb_imap_user.clone();
+        parser.cb_pass = prolens.cb_imap_pass.clone();
+        parser.cb_auth = prolens.cb_imap_auth.clone();
+        parser.cb_reply = prolens.cb_imap_reply.clone();
+        parser.cb_state = prolens.cb_imap_state.clone();
+        parser.cb_body_info = prolens.cb_imap_body_info.clone();
+        parser.cb_fetch = prolens.cb_imap_fetch.clone();
         parser
     }
 }
 
-fn append(input: &str) -> (bool, usize) {
-    fn command_id(input: &str) -> IResult<&str, &str> {
-        take_while1(|c: char| !c.is_whitespace())(input)
+fn sasl_resp(
+    mech: &str,
+    cnt: usize,
+    resp: &[u8],
+    seq: u32,
+    cb_cmd: &ImapCmdCallbacks,
+    cb_ctx: *mut c_void,
+) {
+    if let Some(ref cb) = cb_cmd.auth {
+        cb.borrow_mut()(mech.as_bytes(), resp, seq, cb_ctx);
     }
 
-    fn append_command(input: &str) -> IResult<&str, &str> {
-        tag("APPEND")(input)
+    let (user, pass) = sasl_cred(mech, cnt, resp);
+    if let Some(user) = user
+        && let Some(ref cb) = cb_cmd.user
+    {
+        cb.borrow_mut()(&user, seq, cb_ctx);
+    }
+    if let Some(pass) = pass
+        && let Some(ref cb) = cb_cmd.pass
+    {
+        cb.borrow_mut()(&pass, seq, cb_ctx);
     }
+}
 
-    fn size_info(input: &str) -> IResult<&str, usize> {
-        delimited(
-            char('{'),
-            map_res(digit1, |s: &str| s.parse::<usize>()),
-            char('}'),
-        )(input)
+// tag SP command [SP args]. UID命令合并成"UID FETCH"这样
+fn imap_cmd(line: &str) -> Option<(&str, String, &str)> {
+    let (tag, rest) = line.split_once(' ')?;
+    if tag.is_empty() || tag == "*" || tag == "+" {
+        return None;
+    }
+    let (cmd, args) = rest.split_once(' ').unwrap_or((rest, ""));
+    let mut cmd = cmd.to_ascii_uppercase();
+    let mut args = args;
+    if cmd == "UID" {
+        let (sub_cmd, sub_args) = args.split_once(' ').unwrap_or((args, ""));
+        cmd.push(' ');
+        cmd.push_str(&sub_cmd.to_ascii_uppercase());
+        args = sub_args;
     }
+    Some((tag, cmd, args))
+}
 
-    fn extract_size(input: &str) -> Option<usize> {
-        let trimmed = input.trim_end();
-        if !trimmed.ends_with('}') {
+// atom或者quoted string，返回值和剩余部分
+fn astring(input: &str) -> Option<(String, &str)> {
+    if let Some(quoted) = input.strip_prefix('"') {
+        let mut value = String::new();
+        let mut chars = quoted.char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '\\' => value.push(chars.next()?.1),
+                '"' => return Some((value, &quoted[i + 1..])),
+                _ => value.push(c),
+            }
+        }
+        None
+    } else {
+        let end = input.find([' ', '(', ')', '{', '"']).unwrap_or(input.len());
+        if end == 0 {
             return None;
         }
+        Some((input[..end].to_string(), &input[end..]))
+    }
+}
+
+// tag OK/NO/BAD text
+fn tagged_rsp(line: &str) -> Option<(&str, ImapStatus, &str)> {
+    let line = line.trim_end_matches("\r\n");
+    let (tag, rest) = line.split_once(' ')?;
+    if tag == "*" || tag == "+" {
+        return None;
+    }
+    let (status, text) = rest.split_once(' ').unwrap_or((rest, ""));
+    let status = if status.eq_ignore_ascii_case("OK") {
+        ImapStatus::Ok
+    } else if status.eq_ignore_ascii_case("NO") {
+        ImapStatus::No
+    } else if status.eq_ignore_ascii_case("BAD") {
+        ImapStatus::Bad
+    } else {
+        return None;
+    };
+    Some((tag, status, text))
+}
 
-        // 从右向左查找 '{'
-        if let Some(start_pos) = trimmed.rfind('{') {
-            let size_part = &trimmed[start_pos..];
-            if let Ok((_, size)) = size_info(size_part) {
-                return Some(size);
+// * n FETCH (...
+fn fetch_num(line: &str) -> Option<u32> {
+    let mut parts = line.strip_prefix("* ")?.splitn(3, ' ');
+    let num = parts.next()?.parse().ok()?;
+    let fetch = parts.next()?.trim_end_matches("\r\n");
+    fetch.eq_ignore_ascii_case("FETCH").then_some(num)
+}
+
+fn fetch_uid(line: &str) -> Option<u32> {
+    let bytes = line.as_bytes();
+    let mut start = 0;
+    while let Some(pos) = line[start..].find("UID ") {
+        let pos = start + pos;
+        if pos > 0 && (bytes[pos - 1] == b'(' || bytes[pos - 1] == b' ') {
+            let digits: &str = &line[pos + 4..];
+            let end = digits
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(digits.len());
+            if let Ok(uid) = digits[..end].parse() {
+                return Some(uid);
             }
         }
-        None
+        start = pos + 4;
+    }
+    None
+}
+
+// 行尾是{n}、{n+}(LITERAL+/LITERAL-)或者~{n}(literal8)时，返回前面的部分、literal长度和是否为非同步literal
+fn literal(line: &str) -> Option<(&str, usize, bool)> {
+    let trimmed = line.trim_end_matches("\r\n");
+    let size = trimmed.strip_suffix('}')?;
+    let start = size.rfind('{')?;
+    let (size, non_sync) = match size[start + 1..].strip_suffix('+') {
+        Some(size) => (size, true),
+        None => (&size[start + 1..], false),
+    };
+    if size.is_empty() || !size.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let size = size.parse().ok()?;
+    let prefix = &line[..start];
+    Some((prefix.strip_suffix('~').unwrap_or(prefix), size, non_sync))
+}
+
+fn imap_addrs<'a>(addrs: &'a Option<Vec<Address>>) -> Vec<ImapAddr<'a>> {
+    addrs
+        .iter()
+        .flatten()
+        .map(|addr| ImapAddr {
+            name: addr.name.as_deref(),
+            mailbox: addr.mailbox.as_deref(),
+            host: addr.host.as_deref(),
+        })
+        .collect()
+}
+
+fn imap_envelope<'a>(env: &'a Envelope) -> ImapEnvelope<'a> {
+    ImapEnvelope {
+        date: env.date.as_deref(),
+        subject: env.subject.as_deref(),
+        from: imap_addrs(&env.from),
+        sender: imap_addrs(&env.sender),
+        reply_to: imap_addrs(&env.reply_to),
+        to: imap_addrs(&env.to),
+        cc: imap_addrs(&env.cc),
+        bcc: imap_addrs(&env.bcc),
+        in_reply_to: env.in_reply_to.as_deref(),
+        message_id: env.message_id.as_deref(),
+    }
+}
+
+fn imap_fetch<'a>(num: u32, attrs: &'a [AttributeValue]) -> ImapFetch<'a> {
+    let mut fetch = ImapFetch {
+        num,
+        ..Default::default()
+    };
+    for attr in attrs {
+        match attr {
+            AttributeValue::Uid(uid) => fetch.uid = Some(*uid),
+            AttributeValue::Flags(flags) => {
+                fetch.flags = flags.iter().map(|flag| flag.as_ref()).collect()
+            }
+            AttributeValue::InternalDate(date) => fetch.internal_date = Some(date.as_ref()),
+            AttributeValue::Rfc822Size(size) => fetch.size = Some(*size),
+            AttributeValue::Envelope(env) => fetch.envelope = Some(imap_envelope(env)),
+            _ => {}
+        }
+    }
+    fetch
+}
+
+// BODY[1.2.HEADER] 中的section，RFC822.HEADER/RFC822.TEXT对应HEADER/TEXT
+fn fetch_section(attr: Option<&AttributeValue2>) -> String {
+    fn msg_section(section: &MessageSection) -> &'static str {
+        match section {
+            MessageSection::Header => "HEADER",
+            MessageSection::Mime => "MIME",
+            MessageSection::Text => "TEXT",
+        }
+    }
+
+    match attr {
+        Some(AttributeValue2::BodySection {
+            section: Some(SectionPath::Full(section)),
+            ..
+        }) => msg_section(section).to_string(),
+        Some(AttributeValue2::BodySection {
+            section: Some(SectionPath::Part(parts, section)),
+            ..
+        }) => {
+            let mut ret = parts
+                .iter()
+                .map(|part| part.to_string())
+                .collect::<Vec<_>>()
+                .join(".");
+            if let Some(section) = section {
+                ret.push('.');
+                ret.push_str(msg_section(section));
+            }
+            ret
+        }
+        Some(AttributeValue2::Rfc822Header(_)) => "HEADER".to_string(),
+        Some(AttributeValue2::Rfc822Text(_)) => "TEXT".to_string(),
+        _ => String::new(),
+    }
+}
+
+fn append(input: &str) -> (bool, usize) {
+    fn command_id(input: &str) -> IResult<&str, &str> {
+        take_while1(|c: char| !c.is_whitespace())(input)
+    }
+
+    fn append_command(input: &str) -> IResult<&str, &str> {
+        tag("APPEND")(input)
     }
 
     fn is_append_command(input: &str) -> bool {
@@ -459,7 +1130,7 @@ fn append(input: &str) -> (bool, usize) {
 
     let is_append = is_append_command(input);
     let size = if is_append {
-        extract_size(input).unwrap_or(0)
+        literal(input).map_or(0, |(_, size, _)| size)
     } else {
         0
     };
@@ -467,14 +1138,14 @@ fn append(input: &str) -> (bool, usize) {
     (is_append, size)
 }
 
-// server应答append有可能是出错。如果是这种情况，那么append的后续内容就不是邮件而是其他的命令
-// 判断是否是邮件头，如果是邮件头说明是邮件内容
-async fn append_ok<T>(stm: &mut PktStrm<T>) -> Result<bool, ()>
+// 同步literal要等server的+，server也可能应答NO/BAD。被拒绝时client不会发送literal，
+// 后面直接是下一个命令。后续内容不像命令时说明是literal
+async fn literal_ok<T>(stm: &mut PktStrm<T>) -> Result<bool, ()>
 where
     T: Packet,
 {
     let line = stm.peekline_str().await?;
-    Ok(line.contains(':'))
+    Ok(!clt_cmd(line))
 }
 
 fn clt_cmd(input: &str) -> bool {
@@ -543,11 +1214,627 @@ mod tests {
         assert_eq!(append("A02 SELECT INBOX"), (false, 0)); // 不是APPEND命令
         assert_eq!(append("tag1 APPEND mailbox (\\Seen)"), (true, 0)); // 没有大小信息
         assert_eq!(append("tag1 APPEND mailbox {abc}"), (true, 0)); // 大小格式错误
+        assert_eq!(append("A01 APPEND INBOX {310+}"), (true, 310)); // LITERAL+
+        assert_eq!(append("A01 APPEND INBOX ~{310}"), (true, 310)); // literal8
         assert_eq!(append("APPEND mailbox {100}"), (false, 0)); // 缺少命令ID
         assert_eq!(append("tag"), (false, 0)); // 不完整的命令
         assert_eq!(append(""), (false, 0)); // 空字符串
     }
 
+    #[test]
+    fn test_imap_cmd() {
+        assert_eq!(
+            imap_cmd("a1 login tim \"pa ss\""),
+            Some(("a1", "LOGIN".to_string(), "tim \"pa ss\""))
+        );
+        assert_eq!(
+            imap_cmd("a2 UID fetch 1:* (FLAGS)"),
+            Some(("a2", "UID FETCH".to_string(), "1:* (FLAGS)"))
+        );
+        assert_eq!(imap_cmd("a3 NOOP"), Some(("a3", "NOOP".to_string(), "")));
+        assert_eq!(imap_cmd("* OK ready"), None);
+        assert_eq!(imap_cmd("dGVzdA=="), None);
+
+        assert_eq!(astring("INBOX rest"), Some(("INBOX".to_string(), " rest")));
+        assert_eq!(
+            astring("\"a \\\"b\\\\\" c"),
+            Some(("a \"b\\".to_string(), " c"))
+        );
+        assert_eq!(astring("\"unterminated"), None);
+        assert_eq!(astring(""), None);
+
+        assert_eq!(
+            tagged_rsp("a1 OK [READ-ONLY] EXAMINE completed\r\n"),
+            Some(("a1", ImapStatus::Ok, "[READ-ONLY] EXAMINE completed"))
+        );
+        assert_eq!(
+            tagged_rsp("a2 no failed\r\n"),
+            Some(("a2", ImapStatus::No, "failed"))
+        );
+        assert_eq!(tagged_rsp("* OK ready\r\n"), None);
+        assert_eq!(tagged_rsp("a3 FETCH\r\n"), None);
+
+        assert_eq!(fetch_num("* 12 FETCH (UID 30 FLAGS ())"), Some(12));
+        assert_eq!(fetch_uid("* 12 FETCH (UID 30 FLAGS ())"), Some(30));
+        assert_eq!(fetch_uid("* 12 FETCH (FLAGS () UID 31)"), Some(31));
+        assert_eq!(fetch_uid("* 12 FETCH (XUID 5 FLAGS ())"), None);
+    }
+
+    #[test]
+    fn test_imap_session() {
+        let session = [
+            (false, "* OK IMAP4rev1 ready\r\n"),
+            (true, "a1 LOGIN tim \"bad pass\"\r\n"),
+            (false, "a1 NO LOGIN failed\r\n"),
+            (true, "a2 LOGIN tim secret\r\n"),
+            (false, "a2 OK LOGIN completed\r\n"),
+            (true, "a3 EXAMINE Archive\r\n"),
+            (false, "* 3 EXISTS\r\n"),
+            (false, "a3 OK [READ-ONLY] EXAMINE completed\r\n"),
+            (true, "a4 SELECT \"Sent Items\"\r\n"),
+            (false, "a4 OK [READ-WRITE] SELECT completed\r\n"),
+            (true, "a5 UID FETCH 40 (BODY.PEEK[1])\r\n"),
+            (false, "* 2 FETCH (UID 40 BODY[1] {5}\r\n"),
+            (false, "hello"),
+            (false, ")\r\n"),
+            (false, "a5 OK FETCH completed\r\n"),
+            (true, "a6 CLOSE\r\n"),
+            (false, "a6 OK CLOSE completed\r\n"),
+            (true, "a7 LOGOUT\r\n"),
+            (false, "* BYE logging out\r\n"),
+            (false, "a7 OK LOGOUT completed\r\n"),
+        ];
+
+        let users = Rc::new(RefCell::new(Vec::<Vec<u8>>::new()));
+        let passes = Rc::new(RefCell::new(Vec::<Vec<u8>>::new()));
+        let replies = Rc::new(RefCell::new(Vec::new()));
+        let states = Rc::new(RefCell::new(Vec::new()));
+        let infos = Rc::new(RefCell::new(Vec::new()));
+        let bodies = Rc::new(RefCell::new(Vec::<u8>::new()));
+
+        let mut protolens = Prolens::<CapPacket>::default();
+        let users_clone = users.clone();
+        protolens.set_cb_imap_user(move |user: &[u8], _seq: u32, _cb_ctx: *mut c_void| {
+            users_clone.borrow_mut().push(user.to_vec());
+        });
+        let passes_clone = passes.clone();
+        protolens.set_cb_imap_pass(move |pass: &[u8], _seq: u32, _cb_ctx: *mut c_void| {
+            passes_clone.borrow_mut().push(pass.to_vec());
+        });
+        let replies_clone = replies.clone();
+        protolens.set_cb_imap_reply(
+            move |tag: &[u8],
+                  cmd: &[u8],
+                  status: ImapStatus,
+                  _text: &[u8],
+                  _seq: u32,
+                  _cb_ctx: *mut c_void| {
+                replies_clone
+                    .borrow_mut()
+                    .push((tag.to_vec(), cmd.to_vec(), status));
+            },
+        );
+        let states_clone = states.clone();
+        protolens.set_cb_imap_state(
+            move |state: ImapState,
+                  mailbox: Option<&[u8]>,
+                  read_only: bool,
+                  _cb_ctx: *mut c_void| {
+                states_clone
+                    .borrow_mut()
+                    .push((state, mailbox.map(|m| m.to_vec()), read_only));
+            },
+        );
+        let infos_clone = infos.clone();
+        protolens.set_cb_imap_body_info(
+            move |mailbox: Option<&[u8]>,
+                  num: u32,
+                  uid: Option<u32>,
+                  section: &[u8],
+                  _cb_ctx: *mut c_void| {
+                infos_clone.borrow_mut().push((
+                    mailbox.map(|m| m.to_vec()),
+                    num,
+                    uid,
+                    section.to_vec(),
+                ));
+            },
+        );
+        let bodies_clone = bodies.clone();
+        protolens.set_cb_imap_body(
+            move |data: &[u8],
+                  _seq: u32,
+                  _cb_ctx: *mut c_void,
+                  _dir: Direction,
+                  _te: Option<TransferEncoding>| {
+                bodies_clone.borrow_mut().extend_from_slice(data);
+            },
+        );
+
+        run_session(&mut protolens, L7Proto::Imap, IMAP_PORT, &session, false);
+
+        assert_eq!(*users.borrow(), vec![b"tim".to_vec(), b"tim".to_vec()]);
+        assert_eq!(
+            *passes.borrow(),
+            vec![b"bad pass".to_vec(), b"secret".to_vec()]
+        );
+        assert_eq!(
+            *replies.borrow(),
+            vec![
+                (b"a1".to_vec(), b"LOGIN".to_vec(), ImapStatus::No),
+                (b"a2".to_vec(), b"LOGIN".to_vec(), ImapStatus::Ok),
+                (b"a3".to_vec(), b"EXAMINE".to_vec(), ImapStatus::Ok),
+                (b"a4".to_vec(), b"SELECT".to_vec(), ImapStatus::Ok),
+                (b"a5".to_vec(), b"UID FETCH".to_vec(), ImapStatus::Ok),
+                (b"a6".to_vec(), b"CLOSE".to_vec(), ImapStatus::Ok),
+                (b"a7".to_vec(), b"LOGOUT".to_vec(), ImapStatus::Ok),
+            ]
+        );
+        assert_eq!(
+            *states.borrow(),
+            vec![
+                (ImapState::Auth, None, false),
+                (ImapState::Selected, Some(b"Archive".to_vec()), true),
+                (ImapState::Selected, Some(b"Sent Items".to_vec()), false),
+                (ImapState::Auth, None, false),
+                (ImapState::Logout, None, false),
+            ]
+        );
+        assert_eq!(
+            *infos.borrow(),
+            vec![(Some(b"Sent Items".to_vec()), 2, Some(40), b"1".to_vec())]
+        );
+        assert_eq!(*bodies.borrow(), b"hello".to_vec());
+    }
+
+    #[test]
+    fn test_literal() {
+        assert_eq!(
+            literal("* 1 FETCH (BODY[] {12}\r\n"),
+            Some(("* 1 FETCH (BODY[] ", 12, false))
+        );
+        assert_eq!(literal("a1 LOGIN {3+}"), Some(("a1 LOGIN ", 3, true)));
+        assert_eq!(
+            literal("a1 APPEND INBOX ~{10}"),
+            Some(("a1 APPEND INBOX ", 10, false))
+        );
+        assert_eq!(literal("* 1 FETCH (FLAGS ())\r\n"), None);
+        assert_eq!(literal("* 1 FETCH (X {}\r\n"), None);
+        assert_eq!(literal("a1 LOGIN {+}"), None);
+        assert_eq!(literal("* 1 FETCH (X {1a}\r\n"), None);
+    }
+
+    #[test]
+    fn test_imap_c2s_literal() {
+        let session = [
+            (false, "* OK ready\r\n"),
+            (true, "a1 LOGIN {3}\r\n"),
+            (false, "+ Ready\r\n"),
+            (true, "tim {6}\r\n"),
+            (false, "+ Ready\r\n"),
+            (true, "se\"c t\r\n"),
+            (false, "a1 NO failed\r\n"),
+            (true, "a2 LOGIN {3+}\r\ntim {6+}\r\nsecret\r\n"),
+            (false, "a2 OK done\r\n"),
+            (true, "a3 SELECT {5+}\r\nINBOX\r\n"),
+            (false, "a3 OK done\r\n"),
+            (
+                true,
+                "a4 SEARCH CHARSET UTF-8 SUBJECT {9+}\r\n\r\nline2\r\n\r\n",
+            ),
+            (false, "a4 OK done\r\n"),
+            (true, "a5 APPEND INBOX {38+}\r\n"),
+            (true, "From: a@example.com\r\n\r\nfirst mail.\r\n\r\n"),
+            (true, " {39+}\r\n"),
+            (true, "From: b@example.com\r\n\r\nsecond mail.\r\n\r\n\r\n"),
+            (false, "a5 OK done\r\n"),
+            (true, "a6 APPEND INBOX {40}\r\n"),
+            (false, "a6 NO too big\r\n"),
+            (true, "a7 NOOP\r\n"),
+            (false, "a7 OK done\r\n"),
+        ];
+
+        let users = Rc::new(RefCell::new(Vec::<Vec<u8>>::new()));
+        let passes = Rc::new(RefCell::new(Vec::<Vec<u8>>::new()));
+        let replies = Rc::new(RefCell::new(Vec::new()));
+        let states = Rc::new(RefCell::new(Vec::new()));
+        let bodies = Rc::new(RefCell::new(Vec::<u8>::new()));
+
+        let mut protolens = Prolens::<CapPacket>::default();
+        let users_clone = users.clone();
+        protolens.set_cb_imap_user(move |user: &[u8], _seq: u32, _cb_ctx: *mut c_void| {
+            users_clone.borrow_mut().push(user.to_vec());
+        });
+        let passes_clone = passes.clone();
+        protolens.set_cb_imap_pass(move |pass: &[u8], _seq: u32, _cb_ctx: *mut c_void| {
+            passes_clone.borrow_mut().push(pass.to_vec());
+        });
+        let replies_clone = replies.clone();
+        protolens.set_cb_imap_reply(
+            move |tag: &[u8],
+                  cmd: &[u8],
+                  _status: ImapStatus,
+                  _text: &[u8],
+                  _seq: u32,
+                  _cb_ctx: *mut c_void| {
+                replies_clone
+                    .borrow_mut()
+                    .push((tag.to_vec(), cmd.to_vec()));
+            },
+        );
+        let states_clone = states.clone();
+        protolens.set_cb_imap_state(
+            move |state: ImapState,
+                  mailbox: Option<&[u8]>,
+                  _read_only: bool,
+                  _cb_ctx: *mut c_void| {
+                states_clone
+                    .borrow_mut()
+                    .push((state, mailbox.map(|m| m.to_vec())));
+            },
+        );
+        let bodies_clone = bodies.clone();
+        protolens.set_cb_imap_body(
+            move |data: &[u8],
+                  _seq: u32,
+                  _cb_ctx: *mut c_void,
+                  _dir: Direction,
+                  _te: Option<TransferEncoding>| {
+                bodies_clone.borrow_mut().extend_from_slice(data);
+            },
+        );
+
+        run_session(&mut protolens, L7Proto::Imap, IMAP_PORT, &session, false);
+
+        assert_eq!(*users.borrow(), vec![b"tim".to_vec(), b"tim".to_vec()]);
+        assert_eq!(
+            *passes.borrow(),
+            vec![b"se\"c t".to_vec(), b"secret".to_vec()]
+        );
+        assert_eq!(
+            *states.borrow(),
+            vec![
+                (ImapState::Auth, None),
+                (ImapState::Selected, Some(b"INBOX".to_vec())),
+            ]
+        );
+        let tags: Vec<_> = replies
+            .borrow()
+            .iter()
+            .map(|(tag, _)| tag.clone())
+            .collect();
+        assert_eq!(
+            tags,
+            vec![
+                b"a1".to_vec(),
+                b"a2".to_vec(),
+                b"a3".to_vec(),
+                b"a4".to_vec(),
+                b"a5".to_vec(),
+                b"a6".to_vec(),
+                b"a7".to_vec(),
+            ]
+        );
+        let bodies = bodies.borrow();
+        let bodies = std::str::from_utf8(&bodies).unwrap();
+        assert!(bodies.contains("first mail."));
+        assert!(bodies.contains("second mail."));
+    }
+
+    #[test]
+    fn test_imap_literal_rejected() {
+        let session = [
+            (false, "* OK ready\r\n"),
+            (true, "b1 LOGIN {300}\r\n"),
+            (false, "b1 BAD literal too long\r\n"),
+            (true, "b2 LOGIN tim secret\r\n"),
+            (false, "b2 OK done\r\n"),
+        ];
+
+        let creds = Rc::new(RefCell::new(Vec::<Vec<u8>>::new()));
+        let replies = Rc::new(RefCell::new(Vec::new()));
+
+        let mut protolens = Prolens::<CapPacket>::default();
+        let creds_clone = creds.clone();
+        protolens.set_cb_imap_user(move |user: &[u8], _seq: u32, _cb_ctx: *mut c_void| {
+            creds_clone.borrow_mut().push(user.to_vec());
+        });
+        let creds_clone = creds.clone();
+        protolens.set_cb_imap_pass(move |pass: &[u8], _seq: u32, _cb_ctx: *mut c_void| {
+            creds_clone.borrow_mut().push(pass.to_vec());
+        });
+        let replies_clone = replies.clone();
+        protolens.set_cb_imap_reply(
+            move |tag: &[u8],
+                  cmd: &[u8],
+                  status: ImapStatus,
+                  _text: &[u8],
+                  _seq: u32,
+                  _cb_ctx: *mut c_void| {
+                replies_clone
+                    .borrow_mut()
+                    .push((tag.to_vec(), cmd.to_vec(), status));
+            },
+        );
+
+        run_session(&mut protolens, L7Proto::Imap, IMAP_PORT, &session, false);
+
+        // 被拒绝的literal没有发送，后面的命令照常解析
+        assert_eq!(*creds.borrow(), vec![b"tim".to_vec(), b"secret".to_vec()]);
+        assert_eq!(
+            *replies.borrow(),
+            vec![
+                (b"b1".to_vec(), b"LOGIN".to_vec(), ImapStatus::Bad),
+                (b"b2".to_vec(), b"LOGIN".to_vec(), ImapStatus::Ok),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_imap_fetch() {
+        let session = [
+            (false, "* OK ready\r\n"),
+            (true, "a1 SELECT INBOX\r\n"),
+            (false, "a1 OK SELECT completed\r\n"),
+            (
+                true,
+                "a2 FETCH 1:3 (UID FLAGS INTERNALDATE RFC822.SIZE ENVELOPE)\r\n",
+            ),
+            (
+                false,
+                "* 1 FETCH (UID 7 FLAGS (\\Seen \\Answered) INTERNALDATE \"17-Jul-1996 02:44:25 -0700\" \
+                 RFC822.SIZE 4286 ENVELOPE (\"Wed, 17 Jul 1996 02:23:25 -0700\" \"meeting\" \
+                 ((\"Terry Gray\" NIL \"gray\" \"example.com\")) NIL NIL \
+                 ((NIL NIL \"imap\" \"example.com\") (\"Bob\" NIL \"bob\" \"example.org\")) \
+                 NIL NIL NIL \"<id1@example.com>\"))\r\n",
+            ),
+            (false, "* 2 FETCH (UID 8 ENVELOPE (NIL {5}\r\n"),
+            (
+                false,
+                "hello NIL NIL NIL NIL NIL NIL NIL NIL) FLAGS ())\r\n",
+            ),
+            (false, "a2 OK FETCH completed\r\n"),
+            (true, "a3 FETCH 3 (BODY[TEXT] FLAGS)\r\n"),
+            (false, "* 3 FETCH (BODY[TEXT] {4}\r\n"),
+            (false, "body"),
+            (false, " FLAGS (\\Deleted))\r\n"),
+            (false, "a3 OK FETCH completed\r\n"),
+        ];
+
+        let fetches = Rc::new(RefCell::new(Vec::new()));
+        let mut protolens = Prolens::<CapPacket>::default();
+        let fetches_clone = fetches.clone();
+        protolens.set_cb_imap_fetch(move |fetch: &ImapFetch, _cb_ctx: *mut c_void| {
+            let env = fetch.envelope.as_ref().map(|env| {
+                (
+                    env.subject.map(|s| s.to_vec()),
+                    env.from
+                        .iter()
+                        .map(|addr| addr.mailbox.unwrap_or_default().to_vec())
+                        .collect::<Vec<_>>(),
+                    env.to
+                        .iter()
+                        .map(|addr| (addr.name.map(|n| n.to_vec()), addr.host.map(|h| h.to_vec())))
+                        .collect::<Vec<_>>(),
+                    env.message_id.map(|m| m.to_vec()),
+                )
+            });
+            fetches_clone.borrow_mut().push((
+                fetch.mailbox.map(|m| m.to_vec()),
+                fetch.num,
+                fetch.uid,
+                fetch
+                    .flags
+                    .iter()
+                    .map(|f| f.to_string())
+                    .collect::<Vec<_>>(),
+                fetch.internal_date.map(|d| d.to_string()),
+                fetch.size,
+                env,
+            ));
+        });
+
+        run_session(&mut protolens, L7Proto::Imap, IMAP_PORT, &session, false);
+
+        let fetches = fetches.borrow();
+        assert_eq!(fetches.len(), 3);
+        assert_eq!(
+            fetches[0],
+            (
+                Some(b"INBOX".to_vec()),
+                1,
+                Some(7),
+                vec!["\\Seen".to_string(), "\\Answered".to_string()],
+                Some("17-Jul-1996 02:44:25 -0700".to_string()),
+                Some(4286),
+                Some((
+                    Some(b"meeting".to_vec()),
+                    vec![b"gray".to_vec()],
+                    vec![
+                        (None, Some(b"example.com".to_vec())),
+                        (Some(b"Bob".to_vec()), Some(b"example.org".to_vec())),
+                    ],
+                    Some(b"<id1@example.com>".to_vec()),
+                ))
+            )
+        );
+        assert_eq!(fetches[1].1, 2);
+        assert_eq!(fetches[1].2, Some(8));
+        assert_eq!(
+            fetches[1].6,
+            Some((Some(b"hello".to_vec()), vec![], vec![], None))
+        );
+        assert_eq!(
+            fetches[2],
+            (
+                Some(b"INBOX".to_vec()),
+                3,
+                None,
+                vec!["\\Deleted".to_string()],
+                None,
+                None,
+                None
+            )
+        );
+    }
+
+    #[test]
+    fn test_imap_compress() {
+        // 各段都是Z_SYNC_FLUSH结尾的raw deflate
+        let s2c_1: &[u8] = &[
+            0xd2, 0x52, 0x70, 0x76, 0x0c, 0x70, 0x74, 0xf2, 0xf4, 0xf1, 0x0c, 0x89, 0x54, 0xf0,
+            0xf4, 0x75, 0x0c, 0x30, 0x29, 0x4a, 0x2d, 0x33, 0xe4, 0xe5, 0x02, 0x00, 0x00, 0x00,
+            0xff, 0xff,
+        ];
+        let c2s_1: &[u8] = &[
+            0x4a, 0x34, 0x52, 0xf0, 0xf1, 0x77, 0xf7, 0xf4, 0x53, 0x28, 0xc9, 0xcc, 0x55, 0x28,
+            0x4e, 0x4d, 0x2e, 0x4a, 0x2d, 0xe1, 0xe5, 0x02, 0x00, 0x00, 0x00, 0xff, 0xff,
+        ];
+        let s2c_2: &[u8] = &[
+            0x4a, 0x34, 0x52, 0xf0, 0xf7, 0x56, 0xf0, 0xf1, 0x77, 0xf7, 0xf4, 0x53, 0x48, 0xce,
+            0xcf, 0x2d, 0xc8, 0x49, 0x2d, 0x49, 0x4d, 0xe1, 0xe5, 0x02, 0x00, 0x00, 0x00, 0xff,
+            0xff,
+        ];
+        let c2s_2: &[u8] = &[
+            0x4a, 0x34, 0x56, 0x70, 0x73, 0x0d, 0x71, 0xf6, 0x50, 0x30, 0x54, 0xd0, 0x70, 0xf2,
+            0x77, 0x89, 0x8c, 0x0e, 0x71, 0x8d, 0x08, 0x89, 0xd5, 0xe4, 0xe5, 0x02, 0x00, 0x00,
+            0x00, 0xff, 0xff,
+        ];
+        let s2c_3: &[u8] = &[
+            0xd2, 0x52, 0x30, 0x54, 0x70, 0x73, 0x0d, 0x71, 0xf6, 0x50, 0xd0, 0x70, 0xf2, 0x77,
+            0x89, 0x8c, 0x0e, 0x71, 0x8d, 0x08, 0x89, 0x55, 0xa8, 0x36, 0x34, 0xac, 0xe5, 0xe5,
+            0xca, 0x48, 0xcd, 0xc9, 0xc9, 0x57, 0x28, 0xcf, 0x2f, 0xca, 0x49, 0xd1, 0x04, 0x1a,
+            0x60, 0x0c, 0x32, 0x00, 0xa2, 0x16, 0xc9, 0x00, 0x00, 0x00, 0x00, 0x00, 0xff, 0xff,
+        ];
+        // tagged OK和第一段压缩数据在同一个包里
+        let mut s2c_ok = b"a1 OK DEFLATE active\r\n".to_vec();
+        s2c_ok.extend_from_slice(s2c_1);
+
+        let session: [(bool, &[u8]); 8] = [
+            (false, b"* OK ready\r\n"),
+            (true, b"a1 COMPRESS DEFLATE\r\n"),
+            (false, &s2c_ok),
+            (true, &c2s_1[..10]),
+            (true, &c2s_1[10..]),
+            (false, s2c_2),
+            (true, c2s_2),
+            (false, s2c_3),
+        ];
+
+        let users = Rc::new(RefCell::new(Vec::<Vec<u8>>::new()));
+        let srv = Rc::new(RefCell::new(Vec::<Vec<u8>>::new()));
+        let replies = Rc::new(RefCell::new(Vec::new()));
+        let bodies = Rc::new(RefCell::new(Vec::<u8>::new()));
+
+        let mut protolens = Prolens::<CapPacket>::default();
+        let users_clone = users.clone();
+        protolens.set_cb_imap_user(move |user: &[u8], _seq: u32, _cb_ctx: *mut c_void| {
+            users_clone.borrow_mut().push(user.to_vec());
+        });
+        let srv_clone = srv.clone();
+        protolens.set_cb_imap_srv(move |line: &[u8], _seq: u32, _cb_ctx: *mut c_void| {
+            srv_clone.borrow_mut().push(line.to_vec());
+        });
+        let replies_clone = replies.clone();
+        protolens.set_cb_imap_reply(
+            move |_tag: &[u8],
+                  cmd: &[u8],
+                  status: ImapStatus,
+                  _text: &[u8],
+                  _seq: u32,
+                  _cb_ctx: *mut c_void| {
+                replies_clone.borrow_mut().push((cmd.to_vec(), status));
+            },
+        );
+        let bodies_clone = bodies.clone();
+        protolens.set_cb_imap_body(
+            move |data: &[u8],
+                  _seq: u32,
+                  _cb_ctx: *mut c_void,
+                  _dir: Direction,
+                  _te: Option<TransferEncoding>| {
+                bodies_clone.borrow_mut().extend_from_slice(data);
+            },
+        );
+
+        run_session(&mut protolens, L7Proto::Imap, IMAP_PORT, &session, false);
+
+        assert_eq!(*users.borrow(), vec![b"tim".to_vec()]);
+        assert_eq!(srv.borrow()[2], b"* CAPABILITY IMAP4rev1\r\n".to_vec());
+        assert_eq!(
+            *replies.borrow(),
+            vec![
+                (b"COMPRESS".to_vec(), ImapStatus::Ok),
+                (b"LOGIN".to_vec(), ImapStatus::Ok),
+                (b"FETCH".to_vec(), ImapStatus::Ok),
+            ]
+        );
+        assert_eq!(*bodies.borrow(), b"hello world".to_vec());
+    }
+
+    #[test]
+    fn test_imap_authenticate() {
+        // PLAIN走sasl交互，XOAUTH2走SASL-IR
+        let session = [
+            (false, "* OK ready\r\n"),
+            (true, "a1 AUTHENTICATE PLAIN\r\n"),
+            (false, "+ \r\n"),
+            (true, "AHRpbQBzZWNyZXQ=\r\n"),
+            (false, "a1 NO authentication failed\r\n"),
+            (
+                true,
+                "a2 AUTHENTICATE XOAUTH2 dXNlcj10aW1AZXhhbXBsZS5jb20BYXV0aD1CZWFyZXIgdG9rZW4xMjMBAQ==\r\n",
+            ),
+            (false, "a2 OK authenticated\r\n"),
+        ];
+
+        let creds = Rc::new(RefCell::new(Vec::<Vec<u8>>::new()));
+        let mechs = Rc::new(RefCell::new(Vec::<Vec<u8>>::new()));
+        let states = Rc::new(RefCell::new(Vec::new()));
+
+        let mut protolens = Prolens::<CapPacket>::default();
+        let creds_clone = creds.clone();
+        protolens.set_cb_imap_user(move |user: &[u8], _seq: u32, _cb_ctx: *mut c_void| {
+            creds_clone.borrow_mut().push(user.to_vec());
+        });
+        let creds_clone = creds.clone();
+        protolens.set_cb_imap_pass(move |pass: &[u8], _seq: u32, _cb_ctx: *mut c_void| {
+            creds_clone.borrow_mut().push(pass.to_vec());
+        });
+        let mechs_clone = mechs.clone();
+        protolens.set_cb_imap_auth(
+            move |mech: &[u8], _resp: &[u8], _seq: u32, _cb_ctx: *mut c_void| {
+                mechs_clone.borrow_mut().push(mech.to_vec());
+            },
+        );
+        let states_clone = states.clone();
+        protolens.set_cb_imap_state(
+            move |state: ImapState,
+                  _mailbox: Option<&[u8]>,
+                  _read_only: bool,
+                  _cb_ctx: *mut c_void| {
+                states_clone.borrow_mut().push(state);
+            },
+        );
+
+        run_session(&mut protolens, L7Proto::Imap, IMAP_PORT, &session, false);
+
+        assert_eq!(
+            *mechs.borrow(),
+            vec![b"PLAIN".to_vec(), b"XOAUTH2".to_vec()]
+        );
+        assert_eq!(
+            *creds.borrow(),
+            vec![
+                b"tim".to_vec(),
+                b"secret".to_vec(),
+                b"tim@example.com".to_vec(),
+                b"token123".to_vec(),
+            ]
+        );
+        assert_eq!(*states.borrow(), vec![ImapState::Auth]);
+    }
+
     #[test]
     fn test_imap_s2c() {
         let lines = [
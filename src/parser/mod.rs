@@ -1,12 +1,23 @@
+pub mod dnstcp;
 pub mod dnsudp;
 pub mod ftpcmd;
 pub mod ftpdata;
+pub mod ftplist;
+pub mod ftpsess;
+pub mod grpc;
 pub mod http;
+pub mod http2;
+pub mod httpauth;
 pub mod imap;
 pub mod ordpacket;
 pub mod pop3;
+pub mod rtp;
+pub mod sdp;
 pub mod sip;
+pub mod sipdialog;
 pub mod smtp;
+pub mod tls;
+pub mod websocket;
 
 #[cfg(test)]
 pub mod byte;
@@ -23,15 +34,42 @@ pub mod readline;
 #[cfg(test)]
 pub mod readn;
 
+use crate::DecodeError;
 use crate::Direction;
+use crate::EsmtpParam;
+use crate::FtpEntry;
+use crate::FtpFile;
+use crate::FtpLogin;
+use crate::FtpTransfer;
+use crate::FtpXfer;
+use crate::GrpcCall;
+use crate::GrpcMsg;
+use crate::GrpcStatus;
 use crate::Header;
+use crate::HttpAuth;
+use crate::HttpPart;
+use crate::HttpRequest;
+use crate::HttpResponse;
+use crate::ImapFetch;
+use crate::ImapState;
+use crate::ImapStatus;
 use crate::OptRR;
 use crate::Packet;
 use crate::PktStrm;
+use crate::Pop3Cmd;
 use crate::Prolens;
 use crate::Qtype;
 use crate::RR;
 use crate::ReadRet;
+use crate::RtcpReport;
+use crate::RtpHeader;
+use crate::RtpStats;
+use crate::Sdp;
+use crate::SipCall;
+use crate::SipMediaLink;
+use crate::TlsHello;
+use crate::TunnelKind;
+use crate::WsFrame;
 use dnsudp::Qclass;
 use futures::Future;
 use memchr::memmem::Finder;
@@ -42,6 +80,7 @@ use nom::{
     combinator::{map_res, value},
     sequence::{preceded, terminated},
 };
+use std::borrow::Cow;
 use std::cell::RefCell;
 use std::ffi::c_void;
 use std::net::IpAddr;
@@ -131,12 +170,109 @@ impl<F: FnMut(&[u8], u32, *mut c_void)> DataCbFn for F {}
 pub trait DataCbDirFn: FnMut(&[u8], u32, *mut c_void, Direction) {}
 impl<F: FnMut(&[u8], u32, *mut c_void, Direction)> DataCbDirFn for F {}
 
+pub trait SmtpAddrCbFn: FnMut(&[u8], &[EsmtpParam], u32, *mut c_void) {}
+impl<F: FnMut(&[u8], &[EsmtpParam], u32, *mut c_void)> SmtpAddrCbFn for F {}
+
+pub trait Pop3ApopCbFn: FnMut(&[u8], &[u8], u32, *mut c_void) {}
+impl<F: FnMut(&[u8], &[u8], u32, *mut c_void)> Pop3ApopCbFn for F {}
+
+pub trait Pop3AuthCbFn: FnMut(&[u8], &[u8], u32, *mut c_void) {}
+impl<F: FnMut(&[u8], &[u8], u32, *mut c_void)> Pop3AuthCbFn for F {}
+
+pub trait Pop3ReplyCbFn: FnMut(Pop3Cmd, &[u8], bool, &[u8], u32, *mut c_void) {}
+impl<F: FnMut(Pop3Cmd, &[u8], bool, &[u8], u32, *mut c_void)> Pop3ReplyCbFn for F {}
+
+pub trait Pop3StatCbFn: FnMut(u32, u64, *mut c_void) {}
+impl<F: FnMut(u32, u64, *mut c_void)> Pop3StatCbFn for F {}
+
+pub trait Pop3ListCbFn: FnMut(u32, u64, *mut c_void) {}
+impl<F: FnMut(u32, u64, *mut c_void)> Pop3ListCbFn for F {}
+
+pub trait Pop3UidlCbFn: FnMut(u32, &[u8], *mut c_void) {}
+impl<F: FnMut(u32, &[u8], *mut c_void)> Pop3UidlCbFn for F {}
+
+pub trait Pop3MsgCbFn: FnMut(u32, Option<&[u8]>, bool, *mut c_void) {}
+impl<F: FnMut(u32, Option<&[u8]>, bool, *mut c_void)> Pop3MsgCbFn for F {}
+
+pub trait ImapAuthCbFn: FnMut(&[u8], &[u8], u32, *mut c_void) {}
+impl<F: FnMut(&[u8], &[u8], u32, *mut c_void)> ImapAuthCbFn for F {}
+
+pub trait ImapReplyCbFn: FnMut(&[u8], &[u8], ImapStatus, &[u8], u32, *mut c_void) {}
+impl<F: FnMut(&[u8], &[u8], ImapStatus, &[u8], u32, *mut c_void)> ImapReplyCbFn for F {}
+
+pub trait ImapStateCbFn: FnMut(ImapState, Option<&[u8]>, bool, *mut c_void) {}
+impl<F: FnMut(ImapState, Option<&[u8]>, bool, *mut c_void)> ImapStateCbFn for F {}
+
+pub trait ImapBodyInfoCbFn: FnMut(Option<&[u8]>, u32, Option<u32>, &[u8], *mut c_void) {}
+impl<F: FnMut(Option<&[u8]>, u32, Option<u32>, &[u8], *mut c_void)> ImapBodyInfoCbFn for F {}
+
+pub trait ImapFetchCbFn: FnMut(&ImapFetch, *mut c_void) {}
+impl<F: FnMut(&ImapFetch, *mut c_void)> ImapFetchCbFn for F {}
+
 pub trait EvtCbFn: FnMut(*mut c_void, Direction) {}
 impl<F: FnMut(*mut c_void, Direction)> EvtCbFn for F {}
 
 pub trait BodyCbFn: FnMut(&[u8], u32, *mut c_void, Direction, Option<TransferEncoding>) {}
 impl<F: FnMut(&[u8], u32, *mut c_void, Direction, Option<TransferEncoding>)> BodyCbFn for F {}
 
+pub trait HttpTransCbFn: FnMut(u64, *mut c_void, Direction) {}
+impl<F: FnMut(u64, *mut c_void, Direction)> HttpTransCbFn for F {}
+
+pub trait HttpRequestCbFn: FnMut(&HttpRequest, *mut c_void) {}
+impl<F: FnMut(&HttpRequest, *mut c_void)> HttpRequestCbFn for F {}
+
+pub trait HttpResponseCbFn: FnMut(&HttpResponse, *mut c_void) {}
+impl<F: FnMut(&HttpResponse, *mut c_void)> HttpResponseCbFn for F {}
+
+pub trait HttpPartCbFn: FnMut(&HttpPart, *mut c_void, Direction) {}
+impl<F: FnMut(&HttpPart, *mut c_void, Direction)> HttpPartCbFn for F {}
+
+// 事务id, key, value。已经做过url解码
+pub trait HttpFormCbFn: FnMut(u64, &[u8], &[u8], *mut c_void, Direction) {}
+impl<F: FnMut(u64, &[u8], &[u8], *mut c_void, Direction)> HttpFormCbFn for F {}
+
+// 事务id, 类型, CONNECT的host:port或者Upgrade请求的路径
+pub trait HttpTunnelCbFn: FnMut(u64, TunnelKind, &[u8], *mut c_void) {}
+impl<F: FnMut(u64, TunnelKind, &[u8], *mut c_void)> HttpTunnelCbFn for F {}
+
+pub trait WsFrameCbFn: FnMut(&WsFrame, *mut c_void, Direction) {}
+impl<F: FnMut(&WsFrame, *mut c_void, Direction)> WsFrameCbFn for F {}
+
+pub trait TlsHelloCbFn: FnMut(&TlsHello, *mut c_void, Direction) {}
+impl<F: FnMut(&TlsHello, *mut c_void, Direction)> TlsHelloCbFn for F {}
+
+// 请求中的凭证方向是C2s，应答中的NTLM challenge方向是S2c
+pub trait HttpAuthCbFn: FnMut(&HttpAuth, *mut c_void, Direction) {}
+impl<F: FnMut(&HttpAuth, *mut c_void, Direction)> HttpAuthCbFn for F {}
+
+// 请求id, proxy, 最终应答的状态码。请求中的每个凭证通知一次，方向是S2c
+pub trait HttpAuthResultCbFn: FnMut(u64, bool, u16, *mut c_void, Direction) {}
+impl<F: FnMut(u64, bool, u16, *mut c_void, Direction)> HttpAuthResultCbFn for F {}
+
+// stream id, 起始行或者一行头。和http/1.1一样以\r\n结尾，头最后是一个空行
+pub trait Http2LineCbFn: FnMut(u32, &[u8], *mut c_void, Direction) {}
+impl<F: FnMut(u32, &[u8], *mut c_void, Direction)> Http2LineCbFn for F {}
+
+pub trait Http2BodyEvtCbFn: FnMut(u32, *mut c_void, Direction) {}
+impl<F: FnMut(u32, *mut c_void, Direction)> Http2BodyEvtCbFn for F {}
+
+// stream id, data, ctx, dir, content-encoding
+pub trait Http2BodyCbFn: FnMut(u32, &[u8], *mut c_void, Direction, &Option<Vec<Encoding>>) {}
+impl<F: FnMut(u32, &[u8], *mut c_void, Direction, &Option<Vec<Encoding>>)> Http2BodyCbFn for F {}
+
+// grpc_call只在c2s，grpc_status通常在s2c的trailer
+pub trait GrpcCallCbFn: FnMut(&GrpcCall, *mut c_void, Direction) {}
+impl<F: FnMut(&GrpcCall, *mut c_void, Direction)> GrpcCallCbFn for F {}
+
+pub trait GrpcMsgCbFn: FnMut(&GrpcMsg, *mut c_void, Direction) {}
+impl<F: FnMut(&GrpcMsg, *mut c_void, Direction)> GrpcMsgCbFn for F {}
+
+pub trait GrpcStatusCbFn: FnMut(&GrpcStatus, *mut c_void, Direction) {}
+impl<F: FnMut(&GrpcStatus, *mut c_void, Direction)> GrpcStatusCbFn for F {}
+
+pub trait HttpDecodeErrCbFn: FnMut(DecodeError, *mut c_void, Direction) {}
+impl<F: FnMut(DecodeError, *mut c_void, Direction)> HttpDecodeErrCbFn for F {}
+
 pub trait HttpBodyCbFn:
     FnMut(
     &[u8],
@@ -164,9 +300,51 @@ impl<
 pub trait FtpLinkCbFn: FnMut(Option<IpAddr>, u16, *mut c_void, Direction) {}
 impl<F: FnMut(Option<IpAddr>, u16, *mut c_void, Direction)> FtpLinkCbFn for F {}
 
+pub trait FtpXferEvtCbFn: FnMut(&FtpXfer, *mut c_void, Direction) {}
+impl<F: FnMut(&FtpXfer, *mut c_void, Direction)> FtpXferEvtCbFn for F {}
+
+pub trait FtpXferBodyCbFn: FnMut(&FtpXfer, &[u8], u32, *mut c_void, Direction) {}
+impl<F: FnMut(&FtpXfer, &[u8], u32, *mut c_void, Direction)> FtpXferBodyCbFn for F {}
+
+pub trait FtpLoginCbFn: FnMut(&FtpLogin, *mut c_void) {}
+impl<F: FnMut(&FtpLogin, *mut c_void)> FtpLoginCbFn for F {}
+
+pub trait FtpFileCbFn: FnMut(&FtpFile, *mut c_void) {}
+impl<F: FnMut(&FtpFile, *mut c_void)> FtpFileCbFn for F {}
+
+pub trait FtpTransferCbFn: FnMut(&FtpTransfer, *mut c_void) {}
+impl<F: FnMut(&FtpTransfer, *mut c_void)> FtpTransferCbFn for F {}
+
+pub trait FtpListEntryCbFn: FnMut(&FtpEntry, *mut c_void, Direction) {}
+impl<F: FnMut(&FtpEntry, *mut c_void, Direction)> FtpListEntryCbFn for F {}
+
 pub trait SipBodyCbFn: FnMut(&[u8], u32, *mut c_void, Direction) {}
 impl<F: FnMut(&[u8], u32, *mut c_void, Direction)> SipBodyCbFn for F {}
 
+pub trait SipCallCbFn: FnMut(&SipCall, *mut c_void) {}
+impl<F: FnMut(&SipCall, *mut c_void)> SipCallCbFn for F {}
+
+pub trait SipSdpCbFn: FnMut(&Sdp, *mut c_void, Direction) {}
+impl<F: FnMut(&Sdp, *mut c_void, Direction)> SipSdpCbFn for F {}
+
+pub trait SipMediaLinkCbFn: FnMut(&SipMediaLink, *mut c_void, Direction) {}
+impl<F: FnMut(&SipMediaLink, *mut c_void, Direction)> SipMediaLinkCbFn for F {}
+
+pub trait RtpPktCbFn: FnMut(&RtpHeader, &[u8], *mut c_void, Direction) {}
+impl<F: FnMut(&RtpHeader, &[u8], *mut c_void, Direction)> RtpPktCbFn for F {}
+
+pub trait RtpStatsCbFn: FnMut(&RtpStats, *mut c_void, Direction) {}
+impl<F: FnMut(&RtpStats, *mut c_void, Direction)> RtpStatsCbFn for F {}
+
+pub trait RtcpReportCbFn: FnMut(&RtcpReport, *mut c_void, Direction) {}
+impl<F: FnMut(&RtcpReport, *mut c_void, Direction)> RtcpReportCbFn for F {}
+
+pub trait RtcpSdesCbFn: FnMut(u32, u8, &[u8], *mut c_void, Direction) {}
+impl<F: FnMut(u32, u8, &[u8], *mut c_void, Direction)> RtcpSdesCbFn for F {}
+
+pub trait RtcpByeCbFn: FnMut(&[u32], Option<&[u8]>, *mut c_void, Direction) {}
+impl<F: FnMut(&[u32], Option<&[u8]>, *mut c_void, Direction)> RtcpByeCbFn for F {}
+
 pub trait DnsHeaderCbFn: FnMut(Header, usize, *mut c_void) {}
 impl<F: FnMut(Header, usize, *mut c_void)> DnsHeaderCbFn for F {}
 
@@ -186,7 +364,22 @@ pub(crate) type CbOrdPkt<T> = Rc<RefCell<dyn OrdPktCbFn<T> + 'static>>;
 pub(crate) type CbUser = Rc<RefCell<dyn DataCbFn + 'static>>;
 pub(crate) type CbPass = Rc<RefCell<dyn DataCbFn + 'static>>;
 pub(crate) type CbMailFrom = Rc<RefCell<dyn DataCbFn + 'static>>;
+pub(crate) type CbMailFromExt = Rc<RefCell<dyn SmtpAddrCbFn + 'static>>;
 pub(crate) type CbRcpt = Rc<RefCell<dyn DataCbFn + 'static>>;
+pub(crate) type CbRcptExt = Rc<RefCell<dyn SmtpAddrCbFn + 'static>>;
+pub(crate) type CbPop3Apop = Rc<RefCell<dyn Pop3ApopCbFn + 'static>>;
+pub(crate) type CbPop3Auth = Rc<RefCell<dyn Pop3AuthCbFn + 'static>>;
+pub(crate) type CbPop3Reply = Rc<RefCell<dyn Pop3ReplyCbFn + 'static>>;
+pub(crate) type CbPop3Capa = Rc<RefCell<dyn DataCbFn + 'static>>;
+pub(crate) type CbPop3Stat = Rc<RefCell<dyn Pop3StatCbFn + 'static>>;
+pub(crate) type CbPop3List = Rc<RefCell<dyn Pop3ListCbFn + 'static>>;
+pub(crate) type CbPop3Uidl = Rc<RefCell<dyn Pop3UidlCbFn + 'static>>;
+pub(crate) type CbPop3Msg = Rc<RefCell<dyn Pop3MsgCbFn + 'static>>;
+pub(crate) type CbImapAuth = Rc<RefCell<dyn ImapAuthCbFn + 'static>>;
+pub(crate) type CbImapReply = Rc<RefCell<dyn ImapReplyCbFn + 'static>>;
+pub(crate) type CbImapState = Rc<RefCell<dyn ImapStateCbFn + 'static>>;
+pub(crate) type CbImapBodyInfo = Rc<RefCell<dyn ImapBodyInfoCbFn + 'static>>;
+pub(crate) type CbImapFetch = Rc<RefCell<dyn ImapFetchCbFn + 'static>>;
 pub(crate) type CbSrv = Rc<RefCell<dyn DataCbFn + 'static>>;
 pub(crate) type CbClt = Rc<RefCell<dyn DataCbFn + 'static>>;
 pub(crate) type CbHeader = Rc<RefCell<dyn DataCbDirFn + 'static>>;
@@ -194,9 +387,41 @@ pub(crate) type CbBodyEvt = Rc<RefCell<dyn EvtCbFn + 'static>>;
 pub(crate) type CbBody = Rc<RefCell<dyn BodyCbFn + 'static>>;
 pub(crate) type CbStartLine = Rc<RefCell<dyn DataCbDirFn + 'static>>;
 pub(crate) type CbHttpBody = Rc<RefCell<dyn HttpBodyCbFn + 'static>>;
+pub(crate) type CbHttpTrans = Rc<RefCell<dyn HttpTransCbFn + 'static>>;
+pub(crate) type CbHttpRequest = Rc<RefCell<dyn HttpRequestCbFn + 'static>>;
+pub(crate) type CbHttpResponse = Rc<RefCell<dyn HttpResponseCbFn + 'static>>;
+pub(crate) type CbHttpPart = Rc<RefCell<dyn HttpPartCbFn + 'static>>;
+pub(crate) type CbHttpForm = Rc<RefCell<dyn HttpFormCbFn + 'static>>;
+pub(crate) type CbHttpTunnel = Rc<RefCell<dyn HttpTunnelCbFn + 'static>>;
+pub(crate) type CbHttpTunnelData = Rc<RefCell<dyn DataCbDirFn + 'static>>;
+pub(crate) type CbWsFrame = Rc<RefCell<dyn WsFrameCbFn + 'static>>;
+pub(crate) type CbTlsHello = Rc<RefCell<dyn TlsHelloCbFn + 'static>>;
+pub(crate) type CbHttpAuth = Rc<RefCell<dyn HttpAuthCbFn + 'static>>;
+pub(crate) type CbHttpAuthResult = Rc<RefCell<dyn HttpAuthResultCbFn + 'static>>;
+pub(crate) type CbHttp2Line = Rc<RefCell<dyn Http2LineCbFn + 'static>>;
+pub(crate) type CbHttp2BodyEvt = Rc<RefCell<dyn Http2BodyEvtCbFn + 'static>>;
+pub(crate) type CbHttp2Body = Rc<RefCell<dyn Http2BodyCbFn + 'static>>;
+pub(crate) type CbGrpcCall = Rc<RefCell<dyn GrpcCallCbFn + 'static>>;
+pub(crate) type CbGrpcMsg = Rc<RefCell<dyn GrpcMsgCbFn + 'static>>;
+pub(crate) type CbGrpcStatus = Rc<RefCell<dyn GrpcStatusCbFn + 'static>>;
+pub(crate) type CbHttpDecodeErr = Rc<RefCell<dyn HttpDecodeErrCbFn + 'static>>;
 pub(crate) type CbFtpLink = Rc<RefCell<dyn FtpLinkCbFn + 'static>>;
 pub(crate) type CbFtpBody = Rc<RefCell<dyn DataCbDirFn + 'static>>;
+pub(crate) type CbFtpXferEvt = Rc<RefCell<dyn FtpXferEvtCbFn + 'static>>;
+pub(crate) type CbFtpXferBody = Rc<RefCell<dyn FtpXferBodyCbFn + 'static>>;
+pub(crate) type CbFtpLogin = Rc<RefCell<dyn FtpLoginCbFn + 'static>>;
+pub(crate) type CbFtpFile = Rc<RefCell<dyn FtpFileCbFn + 'static>>;
+pub(crate) type CbFtpTransfer = Rc<RefCell<dyn FtpTransferCbFn + 'static>>;
+pub(crate) type CbFtpListEntry = Rc<RefCell<dyn FtpListEntryCbFn + 'static>>;
 pub(crate) type CbSipBody = Rc<RefCell<dyn SipBodyCbFn + 'static>>;
+pub(crate) type CbSipCall = Rc<RefCell<dyn SipCallCbFn + 'static>>;
+pub(crate) type CbSipSdp = Rc<RefCell<dyn SipSdpCbFn + 'static>>;
+pub(crate) type CbSipMediaLink = Rc<RefCell<dyn SipMediaLinkCbFn + 'static>>;
+pub(crate) type CbRtpPkt = Rc<RefCell<dyn RtpPktCbFn + 'static>>;
+pub(crate) type CbRtpStats = Rc<RefCell<dyn RtpStatsCbFn + 'static>>;
+pub(crate) type CbRtcpReport = Rc<RefCell<dyn RtcpReportCbFn + 'static>>;
+pub(crate) type CbRtcpSdes = Rc<RefCell<dyn RtcpSdesCbFn + 'static>>;
+pub(crate) type CbRtcpBye = Rc<RefCell<dyn RtcpByeCbFn + 'static>>;
 pub(crate) type CbDnsHeader = Rc<RefCell<dyn DnsHeaderCbFn + 'static>>;
 pub(crate) type CbDnsQuery = Rc<RefCell<dyn DnsQueryCbFn + 'static>>;
 pub(crate) type CbDnsAnswer = Rc<RefCell<dyn DnsRrCbFn + 'static>>;
@@ -214,6 +439,7 @@ pub(crate) struct Callbacks {
     pub(crate) clt: Option<CbClt>,
     pub(crate) srv: Option<CbSrv>,
     pub(crate) dir: Direction,
+    pub(crate) dot_unstuff: bool,
 }
 
 pub(crate) async fn header<T>(
@@ -275,17 +501,14 @@ where
 pub(crate) async fn body<T>(
     stm: &mut PktStrm<T>,
     te: Option<TransferEncoding>,
-    cb_body_start: Option<&CbBodyEvt>,
-    cb_body: Option<&CbBody>,
-    cb_body_stop: Option<&CbBodyEvt>,
+    cb: &Callbacks,
     cb_ctx: *mut c_void,
-    dir: Direction,
 ) -> Result<bool, ()>
 where
     T: Packet,
 {
-    if let Some(cb) = cb_body_start {
-        cb.borrow_mut()(cb_ctx, dir);
+    if let Some(ref cb_start) = cb.body_start {
+        cb_start.borrow_mut()(cb_ctx, cb.dir);
     }
     loop {
         let (line, seq) = stm.readline_str().await?;
@@ -294,12 +517,17 @@ where
             break;
         }
 
-        if let Some(cb) = &cb_body {
-            cb.borrow_mut()(line.as_bytes(), seq, cb_ctx, dir, te.clone());
+        if let Some(ref cb_body) = cb.body {
+            let line = if cb.dot_unstuff {
+                line.strip_prefix('.').unwrap_or(line)
+            } else {
+                line
+            };
+            cb_body.borrow_mut()(line.as_bytes(), seq, cb_ctx, cb.dir, te.clone());
         }
     }
-    if let Some(cb) = cb_body_stop {
-        cb.borrow_mut()(cb_ctx, dir);
+    if let Some(ref cb_stop) = cb.body_stop {
+        cb_stop.borrow_mut()(cb_ctx, cb.dir);
     }
     Ok(true)
 }
@@ -333,6 +561,7 @@ where
                 cb_body_stop: cb.body_stop.as_ref(),
                 cb_ctx,
                 dir: cb.dir,
+                dot_unstuff: cb.dot_unstuff,
             };
             mime_body(stm, params).await?;
         }
@@ -364,6 +593,7 @@ where
         cb_body_stop: None,
         cb_ctx: std::ptr::null_mut(),
         dir: Direction::Unknown,
+        dot_unstuff: false,
     };
     mime_body(stm, params).await?;
 
@@ -380,6 +610,7 @@ pub(crate) struct MimeBodyParams<'a> {
     cb_body_stop: Option<&'a CbBodyEvt>,
     cb_ctx: *mut c_void,
     dir: Direction,
+    dot_unstuff: bool,
 }
 
 pub(crate) async fn mime_body<T>(stm: &mut PktStrm<T>, params: MimeBodyParams<'_>) -> Result<(), ()>
@@ -393,13 +624,19 @@ where
     if let Some(cb) = params.cb_body_start {
         cb.borrow_mut()(params.cb_ctx, params.dir);
     }
+    let mut line_start = true;
     loop {
         let (ret, content, seq) = stm
             .read_mime_octet2(params.bdry_finder.unwrap(), params.bdry)
             .await?;
 
         if let Some(cb) = params.cb_body {
-            cb.borrow_mut()(content, seq, params.cb_ctx, params.dir, params.te.clone());
+            if params.dot_unstuff {
+                let content = dot_unstuff(content, &mut line_start);
+                cb.borrow_mut()(&content, seq, params.cb_ctx, params.dir, params.te.clone());
+            } else {
+                cb.borrow_mut()(content, seq, params.cb_ctx, params.dir, params.te.clone());
+            }
         }
 
         if ret == ReadRet::DashBdry {
@@ -412,6 +649,23 @@ where
     Ok(())
 }
 
+// 去掉行首的填充'.'。content不一定按行切分，line_start记录上一块是否以行结束
+fn dot_unstuff<'a>(content: &'a [u8], line_start: &mut bool) -> Cow<'a, [u8]> {
+    let mut out: Option<Vec<u8>> = None;
+    for (i, &c) in content.iter().enumerate() {
+        if *line_start && c == b'.' {
+            out.get_or_insert_with(|| content[..i].to_vec());
+        } else if let Some(ref mut out) = out {
+            out.push(c);
+        }
+        *line_start = c == b'\n';
+    }
+    match out {
+        Some(out) => Cow::Owned(out),
+        None => Cow::Borrowed(content),
+    }
+}
+
 pub(crate) async fn epilogue<T>(stm: &mut PktStrm<T>, bdry: &str) -> Result<(), ()>
 where
     T: Packet,
@@ -522,6 +776,72 @@ fn transfer_encoding(input: &[u8]) -> Option<TransferEncoding> {
     }
 }
 
+// sasl等场景用到的base64解码，遇到'='结束
+pub(crate) fn base64_decode(input: &[u8]) -> Option<Vec<u8>> {
+    fn val(c: u8) -> Option<u32> {
+        match c {
+            b'A'..=b'Z' => Some((c - b'A') as u32),
+            b'a'..=b'z' => Some((c - b'a' + 26) as u32),
+            b'0'..=b'9' => Some((c - b'0' + 52) as u32),
+            b'+' => Some(62),
+            b'/' => Some(63),
+            _ => None,
+        }
+    }
+
+    let mut out = Vec::with_capacity(input.len() * 3 / 4);
+    let mut acc = 0u32;
+    let mut bits = 0;
+    for &c in input {
+        if c == b'=' {
+            break;
+        }
+        acc = (acc << 6) | val(c)?;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((acc >> bits) as u8);
+            acc &= (1 << bits) - 1;
+        }
+    }
+    Some(out)
+}
+
+// sasl交互中客户端第cnt个应答里的用户名和密码(或token)
+pub(crate) fn sasl_cred(mech: &str, cnt: usize, resp: &[u8]) -> (Option<Vec<u8>>, Option<Vec<u8>>) {
+    let Some(plain) = base64_decode(resp) else {
+        return (None, None);
+    };
+
+    match (mech, cnt) {
+        ("PLAIN", 0) => {
+            // authzid\0authcid\0passwd
+            let mut parts = plain.split(|&c| c == 0);
+            match (parts.next(), parts.next(), parts.next()) {
+                (Some(_), Some(user), Some(pass)) => (Some(user.to_vec()), Some(pass.to_vec())),
+                _ => (None, None),
+            }
+        }
+        ("LOGIN", 0) => (Some(plain), None),
+        ("LOGIN", 1) => (None, Some(plain)),
+        ("XOAUTH2", 0) => {
+            // user=xxx\x01auth=Bearer token\x01\x01
+            let mut user = None;
+            let mut token = None;
+            for kv in plain.split(|&c| c == 1) {
+                if let Some(val) = kv.strip_prefix(b"user=") {
+                    user = Some(val.to_vec());
+                } else if let Some(val) = kv.strip_prefix(b"auth=") {
+                    let val = val.strip_prefix(b"Bearer ").unwrap_or(val);
+                    token = Some(val.to_vec());
+                }
+            }
+            (user, token)
+        }
+        _ => (None, None),
+    }
+}
+
 pub(crate) fn content_length(line: &str) -> Option<usize> {
     fn parse_content_length(input: &str) -> IResult<&str, usize> {
         let (input, _) = tag_no_case("content-length:")(input)?;
@@ -660,4 +980,63 @@ mod tests {
             None
         );
     }
+
+    #[test]
+    fn test_base64_decode() {
+        assert_eq!(base64_decode(b"dXNlcg==").unwrap(), b"user");
+        assert_eq!(
+            base64_decode(b"AHRpbQB0YW5zdGFhZnRhbnN0YWFm").unwrap(),
+            b"\0tim\0tanstaaftanstaaf"
+        );
+        assert_eq!(base64_decode(b"YWJj").unwrap(), b"abc");
+        assert_eq!(base64_decode(b"").unwrap(), b"");
+        assert!(base64_decode(b"a b").is_none());
+    }
+
+    #[test]
+    fn test_dot_unstuff() {
+        let mut line_start = true;
+        assert_eq!(
+            &*dot_unstuff(b"..a\r\nb\r\n", &mut line_start),
+            b".a\r\nb\r\n"
+        );
+        assert!(line_start);
+        assert_eq!(&*dot_unstuff(b"x\r\n..y\r", &mut line_start), b"x\r\n.y\r");
+        assert!(!line_start);
+        // 上一块以\r结束，下一块的\n之后才是行首
+        assert_eq!(&*dot_unstuff(b"\n..z", &mut line_start), b"\n.z");
+        assert!(matches!(
+            dot_unstuff(b"plain", &mut line_start),
+            Cow::Borrowed(_)
+        ));
+    }
+
+    #[test]
+    fn test_sasl_cred() {
+        assert_eq!(
+            sasl_cred("PLAIN", 0, b"AHRpbQB0YW5zdGFhZnRhbnN0YWFm"),
+            (Some(b"tim".to_vec()), Some(b"tanstaaftanstaaf".to_vec()))
+        );
+        assert_eq!(
+            sasl_cred("LOGIN", 0, b"dGlt"),
+            (Some(b"tim".to_vec()), None)
+        );
+        assert_eq!(
+            sasl_cred("LOGIN", 1, b"dGFuc3RhYWY="),
+            (None, Some(b"tanstaaf".to_vec()))
+        );
+        // user=someuser@example.com\x01auth=Bearer ya29.vF9dft4qmTc2Nvb3RlckBhdHRhdmlzdGEuY29tCg\x01\x01
+        assert_eq!(
+            sasl_cred(
+                "XOAUTH2",
+                0,
+                b"dXNlcj1zb21ldXNlckBleGFtcGxlLmNvbQFhdXRoPUJlYXJlciB5YTI5LnZGOWRmdDRxbVRjMk52YjNSbGNrQmhkSFJoZG1semRHRXVZMjl0Q2cBAQ=="
+            ),
+            (
+                Some(b"someuser@example.com".to_vec()),
+                Some(b"ya29.vF9dft4qmTc2Nvb3RlckBhdHRhdmlzdGEuY29tCg".to_vec())
+            )
+        );
+        assert_eq!(sasl_cred("CRAM-MD5", 0, b"dGlt"), (None, None));
+    }
 }
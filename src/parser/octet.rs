@@ -47,10 +47,10 @@ where
 
         loop {
             let (ret, bytes, seq) = stm.read_mime_octet2(&finder, BDRY).await?;
-            if !bytes.is_empty() {
-                if let Some(ref cb) = cb_read {
-                    cb.borrow_mut()(bytes, seq, cb_ctx);
-                }
+            if !bytes.is_empty()
+                && let Some(ref cb) = cb_read
+            {
+                cb.borrow_mut()(bytes, seq, cb_ctx);
             }
 
             match ret {
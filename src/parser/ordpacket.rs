@@ -34,10 +34,10 @@ where
         while !stm.fin() {
             let pkt = stm.next_ord_pkt().await;
 
-            if let Some(ref cb) = cb_ord_pkt {
-                if let Some(pkt) = pkt {
-                    cb.borrow_mut()(pkt, cb_ctx, dir);
-                }
+            if let Some(ref cb) = cb_ord_pkt
+                && let Some(pkt) = pkt
+            {
+                cb.borrow_mut()(pkt, cb_ctx, dir);
             }
         }
         Ok(())
@@ -188,13 +188,13 @@ mod tests {
         let seq1 = 1;
         let pkt1 = build_pkt(seq1, false);
         let _ = pkt1.decode();
-        let seq2 = seq1 + pkt1.payload_len() as u32;
+        let seq2 = seq1 + pkt1.payload_len();
         let pkt2 = build_pkt(seq2, false);
         let _ = pkt2.decode();
-        let seq3 = seq2 + pkt2.payload_len() as u32;
+        let seq3 = seq2 + pkt2.payload_len();
         let pkt3 = build_pkt(seq3, false);
         let _ = pkt3.decode();
-        let seq4 = seq3 + pkt3.payload_len() as u32;
+        let seq4 = seq3 + pkt3.payload_len();
         let pkt4 = build_pkt(seq4, true); // 最后一个包带 fin
         let _ = pkt4.decode();
 
@@ -240,7 +240,7 @@ mod tests {
         let pkt1 = build_pkt(seq1, false);
         let _ = pkt1.decode();
 
-        let seq2 = seq1 + pkt1.payload_len() as u32;
+        let seq2 = seq1 + pkt1.payload_len();
         let pkt2 = build_pkt(seq2, false);
         let _ = pkt2.decode();
 
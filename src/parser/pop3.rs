@@ -3,7 +3,17 @@ use crate::CbBody;
 use crate::CbBodyEvt;
 use crate::CbClt;
 use crate::CbHeader;
+use crate::CbPass;
+use crate::CbPop3Apop;
+use crate::CbPop3Auth;
+use crate::CbPop3Capa;
+use crate::CbPop3List;
+use crate::CbPop3Msg;
+use crate::CbPop3Reply;
+use crate::CbPop3Stat;
+use crate::CbPop3Uidl;
 use crate::CbSrv;
+use crate::CbUser;
 use crate::DirConfirmFn;
 use crate::Direction;
 use crate::POP3_PORT;
@@ -16,6 +26,7 @@ use crate::body;
 use crate::header;
 use crate::multi_body;
 use crate::packet::*;
+use crate::sasl_cred;
 use nom::{
     IResult,
     bytes::complete::{tag, take_till},
@@ -24,8 +35,55 @@ use nom::{
     combinator::{map_res, recognize},
     sequence::{preceded, terminated},
 };
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
 use std::ffi::c_void;
 use std::marker::PhantomData;
+use std::rc::Rc;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Pop3Cmd {
+    User,
+    Pass,
+    Apop,
+    Auth,
+    Capa,
+    Stat,
+    List,
+    Uidl,
+    Retr,
+    Top,
+    Dele,
+    Noop,
+    Rset,
+    Quit,
+    Stls,
+    Other,
+}
+
+// c2s解析出的命令，等待s2c的应答
+struct Pop3Req {
+    cmd: Pop3Cmd,
+    arg: String,
+}
+
+type Pop3Pending = Rc<RefCell<VecDeque<Pop3Req>>>;
+
+pub(crate) struct Pop3CmdCallbacks {
+    user: Option<CbUser>,
+    pass: Option<CbPass>,
+    apop: Option<CbPop3Apop>,
+    auth: Option<CbPop3Auth>,
+}
+
+pub(crate) struct Pop3RspCallbacks {
+    reply: Option<CbPop3Reply>,
+    capa: Option<CbPop3Capa>,
+    stat: Option<CbPop3Stat>,
+    list: Option<CbPop3List>,
+    uidl: Option<CbPop3Uidl>,
+    msg: Option<CbPop3Msg>,
+}
 
 pub struct Pop3Parser<T>
 where
@@ -37,6 +95,17 @@ where
     cb_body_stop: Option<CbBodyEvt>,
     cb_clt: Option<CbClt>,
     cb_srv: Option<CbSrv>,
+    cb_user: Option<CbUser>,
+    cb_pass: Option<CbPass>,
+    cb_apop: Option<CbPop3Apop>,
+    cb_auth: Option<CbPop3Auth>,
+    cb_reply: Option<CbPop3Reply>,
+    cb_capa: Option<CbPop3Capa>,
+    cb_stat: Option<CbPop3Stat>,
+    cb_list: Option<CbPop3List>,
+    cb_uidl: Option<CbPop3Uidl>,
+    cb_msg: Option<CbPop3Msg>,
+    pending: Pop3Pending,
     _phantom_t: PhantomData<T>,
 }
 
@@ -52,6 +121,17 @@ where
             cb_body_stop: None,
             cb_clt: None,
             cb_srv: None,
+            cb_user: None,
+            cb_pass: None,
+            cb_apop: None,
+            cb_auth: None,
+            cb_reply: None,
+            cb_capa: None,
+            cb_stat: None,
+            cb_list: None,
+            cb_uidl: None,
+            cb_msg: None,
+            pending: Rc::new(RefCell::new(VecDeque::new())),
             _phantom_t: PhantomData,
         }
     }
@@ -59,9 +139,13 @@ where
     async fn c2s_parser_inner(
         strm: *mut PktStrm<T>,
         cb_pop3: Callbacks,
+        cb_cmd: Pop3CmdCallbacks,
+        pending: Pop3Pending,
         cb_ctx: *mut c_void,
     ) -> Result<(), ()> {
         let stm = unsafe { &mut *strm };
+        // AUTH之后的sasl交互: 机制名和已收到的应答数
+        let mut sasl: Option<(String, usize)> = None;
 
         loop {
             let (line, seq) = stm.read_clean_line_str().await?;
@@ -70,7 +154,63 @@ where
                 cb.borrow_mut()(line.as_bytes(), seq, cb_ctx);
             }
 
-            if line == "QUIT" || line == "STLS" {
+            let (cmd, arg) = pop3_cmd(line);
+            if let Some((mech, cnt)) = sasl.as_mut()
+                && cmd == Pop3Cmd::Other
+            {
+                // "*" 是客户端取消认证，服务器的应答仍属于AUTH命令
+                if line == "*" {
+                    sasl = None;
+                } else {
+                    sasl_resp(mech, *cnt, line.as_bytes(), seq, &cb_cmd, cb_ctx);
+                    *cnt += 1;
+                }
+                continue;
+            }
+            sasl = None;
+
+            match cmd {
+                Pop3Cmd::User => {
+                    if let Some(ref cb) = cb_cmd.user {
+                        cb.borrow_mut()(arg.as_bytes(), seq, cb_ctx);
+                    }
+                }
+                Pop3Cmd::Pass => {
+                    if let Some(ref cb) = cb_cmd.pass {
+                        cb.borrow_mut()(arg.as_bytes(), seq, cb_ctx);
+                    }
+                }
+                Pop3Cmd::Apop => {
+                    if let Some((name, digest)) = arg.split_once(' ')
+                        && let Some(ref cb) = cb_cmd.apop
+                    {
+                        cb.borrow_mut()(name.as_bytes(), digest.trim().as_bytes(), seq, cb_ctx);
+                    }
+                }
+                Pop3Cmd::Auth if !arg.is_empty() => {
+                    // AUTH不带参数是列出支持的机制，不进入sasl交互
+                    let (mech, ir) = match arg.split_once(' ') {
+                        Some((mech, ir)) => (mech, Some(ir.trim())),
+                        None => (arg, None),
+                    };
+                    let mech = mech.to_ascii_uppercase();
+                    let mut cnt = 0;
+                    if let Some(ir) = ir {
+                        let ir = if ir == "=" { "" } else { ir };
+                        sasl_resp(&mech, cnt, ir.as_bytes(), seq, &cb_cmd, cb_ctx);
+                        cnt += 1;
+                    }
+                    sasl = Some((mech, cnt));
+                }
+                _ => {}
+            }
+
+            pending.borrow_mut().push_back(Pop3Req {
+                cmd,
+                arg: arg.to_string(),
+            });
+
+            if cmd == Pop3Cmd::Quit || cmd == Pop3Cmd::Stls {
                 break;
             }
         }
@@ -80,9 +220,12 @@ where
     async fn s2c_parser_inner(
         strm: *mut PktStrm<T>,
         cb_pop3: Callbacks,
+        cb_rsp: Pop3RspCallbacks,
+        pending: Pop3Pending,
         cb_ctx: *mut c_void,
     ) -> Result<(), ()> {
         let stm = unsafe { &mut *strm };
+        let mut uidls: HashMap<u32, String> = HashMap::new();
 
         loop {
             let (line, seq) = stm.read_clean_line_str().await?;
@@ -91,12 +234,101 @@ where
                 cb.borrow_mut()(line.as_bytes(), seq, cb_ctx);
             }
 
-            if stls_answer(line) {
-                break;
+            // sasl的"+ "挑战等不是命令的应答
+            let Some((ok, text)) = status_line(line) else {
+                continue;
+            };
+
+            let req = pending.borrow_mut().pop_front();
+            let Some(req) = req else {
+                // 问候语，或者没有看到对应的命令，只能根据应答内容猜
+                if stls_answer(line) {
+                    break;
+                }
+                if retr_answer(line) {
+                    Self::parser_inner(stm, &cb_pop3, false, cb_ctx).await?;
+                }
+                continue;
+            };
+
+            if let Some(ref cb) = cb_rsp.reply {
+                cb.borrow_mut()(
+                    req.cmd,
+                    req.arg.as_bytes(),
+                    ok,
+                    text.as_bytes(),
+                    seq,
+                    cb_ctx,
+                );
+            }
+            if !ok {
+                continue;
             }
 
-            if retr_answer(line) {
-                Self::parser_inner(stm, &cb_pop3, cb_ctx).await?;
+            match req.cmd {
+                Pop3Cmd::Stls => break,
+                Pop3Cmd::Capa => {
+                    multi_line(stm, cb_pop3.srv.as_ref(), cb_ctx, |line, seq| {
+                        if let Some(ref cb) = cb_rsp.capa {
+                            cb.borrow_mut()(line.as_bytes(), seq, cb_ctx);
+                        }
+                    })
+                    .await?;
+                }
+                Pop3Cmd::Auth if req.arg.is_empty() => {
+                    multi_line(stm, cb_pop3.srv.as_ref(), cb_ctx, |_, _| {}).await?;
+                }
+                Pop3Cmd::Stat => {
+                    if let Some((count, size)) = num_size(text)
+                        && let Some(ref cb) = cb_rsp.stat
+                    {
+                        cb.borrow_mut()(count, size, cb_ctx);
+                    }
+                }
+                Pop3Cmd::List => {
+                    let list_cb = |line: &str| {
+                        if let Some((num, size)) = num_size(line)
+                            && let Some(ref cb) = cb_rsp.list
+                        {
+                            cb.borrow_mut()(num, size, cb_ctx);
+                        }
+                    };
+                    if req.arg.is_empty() {
+                        multi_line(stm, cb_pop3.srv.as_ref(), cb_ctx, |line, _| list_cb(line))
+                            .await?;
+                    } else {
+                        list_cb(text);
+                    }
+                }
+                Pop3Cmd::Uidl => {
+                    let mut uidl_cb = |line: &str| {
+                        if let Some((num, uid)) = num_uid(line) {
+                            if let Some(ref cb) = cb_rsp.uidl {
+                                cb.borrow_mut()(num, uid.as_bytes(), cb_ctx);
+                            }
+                            uidls.insert(num, uid.to_string());
+                        }
+                    };
+                    if req.arg.is_empty() {
+                        multi_line(stm, cb_pop3.srv.as_ref(), cb_ctx, |line, _| uidl_cb(line))
+                            .await?;
+                    } else {
+                        uidl_cb(text);
+                    }
+                }
+                Pop3Cmd::Retr | Pop3Cmd::Top => {
+                    // TOP n m 只有头和前m行
+                    let partial = req.cmd == Pop3Cmd::Top;
+                    let num = req.arg.split_ascii_whitespace().next();
+                    if let Some(Ok(num)) = num.map(str::parse::<u32>)
+                        && let Some(ref cb) = cb_rsp.msg
+                    {
+                        let uidl = uidls.get(&num).map(|uid| uid.as_bytes());
+                        cb.borrow_mut()(num, uidl, partial, cb_ctx);
+                    }
+                    Self::parser_inner(stm, &cb_pop3, partial, cb_ctx).await?;
+                }
+                _ => {}
             }
         }
         Ok(())
@@ -105,22 +337,17 @@ where
     async fn parser_inner(
         stm: &mut PktStrm<T>,
         cb_pop3: &Callbacks,
+        partial: bool,
         cb_ctx: *mut c_void,
     ) -> Result<(), ()> {
         let (boundary, te) = header(stm, cb_pop3.header.as_ref(), cb_ctx, Direction::S2c).await?;
-        if let Some(bdry) = boundary {
+        // 截断的邮件可能到不了结束的boundary，只能按行读到"."
+        if let Some(bdry) = boundary
+            && !partial
+        {
             multi_body(stm, &bdry, &bdry, cb_pop3, cb_ctx).await?;
         } else {
-            body(
-                stm,
-                te,
-                cb_pop3.body_start.as_ref(),
-                cb_pop3.body.as_ref(),
-                cb_pop3.body_stop.as_ref(),
-                cb_ctx,
-                cb_pop3.dir,
-            )
-            .await?;
+            body(stm, te, cb_pop3, cb_ctx).await?;
         }
         Ok(())
     }
@@ -160,10 +387,11 @@ where
                 }
             }
 
-            if let Ok(payload) = payload_c2s {
-                if payload.len() >= 4 && (payload.starts_with(b"+OK ")) {
-                    return Some(false);
-                }
+            if let Ok(payload) = payload_c2s
+                && payload.len() >= 4
+                && (payload.starts_with(b"+OK "))
+            {
+                return Some(false);
             }
 
             Some(true)
@@ -179,8 +407,21 @@ where
             clt: self.cb_clt.clone(),
             srv: None,
             dir: Direction::C2s,
+            dot_unstuff: false,
+        };
+        let cb_cmd = Pop3CmdCallbacks {
+            user: self.cb_user.clone(),
+            pass: self.cb_pass.clone(),
+            apop: self.cb_apop.clone(),
+            auth: self.cb_auth.clone(),
         };
-        Some(Box::pin(Self::c2s_parser_inner(stream, cb_pop3, cb_ctx)))
+        Some(Box::pin(Self::c2s_parser_inner(
+            stream,
+            cb_pop3,
+            cb_cmd,
+            self.pending.clone(),
+            cb_ctx,
+        )))
     }
 
     fn s2c_parser(&self, stream: *mut PktStrm<T>, cb_ctx: *mut c_void) -> Option<ParserFuture> {
@@ -192,8 +433,23 @@ where
             clt: None,
             srv: self.cb_srv.clone(),
             dir: Direction::S2c,
+            dot_unstuff: true,
         };
-        Some(Box::pin(Self::s2c_parser_inner(stream, cb_pop3, cb_ctx)))
+        let cb_rsp = Pop3RspCallbacks {
+            reply: self.cb_reply.clone(),
+            capa: self.cb_capa.clone(),
+            stat: self.cb_stat.clone(),
+            list: self.cb_list.clone(),
+            uidl: self.cb_uidl.clone(),
+            msg: self.cb_msg.clone(),
+        };
+        Some(Box::pin(Self::s2c_parser_inner(
+            stream,
+            cb_pop3,
+            cb_rsp,
+            self.pending.clone(),
+            cb_ctx,
+        )))
     }
 }
 
@@ -219,10 +475,120 @@ where
         parser.cb_body_stop = prolens.cb_pop3_body_stop.clone();
         parser.cb_clt = prolens.cb_pop3_clt.clone();
         parser.cb_srv = prolens.cb_pop3_srv.clone();
+        parser.cb_user = prolens.cb_pop3_user.clone();
+        parser.cb_pass = prolens.cb_pop3_pass.clone();
+        parser.cb_apop = prolens.cb_pop3_apop.clone();
+        parser.cb_auth = prolens.cb_pop3_auth.clone();
+        parser.cb_reply = prolens.cb_pop3_reply.clone();
+        parser.cb_capa = prolens.cb_pop3_capa.clone();
+        parser.cb_stat = prolens.cb_pop3_stat.clone();
+        parser.cb_list = prolens.cb_pop3_list.clone();
+        parser.cb_uidl = prolens.cb_pop3_uidl.clone();
+        parser.cb_msg = prolens.cb_pop3_msg.clone();
         parser
     }
 }
 
+// 以"."结束的多行应答，每行同样交给srv回调
+async fn multi_line<T, F>(
+    stm: &mut PktStrm<T>,
+    cb_srv: Option<&CbSrv>,
+    cb_ctx: *mut c_void,
+    mut f: F,
+) -> Result<(), ()>
+where
+    T: Packet,
+    F: FnMut(&str, u32),
+{
+    loop {
+        let (line, seq) = stm.read_clean_line_str().await?;
+        if let Some(cb) = cb_srv {
+            cb.borrow_mut()(line.as_bytes(), seq, cb_ctx);
+        }
+        if line == "." {
+            return Ok(());
+        }
+        f(line.strip_prefix('.').unwrap_or(line), seq);
+    }
+}
+
+fn sasl_resp(
+    mech: &str,
+    cnt: usize,
+    resp: &[u8],
+    seq: u32,
+    cb_cmd: &Pop3CmdCallbacks,
+    cb_ctx: *mut c_void,
+) {
+    if let Some(ref cb) = cb_cmd.auth {
+        cb.borrow_mut()(mech.as_bytes(), resp, seq, cb_ctx);
+    }
+
+    let (user, pass) = sasl_cred(mech, cnt, resp);
+    if let Some(user) = user
+        && let Some(ref cb) = cb_cmd.user
+    {
+        cb.borrow_mut()(&user, seq, cb_ctx);
+    }
+    if let Some(pass) = pass
+        && let Some(ref cb) = cb_cmd.pass
+    {
+        cb.borrow_mut()(&pass, seq, cb_ctx);
+    }
+}
+
+fn pop3_cmd(line: &str) -> (Pop3Cmd, &str) {
+    let (keyword, arg) = match line.split_once(' ') {
+        Some((keyword, arg)) => (keyword, arg),
+        None => (line, ""),
+    };
+    let cmd = match keyword.to_ascii_uppercase().as_str() {
+        "USER" => Pop3Cmd::User,
+        "PASS" => Pop3Cmd::Pass,
+        "APOP" => Pop3Cmd::Apop,
+        "AUTH" => Pop3Cmd::Auth,
+        "CAPA" => Pop3Cmd::Capa,
+        "STAT" => Pop3Cmd::Stat,
+        "LIST" => Pop3Cmd::List,
+        "UIDL" => Pop3Cmd::Uidl,
+        "RETR" => Pop3Cmd::Retr,
+        "TOP" => Pop3Cmd::Top,
+        "DELE" => Pop3Cmd::Dele,
+        "NOOP" => Pop3Cmd::Noop,
+        "RSET" => Pop3Cmd::Rset,
+        "QUIT" => Pop3Cmd::Quit,
+        "STLS" => Pop3Cmd::Stls,
+        _ => Pop3Cmd::Other,
+    };
+    (cmd, arg)
+}
+
+// +OK/-ERR 应答，返回是否成功和后面的文本
+fn status_line(line: &str) -> Option<(bool, &str)> {
+    if let Some(text) = line.strip_prefix("+OK") {
+        Some((true, text.trim_start()))
+    } else {
+        line.strip_prefix("-ERR")
+            .map(|text| (false, text.trim_start()))
+    }
+}
+
+// STAT和LIST的 "n size"
+fn num_size(input: &str) -> Option<(u32, u64)> {
+    let mut iter = input.split_ascii_whitespace();
+    let num = iter.next()?.parse().ok()?;
+    let size = iter.next()?.parse().ok()?;
+    Some((num, size))
+}
+
+// UIDL的 "n uid"
+fn num_uid(input: &str) -> Option<(u32, &str)> {
+    let mut iter = input.split_ascii_whitespace();
+    let num = iter.next()?.parse().ok()?;
+    let uid = iter.next()?;
+    Some((num, uid))
+}
+
 fn retr_answer(input: &str) -> bool {
     fn parse_ok_tag(input: &str) -> IResult<&str, &str> {
         tag("+OK")(input)
@@ -757,7 +1123,9 @@ mod tests {
         assert!(body1_str.contains(
             "<html><head><meta http-equiv=3D\"content-type\" content=3D\"text/html; charse=\r\n"
         ));
-        assert!(body1_str.contains("..com.cn</span></div>=0A</body></html>")); // 最后的\r\n不属于body
+        // 行首填充的"."已经去掉，最后的\r\n不属于body
+        assert!(body1_str.contains("\r\n.com.cn</span></div>=0A</body></html>"));
+        assert!(!body1_str.contains("..com.cn"));
 
         let body2 = &bodies_guard[2];
         let body2_str = std::str::from_utf8(body2).unwrap();
@@ -797,4 +1165,344 @@ mod tests {
         assert_eq!(tes[1], Some(TransferEncoding::QuotedPrintable));
         assert_eq!(tes[2], Some(TransferEncoding::Base64));
     }
+
+    #[test]
+    fn test_pop3_cmd() {
+        assert_eq!(pop3_cmd("USER tim"), (Pop3Cmd::User, "tim"));
+        assert_eq!(pop3_cmd("retr 1"), (Pop3Cmd::Retr, "1"));
+        assert_eq!(pop3_cmd("STAT"), (Pop3Cmd::Stat, ""));
+        assert_eq!(
+            pop3_cmd("AUTH PLAIN dGVzdA=="),
+            (Pop3Cmd::Auth, "PLAIN dGVzdA==")
+        );
+        assert_eq!(pop3_cmd("dGVzdA=="), (Pop3Cmd::Other, ""));
+
+        assert_eq!(status_line("+OK 2 320"), Some((true, "2 320")));
+        assert_eq!(
+            status_line("-ERR no such message"),
+            Some((false, "no such message"))
+        );
+        assert_eq!(status_line("+ "), None);
+
+        assert_eq!(num_size("2 320"), Some((2, 320)));
+        assert_eq!(num_size("2"), None);
+        assert_eq!(
+            num_uid("1 whqtswO00WBw418f9t5JxYwZ"),
+            Some((1, "whqtswO00WBw418f9t5JxYwZ"))
+        );
+    }
+
+    #[test]
+    fn test_pop3_session() {
+        let session = [
+            (false, "+OK POP3 server ready\r\n"),
+            (true, "CAPA\r\n"),
+            (false, "+OK Capability list follows\r\n"),
+            (false, "TOP\r\n"),
+            (false, "UIDL\r\n"),
+            (false, "SASL PLAIN LOGIN\r\n"),
+            (false, ".\r\n"),
+            (true, "USER tim\r\n"),
+            (false, "+OK\r\n"),
+            (true, "PASS secret\r\n"),
+            (false, "-ERR invalid password\r\n"),
+            (true, "PASS tanstaaf\r\n"),
+            (false, "+OK maildrop locked\r\n"),
+            (true, "STAT\r\n"),
+            (false, "+OK 2 320\r\n"),
+            (true, "LIST\r\n"),
+            (false, "+OK 2 messages\r\n"),
+            (false, "1 120\r\n"),
+            (false, "2 200\r\n"),
+            (false, ".\r\n"),
+            (true, "LIST 2\r\n"),
+            (false, "+OK 2 200\r\n"),
+            (true, "UIDL\r\n"),
+            (false, "+OK\r\n"),
+            (false, "1 whqtswO00WBw418f9t5JxYwZ\r\n"),
+            (false, "2 QhdPYR:00WBw1Ph7x7\r\n"),
+            (false, ".\r\n"),
+            (true, "RETR 2\r\n"),
+            (false, "+OK 200 octets\r\n"),
+            (false, "Subject: hi\r\n"),
+            (false, "\r\n"),
+            (false, "body line\r\n"),
+            (false, "..dotted line\r\n"),
+            (false, ".\r\n"),
+            (true, "TOP 1 1\r\n"),
+            (false, "+OK\r\n"),
+            (false, "Content-Type: multipart/mixed; boundary=\"xx\"\r\n"),
+            (false, "\r\n"),
+            (false, "--xx\r\n"),
+            (false, ".\r\n"),
+            (true, "QUIT\r\n"),
+            (false, "+OK bye\r\n"),
+        ];
+
+        let users = Rc::new(RefCell::new(Vec::<Vec<u8>>::new()));
+        let passes = Rc::new(RefCell::new(Vec::<Vec<u8>>::new()));
+        let replies = Rc::new(RefCell::new(Vec::<(Pop3Cmd, String, bool, String)>::new()));
+        let capas = Rc::new(RefCell::new(Vec::<Vec<u8>>::new()));
+        let stats = Rc::new(RefCell::new(Vec::<(u32, u64)>::new()));
+        let lists = Rc::new(RefCell::new(Vec::<(u32, u64)>::new()));
+        let uidls = Rc::new(RefCell::new(Vec::<(u32, Vec<u8>)>::new()));
+        let msgs = Rc::new(RefCell::new(Vec::<(u32, Option<Vec<u8>>, bool)>::new()));
+        let headers = Rc::new(RefCell::new(Vec::<Vec<u8>>::new()));
+        let bodies = Rc::new(RefCell::new(Vec::<Vec<u8>>::new()));
+
+        let mut protolens = Prolens::<CapPacket>::default();
+        let users_clone = users.clone();
+        protolens.set_cb_pop3_user(move |user: &[u8], _seq: u32, _cb_ctx: *mut c_void| {
+            users_clone.borrow_mut().push(user.to_vec());
+        });
+        let passes_clone = passes.clone();
+        protolens.set_cb_pop3_pass(move |pass: &[u8], _seq: u32, _cb_ctx: *mut c_void| {
+            passes_clone.borrow_mut().push(pass.to_vec());
+        });
+        let replies_clone = replies.clone();
+        protolens.set_cb_pop3_reply(
+            move |cmd: Pop3Cmd,
+                  arg: &[u8],
+                  ok: bool,
+                  text: &[u8],
+                  _seq: u32,
+                  _cb_ctx: *mut c_void| {
+                replies_clone.borrow_mut().push((
+                    cmd,
+                    String::from_utf8_lossy(arg).to_string(),
+                    ok,
+                    String::from_utf8_lossy(text).to_string(),
+                ));
+            },
+        );
+        let capas_clone = capas.clone();
+        protolens.set_cb_pop3_capa(move |capa: &[u8], _seq: u32, _cb_ctx: *mut c_void| {
+            capas_clone.borrow_mut().push(capa.to_vec());
+        });
+        let stats_clone = stats.clone();
+        protolens.set_cb_pop3_stat(move |count: u32, size: u64, _cb_ctx: *mut c_void| {
+            stats_clone.borrow_mut().push((count, size));
+        });
+        let lists_clone = lists.clone();
+        protolens.set_cb_pop3_list(move |num: u32, size: u64, _cb_ctx: *mut c_void| {
+            lists_clone.borrow_mut().push((num, size));
+        });
+        let uidls_clone = uidls.clone();
+        protolens.set_cb_pop3_uidl(move |num: u32, uid: &[u8], _cb_ctx: *mut c_void| {
+            uidls_clone.borrow_mut().push((num, uid.to_vec()));
+        });
+        let msgs_clone = msgs.clone();
+        protolens.set_cb_pop3_msg(
+            move |num: u32, uid: Option<&[u8]>, partial: bool, _cb_ctx: *mut c_void| {
+                msgs_clone
+                    .borrow_mut()
+                    .push((num, uid.map(|u| u.to_vec()), partial));
+            },
+        );
+        let bodies_clone = bodies.clone();
+        protolens.set_cb_pop3_body(
+            move |body: &[u8],
+                  _seq: u32,
+                  _cb_ctx: *mut c_void,
+                  _dir: Direction,
+                  _te: Option<TransferEncoding>| {
+                bodies_clone.borrow_mut().push(body.to_vec());
+            },
+        );
+        let headers_clone = headers.clone();
+        protolens.set_cb_pop3_header(
+            move |header: &[u8], _seq: u32, _cb_ctx: *mut c_void, _dir: Direction| {
+                headers_clone.borrow_mut().push(header.to_vec());
+            },
+        );
+
+        run_session(&mut protolens, L7Proto::Pop3, POP3_PORT, &session, false);
+
+        assert_eq!(*users.borrow(), vec![b"tim".to_vec()]);
+        assert_eq!(
+            *passes.borrow(),
+            vec![b"secret".to_vec(), b"tanstaaf".to_vec()]
+        );
+        assert_eq!(
+            *capas.borrow(),
+            vec![
+                b"TOP".to_vec(),
+                b"UIDL".to_vec(),
+                b"SASL PLAIN LOGIN".to_vec()
+            ]
+        );
+        assert_eq!(*stats.borrow(), vec![(2, 320)]);
+        assert_eq!(*lists.borrow(), vec![(1, 120), (2, 200), (2, 200)]);
+        assert_eq!(
+            *uidls.borrow(),
+            vec![
+                (1, b"whqtswO00WBw418f9t5JxYwZ".to_vec()),
+                (2, b"QhdPYR:00WBw1Ph7x7".to_vec())
+            ]
+        );
+        assert_eq!(
+            *msgs.borrow(),
+            vec![
+                (2, Some(b"QhdPYR:00WBw1Ph7x7".to_vec()), false),
+                (1, Some(b"whqtswO00WBw418f9t5JxYwZ".to_vec()), true)
+            ]
+        );
+        assert_eq!(
+            *headers.borrow(),
+            vec![
+                b"Subject: hi\r\n".to_vec(),
+                b"\r\n".to_vec(),
+                b"Content-Type: multipart/mixed; boundary=\"xx\"\r\n".to_vec(),
+                b"\r\n".to_vec()
+            ]
+        );
+        assert_eq!(
+            *bodies.borrow(),
+            vec![
+                b"body line\r\n".to_vec(),
+                b".dotted line\r\n".to_vec(),
+                b"--xx\r\n".to_vec()
+            ]
+        );
+
+        let replies = replies.borrow();
+        assert_eq!(replies.len(), 11);
+        assert_eq!(
+            replies[0],
+            (
+                Pop3Cmd::Capa,
+                "".to_string(),
+                true,
+                "Capability list follows".to_string()
+            )
+        );
+        assert_eq!(
+            replies[2],
+            (
+                Pop3Cmd::Pass,
+                "secret".to_string(),
+                false,
+                "invalid password".to_string()
+            )
+        );
+        assert_eq!(replies[3].0, Pop3Cmd::Pass);
+        assert!(replies[3].2);
+        assert_eq!(
+            replies[6],
+            (Pop3Cmd::List, "2".to_string(), true, "2 200".to_string())
+        );
+        assert_eq!(
+            replies[8],
+            (
+                Pop3Cmd::Retr,
+                "2".to_string(),
+                true,
+                "200 octets".to_string()
+            )
+        );
+        assert_eq!(replies[9].0, Pop3Cmd::Top);
+        assert_eq!(replies[10].0, Pop3Cmd::Quit);
+    }
+
+    #[test]
+    fn test_pop3_auth() {
+        let session = [
+            (
+                false,
+                "+OK POP3 server ready <1896.697170952@dbc.mtview.ca.us>\r\n",
+            ),
+            (true, "APOP mrose c4c9334bac560ecc979e58001b3e22fb\r\n"),
+            (false, "-ERR permission denied\r\n"),
+            (true, "AUTH PLAIN\r\n"),
+            (false, "+ \r\n"),
+            (true, "AHRpbQB0YW5zdGFhZnRhbnN0YWFm\r\n"),
+            (false, "-ERR authentication failed\r\n"),
+            (true, "AUTH LOGIN\r\n"),
+            (false, "+ VXNlcm5hbWU6\r\n"),
+            (true, "dGlt\r\n"),
+            (false, "+ UGFzc3dvcmQ6\r\n"),
+            (true, "dGFuc3RhYWY=\r\n"),
+            (false, "+OK maildrop locked\r\n"),
+            (true, "STAT\r\n"),
+            (false, "+OK 0 0\r\n"),
+        ];
+
+        let apops = Rc::new(RefCell::new(Vec::<(Vec<u8>, Vec<u8>)>::new()));
+        let auths = Rc::new(RefCell::new(Vec::<(Vec<u8>, Vec<u8>)>::new()));
+        let creds = Rc::new(RefCell::new(Vec::<Vec<u8>>::new()));
+        let replies = Rc::new(RefCell::new(Vec::<(Pop3Cmd, bool)>::new()));
+
+        let mut protolens = Prolens::<CapPacket>::default();
+        let apops_clone = apops.clone();
+        protolens.set_cb_pop3_apop(
+            move |user: &[u8], digest: &[u8], _seq: u32, _cb_ctx: *mut c_void| {
+                apops_clone
+                    .borrow_mut()
+                    .push((user.to_vec(), digest.to_vec()));
+            },
+        );
+        let auths_clone = auths.clone();
+        protolens.set_cb_pop3_auth(
+            move |mech: &[u8], resp: &[u8], _seq: u32, _cb_ctx: *mut c_void| {
+                auths_clone
+                    .borrow_mut()
+                    .push((mech.to_vec(), resp.to_vec()));
+            },
+        );
+        let creds_clone = creds.clone();
+        protolens.set_cb_pop3_user(move |user: &[u8], _seq: u32, _cb_ctx: *mut c_void| {
+            creds_clone.borrow_mut().push(user.to_vec());
+        });
+        let creds_clone = creds.clone();
+        protolens.set_cb_pop3_pass(move |pass: &[u8], _seq: u32, _cb_ctx: *mut c_void| {
+            creds_clone.borrow_mut().push(pass.to_vec());
+        });
+        let replies_clone = replies.clone();
+        protolens.set_cb_pop3_reply(
+            move |cmd: Pop3Cmd,
+                  _arg: &[u8],
+                  ok: bool,
+                  _text: &[u8],
+                  _seq: u32,
+                  _cb_ctx: *mut c_void| {
+                replies_clone.borrow_mut().push((cmd, ok));
+            },
+        );
+
+        run_session(&mut protolens, L7Proto::Pop3, POP3_PORT, &session, false);
+
+        assert_eq!(
+            *apops.borrow(),
+            vec![(
+                b"mrose".to_vec(),
+                b"c4c9334bac560ecc979e58001b3e22fb".to_vec()
+            )]
+        );
+        assert_eq!(
+            *auths.borrow(),
+            vec![
+                (b"PLAIN".to_vec(), b"AHRpbQB0YW5zdGFhZnRhbnN0YWFm".to_vec()),
+                (b"LOGIN".to_vec(), b"dGlt".to_vec()),
+                (b"LOGIN".to_vec(), b"dGFuc3RhYWY=".to_vec()),
+            ]
+        );
+        assert_eq!(
+            *creds.borrow(),
+            vec![
+                b"tim".to_vec(),
+                b"tanstaaftanstaaf".to_vec(),
+                b"tim".to_vec(),
+                b"tanstaaf".to_vec()
+            ]
+        );
+        assert_eq!(
+            *replies.borrow(),
+            vec![
+                (Pop3Cmd::Apop, false),
+                (Pop3Cmd::Auth, false),
+                (Pop3Cmd::Auth, true),
+                (Pop3Cmd::Stat, true)
+            ]
+        );
+    }
 }
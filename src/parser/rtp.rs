@@ -0,0 +1,857 @@
+// RTP/RTCP(RFC 3550)。同一个端口上按第二个字节区分RTP和RTCP(RFC 5761 rtcp-mux)。
+// 每个方向按SSRC统计丢包、乱序和到达抖动，定期报告一次，收到这个SSRC的RTCP BYE时再报告一次
+use crate::CbRtcpBye;
+use crate::CbRtcpReport;
+use crate::CbRtcpSdes;
+use crate::CbRtpPkt;
+use crate::CbRtpStats;
+use crate::Parser;
+use crate::ParserFactory;
+use crate::Prolens;
+use crate::UdpParser;
+use crate::UdpParserFn;
+use crate::packet::*;
+use crate::static_clock_rate;
+use byteorder::{BigEndian, ByteOrder};
+use std::cell::RefCell;
+use std::ffi::c_void;
+use std::marker::PhantomData;
+use std::rc::Rc;
+
+const MAX_RTP_SSRC: usize = 16;
+const MAX_RTCP_BLOCKS: usize = 31;
+const MAX_DROPOUT: u16 = 3000;
+const MAX_MISORDER: u16 = 100;
+const RTP_REPORT_MS: u64 = 5000;
+const RTP_REPORT_PKTS: u64 = 500; // 没有包时间戳时按包数
+
+const RTCP_SR: u8 = 200;
+const RTCP_RR: u8 = 201;
+const RTCP_SDES: u8 = 202;
+const RTCP_BYE: u8 = 203;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RtpHeader {
+    pub marker: bool,
+    pub payload_type: u8,
+    pub seq: u16,
+    pub timestamp: u32,
+    pub ssrc: u32,
+    pub csrc_count: u8,
+    pub extension: bool,
+}
+
+// lost是累计值，乱序或重复时可能为负。jitter是RFC 3550的到达抖动，单位是毫秒，
+// 不知道时钟频率或者没有包时间戳时为0
+#[derive(Debug, Clone, PartialEq)]
+pub struct RtpStats<'a> {
+    pub call_id: Option<&'a str>,
+    pub ssrc: u32,
+    pub payload_type: u8,
+    pub clock_rate: u32,
+    pub packets: u64,
+    pub expected: u64,
+    pub lost: i64,
+    pub reordered: u64,
+    pub jitter: f64,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RtcpSender {
+    pub ntp: u64,
+    pub rtp_ts: u32,
+    pub packets: u32,
+    pub octets: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RtcpBlock {
+    pub ssrc: u32,
+    pub fraction_lost: u8,
+    pub lost: i32,
+    pub highest_seq: u32,
+    pub jitter: u32, // 时间戳单位
+    pub lsr: u32,
+    pub dlsr: u32,
+}
+
+// SR有sender，RR没有
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RtcpReport<'a> {
+    pub ssrc: u32,
+    pub sender: Option<RtcpSender>,
+    pub blocks: &'a [RtcpBlock],
+}
+
+// SIP/SDP预测的媒体流，rates是SDP中payload type对应的时钟频率
+#[derive(Debug, Default)]
+pub(crate) struct RtpMedia {
+    pub(crate) call_id: Option<String>,
+    pub(crate) rates: Vec<(u8, u32)>,
+}
+
+pub(crate) type RtpMediaRef = Rc<RtpMedia>;
+
+#[derive(Clone)]
+pub(crate) struct RtpCallbacks {
+    pkt: Option<CbRtpPkt>,
+    stats: Option<CbRtpStats>,
+    report: Option<CbRtcpReport>,
+    sdes: Option<CbRtcpSdes>,
+    bye: Option<CbRtcpBye>,
+}
+
+struct RtpStream {
+    ssrc: u32,
+    payload_type: u8,
+    clock_rate: u32,
+    base_seq: u16,
+    max_seq: u16,
+    cycles: u64,
+    bad_seq: Option<u16>, // 大的跳变之后期待的下一个序号
+    received: u64,
+    reordered: u64,
+    transit: Option<u32>,
+    jitter: f64, // 时间戳单位
+    report_ts: u64,
+    report_pkts: u64,
+}
+
+impl RtpStream {
+    fn new(hdr: &RtpHeader, clock_rate: u32, ts: u64) -> Self {
+        RtpStream {
+            ssrc: hdr.ssrc,
+            payload_type: hdr.payload_type,
+            clock_rate,
+            base_seq: hdr.seq,
+            max_seq: hdr.seq,
+            cycles: 0,
+            bad_seq: None,
+            received: 0,
+            reordered: 0,
+            transit: None,
+            jitter: 0.0,
+            report_ts: ts,
+            report_pkts: 0,
+        }
+    }
+
+    // RFC 3550 A.1。大的跳变先不计数，紧接着的下一个包也连续时才认为对端重新开始了。
+    // 返回false表示这个包被丢弃
+    fn update_seq(&mut self, seq: u16) -> bool {
+        let delta = seq.wrapping_sub(self.max_seq);
+        if self.received > 0 {
+            if delta == 0 || delta > u16::MAX - MAX_MISORDER {
+                self.reordered += 1;
+            } else if delta < MAX_DROPOUT {
+                if seq < self.max_seq {
+                    self.cycles += 1 << 16;
+                }
+                self.max_seq = seq;
+            } else if self.bad_seq == Some(seq) {
+                self.base_seq = seq;
+                self.max_seq = seq;
+                self.cycles = 0;
+                self.received = 0;
+                self.reordered = 0;
+                self.report_pkts = 0;
+                self.transit = None;
+            } else {
+                self.bad_seq = Some(seq.wrapping_add(1));
+                return false;
+            }
+        }
+        self.bad_seq = None;
+        self.received += 1;
+        true
+    }
+
+    // RFC 3550 A.8，到达时间换算成时间戳单位
+    fn update_jitter(&mut self, rtp_ts: u32, ts: u64) {
+        if self.clock_rate == 0 || ts == 0 {
+            return;
+        }
+        // 和rtp时间戳一样按32位回绕
+        let arrival = (ts as u128 * self.clock_rate as u128 / 1000) as u32;
+        let transit = arrival.wrapping_sub(rtp_ts);
+        if let Some(last) = self.transit {
+            let d = (transit.wrapping_sub(last) as i32).unsigned_abs() as f64;
+            self.jitter += (d - self.jitter) / 16.0;
+        }
+        self.transit = Some(transit);
+    }
+
+    fn expected(&self) -> u64 {
+        self.cycles + self.max_seq as u64 - self.base_seq as u64 + 1
+    }
+
+    fn due(&self, ts: u64) -> bool {
+        if ts == 0 {
+            self.received - self.report_pkts >= RTP_REPORT_PKTS
+        } else {
+            ts.saturating_sub(self.report_ts) >= RTP_REPORT_MS
+        }
+    }
+
+    fn report(
+        &mut self,
+        call_id: Option<&str>,
+        cb: &CbRtpStats,
+        ts: u64,
+        cb_ctx: *mut c_void,
+        dir: Direction,
+    ) {
+        let expected = self.expected();
+        let jitter = if self.clock_rate == 0 {
+            0.0
+        } else {
+            self.jitter * 1000.0 / self.clock_rate as f64
+        };
+        let stats = RtpStats {
+            call_id,
+            ssrc: self.ssrc,
+            payload_type: self.payload_type,
+            clock_rate: self.clock_rate,
+            packets: self.received,
+            expected,
+            lost: expected as i64 - self.received as i64,
+            reordered: self.reordered,
+            jitter,
+        };
+        cb.borrow_mut()(&stats, cb_ctx, dir);
+        self.report_ts = ts;
+        self.report_pkts = self.received;
+    }
+}
+
+// 一个方向的状态
+struct RtpState {
+    dir: Direction,
+    cb: RtpCallbacks,
+    media: Option<RtpMediaRef>,
+    streams: Vec<RtpStream>,
+}
+
+impl RtpState {
+    fn parse(&mut self, data: &[u8], ts: u64, cb_ctx: *mut c_void) -> Result<(), ()> {
+        // 不是版本2的包忽略，同一个端口上可能还有STUN/DTLS
+        if data.len() < 8 || data[0] >> 6 != 2 {
+            return Ok(());
+        }
+        // 格式错误的包跳过，不结束整个流
+        let _ = if (192..=223).contains(&data[1]) {
+            self.rtcp(data, ts, cb_ctx)
+        } else {
+            self.rtp(data, ts, cb_ctx)
+        };
+        Ok(())
+    }
+
+    fn rtp(&mut self, data: &[u8], ts: u64, cb_ctx: *mut c_void) -> Result<(), ()> {
+        if data.len() < 12 {
+            return Err(());
+        }
+        let hdr = RtpHeader {
+            marker: data[1] & 0x80 != 0,
+            payload_type: data[1] & 0x7f,
+            seq: BigEndian::read_u16(&data[2..4]),
+            timestamp: BigEndian::read_u32(&data[4..8]),
+            ssrc: BigEndian::read_u32(&data[8..12]),
+            csrc_count: data[0] & 0x0f,
+            extension: data[0] & 0x10 != 0,
+        };
+        let mut start = 12 + hdr.csrc_count as usize * 4;
+        if hdr.extension {
+            let ext = data.get(start..start + 4).ok_or(())?;
+            start += 4 + BigEndian::read_u16(&ext[2..4]) as usize * 4;
+        }
+        let mut end = data.len();
+        if data[0] & 0x20 != 0 {
+            end = end.checked_sub(data[end - 1] as usize).ok_or(())?;
+        }
+        let payload = data.get(start..end).ok_or(())?;
+
+        if let Some(ref cb) = self.cb.pkt {
+            cb.borrow_mut()(&hdr, payload, cb_ctx, self.dir);
+        }
+        let Some(ref cb) = self.cb.stats else {
+            return Ok(());
+        };
+
+        let rate = self.clock_rate(hdr.payload_type);
+        let index = match self.streams.iter().position(|s| s.ssrc == hdr.ssrc) {
+            Some(index) => index,
+            None => {
+                if self.streams.len() >= MAX_RTP_SSRC {
+                    return Ok(());
+                }
+                self.streams.push(RtpStream::new(&hdr, rate, ts));
+                self.streams.len() - 1
+            }
+        };
+        let call_id = self.media.as_ref().and_then(|m| m.call_id.as_deref());
+        let stream = &mut self.streams[index];
+        // 中途换了编码，比如切到舒适噪声
+        if stream.payload_type != hdr.payload_type {
+            stream.payload_type = hdr.payload_type;
+            if rate != stream.clock_rate {
+                stream.clock_rate = rate;
+                stream.transit = None;
+            }
+        }
+        if !stream.update_seq(hdr.seq) {
+            return Ok(());
+        }
+        stream.update_jitter(hdr.timestamp, ts);
+        if stream.due(ts) {
+            stream.report(call_id, cb, ts, cb_ctx, self.dir);
+        }
+        Ok(())
+    }
+
+    fn clock_rate(&self, payload_type: u8) -> u32 {
+        self.media
+            .as_ref()
+            .and_then(|m| m.rates.iter().find(|(pt, _)| *pt == payload_type))
+            .map(|(_, rate)| *rate)
+            .or_else(|| static_clock_rate(payload_type))
+            .unwrap_or(0)
+    }
+
+    // 复合包，一个接一个
+    fn rtcp(&mut self, mut data: &[u8], ts: u64, cb_ctx: *mut c_void) -> Result<(), ()> {
+        while data.len() >= 4 {
+            if data[0] >> 6 != 2 {
+                return Err(());
+            }
+            let count = (data[0] & 0x1f) as usize;
+            let pt = data[1];
+            let len = (BigEndian::read_u16(&data[2..4]) as usize + 1) * 4;
+            let pkt = data.get(..len).ok_or(())?;
+            data = &data[len..];
+
+            let body = &pkt[4..];
+            match pt {
+                RTCP_SR => self.rtcp_report(body, count, true, cb_ctx)?,
+                RTCP_RR => self.rtcp_report(body, count, false, cb_ctx)?,
+                RTCP_SDES => self.rtcp_sdes(body, count, cb_ctx)?,
+                RTCP_BYE => self.rtcp_bye(body, count, ts, cb_ctx)?,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn rtcp_report(
+        &self,
+        body: &[u8],
+        count: usize,
+        sr: bool,
+        cb_ctx: *mut c_void,
+    ) -> Result<(), ()> {
+        let Some(ref cb) = self.cb.report else {
+            return Ok(());
+        };
+        let ssrc = BigEndian::read_u32(body.get(..4).ok_or(())?);
+        let mut rest = &body[4..];
+        let mut sender = None;
+        if sr {
+            let info = rest.get(..20).ok_or(())?;
+            sender = Some(RtcpSender {
+                ntp: BigEndian::read_u64(&info[..8]),
+                rtp_ts: BigEndian::read_u32(&info[8..12]),
+                packets: BigEndian::read_u32(&info[12..16]),
+                octets: BigEndian::read_u32(&info[16..20]),
+            });
+            rest = &rest[20..];
+        }
+
+        let mut blocks = Vec::with_capacity(count.min(MAX_RTCP_BLOCKS));
+        for _ in 0..count {
+            let block = rest.get(..24).ok_or(())?;
+            rest = &rest[24..];
+            // 24位有符号数
+            let lost = (BigEndian::read_u32(&block[4..8]) << 8) as i32 >> 8;
+            blocks.push(RtcpBlock {
+                ssrc: BigEndian::read_u32(&block[..4]),
+                fraction_lost: block[4],
+                lost,
+                highest_seq: BigEndian::read_u32(&block[8..12]),
+                jitter: BigEndian::read_u32(&block[12..16]),
+                lsr: BigEndian::read_u32(&block[16..20]),
+                dlsr: BigEndian::read_u32(&block[20..24]),
+            });
+        }
+        let report = RtcpReport {
+            ssrc,
+            sender,
+            blocks: &blocks,
+        };
+        cb.borrow_mut()(&report, cb_ctx, self.dir);
+        Ok(())
+    }
+
+    // 每个chunk是ssrc加若干个item，以类型0结束并补齐到4字节
+    fn rtcp_sdes(&self, body: &[u8], count: usize, cb_ctx: *mut c_void) -> Result<(), ()> {
+        let Some(ref cb) = self.cb.sdes else {
+            return Ok(());
+        };
+        let mut pos = 0;
+        for _ in 0..count {
+            let ssrc = BigEndian::read_u32(body.get(pos..pos + 4).ok_or(())?);
+            pos += 4;
+            loop {
+                let item = *body.get(pos).ok_or(())?;
+                if item == 0 {
+                    pos = (pos + 4) & !3;
+                    break;
+                }
+                let len = *body.get(pos + 1).ok_or(())? as usize;
+                let value = body.get(pos + 2..pos + 2 + len).ok_or(())?;
+                cb.borrow_mut()(ssrc, item, value, cb_ctx, self.dir);
+                pos += 2 + len;
+            }
+        }
+        Ok(())
+    }
+
+    fn rtcp_bye(
+        &mut self,
+        body: &[u8],
+        count: usize,
+        ts: u64,
+        cb_ctx: *mut c_void,
+    ) -> Result<(), ()> {
+        let ids = body.get(..count * 4).ok_or(())?;
+        let ssrcs: Vec<u32> = ids.chunks(4).map(BigEndian::read_u32).collect();
+        let reason = body
+            .get(count * 4)
+            .and_then(|&len| body.get(count * 4 + 1..count * 4 + 1 + len as usize));
+        if let Some(ref cb) = self.cb.bye {
+            cb.borrow_mut()(&ssrcs, reason, cb_ctx, self.dir);
+        }
+
+        if let Some(ref cb) = self.cb.stats {
+            let call_id = self.media.as_ref().and_then(|m| m.call_id.as_deref());
+            for stream in self.streams.iter_mut().filter(|s| ssrcs.contains(&s.ssrc)) {
+                stream.report(call_id, cb, ts, cb_ctx, self.dir);
+            }
+        }
+        self.streams.retain(|s| !ssrcs.contains(&s.ssrc));
+        Ok(())
+    }
+}
+
+pub struct RtpParser<T>
+where
+    T: Packet,
+{
+    cb: RtpCallbacks,
+    media: Option<RtpMediaRef>,
+    _phantom_t: PhantomData<T>,
+}
+
+impl<T> RtpParser<T>
+where
+    T: Packet + 'static,
+{
+    pub(crate) fn new() -> Self {
+        Self {
+            cb: RtpCallbacks {
+                pkt: None,
+                stats: None,
+                report: None,
+                sdes: None,
+                bye: None,
+            },
+            media: None,
+            _phantom_t: PhantomData,
+        }
+    }
+
+    fn dir_parser(&self, dir: Direction) -> UdpParserFn<T> {
+        struct ParserImpl<T> {
+            state: RefCell<RtpState>,
+            _phantom: PhantomData<T>,
+        }
+
+        impl<T> UdpParser for ParserImpl<T>
+        where
+            T: Packet,
+        {
+            type T = T;
+
+            fn parse(&self, pkt: Self::T, cb_ctx: *mut c_void) -> Result<(), ()> {
+                self.state
+                    .borrow_mut()
+                    .parse(pkt.payload(), pkt.timestamp(), cb_ctx)
+            }
+        }
+
+        Box::new(ParserImpl {
+            state: RefCell::new(RtpState {
+                dir,
+                cb: self.cb.clone(),
+                media: self.media.clone(),
+                streams: Vec::new(),
+            }),
+            _phantom: PhantomData,
+        })
+    }
+}
+
+impl<T> Parser for RtpParser<T>
+where
+    T: Packet + 'static,
+{
+    type T = T;
+
+    fn pkt_c2s_parser(&self) -> Option<UdpParserFn<T>> {
+        Some(self.dir_parser(Direction::C2s))
+    }
+
+    fn pkt_s2c_parser(&self) -> Option<UdpParserFn<T>> {
+        Some(self.dir_parser(Direction::S2c))
+    }
+}
+
+pub(crate) struct RtpFactory<T> {
+    _phantom_t: PhantomData<T>,
+}
+
+impl<T> RtpFactory<T>
+where
+    T: Packet + 'static,
+{
+    pub(crate) fn create_media(
+        prolens: &Prolens<T>,
+        media: Option<RtpMediaRef>,
+    ) -> Box<dyn Parser<T = T>> {
+        let mut parser = Box::new(RtpParser::new());
+        parser.cb = RtpCallbacks {
+            pkt: prolens.cb_rtp_pkt.clone(),
+            stats: prolens.cb_rtp_stats.clone(),
+            report: prolens.cb_rtcp_report.clone(),
+            sdes: prolens.cb_rtcp_sdes.clone(),
+            bye: prolens.cb_rtcp_bye.clone(),
+        };
+        parser.media = media;
+        parser
+    }
+}
+
+impl<T> ParserFactory<T> for RtpFactory<T>
+where
+    T: Packet + 'static,
+{
+    fn new() -> Self {
+        Self {
+            _phantom_t: PhantomData,
+        }
+    }
+
+    fn create(&self, prolens: &Prolens<T>) -> Box<dyn Parser<T = T>> {
+        Self::create_media(prolens, None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    fn rtp_pkt(seq: u16, ts: u32, ssrc: u32, pt: u8) -> Vec<u8> {
+        let mut pkt = vec![0x80, pt];
+        pkt.extend_from_slice(&seq.to_be_bytes());
+        pkt.extend_from_slice(&ts.to_be_bytes());
+        pkt.extend_from_slice(&ssrc.to_be_bytes());
+        pkt.extend_from_slice(&[0xd5; 160]);
+        pkt
+    }
+
+    fn run_udp(
+        protolens: &mut Prolens<CapPacket>,
+        task: &mut crate::Task<CapPacket>,
+        payload: &[u8],
+        ts: u64,
+    ) -> Option<Result<(), ()>> {
+        let mut pkt = build_udp_payload(payload, 30000, 40000, false);
+        pkt.timestamp = ts as u128;
+        let _ = pkt.decode();
+        protolens.run_task(task, pkt)
+    }
+
+    #[test]
+    fn test_rtp_jitter() {
+        let hdr = RtpHeader {
+            marker: false,
+            payload_type: 0,
+            seq: 1,
+            timestamp: 0,
+            ssrc: 1,
+            csrc_count: 0,
+            extension: false,
+        };
+        let mut stream = RtpStream::new(&hdr, 8000, 1000);
+        stream.update_jitter(u32::MAX - 159, 1000);
+        // 晚到10ms，就是80个时间戳单位，时间戳回绕
+        stream.update_jitter(0, 1030);
+        assert_eq!(stream.jitter, 5.0);
+        stream.update_jitter(160, 1040);
+        assert_eq!(stream.jitter, 5.0 + 75.0 / 16.0);
+    }
+
+    #[test]
+    fn test_rtp_bad_seq() {
+        let hdr = RtpHeader {
+            marker: false,
+            payload_type: 0,
+            seq: 100,
+            timestamp: 0,
+            ssrc: 1,
+            csrc_count: 0,
+            extension: false,
+        };
+        let mut stream = RtpStream::new(&hdr, 8000, 0);
+        assert!(stream.update_seq(100));
+        assert!(stream.update_seq(101));
+        // 单个跳变的包丢掉，之后回到原来的序号继续
+        assert!(!stream.update_seq(30000));
+        assert!(stream.update_seq(102));
+        assert_eq!(
+            (stream.base_seq, stream.received, stream.expected()),
+            (100, 3, 3)
+        );
+
+        // 连续两个跳变的包，从第二个开始重新计数
+        assert!(!stream.update_seq(40000));
+        assert!(stream.update_seq(40001));
+        assert!(stream.update_seq(40002));
+        assert_eq!(
+            (stream.base_seq, stream.received, stream.expected()),
+            (40001, 2, 2)
+        );
+
+        // 中间隔了别的包，不算连续
+        assert!(!stream.update_seq(10000));
+        assert!(stream.update_seq(40003));
+        assert!(!stream.update_seq(10001));
+        assert_eq!(stream.max_seq, 40003);
+    }
+
+    #[test]
+    fn test_rtp_malformed() {
+        let pkts = Rc::new(RefCell::new(Vec::new()));
+        let mut protolens = Prolens::<CapPacket>::default();
+        let p = pkts.clone();
+        protolens.set_cb_rtp_pkt(
+            move |hdr: &RtpHeader, _payload: &[u8], _ctx: *mut c_void, _dir: Direction| {
+                p.borrow_mut().push(hdr.seq);
+            },
+        );
+
+        let mut task = protolens.new_task(TransProto::Udp);
+        protolens.set_task_parser(&mut task, L7Proto::Rtp);
+        let short: &[u8] = &[0x80, 0, 0, 2, 0, 0, 0, 0, 0, 0];
+        let rtcp_len: &[u8] = &[0x80, 201, 0, 9, 0, 0, 0, 0];
+        let rtcp_ver: &[u8] = &[0x80, 201, 0, 1, 0, 0, 0, 0, 0x40, 200, 0, 0];
+        let ret = run_udp(&mut protolens, &mut task, &rtp_pkt(1, 160, 0x1234, 0), 1000);
+        assert_eq!(ret, Some(Ok(())));
+        // 不到12字节的RTP、长度超出或者版本不对的RTCP都跳过，不报错
+        for (i, pkt) in [short, rtcp_len, rtcp_ver].iter().enumerate() {
+            let ret = run_udp(&mut protolens, &mut task, pkt, 1020 + i as u64);
+            assert_eq!(ret, Some(Ok(())));
+        }
+        let ret = run_udp(&mut protolens, &mut task, &rtp_pkt(2, 320, 0x1234, 0), 1040);
+        assert_eq!(ret, Some(Ok(())));
+
+        assert_eq!(*pkts.borrow(), vec![1, 2]);
+    }
+
+    #[test]
+    fn test_rtp_stats() {
+        let stats = Rc::new(RefCell::new(Vec::new()));
+        let byes = Rc::new(RefCell::new(Vec::new()));
+        let markers = Rc::new(RefCell::new(0));
+
+        let mut protolens = Prolens::<CapPacket>::default();
+        let s = stats.clone();
+        protolens.set_cb_rtp_stats(move |st: &RtpStats, _ctx: *mut c_void, dir: Direction| {
+            assert_eq!(dir, Direction::C2s);
+            s.borrow_mut().push((
+                st.ssrc,
+                st.clock_rate,
+                st.packets,
+                st.expected,
+                st.lost,
+                st.reordered,
+                st.jitter,
+            ));
+        });
+        let b = byes.clone();
+        protolens.set_cb_rtcp_bye(
+            move |ssrcs: &[u32], reason: Option<&[u8]>, _ctx: *mut c_void, _dir: Direction| {
+                b.borrow_mut()
+                    .push((ssrcs.to_vec(), reason.map(|r| r.to_vec())));
+            },
+        );
+        let m = markers.clone();
+        protolens.set_cb_rtp_pkt(
+            move |hdr: &RtpHeader, payload: &[u8], _ctx: *mut c_void, _dir: Direction| {
+                assert_eq!(payload.len(), 160);
+                if hdr.marker {
+                    *m.borrow_mut() += 1;
+                }
+            },
+        );
+
+        let mut task = protolens.new_task(TransProto::Udp);
+        protolens.set_task_parser(&mut task, L7Proto::Rtp);
+        // 4丢了，6和7乱序
+        for (seq, ms) in [
+            (1, 1000),
+            (2, 1020),
+            (3, 1040),
+            (5, 1080),
+            (7, 1120),
+            (6, 1140),
+            (8, 1160),
+            (9, 6200),
+        ] {
+            let mut pkt = rtp_pkt(seq, seq as u32 * 160, 0x1234, 0);
+            if seq == 1 {
+                pkt[1] |= 0x80;
+            }
+            run_udp(&mut protolens, &mut task, &pkt, ms);
+        }
+        // 另一个ssrc的数据不影响
+        run_udp(&mut protolens, &mut task, &rtp_pkt(100, 0, 0x9999, 8), 6210);
+
+        // rtcp-mux，RR + BYE
+        let mut rtcp = vec![0x80, 201, 0, 1];
+        rtcp.extend_from_slice(&0x1234u32.to_be_bytes());
+        rtcp.extend_from_slice(&[0x81, 203, 0, 2]);
+        rtcp.extend_from_slice(&0x1234u32.to_be_bytes());
+        rtcp.extend_from_slice(&[3, b'b', b'y', b'e']);
+        run_udp(&mut protolens, &mut task, &rtcp, 6300);
+
+        let stats = stats.borrow();
+        assert_eq!(stats.len(), 2);
+        for &(ssrc, clock_rate, packets, expected, lost, reordered, jitter) in stats.iter() {
+            assert_eq!((ssrc, clock_rate), (0x1234, 8000));
+            assert_eq!((packets, expected, lost, reordered), (8, 9, 1, 1));
+            assert!(jitter > 0.0);
+        }
+        assert_eq!(*byes.borrow(), vec![(vec![0x1234], Some(b"bye".to_vec()))]);
+        assert_eq!(*markers.borrow(), 1);
+    }
+
+    #[test]
+    fn test_rtcp_sr_sdes() {
+        let reports = Rc::new(RefCell::new(Vec::new()));
+        let items = Rc::new(RefCell::new(Vec::new()));
+
+        let mut protolens = Prolens::<CapPacket>::default();
+        let r = reports.clone();
+        protolens.set_cb_rtcp_report(
+            move |report: &RtcpReport, _ctx: *mut c_void, _dir: Direction| {
+                r.borrow_mut()
+                    .push((report.ssrc, report.sender, report.blocks.to_vec()));
+            },
+        );
+        let i = items.clone();
+        protolens.set_cb_rtcp_sdes(
+            move |ssrc: u32, item: u8, value: &[u8], _ctx: *mut c_void, _dir: Direction| {
+                i.borrow_mut().push((ssrc, item, value.to_vec()));
+            },
+        );
+
+        // SR带一个report block
+        let mut rtcp = vec![0x81, 200, 0, 12];
+        rtcp.extend_from_slice(&1u32.to_be_bytes());
+        rtcp.extend_from_slice(&0x0102030405060708u64.to_be_bytes());
+        rtcp.extend_from_slice(&160u32.to_be_bytes());
+        rtcp.extend_from_slice(&10u32.to_be_bytes());
+        rtcp.extend_from_slice(&1600u32.to_be_bytes());
+        rtcp.extend_from_slice(&2u32.to_be_bytes());
+        rtcp.extend_from_slice(&[64, 0xff, 0xff, 0xfe]);
+        rtcp.extend_from_slice(&65546u32.to_be_bytes());
+        rtcp.extend_from_slice(&12u32.to_be_bytes());
+        rtcp.extend_from_slice(&0x11u32.to_be_bytes());
+        rtcp.extend_from_slice(&0x22u32.to_be_bytes());
+        // SDES: CNAME和TOOL，结束后补齐
+        rtcp.extend_from_slice(&[0x81, 202, 0, 4]);
+        rtcp.extend_from_slice(&1u32.to_be_bytes());
+        rtcp.extend_from_slice(&[1, 3, b'a', b'@', b'b', 6, 2, b'x', b'y', 0, 0, 0]);
+
+        let mut task = protolens.new_task(TransProto::Udp);
+        protolens.set_task_parser(&mut task, L7Proto::Rtp);
+        run_udp(&mut protolens, &mut task, &rtcp, 0);
+
+        assert_eq!(
+            *reports.borrow(),
+            vec![(
+                1,
+                Some(RtcpSender {
+                    ntp: 0x0102030405060708,
+                    rtp_ts: 160,
+                    packets: 10,
+                    octets: 1600,
+                }),
+                vec![RtcpBlock {
+                    ssrc: 2,
+                    fraction_lost: 64,
+                    lost: -2,
+                    highest_seq: 65546,
+                    jitter: 12,
+                    lsr: 0x11,
+                    dlsr: 0x22,
+                }]
+            )]
+        );
+        assert_eq!(
+            *items.borrow(),
+            vec![(1, 1, b"a@b".to_vec()), (1, 6, b"xy".to_vec())]
+        );
+    }
+
+    #[test]
+    fn test_rtp_expect() {
+        let stats = Rc::new(RefCell::new(Vec::new()));
+
+        let mut protolens = Prolens::<CapPacket>::default();
+        let s = stats.clone();
+        protolens.set_cb_rtp_stats(move |st: &RtpStats, _ctx: *mut c_void, _dir: Direction| {
+            s.borrow_mut()
+                .push((st.call_id.map(str::to_string), st.clock_rate, st.packets));
+        });
+
+        let body = "v=0\r\no=- 1 1 IN IP4 192.168.1.2\r\ns=-\r\nc=IN IP4 192.168.1.2\r\n\
+            t=0 0\r\nm=audio 40000 RTP/AVP 111\r\na=rtpmap:111 opus/48000/2\r\n";
+        let invite = format!(
+            "INVITE sip:bob@b.com SIP/2.0\r\nCall-ID: c1@a.com\r\n\
+            Content-Type: application/sdp\r\nContent-Length: {}\r\n\r\n{body}",
+            body.len()
+        );
+        let mut sip_task = protolens.new_task(TransProto::Udp);
+        protolens.set_task_parser(&mut sip_task, L7Proto::Sip);
+        let pkt = build_udp_payload(invite.as_bytes(), 5060, 5060, false);
+        let _ = pkt.decode();
+        protolens.run_task(&mut sip_task, pkt);
+        assert_eq!(protolens.expect.borrow().len(), 2);
+
+        // 没有设置parser的task，第一个包命中预期的地址
+        let mut task = protolens.new_task(TransProto::Udp);
+        for seq in 0..RTP_REPORT_PKTS as u16 {
+            run_udp(&mut protolens, &mut task, &rtp_pkt(seq, 0, 7, 111), 0);
+        }
+        assert_eq!(protolens.expect.borrow().len(), 1);
+        assert_eq!(
+            *stats.borrow(),
+            vec![(Some("c1@a.com".to_string()), 48000, RTP_REPORT_PKTS)]
+        );
+    }
+}
@@ -0,0 +1,358 @@
+// SIP消息体中的SDP(RFC 8866)。只取会话名、连接地址、媒体行和rtpmap/方向属性
+use std::net::IpAddr;
+
+const MAX_SDP_MEDIA: usize = 16;
+const MAX_SDP_CODEC: usize = 32;
+
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum SdpDir {
+    #[default]
+    SendRecv,
+    SendOnly,
+    RecvOnly,
+    Inactive,
+}
+
+impl SdpDir {
+    fn from_attr(attr: &str) -> Option<Self> {
+        match attr {
+            "sendrecv" => Some(SdpDir::SendRecv),
+            "sendonly" => Some(SdpDir::SendOnly),
+            "recvonly" => Some(SdpDir::RecvOnly),
+            "inactive" => Some(SdpDir::Inactive),
+            _ => None,
+        }
+    }
+}
+
+// 静态payload type没有rtpmap时按RFC 3551补上
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SdpCodec<'a> {
+    pub payload_type: u8,
+    pub name: &'a str,
+    pub clock_rate: u32,
+    pub channels: u8,
+}
+
+// conn和dir已经合并了会话级的值。port为0表示媒体被拒绝。
+// rtcp_port来自a=rtcp，rtcp-mux时和port相同，否则是port+1
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SdpMedia<'a> {
+    pub media: &'a str,
+    pub port: u16,
+    pub port_count: u16,
+    pub proto: &'a str,
+    pub conn: Option<IpAddr>,
+    pub rtcp_port: u16,
+    pub dir: SdpDir,
+    pub codecs: Vec<SdpCodec<'a>>,
+}
+
+// 一对预期的RTP/RTCP地址，由SDP中的媒体行得到。call_id用来和呼叫事件关联
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SipMediaLink<'a> {
+    pub call_id: Option<&'a str>,
+    pub media: &'a str,
+    pub ip: IpAddr,
+    pub rtp_port: u16,
+    pub rtcp_port: u16,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Sdp<'a> {
+    pub origin: Option<&'a str>,
+    pub session_name: Option<&'a str>,
+    pub conn: Option<IpAddr>,
+    pub media: Vec<SdpMedia<'a>>,
+}
+
+static STATIC_CODECS: [(u8, &str, u32); 11] = [
+    (0, "PCMU", 8000),
+    (3, "GSM", 8000),
+    (4, "G723", 8000),
+    (5, "DVI4", 8000),
+    (6, "DVI4", 16000),
+    (8, "PCMA", 8000),
+    (9, "G722", 8000),
+    (13, "CN", 8000),
+    (15, "G728", 8000),
+    (18, "G729", 8000),
+    (34, "H263", 90000),
+];
+
+pub(crate) fn static_clock_rate(payload_type: u8) -> Option<u32> {
+    STATIC_CODECS
+        .iter()
+        .find(|c| c.0 == payload_type)
+        .map(|c| c.2)
+}
+
+struct MediaState<'a> {
+    media: SdpMedia<'a>,
+    fmts: Vec<u8>,
+    conn: Option<IpAddr>,
+    dir: Option<SdpDir>,
+    rtcp: Option<u16>,
+    rtcp_mux: bool,
+}
+
+impl<'a> Sdp<'a> {
+    pub(crate) fn parse(text: &'a str) -> Option<Self> {
+        let mut sdp = Sdp::default();
+        let mut session_dir = None;
+        let mut media: Vec<MediaState> = Vec::new();
+        let mut version = false;
+
+        for line in text.lines() {
+            let line = line.trim_end_matches('\r');
+            let Some((kind, value)) = line.split_once('=') else {
+                continue;
+            };
+            match kind {
+                "v" => version = true,
+                "o" if media.is_empty() => sdp.origin = Some(value),
+                "s" if media.is_empty() => sdp.session_name = Some(value),
+                "c" => {
+                    let addr = conn_addr(value);
+                    match media.last_mut() {
+                        Some(cur) => cur.conn = addr,
+                        None => sdp.conn = addr,
+                    }
+                }
+                "m" => {
+                    if media.len() < MAX_SDP_MEDIA
+                        && let Some(state) = media_line(value)
+                    {
+                        media.push(state);
+                    }
+                }
+                "a" => {
+                    let (name, arg) = value.split_once(':').unwrap_or((value, ""));
+                    match media.last_mut() {
+                        Some(cur) => media_attr(cur, name, arg),
+                        None => {
+                            if let Some(dir) = SdpDir::from_attr(name) {
+                                session_dir = Some(dir);
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        if !version {
+            return None;
+        }
+
+        for state in media {
+            let mut m = state.media;
+            m.conn = state.conn.or(sdp.conn);
+            m.dir = state.dir.or(session_dir).unwrap_or_default();
+            m.rtcp_port = match (state.rtcp, state.rtcp_mux) {
+                (Some(port), _) => port,
+                (None, true) => m.port,
+                (None, false) if m.port != 0 => m.port.wrapping_add(1),
+                _ => 0,
+            };
+            for pt in state.fmts {
+                if m.codecs.iter().any(|c| c.payload_type == pt) || m.codecs.len() >= MAX_SDP_CODEC
+                {
+                    continue;
+                }
+                if let Some(&(_, name, clock_rate)) = STATIC_CODECS.iter().find(|c| c.0 == pt) {
+                    m.codecs.push(SdpCodec {
+                        payload_type: pt,
+                        name,
+                        clock_rate,
+                        channels: 1,
+                    });
+                }
+            }
+            sdp.media.push(m);
+        }
+        Some(sdp)
+    }
+}
+
+impl<'a> SdpMedia<'a> {
+    // 被拒绝、inactive和hold(0.0.0.0)的媒体没有流。port_count大于1时端口按2递增
+    pub(crate) fn links(&self, call_id: Option<&'a str>) -> Vec<SipMediaLink<'a>> {
+        let Some(ip) = self.conn else {
+            return Vec::new();
+        };
+        if self.port == 0 || self.dir == SdpDir::Inactive || ip.is_unspecified() {
+            return Vec::new();
+        }
+        (0..self.port_count.min(MAX_SDP_MEDIA as u16))
+            .map(|i| SipMediaLink {
+                call_id,
+                media: self.media,
+                ip,
+                rtp_port: self.port.wrapping_add(i * 2),
+                rtcp_port: self.rtcp_port.wrapping_add(i * 2),
+            })
+            .collect()
+    }
+}
+
+// IN IP4 224.2.1.1/127，组播地址后面可能有ttl和个数
+fn conn_addr(value: &str) -> Option<IpAddr> {
+    let mut parts = value.split_ascii_whitespace();
+    if parts.next()? != "IN" {
+        return None;
+    }
+    let _addr_type = parts.next()?;
+    let addr = parts.next()?;
+    addr.split('/').next()?.parse().ok()
+}
+
+// audio 49170/2 RTP/AVP 0 8 97
+fn media_line(value: &str) -> Option<MediaState<'_>> {
+    let mut parts = value.split_ascii_whitespace();
+    let media = parts.next()?;
+    let port = parts.next()?;
+    let (port, count) = match port.split_once('/') {
+        Some((port, count)) => (port, count.parse().ok()?),
+        None => (port, 1),
+    };
+    let port = port.parse().ok()?;
+    let proto = parts.next()?;
+    let fmts = parts.filter_map(|fmt| fmt.parse().ok()).collect();
+    Some(MediaState {
+        media: SdpMedia {
+            media,
+            port,
+            port_count: count,
+            proto,
+            conn: None,
+            rtcp_port: 0,
+            dir: SdpDir::default(),
+            codecs: Vec::new(),
+        },
+        fmts,
+        conn: None,
+        dir: None,
+        rtcp: None,
+        rtcp_mux: false,
+    })
+}
+
+fn media_attr<'a>(state: &mut MediaState<'a>, name: &str, arg: &'a str) {
+    if let Some(dir) = SdpDir::from_attr(name) {
+        state.dir = Some(dir);
+        return;
+    }
+    match name {
+        "rtcp-mux" => state.rtcp_mux = true,
+        // a=rtcp:53020 IN IP4 126.16.64.4
+        "rtcp" => {
+            state.rtcp = arg
+                .split_ascii_whitespace()
+                .next()
+                .and_then(|p| p.parse().ok())
+        }
+        // a=rtpmap:97 speex/8000/1
+        "rtpmap" => {
+            let Some((pt, encoding)) = arg.split_once(' ') else {
+                return;
+            };
+            let Ok(payload_type) = pt.parse() else {
+                return;
+            };
+            let mut parts = encoding.trim().split('/');
+            let name = parts.next().unwrap_or("");
+            let clock_rate = parts.next().and_then(|r| r.parse().ok()).unwrap_or(0);
+            let channels = parts.next().and_then(|c| c.parse().ok()).unwrap_or(1);
+            let codecs = &mut state.media.codecs;
+            if codecs.len() < MAX_SDP_CODEC
+                && !codecs.iter().any(|c| c.payload_type == payload_type)
+            {
+                codecs.push(SdpCodec {
+                    payload_type,
+                    name,
+                    clock_rate,
+                    channels,
+                });
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn test_sdp_parse() {
+        let text = "v=0\r\n\
+            o=alice 2890844526 2890844526 IN IP4 host.a.com\r\n\
+            s=X-Lite\r\n\
+            c=IN IP4 10.0.0.1\r\n\
+            t=0 0\r\n\
+            a=sendonly\r\n\
+            m=audio 49170 RTP/AVP 0 8 97\r\n\
+            a=rtpmap:97 iLBC/8000\r\n\
+            a=rtpmap:8 pcma/8000\r\n\
+            m=video 51372/2 RTP/AVP 31\r\n\
+            c=IN IP4 224.2.1.1/127\r\n\
+            a=rtcp:53020 IN IP4 10.0.0.2\r\n\
+            a=recvonly\r\n\
+            m=audio 0 RTP/AVP 0\r\n\
+            m=audio 5004 RTP/SAVPF 111\r\n\
+            a=rtpmap:111 opus/48000/2\r\n\
+            a=rtcp-mux\r\n";
+        let sdp = Sdp::parse(text).unwrap();
+        assert_eq!(
+            sdp.origin,
+            Some("alice 2890844526 2890844526 IN IP4 host.a.com")
+        );
+        assert_eq!(sdp.session_name, Some("X-Lite"));
+        assert_eq!(sdp.conn, Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1))));
+        assert_eq!(sdp.media.len(), 4);
+
+        let audio = &sdp.media[0];
+        assert_eq!(audio.media, "audio");
+        assert_eq!(
+            (audio.port, audio.port_count, audio.rtcp_port),
+            (49170, 1, 49171)
+        );
+        assert_eq!(audio.proto, "RTP/AVP");
+        assert_eq!(audio.conn, sdp.conn);
+        assert_eq!(audio.dir, SdpDir::SendOnly);
+        let codecs: Vec<_> = audio
+            .codecs
+            .iter()
+            .map(|c| (c.payload_type, c.name, c.clock_rate))
+            .collect();
+        assert_eq!(
+            codecs,
+            vec![(97, "iLBC", 8000), (8, "pcma", 8000), (0, "PCMU", 8000)]
+        );
+
+        let video = &sdp.media[1];
+        assert_eq!(
+            (video.port, video.port_count, video.rtcp_port),
+            (51372, 2, 53020)
+        );
+        assert_eq!(video.conn, Some(IpAddr::V4(Ipv4Addr::new(224, 2, 1, 1))));
+        assert_eq!(video.dir, SdpDir::RecvOnly);
+        assert!(video.codecs.is_empty());
+
+        assert_eq!((sdp.media[2].port, sdp.media[2].rtcp_port), (0, 0));
+
+        let opus = &sdp.media[3];
+        assert_eq!(opus.rtcp_port, 5004);
+        assert_eq!(opus.codecs[0].channels, 2);
+
+        assert_eq!(Sdp::parse("hello world"), None);
+
+        let links = video.links(Some("abc"));
+        assert_eq!(links.len(), 2);
+        assert_eq!((links[1].rtp_port, links[1].rtcp_port), (51374, 53022));
+        assert_eq!(links[1].call_id, Some("abc"));
+        assert!(sdp.media[2].links(None).is_empty());
+    }
+}
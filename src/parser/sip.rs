@@ -1,18 +1,32 @@
 use crate::CbBodyEvt;
 use crate::CbHeader;
 use crate::CbSipBody;
+use crate::CbSipCall;
+use crate::CbSipMediaLink;
+use crate::CbSipSdp;
 use crate::CbStartLine;
+use crate::ExpectKey;
+use crate::ExpectRef;
+use crate::Expected;
 use crate::Parser;
 use crate::ParserFactory;
+use crate::ParserFuture;
+use crate::PktStrm;
 use crate::Prolens;
+use crate::RtpMedia;
+use crate::Sdp;
+use crate::SipDialogsRef;
+use crate::SipMsg;
 use crate::UdpParser;
 use crate::UdpParserFn;
-use crate::content_length;
 use crate::packet::*;
 use crate::pktdata::*;
 use phf::phf_set;
 use std::ffi::c_void;
 use std::marker::PhantomData;
+use std::rc::Rc;
+
+const MAX_SDP_LEN: usize = 64 * 1024;
 
 pub struct SipParser<T>
 where
@@ -23,6 +37,11 @@ where
     cb_body_start: Option<CbBodyEvt>,
     cb_body: Option<CbSipBody>,
     cb_body_stop: Option<CbBodyEvt>,
+    cb_call: Option<CbSipCall>,
+    cb_sdp: Option<CbSipSdp>,
+    cb_media_link: Option<CbSipMediaLink>,
+    dialogs: Option<SipDialogsRef>,
+    rtp_expect: Option<ExpectRef>,
     _phantom_t: PhantomData<T>,
 }
 
@@ -37,6 +56,11 @@ where
             cb_body_start: None,
             cb_body: None,
             cb_body_stop: None,
+            cb_call: None,
+            cb_sdp: None,
+            cb_media_link: None,
+            dialogs: None,
+            rtp_expect: None,
             _phantom_t: PhantomData,
         }
     }
@@ -47,81 +71,108 @@ where
             return Ok(());
         }
 
+        let ts = pkt.timestamp();
         let mut pktdata = PktData::new(pkt);
         while pktdata.remain_data() {
             let (start_line, offset) = pktdata.readline_str()?;
-            if let Some(ref cb) = cb_sip.start_line {
-                cb.borrow_mut()(start_line.as_bytes(), offset as u32, cb_ctx, dir);
-            }
+            cb_sip.start_line(start_line, offset as u32, dir, cb_ctx);
 
-            let header_ret = Self::header(&mut pktdata, dir, &cb_sip, cb_ctx)?;
-
-            if header_ret.content_len > 0 {
-                if let Some(ref cb) = cb_sip.body_start {
-                    cb.borrow_mut()(cb_ctx, dir);
+            let mut msg = SipMsg::new(start_line);
+            loop {
+                let (line, offset) = pktdata.readline_str()?;
+                if cb_sip.header(line, offset as u32, dir, cb_ctx, &mut msg) {
+                    break;
                 }
-                Self::body(&mut pktdata, header_ret.content_len, dir, &cb_sip, cb_ctx)?;
-                if let Some(ref cb) = cb_sip.body_stop {
-                    cb.borrow_mut()(cb_ctx, dir);
+            }
+            cb_sip.dialog(&msg, ts, cb_ctx);
+
+            let content_len = msg.content_len.unwrap_or(0);
+            if content_len > 0 {
+                cb_sip.body_start(dir, cb_ctx);
+                let (bytes, offset) = pktdata.readn(content_len)?;
+                cb_sip.body(bytes, offset as u32, dir, cb_ctx);
+                cb_sip.body_stop(dir, cb_ctx);
+                if cb_sip.want_sdp(&msg) {
+                    cb_sip.sdp(bytes, &msg, dir, cb_ctx);
                 }
             }
         }
         Ok(())
     }
 
-    fn pkt_dir(pkt: &T) -> Direction {
-        let payload = pkt.payload();
-        if req(unsafe { std::str::from_utf8_unchecked(payload) }) {
-            return Direction::C2s;
-        } else if rsp(unsafe { std::str::from_utf8_unchecked(payload) }) {
-            return Direction::S2c;
-        }
-        Direction::Unknown
-    }
-
-    fn header(
-        pktdata: &mut PktData<T>,
-        dir: Direction,
-        cb_sip: &SipCallbacks,
+    // tcp上一个段可能有多个消息，一个消息也可能跨段，按Content-Length分帧。
+    // 两端都可能发请求，dir和udp一样按起始行区分，请求是C2s，应答是S2c
+    async fn parser_inner(
+        strm: *mut PktStrm<T>,
+        cb_sip: SipCallbacks,
         cb_ctx: *mut c_void,
-    ) -> Result<HeaderRet, ()> {
-        let mut content_len = None;
+    ) -> Result<(), ()> {
+        let stm = unsafe { &mut *strm };
 
         loop {
-            let (line, offset) = pktdata.readline_str()?;
-
-            if let Some(ref cb) = cb_sip.header {
-                cb.borrow_mut()(line.as_bytes(), offset as u32, cb_ctx, dir);
+            let (start_line, seq) = stm.readline_str().await?;
+            // RFC 5626 的keepalive是空行
+            if start_line == "\r\n" || start_line == "\n" {
+                continue;
             }
+            let dir = msg_dir(start_line);
+            if dir == Direction::Unknown {
+                return Err(());
+            }
+            cb_sip.start_line(start_line, seq, dir, cb_ctx);
 
-            if line == "\r\n" {
-                break;
+            let mut msg = SipMsg::new(start_line);
+            loop {
+                let (line, seq) = stm.readline_str().await?;
+                if cb_sip.header(line, seq, dir, cb_ctx, &mut msg) {
+                    break;
+                }
             }
+            cb_sip.dialog(&msg, stm.timestamp(), cb_ctx);
 
-            if content_len.is_none() {
-                content_len = content_length(line);
+            let mut remain = msg.content_len.unwrap_or(0);
+            if remain == 0 {
+                continue;
+            }
+            // sdp可能跨段，需要的时候攒起来
+            let mut sdp = cb_sip.want_sdp(&msg).then(Vec::new);
+            cb_sip.body_start(dir, cb_ctx);
+            while remain > 0 {
+                let (bytes, seq) = stm.read(remain).await?;
+                remain -= bytes.len();
+                if let Some(ref mut buf) = sdp {
+                    if buf.len() + bytes.len() <= MAX_SDP_LEN {
+                        buf.extend_from_slice(bytes);
+                    } else {
+                        sdp = None;
+                    }
+                }
+                cb_sip.body(bytes, seq, dir, cb_ctx);
+            }
+            cb_sip.body_stop(dir, cb_ctx);
+            if let Some(buf) = sdp {
+                cb_sip.sdp(&buf, &msg, dir, cb_ctx);
             }
         }
-        Ok(HeaderRet {
-            content_len: content_len.unwrap_or(0),
-            content_type: ContentType::Sdp,
-        })
     }
 
-    fn body(
-        pktdata: &mut PktData<T>,
-        size: usize,
-        dir: Direction,
-        cb_sip: &SipCallbacks,
-        cb_ctx: *mut c_void,
-    ) -> Result<(), ()> {
-        let (bytes, offset) = pktdata.readn(size)?;
+    fn pkt_dir(pkt: &T) -> Direction {
+        msg_dir(unsafe { std::str::from_utf8_unchecked(pkt.payload()) })
+    }
 
-        if let Some(ref cb) = cb_sip.body {
-            cb.borrow_mut()(bytes, offset as u32, cb_ctx, dir);
+    fn callbacks(&self) -> SipCallbacks {
+        SipCallbacks {
+            start_line: self.cb_start_line.clone(),
+            header: self.cb_header.clone(),
+            body_start: self.cb_body_start.clone(),
+            body: self.cb_body.clone(),
+            body_stop: self.cb_body_stop.clone(),
+            call: self.cb_call.clone(),
+            sdp: self.cb_sdp.clone(),
+            media_link: self.cb_media_link.clone(),
+            dialogs: self.dialogs.clone(),
+            rtp_expect: self.rtp_expect.clone(),
         }
-
-        Ok(())
     }
 }
 
@@ -148,19 +199,19 @@ where
             }
         }
 
-        let cb_sip = SipCallbacks {
-            start_line: self.cb_start_line.clone(),
-            header: self.cb_header.clone(),
-            body_start: self.cb_body_start.clone(),
-            body: self.cb_body.clone(),
-            body_stop: self.cb_body_stop.clone(),
-        };
-
         Some(Box::new(ParserImpl {
-            cb_sip,
+            cb_sip: self.callbacks(),
             _phantom: PhantomData,
         }))
     }
+
+    fn c2s_parser(&self, strm: *mut PktStrm<T>, cb_ctx: *mut c_void) -> Option<ParserFuture> {
+        Some(Box::pin(Self::parser_inner(strm, self.callbacks(), cb_ctx)))
+    }
+
+    fn s2c_parser(&self, strm: *mut PktStrm<T>, cb_ctx: *mut c_void) -> Option<ParserFuture> {
+        Some(Box::pin(Self::parser_inner(strm, self.callbacks(), cb_ctx)))
+    }
 }
 
 pub(crate) struct SipFactory<T> {
@@ -184,6 +235,13 @@ where
         parser.cb_body_start = prolens.cb_sip_body_start.clone();
         parser.cb_body = prolens.cb_sip_body.clone();
         parser.cb_body_stop = prolens.cb_sip_body_stop.clone();
+        parser.cb_call = prolens.cb_sip_call.clone();
+        parser.cb_sdp = prolens.cb_sip_sdp.clone();
+        parser.cb_media_link = prolens.cb_sip_media_link.clone();
+        parser.dialogs = Some(prolens.sip_dialogs.clone());
+        if prolens.rtp_enabled() {
+            parser.rtp_expect = Some(prolens.expect.clone());
+        }
         parser
     }
 }
@@ -195,17 +253,126 @@ pub(crate) struct SipCallbacks {
     pub(crate) body_start: Option<CbBodyEvt>,
     pub(crate) body: Option<CbSipBody>,
     pub(crate) body_stop: Option<CbBodyEvt>,
+    pub(crate) call: Option<CbSipCall>,
+    pub(crate) sdp: Option<CbSipSdp>,
+    pub(crate) media_link: Option<CbSipMediaLink>,
+    pub(crate) dialogs: Option<SipDialogsRef>,
+    pub(crate) rtp_expect: Option<ExpectRef>,
 }
 
-#[derive(Debug, PartialEq, Eq, Clone)]
-enum ContentType {
-    Sdp,
+// udp和tcp共用
+impl SipCallbacks {
+    fn start_line(&self, line: &str, offset: u32, dir: Direction, cb_ctx: *mut c_void) {
+        if let Some(ref cb) = self.start_line {
+            cb.borrow_mut()(line.as_bytes(), offset, cb_ctx, dir);
+        }
+    }
+
+    // 返回true表示头部结束
+    fn header(
+        &self,
+        line: &str,
+        offset: u32,
+        dir: Direction,
+        cb_ctx: *mut c_void,
+        msg: &mut SipMsg,
+    ) -> bool {
+        if let Some(ref cb) = self.header {
+            cb.borrow_mut()(line.as_bytes(), offset, cb_ctx, dir);
+        }
+        if line == "\r\n" || line == "\n" {
+            return true;
+        }
+        let track = self.call.is_some() || self.media_link.is_some() || self.rtp_expect.is_some();
+        msg.header(line, track);
+        false
+    }
+
+    fn dialog(&self, msg: &SipMsg, ts: u64, cb_ctx: *mut c_void) {
+        if let Some(ref cb) = self.call
+            && let Some(ref dialogs) = self.dialogs
+        {
+            dialogs.borrow_mut().message(msg, ts, Some(cb), cb_ctx);
+        }
+    }
+
+    fn want_sdp(&self, msg: &SipMsg) -> bool {
+        msg.sdp && (self.sdp.is_some() || self.media_link.is_some() || self.rtp_expect.is_some())
+    }
+
+    fn sdp(&self, body: &[u8], msg: &SipMsg, dir: Direction, cb_ctx: *mut c_void) {
+        let Ok(text) = std::str::from_utf8(body) else {
+            return;
+        };
+        let Some(sdp) = Sdp::parse(text) else {
+            return;
+        };
+        if let Some(ref cb) = self.sdp {
+            cb.borrow_mut()(&sdp, cb_ctx, dir);
+        }
+        for media in &sdp.media {
+            let links = media.links(msg.call_id());
+            if let Some(ref cb) = self.media_link {
+                for link in &links {
+                    cb.borrow_mut()(link, cb_ctx, dir);
+                }
+            }
+            if let Some(ref expect) = self.rtp_expect
+                && !links.is_empty()
+            {
+                let rtp = Rc::new(RtpMedia {
+                    call_id: msg.call_id().map(str::to_string),
+                    rates: media
+                        .codecs
+                        .iter()
+                        .map(|c| (c.payload_type, c.clock_rate))
+                        .collect(),
+                });
+                let mut expect = expect.borrow_mut();
+                for link in &links {
+                    let mut key = ExpectKey {
+                        proto: TransProto::Udp,
+                        ip: link.ip,
+                        port: link.rtp_port,
+                        peer: None,
+                    };
+                    expect.add(key, Expected::Rtp(rtp.clone()));
+                    if link.rtcp_port != link.rtp_port {
+                        key.port = link.rtcp_port;
+                        expect.add(key, Expected::Rtp(rtp.clone()));
+                    }
+                }
+            }
+        }
+    }
+
+    fn body_start(&self, dir: Direction, cb_ctx: *mut c_void) {
+        if let Some(ref cb) = self.body_start {
+            cb.borrow_mut()(cb_ctx, dir);
+        }
+    }
+
+    fn body(&self, bytes: &[u8], offset: u32, dir: Direction, cb_ctx: *mut c_void) {
+        if let Some(ref cb) = self.body {
+            cb.borrow_mut()(bytes, offset, cb_ctx, dir);
+        }
+    }
+
+    fn body_stop(&self, dir: Direction, cb_ctx: *mut c_void) {
+        if let Some(ref cb) = self.body_stop {
+            cb.borrow_mut()(cb_ctx, dir);
+        }
+    }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone)]
-struct HeaderRet {
-    content_len: usize,
-    content_type: ContentType,
+fn msg_dir(start_line: &str) -> Direction {
+    if req(start_line) {
+        Direction::C2s
+    } else if rsp(start_line) {
+        Direction::S2c
+    } else {
+        Direction::Unknown
+    }
 }
 
 static SIP_METHODS: phf::Set<&'static str> = phf_set! {
@@ -242,6 +409,7 @@ fn rsp(input: &str) -> bool {
 mod tests {
     use super::*;
     use crate::test_utils::*;
+    use crate::{Sdp, SipCall, SipCallEvent, SipMediaLink};
     use std::cell::RefCell;
     use std::env;
     use std::rc::Rc;
@@ -586,4 +754,238 @@ mod tests {
         let expected_body = expected_rsp_body0.join("").as_bytes().to_vec();
         assert_eq!(body0, &expected_body);
     }
+
+    #[test]
+    fn test_sip_tcp() {
+        let events = Rc::new(RefCell::new(Vec::new()));
+
+        let mut protolens = Prolens::<CapPacket>::default();
+        let e = events.clone();
+        protolens.set_cb_sip_start_line(
+            move |line: &[u8], _seq: u32, _ctx: *mut c_void, dir: Direction| {
+                e.borrow_mut()
+                    .push((dir, String::from_utf8_lossy(line).into_owned()));
+            },
+        );
+        let e = events.clone();
+        protolens.set_cb_sip_body(
+            move |body: &[u8], _seq: u32, _ctx: *mut c_void, dir: Direction| {
+                e.borrow_mut()
+                    .push((dir, String::from_utf8_lossy(body).into_owned()));
+            },
+        );
+        let e = events.clone();
+        protolens.set_cb_sip_body_stop(move |_ctx: *mut c_void, dir: Direction| {
+            e.borrow_mut().push((dir, "stop".to_string()));
+        });
+
+        let c2s = b"\r\n\r\nOPTIONS sip:a@b SIP/2.0\r\nContent-Length: 0\r\n\r\n\
+            MESSAGE sip:a@b SIP/2.0\r\nl: 11\r\n\r\nhello world";
+        let s2c = b"SIP/2.0 200 OK\r\nContent-Length: 0\r\n\r\n";
+
+        let mut task = protolens.new_task(TransProto::Tcp);
+        protolens.set_task_parser(&mut task, L7Proto::SipTcp);
+        // 第二个消息的body跨段
+        let pkt = build_pkt_payload2(1, &c2s[..c2s.len() - 5], 5062, 5060, false);
+        let _ = pkt.decode();
+        protolens.run_task(&mut task, pkt);
+        let pkt = build_pkt_payload2(
+            c2s.len() as u32 - 4,
+            &c2s[c2s.len() - 5..],
+            5062,
+            5060,
+            false,
+        );
+        let _ = pkt.decode();
+        protolens.run_task(&mut task, pkt);
+        let pkt = build_pkt_payload2_rev(1, s2c, 5060, 5062, false);
+        let _ = pkt.decode();
+        protolens.run_task(&mut task, pkt);
+
+        assert_eq!(
+            *events.borrow(),
+            vec![
+                (Direction::C2s, "OPTIONS sip:a@b SIP/2.0\r\n".to_string()),
+                (Direction::C2s, "MESSAGE sip:a@b SIP/2.0\r\n".to_string()),
+                (Direction::C2s, "hello ".to_string()),
+                (Direction::C2s, "world".to_string()),
+                (Direction::C2s, "stop".to_string()),
+                (Direction::S2c, "SIP/2.0 200 OK\r\n".to_string()),
+            ]
+        );
+    }
+
+    fn sip_msg(start_line: &str, branch: &str, to_tag: &str, cseq: &str) -> Vec<u8> {
+        format!(
+            "{start_line}\r\nv: SIP/2.0/UDP 192.168.1.1:5060;branch={branch}\r\n\
+            f: \"Alice\" <sip:alice@a.com>;tag=11\r\nTo: <sip:bob@b.com>{to_tag}\r\n\
+            i: call1@a.com\r\nCSeq: {cseq}\r\nl: 0\r\n\r\n"
+        )
+        .into_bytes()
+    }
+
+    #[test]
+    fn test_sip_call() {
+        let calls = Rc::new(RefCell::new(Vec::new()));
+
+        let mut protolens = Prolens::<CapPacket>::default();
+        let c = calls.clone();
+        protolens.set_cb_sip_call(move |call: &SipCall, _ctx: *mut c_void| {
+            assert_eq!(call.call_id, "call1@a.com");
+            assert_eq!(call.from, "sip:alice@a.com");
+            assert_eq!(call.to, "sip:bob@b.com");
+            c.borrow_mut()
+                .push((call.event, call.status, call.duration));
+        });
+
+        let invite = "INVITE sip:bob@b.com SIP/2.0";
+        let msgs = [
+            (sip_msg(invite, "z9hG4bK1", "", "1 INVITE"), false, 1000),
+            // 重传
+            (sip_msg(invite, "z9hG4bK1", "", "1 INVITE"), false, 1500),
+            (
+                sip_msg("SIP/2.0 180 Ringing", "z9hG4bK1", ";tag=22", "1 INVITE"),
+                true,
+                2000,
+            ),
+            (
+                sip_msg("SIP/2.0 200 OK", "z9hG4bK1", ";tag=22", "1 INVITE"),
+                true,
+                3000,
+            ),
+            (
+                sip_msg("ACK sip:bob@b.com SIP/2.0", "z9hG4bK2", ";tag=22", "1 ACK"),
+                false,
+                3100,
+            ),
+            (
+                sip_msg("BYE sip:bob@b.com SIP/2.0", "z9hG4bK3", ";tag=22", "2 BYE"),
+                false,
+                63000,
+            ),
+            (
+                sip_msg("SIP/2.0 200 OK", "z9hG4bK3", ";tag=22", "2 BYE"),
+                true,
+                63100,
+            ),
+            // 同一个Call-ID再呼叫，被拒绝
+            (sip_msg(invite, "z9hG4bK4", "", "3 INVITE"), false, 70000),
+            (
+                sip_msg("SIP/2.0 486 Busy Here", "z9hG4bK4", ";tag=33", "3 INVITE"),
+                true,
+                70100,
+            ),
+        ];
+        let mut task = protolens.new_task(TransProto::Udp);
+        protolens.set_task_parser(&mut task, L7Proto::Sip);
+        for (payload, rev, ts) in msgs {
+            let (sport, dport) = if rev { (5060, 5062) } else { (5062, 5060) };
+            let mut pkt = build_udp_payload(&payload, sport, dport, rev);
+            pkt.timestamp = ts;
+            let _ = pkt.decode();
+            protolens.run_task(&mut task, pkt);
+        }
+
+        assert_eq!(
+            *calls.borrow(),
+            vec![
+                (SipCallEvent::Start, 0, 0),
+                (SipCallEvent::Answered, 200, 0),
+                (SipCallEvent::End, 200, 60000),
+                (SipCallEvent::Start, 0, 0),
+                (SipCallEvent::End, 486, 0),
+            ]
+        );
+        assert_eq!(protolens.sip_dialogs.borrow().len(), 0);
+    }
+
+    #[test]
+    fn test_sip_sdp() {
+        let links = Rc::new(RefCell::new(Vec::new()));
+        let names = Rc::new(RefCell::new(Vec::new()));
+
+        let mut protolens = Prolens::<CapPacket>::default();
+        let n = names.clone();
+        protolens.set_cb_sip_sdp(move |sdp: &Sdp, _ctx: *mut c_void, dir: Direction| {
+            let codecs: Vec<_> = sdp.media[0].codecs.iter().map(|c| c.name).collect();
+            n.borrow_mut()
+                .push((dir, sdp.session_name.unwrap().to_string(), codecs.join(",")));
+        });
+        let l = links.clone();
+        protolens.set_cb_sip_media_link(
+            move |link: &SipMediaLink, _ctx: *mut c_void, dir: Direction| {
+                l.borrow_mut().push((
+                    dir,
+                    link.call_id.map(str::to_string),
+                    link.ip.to_string(),
+                    link.rtp_port,
+                    link.rtcp_port,
+                ));
+            },
+        );
+
+        let body = "v=0\r\no=- 1 1 IN IP4 10.0.0.1\r\ns=call\r\nc=IN IP4 10.0.0.1\r\n\
+            t=0 0\r\nm=audio 40000 RTP/AVP 0 101\r\na=rtpmap:101 telephone-event/8000\r\n\
+            m=video 0 RTP/AVP 31\r\n";
+        let msg = format!(
+            "INVITE sip:bob@b.com SIP/2.0\r\ni: c1@a.com\r\nc: application/sdp\r\n\
+            l: {}\r\n\r\n{body}",
+            body.len()
+        );
+        let mut task = protolens.new_task(TransProto::Udp);
+        protolens.set_task_parser(&mut task, L7Proto::Sip);
+        let pkt = build_udp_payload(msg.as_bytes(), 5062, 5060, false);
+        let _ = pkt.decode();
+        protolens.run_task(&mut task, pkt);
+
+        // tcp上sdp跨段
+        let rsp = msg
+            .replace("INVITE sip:bob@b.com", "SIP/2.0 200 OK")
+            .replace(" SIP/2.0\r\ni:", "\r\ni:")
+            .replace("10.0.0.1", "10.0.0.2");
+        let mut task = protolens.new_task(TransProto::Tcp);
+        protolens.set_task_parser(&mut task, L7Proto::SipTcp);
+        let (head, tail) = rsp.as_bytes().split_at(rsp.len() - 20);
+        let pkt = build_pkt_payload2_rev(1, head, 5060, 5062, false);
+        let _ = pkt.decode();
+        protolens.run_task(&mut task, pkt);
+        let pkt = build_pkt_payload2_rev(1 + head.len() as u32, tail, 5060, 5062, false);
+        let _ = pkt.decode();
+        protolens.run_task(&mut task, pkt);
+
+        assert_eq!(
+            *names.borrow(),
+            vec![
+                (
+                    Direction::C2s,
+                    "call".to_string(),
+                    "telephone-event,PCMU".to_string()
+                ),
+                (
+                    Direction::S2c,
+                    "call".to_string(),
+                    "telephone-event,PCMU".to_string()
+                ),
+            ]
+        );
+        assert_eq!(
+            *links.borrow(),
+            vec![
+                (
+                    Direction::C2s,
+                    Some("c1@a.com".to_string()),
+                    "10.0.0.1".to_string(),
+                    40000,
+                    40001
+                ),
+                (
+                    Direction::S2c,
+                    Some("c1@a.com".to_string()),
+                    "10.0.0.2".to_string(),
+                    40000,
+                    40001
+                ),
+            ]
+        );
+    }
 }
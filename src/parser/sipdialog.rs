@@ -0,0 +1,352 @@
+// SIP呼叫跟踪。应答按Via branch和CSeq匹配请求，对话按Call-ID，跨包也跨task。
+// INVITE -> 1xx -> 2xx -> ACK -> BYE
+use crate::CbSipCall;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::ffi::c_void;
+use std::rc::Rc;
+
+const MAX_SIP_DIALOGS: usize = 4096;
+const MAX_SIP_TRANS: usize = 4096;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SipCallEvent {
+    Start,
+    Answered,
+    End,
+}
+
+// status是INVITE的最终应答，还没有时为0。duration是应答到结束的毫秒数，只在End时有效
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SipCall<'a> {
+    pub event: SipCallEvent,
+    pub call_id: &'a str,
+    pub from: &'a str,
+    pub to: &'a str,
+    pub status: u16,
+    pub duration: u64,
+}
+
+// 紧凑形式转成完整的头部名
+pub(crate) fn header_name(name: &str) -> &str {
+    if name.len() != 1 {
+        return name;
+    }
+    match name.as_bytes()[0].to_ascii_lowercase() {
+        b'a' => "Accept-Contact",
+        b'b' => "Referred-By",
+        b'c' => "Content-Type",
+        b'd' => "Request-Disposition",
+        b'e' => "Content-Encoding",
+        b'f' => "From",
+        b'i' => "Call-ID",
+        b'j' => "Reject-Contact",
+        b'k' => "Supported",
+        b'l' => "Content-Length",
+        b'm' => "Contact",
+        b'o' => "Event",
+        b'r' => "Refer-To",
+        b's' => "Subject",
+        b't' => "To",
+        b'u' => "Allow-Events",
+        b'v' => "Via",
+        b'x' => "Session-Expires",
+        _ => name,
+    }
+}
+
+// 一个消息中跟踪需要的字段
+#[derive(Debug, Default)]
+pub(crate) struct SipMsg {
+    pub(crate) content_len: Option<usize>,
+    pub(crate) sdp: bool,
+    method: Option<String>, // 请求才有
+    status: u16,            // 应答才有
+    call_id: Option<String>,
+    from: Option<String>,
+    to: Option<String>,
+    to_tag: bool,
+    branch: Option<String>, // 第一个Via
+    cseq: Option<(u32, String)>,
+}
+
+impl SipMsg {
+    pub(crate) fn new(start_line: &str) -> Self {
+        let mut msg = SipMsg::default();
+        let mut parts = start_line.split_ascii_whitespace();
+        match parts.next() {
+            Some("SIP/2.0") => msg.status = parts.next().and_then(|s| s.parse().ok()).unwrap_or(0),
+            Some(method) => msg.method = Some(method.to_string()),
+            None => {}
+        }
+        msg
+    }
+
+    pub(crate) fn header(&mut self, line: &str, track: bool) {
+        let Some((name, value)) = line.split_once(':') else {
+            return;
+        };
+        let name = header_name(name.trim());
+        let value = value.trim();
+        if name.eq_ignore_ascii_case("Content-Length") {
+            if self.content_len.is_none() {
+                self.content_len = value.parse().ok();
+            }
+            return;
+        }
+        if name.eq_ignore_ascii_case("Content-Type") {
+            let mime = value.split(';').next().unwrap_or("").trim();
+            self.sdp = mime.eq_ignore_ascii_case("application/sdp");
+            return;
+        }
+        if !track {
+            return;
+        }
+        if name.eq_ignore_ascii_case("Call-ID") {
+            self.call_id.get_or_insert_with(|| value.to_string());
+        } else if name.eq_ignore_ascii_case("From") {
+            self.from.get_or_insert_with(|| addr_uri(value).to_string());
+        } else if name.eq_ignore_ascii_case("To") {
+            self.to_tag = param(value, "tag").is_some();
+            self.to.get_or_insert_with(|| addr_uri(value).to_string());
+        } else if name.eq_ignore_ascii_case("Via") {
+            if self.branch.is_none() {
+                // 多个Via可以写在一行，只取第一个
+                let first = value.split(',').next().unwrap_or(value);
+                self.branch = Some(param(first, "branch").unwrap_or("").to_string());
+            }
+        } else if name.eq_ignore_ascii_case("CSeq") {
+            let mut parts = value.split_ascii_whitespace();
+            if let (Some(num), Some(method)) = (parts.next(), parts.next())
+                && let Ok(num) = num.parse()
+            {
+                self.cseq = Some((num, method.to_ascii_uppercase()));
+            }
+        }
+    }
+
+    pub(crate) fn call_id(&self) -> Option<&str> {
+        self.call_id.as_deref()
+    }
+}
+
+// "Alice" <sip:a@b>;tag=1 取尖括号内，否则取到第一个分号
+fn addr_uri(value: &str) -> &str {
+    if let Some(start) = value.find('<')
+        && let Some(len) = value[start + 1..].find('>')
+    {
+        return &value[start + 1..start + 1 + len];
+    }
+    value.split(';').next().unwrap_or(value).trim()
+}
+
+// 尖括号外面的参数
+fn param<'a>(value: &'a str, name: &str) -> Option<&'a str> {
+    let params = match value.rfind('>') {
+        Some(end) => &value[end + 1..],
+        None => value,
+    };
+    params.split(';').skip(1).find_map(|p| {
+        let (key, val) = p.split_once('=').unwrap_or((p, ""));
+        key.trim().eq_ignore_ascii_case(name).then_some(val.trim())
+    })
+}
+
+struct Dialog {
+    call_id: String,
+    from: String,
+    to: String,
+    status: u16,
+    answer_ts: Option<u64>,
+}
+
+struct Trans {
+    branch: String,
+    cseq: (u32, String),
+    call_id: String,
+}
+
+pub(crate) type SipDialogsRef = Rc<RefCell<SipDialogs>>;
+
+#[derive(Default)]
+pub(crate) struct SipDialogs {
+    dialogs: VecDeque<Dialog>,
+    trans: VecDeque<Trans>,
+}
+
+impl SipDialogs {
+    pub(crate) fn message(
+        &mut self,
+        msg: &SipMsg,
+        ts: u64,
+        cb: Option<&CbSipCall>,
+        cb_ctx: *mut c_void,
+    ) {
+        if msg.call_id.is_none() || msg.cseq.is_none() {
+            return;
+        }
+        if msg.method.is_some() {
+            self.request(msg, ts, cb, cb_ctx);
+        } else {
+            self.response(msg, ts, cb, cb_ctx);
+        }
+    }
+
+    fn request(&mut self, msg: &SipMsg, ts: u64, cb: Option<&CbSipCall>, cb_ctx: *mut c_void) {
+        let (Some(method), Some(call_id), Some(cseq)) = (&msg.method, &msg.call_id, &msg.cseq)
+        else {
+            return;
+        };
+        match method.as_str() {
+            // 带To tag的是re-INVITE，重传也不再通知
+            "INVITE" if !msg.to_tag && self.find(call_id).is_none() => {
+                if self.dialogs.len() >= MAX_SIP_DIALOGS {
+                    self.dialogs.pop_front();
+                }
+                self.dialogs.push_back(Dialog {
+                    call_id: call_id.to_string(),
+                    from: msg.from.clone().unwrap_or_default(),
+                    to: msg.to.clone().unwrap_or_default(),
+                    status: 0,
+                    answer_ts: None,
+                });
+                self.notify(call_id, SipCallEvent::Start, ts, cb, cb_ctx);
+            }
+            "BYE" => {
+                if self.find(call_id).is_some() {
+                    self.notify(call_id, SipCallEvent::End, ts, cb, cb_ctx);
+                    self.remove(call_id);
+                }
+                return;
+            }
+            _ => {}
+        }
+        if method == "INVITE" {
+            let branch = msg.branch.as_deref().unwrap_or("");
+            self.trans.retain(|t| t.branch != branch || t.cseq != *cseq);
+            if self.trans.len() >= MAX_SIP_TRANS {
+                self.trans.pop_front();
+            }
+            self.trans.push_back(Trans {
+                branch: branch.to_string(),
+                cseq: cseq.clone(),
+                call_id: call_id.to_string(),
+            });
+        }
+    }
+
+    fn response(&mut self, msg: &SipMsg, ts: u64, cb: Option<&CbSipCall>, cb_ctx: *mut c_void) {
+        let (Some(call_id), Some(cseq)) = (&msg.call_id, &msg.cseq) else {
+            return;
+        };
+        if cseq.1 != "INVITE" || msg.status < 200 {
+            return;
+        }
+        // 先按branch和CSeq匹配事务，没有branch时退回Call-ID和CSeq
+        let branch = msg.branch.as_deref().unwrap_or("");
+        let Some(index) = self
+            .trans
+            .iter()
+            .position(|t| !branch.is_empty() && t.branch == branch && t.cseq == *cseq)
+            .or_else(|| {
+                self.trans
+                    .iter()
+                    .position(|t| t.call_id == *call_id && t.cseq == *cseq)
+            })
+        else {
+            return;
+        };
+        let trans = self.trans.remove(index).unwrap();
+        let Some(dialog) = self.find(&trans.call_id) else {
+            return;
+        };
+        if dialog.status != 0 {
+            return;
+        }
+        dialog.status = msg.status;
+        if msg.status < 300 {
+            dialog.answer_ts = Some(ts);
+            self.notify(&trans.call_id, SipCallEvent::Answered, ts, cb, cb_ctx);
+        } else {
+            self.notify(&trans.call_id, SipCallEvent::End, ts, cb, cb_ctx);
+            self.remove(&trans.call_id);
+        }
+    }
+
+    fn find(&mut self, call_id: &str) -> Option<&mut Dialog> {
+        self.dialogs.iter_mut().find(|d| d.call_id == call_id)
+    }
+
+    fn remove(&mut self, call_id: &str) {
+        self.dialogs.retain(|d| d.call_id != call_id);
+        self.trans.retain(|t| t.call_id != call_id);
+    }
+
+    fn notify(
+        &mut self,
+        call_id: &str,
+        event: SipCallEvent,
+        ts: u64,
+        cb: Option<&CbSipCall>,
+        cb_ctx: *mut c_void,
+    ) {
+        let (Some(cb), Some(dialog)) = (cb, self.find(call_id)) else {
+            return;
+        };
+        let duration = match (event, dialog.answer_ts) {
+            (SipCallEvent::End, Some(answer)) => ts.saturating_sub(answer),
+            _ => 0,
+        };
+        let call = SipCall {
+            event,
+            call_id: &dialog.call_id,
+            from: &dialog.from,
+            to: &dialog.to,
+            status: dialog.status,
+            duration,
+        };
+        cb.borrow_mut()(&call, cb_ctx);
+    }
+
+    #[cfg(test)]
+    pub(crate) fn len(&self) -> usize {
+        self.dialogs.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sip_msg_header() {
+        let mut msg = SipMsg::new("INVITE sip:bob@b.com SIP/2.0\r\n");
+        for line in [
+            "v: SIP/2.0/UDP a.com;branch=z9hG4bK1, SIP/2.0/UDP c.com;branch=z9hG4bK2\r\n",
+            "Via: SIP/2.0/UDP d.com;branch=z9hG4bK3\r\n",
+            "f: \"Alice\" <sip:alice@a.com>;tag=88\r\n",
+            "t: sip:bob@b.com\r\n",
+            "i: abc@a.com\r\n",
+            "CSeq: 1 invite\r\n",
+            "c: application/sdp\r\n",
+            "l: 12\r\n",
+        ] {
+            msg.header(line, true);
+        }
+        assert_eq!(msg.method.as_deref(), Some("INVITE"));
+        assert_eq!(msg.branch.as_deref(), Some("z9hG4bK1"));
+        assert_eq!(msg.from.as_deref(), Some("sip:alice@a.com"));
+        assert_eq!(msg.to.as_deref(), Some("sip:bob@b.com"));
+        assert!(!msg.to_tag);
+        assert_eq!(msg.call_id.as_deref(), Some("abc@a.com"));
+        assert_eq!(msg.cseq, Some((1, "INVITE".to_string())));
+        assert_eq!(msg.content_len, Some(12));
+        assert!(msg.sdp);
+
+        let msg = SipMsg::new("SIP/2.0 486 Busy Here\r\n");
+        assert_eq!(msg.status, 486);
+        assert_eq!(header_name("I"), "Call-ID");
+        assert_eq!(header_name("Via"), "Via");
+    }
+}
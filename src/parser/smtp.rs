@@ -4,8 +4,10 @@ use crate::CbBodyEvt;
 use crate::CbClt;
 use crate::CbHeader;
 use crate::CbMailFrom;
+use crate::CbMailFromExt;
 use crate::CbPass;
 use crate::CbRcpt;
+use crate::CbRcptExt;
 use crate::CbSrv;
 use crate::CbUser;
 use crate::DirConfirmFn;
@@ -22,17 +24,40 @@ use crate::multi_body;
 use crate::packet::*;
 use nom::{
     IResult, Offset,
-    bytes::complete::{tag, take_till, take_while},
+    bytes::complete::{tag, tag_no_case, take_till, take_till1},
+    character::complete::space0,
 };
 use std::ffi::c_void;
 use std::marker::PhantomData;
 
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum EsmtpKey {
+    Size,
+    Body,
+    SmtpUtf8,
+    Notify,
+    Orcpt,
+    Ret,
+    EnvId,
+    Other,
+}
+
+// MAIL FROM/RCPT TO 地址后面的扩展参数。name为原始关键字，value为=后面的内容
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct EsmtpParam<'a> {
+    pub key: EsmtpKey,
+    pub name: &'a str,
+    pub value: Option<&'a str>,
+}
+
 #[derive(Clone)]
 pub(crate) struct SmtpCallbacks {
     user: Option<CbUser>,
     pass: Option<CbPass>,
     mailfrom: Option<CbMailFrom>,
+    mailfrom_ext: Option<CbMailFromExt>,
     rcpt: Option<CbRcpt>,
+    rcpt_ext: Option<CbRcptExt>,
 }
 
 pub struct SmtpParser<T>
@@ -42,7 +67,9 @@ where
     cb_user: Option<CbUser>,
     cb_pass: Option<CbPass>,
     cb_mailfrom: Option<CbMailFrom>,
+    cb_mailfrom_ext: Option<CbMailFromExt>,
     cb_rcpt: Option<CbRcpt>,
+    cb_rcpt_ext: Option<CbRcptExt>,
     cb_header: Option<CbHeader>,
     cb_body_start: Option<CbBodyEvt>,
     cb_body: Option<CbBody>,
@@ -61,7 +88,9 @@ where
             cb_user: None,
             cb_pass: None,
             cb_mailfrom: None,
+            cb_mailfrom_ext: None,
             cb_rcpt: None,
+            cb_rcpt_ext: None,
             cb_header: None,
             cb_body_start: None,
             cb_body: None,
@@ -92,55 +121,33 @@ where
         } else if line.eq_ignore_ascii_case("AUTH LOGIN") {
             // user
             let (user, seq) = stm.read_clean_line().await?;
-            if let Some(cb) = cb_smtp.user {
+            if let Some(ref cb) = cb_smtp.user {
                 cb.borrow_mut()(user, seq, cb_ctx);
             }
 
             // pass
             let (pass, seq) = stm.read_clean_line().await?;
-            if let Some(cb) = cb_smtp.pass {
+            if let Some(ref cb) = cb_smtp.pass {
                 cb.borrow_mut()(pass, seq, cb_ctx);
             }
 
-            // mail from。暂且不管有没有扩展参数
-            let (from, seq) = stm.read_clean_line_str().await?;
-
-            if let Ok((_, (mail, offset))) = mail_from(from) {
-                let mailfrom_seq = seq + offset as u32;
-                if let Some(cb) = cb_smtp.mailfrom {
-                    cb.borrow_mut()(mail.as_bytes(), mailfrom_seq, cb_ctx);
-                }
-            }
-        } else if line.to_ascii_uppercase().starts_with("MAIL FROM:") {
+            read_to_from(stm, &cb_smtp, cb_ctx).await?;
+        } else if mail_from_cmd(line) {
             // 没有auth，直接到mail from的情况
-            if let Ok((_, (mail, offset))) = mail_from(line) {
-                let mailfrom_seq = seq + offset as u32;
-                if let Some(cb) = cb_smtp.mailfrom {
-                    cb.borrow_mut()(mail.as_bytes(), mailfrom_seq, cb_ctx);
-                }
-            }
+            mail_from_cb(line, seq, &cb_smtp, cb_ctx);
         } else {
             // 其他auth情况。AUTH PLAIN，AUTH CRAM-MD5
             // 清空mail from之前或有或无的命令
-            read_to_from(stm, cb_smtp.mailfrom, cb_ctx).await?;
+            read_to_from(stm, &cb_smtp, cb_ctx).await?;
         }
 
-        multi_rcpt_to(stm, cb_smtp.rcpt, cb_ctx).await?;
+        multi_rcpt_to(stm, &cb_smtp, cb_ctx).await?;
 
         let (boundary, te) = header(stm, cb.header.as_ref(), cb_ctx, Direction::C2s).await?;
         if let Some(bdry) = boundary {
             multi_body(stm, &bdry, &bdry, &cb, cb_ctx).await?;
         } else {
-            body(
-                stm,
-                te,
-                cb.body_start.as_ref(),
-                cb.body.as_ref(),
-                cb.body_stop.as_ref(),
-                cb_ctx,
-                cb.dir,
-            )
-            .await?;
+            body(stm, te, &cb, cb_ctx).await?;
         }
 
         Ok(())
@@ -209,15 +216,14 @@ where
                 }
             }
 
-            if let Ok(payload) = payload_c2s {
-                if payload.len() >= 4
-                    && (payload.starts_with(b"220 ")
-                        || payload.starts_with(b"220-")
-                        || payload.starts_with(b"421 ")
-                        || payload.starts_with(b"421-"))
-                {
-                    return Some(false);
-                }
+            if let Ok(payload) = payload_c2s
+                && payload.len() >= 4
+                && (payload.starts_with(b"220 ")
+                    || payload.starts_with(b"220-")
+                    || payload.starts_with(b"421 ")
+                    || payload.starts_with(b"421-"))
+            {
+                return Some(false);
             }
 
             Some(true)
@@ -229,7 +235,9 @@ where
             user: self.cb_user.clone(),
             pass: self.cb_pass.clone(),
             mailfrom: self.cb_mailfrom.clone(),
+            mailfrom_ext: self.cb_mailfrom_ext.clone(),
             rcpt: self.cb_rcpt.clone(),
+            rcpt_ext: self.cb_rcpt_ext.clone(),
         };
         let cb = Callbacks {
             header: self.cb_header.clone(),
@@ -239,6 +247,7 @@ where
             clt: self.cb_clt.clone(),
             srv: None,
             dir: Direction::C2s,
+            dot_unstuff: false,
         };
 
         Some(Box::pin(Self::c2s_parser_inner(strm, cb, cb_smtp, cb_ctx)))
@@ -272,7 +281,9 @@ where
         parser.cb_user = prolens.cb_smtp_user.clone();
         parser.cb_pass = prolens.cb_smtp_pass.clone();
         parser.cb_mailfrom = prolens.cb_smtp_mailfrom.clone();
+        parser.cb_mailfrom_ext = prolens.cb_smtp_mailfrom_ext.clone();
         parser.cb_rcpt = prolens.cb_smtp_rcpt.clone();
+        parser.cb_rcpt_ext = prolens.cb_smtp_rcpt_ext.clone();
         parser.cb_header = prolens.cb_smtp_header.clone();
         parser.cb_body_start = prolens.cb_smtp_body_start.clone();
         parser.cb_body = prolens.cb_smtp_body.clone();
@@ -284,7 +295,7 @@ where
 
 async fn read_to_from<T>(
     stm: &mut PktStrm<T>,
-    cb_mailfrom: Option<CbMailFrom>,
+    cb_smtp: &SmtpCallbacks,
     cb_ctx: *mut c_void,
 ) -> Result<(), ()>
 where
@@ -293,22 +304,17 @@ where
     loop {
         let (line, seq) = stm.read_clean_line_str().await?;
 
-        if line.to_ascii_uppercase().starts_with("MAIL FROM:") {
-            if let Ok((_, (mail, offset))) = mail_from(line) {
-                let mailfrom_seq = seq + offset as u32;
-                if let Some(cb) = cb_mailfrom {
-                    cb.borrow_mut()(mail.as_bytes(), mailfrom_seq, cb_ctx);
-                }
-            }
-
+        if mail_from_cmd(line) {
+            mail_from_cb(line, seq, cb_smtp, cb_ctx);
             return Ok(());
         }
     }
 }
 
+// 无法解析的RCPT，以及RSET/NOOP等其他命令直接跳过
 async fn multi_rcpt_to<T>(
     stm: &mut PktStrm<T>,
-    cb_rcpt: Option<CbRcpt>,
+    cb_smtp: &SmtpCallbacks,
     cb_ctx: *mut c_void,
 ) -> Result<(), ()>
 where
@@ -321,39 +327,128 @@ where
             break;
         }
 
-        if let Ok((_, (mail, offset))) = rcpt_to(line) {
+        if let Ok((rest, (mail, offset))) = rcpt_to(line) {
             let mail_seq = seq + offset as u32;
-            if let Some(ref cb) = cb_rcpt {
+            if let Some(ref cb) = cb_smtp.rcpt {
                 cb.borrow_mut()(mail.as_bytes(), mail_seq, cb_ctx);
             }
-        } else {
-            return Err(());
+            if let Some(ref cb) = cb_smtp.rcpt_ext {
+                let params = esmtp_params(rest);
+                cb.borrow_mut()(mail.as_bytes(), &params, mail_seq, cb_ctx);
+            }
         }
     }
     Ok(())
 }
 
+fn mail_from_cb(line: &str, seq: u32, cb_smtp: &SmtpCallbacks, cb_ctx: *mut c_void) {
+    if let Ok((rest, (mail, offset))) = mail_from(line) {
+        let mail_seq = seq + offset as u32;
+        if let Some(ref cb) = cb_smtp.mailfrom {
+            cb.borrow_mut()(mail.as_bytes(), mail_seq, cb_ctx);
+        }
+        if let Some(ref cb) = cb_smtp.mailfrom_ext {
+            let params = esmtp_params(rest);
+            cb.borrow_mut()(mail.as_bytes(), &params, mail_seq, cb_ctx);
+        }
+    }
+}
+
+fn mail_from_cmd(line: &str) -> bool {
+    line.len() >= 10 && line.as_bytes()[..10].eq_ignore_ascii_case(b"MAIL FROM:")
+}
+
 // MAIL FROM: <user12345@example123.com> SIZE=10557
+// mail from:<>
+// MAIL FROM:<@relay1.com,@relay2.com:user@example.com> BODY=8BITMIME
+// 返回的input为地址之后的扩展参数部分
 fn mail_from(input: &str) -> IResult<&str, (&str, usize)> {
     let original_input = input;
-    let (input, _) = tag("MAIL FROM: <")(input)?;
-
-    let start_pos = original_input.offset(input);
-    let (input, mail) = take_while(|c| c != '>')(input)?;
+    let (input, _) = tag_no_case("MAIL FROM:")(input)?;
+    let (input, _) = space0(input)?;
+    let (input, mail) = path(input)?;
 
-    Ok((input, (mail, start_pos)))
+    Ok((input, (mail, original_input.offset(mail))))
 }
 
 // RCPT TO: <user12345@example123.com>
+// rcpt to:<user@example.com> NOTIFY=SUCCESS,FAILURE ORCPT=rfc822;user@example.com
 fn rcpt_to(input: &str) -> IResult<&str, (&str, usize)> {
     let original_input = input;
-    let (input, _) = tag("RCPT TO: <")(input)?;
+    let (input, _) = tag_no_case("RCPT TO:")(input)?;
+    let (input, _) = space0(input)?;
+    let (input, mail) = path(input)?;
 
-    let start_pos = original_input.offset(input);
-    let (input, mail) = take_while(|c| c != '>')(input)?;
-    let (input, _) = tag(">")(input)?;
+    Ok((input, (mail, original_input.offset(mail))))
+}
 
-    Ok((input, (mail, start_pos)))
+// <user@example.com>、<>、<@a.com,@b.com:user@example.com>，以及不带尖括号的地址。
+// 源路由部分会被去掉，只返回邮箱地址。带引号的local-part中可以包含'>'
+fn path(input: &str) -> IResult<&str, &str> {
+    let Some(inner) = input.strip_prefix('<') else {
+        return take_till1(|c| c == ' ')(input);
+    };
+
+    let mut quoted = false;
+    let mut end = None;
+    for (i, c) in inner.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            '>' if !quoted => {
+                end = Some(i);
+                break;
+            }
+            _ => {}
+        }
+    }
+    let Some(end) = end else {
+        return Err(nom::Err::Error(nom::error::Error::new(
+            input,
+            nom::error::ErrorKind::Tag,
+        )));
+    };
+
+    let mut mail = &inner[..end];
+    if mail.starts_with('@')
+        && let Some(pos) = mail.find(':')
+    {
+        mail = &mail[pos + 1..];
+    }
+    Ok((&inner[end + 1..], mail))
+}
+
+// SIZE=10557 BODY=8BITMIME SMTPUTF8
+fn esmtp_params(input: &str) -> Vec<EsmtpParam<'_>> {
+    input
+        .split_ascii_whitespace()
+        .map(|param| {
+            let (name, value) = match param.split_once('=') {
+                Some((name, value)) => (name, Some(value)),
+                None => (param, None),
+            };
+            EsmtpParam {
+                key: esmtp_key(name),
+                name,
+                value,
+            }
+        })
+        .collect()
+}
+
+fn esmtp_key(name: &str) -> EsmtpKey {
+    const KEYS: [(&str, EsmtpKey); 7] = [
+        ("SIZE", EsmtpKey::Size),
+        ("BODY", EsmtpKey::Body),
+        ("SMTPUTF8", EsmtpKey::SmtpUtf8),
+        ("NOTIFY", EsmtpKey::Notify),
+        ("ORCPT", EsmtpKey::Orcpt),
+        ("RET", EsmtpKey::Ret),
+        ("ENVID", EsmtpKey::EnvId),
+    ];
+
+    KEYS.iter()
+        .find(|(key, _)| name.eq_ignore_ascii_case(key))
+        .map_or(EsmtpKey::Other, |(_, key)| *key)
 }
 
 #[allow(dead_code)]
@@ -421,6 +516,163 @@ mod tests {
         println!("邮件地址: '{}' (起始位置: {})", mail, start);
     }
 
+    #[test]
+    fn test_mail_from_tolerant() {
+        let (rest, (mail, start)) = mail_from("MAIL FROM:<a@b.com>").unwrap();
+        assert_eq!(mail, "a@b.com");
+        assert_eq!(start, 11);
+        assert_eq!(rest, "");
+
+        let (_, (mail, _)) = mail_from("mail from:<a@b.com>").unwrap();
+        assert_eq!(mail, "a@b.com");
+
+        // null reverse-path
+        let (rest, (mail, start)) = mail_from("MAIL FROM:<> SIZE=100").unwrap();
+        assert_eq!(mail, "");
+        assert_eq!(start, 11);
+        assert_eq!(rest, " SIZE=100");
+
+        // 源路由
+        let (_, (mail, start)) =
+            mail_from("MAIL FROM:<@relay1.com,@relay2.com:user@example.com>").unwrap();
+        assert_eq!(mail, "user@example.com");
+        assert_eq!(start, 35);
+
+        // 带引号的local-part
+        let (_, (mail, _)) = mail_from("MAIL FROM:<\"a>b\"@example.com>").unwrap();
+        assert_eq!(mail, "\"a>b\"@example.com");
+
+        // 没有尖括号
+        let (_, (mail, _)) = mail_from("MAIL FROM: user@example.com SIZE=1").unwrap();
+        assert_eq!(mail, "user@example.com");
+
+        assert!(mail_from("MAIL FROM:<user@example.com").is_err());
+        assert!(mail_from("MAIL TO:<user@example.com>").is_err());
+    }
+
+    #[test]
+    fn test_esmtp_params() {
+        let (rest, (mail, _)) = mail_from(
+            "MAIL FROM:<a@b.com> SIZE=10557 BODY=8BITMIME SMTPUTF8 RET=HDRS ENVID=QQ314159 X-FOO=1",
+        )
+        .unwrap();
+        assert_eq!(mail, "a@b.com");
+        let params = esmtp_params(rest);
+        assert_eq!(params.len(), 6);
+        assert_eq!(params[0].key, EsmtpKey::Size);
+        assert_eq!(params[0].name, "SIZE");
+        assert_eq!(params[0].value, Some("10557"));
+        assert_eq!(params[1].key, EsmtpKey::Body);
+        assert_eq!(params[1].value, Some("8BITMIME"));
+        assert_eq!(params[2].key, EsmtpKey::SmtpUtf8);
+        assert_eq!(params[2].value, None);
+        assert_eq!(params[3].key, EsmtpKey::Ret);
+        assert_eq!(params[4].key, EsmtpKey::EnvId);
+        assert_eq!(params[4].value, Some("QQ314159"));
+        assert_eq!(params[5].key, EsmtpKey::Other);
+        assert_eq!(params[5].name, "X-FOO");
+
+        let (rest, (mail, _)) =
+            rcpt_to("rcpt to:<c@d.com> notify=SUCCESS,FAILURE orcpt=rfc822;c@d.com").unwrap();
+        assert_eq!(mail, "c@d.com");
+        let params = esmtp_params(rest);
+        assert_eq!(params.len(), 2);
+        assert_eq!(params[0].key, EsmtpKey::Notify);
+        assert_eq!(params[0].value, Some("SUCCESS,FAILURE"));
+        assert_eq!(params[1].key, EsmtpKey::Orcpt);
+        assert_eq!(params[1].value, Some("rfc822;c@d.com"));
+
+        assert!(esmtp_params("").is_empty());
+    }
+
+    #[test]
+    fn test_smtp_addr_ext() {
+        let lines = [
+            "EHLO client.example.com\r\n",
+            "mail from:<> SIZE=1024 BODY=8BITMIME\r\n",
+            "RCPT TO:<recipient1@example.com> NOTIFY=NEVER\r\n",
+            "RSET\r\n",
+            "RCPT TO:<broken@example.com\r\n",
+            "rcpt to:<@relay.com:recipient2@example.com>\r\n",
+            "DATA\r\n",
+            "Subject: Email Subject\r\n",
+            "\r\n",
+            "mail body line1.\r\n",
+            ".\r\n",
+        ];
+
+        let captured_mailfrom = Rc::new(RefCell::new(Vec::<(String, Vec<String>)>::new()));
+        let captured_rcpt = Rc::new(RefCell::new(Vec::<(String, Vec<String>)>::new()));
+        let captured_rcpt_plain = Rc::new(RefCell::new(Vec::<Vec<u8>>::new()));
+
+        let mailfrom_callback = {
+            let mailfrom_clone = captured_mailfrom.clone();
+            move |mail: &[u8], params: &[EsmtpParam], _seq: u32, _cb_ctx: *mut c_void| {
+                let params = params
+                    .iter()
+                    .map(|p| format!("{:?}:{}", p.key, p.value.unwrap_or("")))
+                    .collect();
+                mailfrom_clone
+                    .borrow_mut()
+                    .push((String::from_utf8_lossy(mail).to_string(), params));
+            }
+        };
+
+        let rcpt_callback = {
+            let rcpt_clone = captured_rcpt.clone();
+            move |mail: &[u8], params: &[EsmtpParam], _seq: u32, _cb_ctx: *mut c_void| {
+                let params = params
+                    .iter()
+                    .map(|p| format!("{:?}:{}", p.key, p.value.unwrap_or("")))
+                    .collect();
+                rcpt_clone
+                    .borrow_mut()
+                    .push((String::from_utf8_lossy(mail).to_string(), params));
+            }
+        };
+
+        let rcpt_plain_callback = {
+            let rcpt_clone = captured_rcpt_plain.clone();
+            move |rcpt: &[u8], _seq: u32, _cb_ctx: *mut c_void| {
+                rcpt_clone.borrow_mut().push(rcpt.to_vec());
+            }
+        };
+
+        let mut protolens = Prolens::<CapPacket>::default();
+        protolens.set_cb_smtp_mailfrom_ext(mailfrom_callback);
+        protolens.set_cb_smtp_rcpt_ext(rcpt_callback);
+        protolens.set_cb_smtp_rcpt(rcpt_plain_callback);
+
+        let mut task = protolens.new_task(TransProto::Tcp);
+        protolens.set_task_parser(&mut task, L7Proto::Smtp);
+
+        let mut seq = 1000;
+        let mut result = None;
+        for line in lines.iter() {
+            let line_bytes = line.as_bytes();
+            let pkt = build_pkt_payload2(seq, line_bytes, 4000, SMTP_PORT, false);
+            let _ = pkt.decode();
+
+            result = protolens.run_task(&mut task, pkt);
+            seq += line_bytes.len() as u32;
+        }
+        assert_eq!(result, Some(Ok(())));
+
+        let mailfrom_guard = captured_mailfrom.borrow();
+        assert_eq!(mailfrom_guard.len(), 1);
+        assert_eq!(mailfrom_guard[0].0, "");
+        assert_eq!(mailfrom_guard[0].1, vec!["Size:1024", "Body:8BITMIME"]);
+
+        let rcpt_guard = captured_rcpt.borrow();
+        assert_eq!(rcpt_guard.len(), 2);
+        assert_eq!(rcpt_guard[0].0, "recipient1@example.com");
+        assert_eq!(rcpt_guard[0].1, vec!["Notify:NEVER"]);
+        assert_eq!(rcpt_guard[1].0, "recipient2@example.com");
+        assert!(rcpt_guard[1].1.is_empty());
+
+        assert_eq!(captured_rcpt_plain.borrow().len(), 2);
+    }
+
     #[test]
     fn test_subject() {
         let input = "Subject: Test email subject\r\n";
@@ -0,0 +1,263 @@
+// TLS握手解析。CONNECT隧道之后由http parser调用，不单独注册
+use crate::CbHttpTunnelData;
+use crate::CbTlsHello;
+use crate::Direction;
+use crate::Packet;
+use crate::PktStrm;
+use nom::{
+    IResult,
+    bytes::complete::take,
+    multi::length_data,
+    number::complete::{be_u8, be_u16},
+};
+use std::ffi::c_void;
+
+const TLS_HANDSHAKE: u8 = 22;
+const TLS_CLIENT_HELLO: u8 = 1;
+const TLS_SERVER_HELLO: u8 = 2;
+const EXT_SERVER_NAME: u16 = 0;
+const EXT_ALPN: u16 = 16;
+const EXT_SUPPORTED_VERSIONS: u16 = 43;
+// 握手消息最多缓存这么多，超过就不再解析
+const MAX_HANDSHAKE: usize = 64 * 1024;
+
+// ClientHello或者ServerHello。ServerHello时cipher_suites和alpn只有选中的一个
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct TlsHello<'a> {
+    pub client: bool,
+    pub version: u16,       // legacy_version
+    pub versions: Vec<u16>, // supported_versions扩展，TLS 1.3靠它协商
+    pub random: &'a [u8],
+    pub session_id: &'a [u8],
+    pub cipher_suites: Vec<u16>,
+    pub sni: Option<&'a str>,
+    pub alpn: Vec<&'a [u8]>,
+}
+
+// content type 20-24，版本号3.x
+pub(crate) fn is_tls_record(head: &[u8]) -> bool {
+    head.len() >= 5 && (20..=24).contains(&head[0]) && head[1] == 3 && head[2] <= 4
+}
+
+// head是已经读出来的第一个record头。record原样交给cb_data，握手中的hello交给cb_hello
+pub(crate) async fn tls_records<T: Packet>(
+    stm: &mut PktStrm<T>,
+    head: [u8; 5],
+    seq: u32,
+    cb_hello: Option<&CbTlsHello>,
+    cb_data: Option<&CbHttpTunnelData>,
+    cb_ctx: *mut c_void,
+    dir: Direction,
+) -> Result<(), ()> {
+    let mut next = Some((head, seq));
+    let mut handshake = Vec::new();
+    let mut done = cb_hello.is_none();
+    loop {
+        if done && cb_data.is_none() {
+            return Ok(());
+        }
+        let (head, seq) = match next.take() {
+            Some(head) => head,
+            None => {
+                let (head, seq) = stm.readn(5).await?;
+                (head.try_into().map_err(|_| ())?, seq)
+            }
+        };
+        if !is_tls_record(&head) {
+            return Err(());
+        }
+        if let Some(cb) = cb_data {
+            cb.borrow_mut()(&head, seq, cb_ctx, dir);
+        }
+
+        let collect = head[0] == TLS_HANDSHAKE && !done;
+        let mut remain = u16::from_be_bytes([head[3], head[4]]) as usize;
+        while remain > 0 {
+            let (data, seq) = stm.read(remain).await?;
+            if let Some(cb) = cb_data {
+                cb.borrow_mut()(data, seq, cb_ctx, dir);
+            }
+            if collect {
+                handshake.extend_from_slice(data);
+            }
+            remain -= data.len();
+        }
+        if collect {
+            done = handshake_msgs(&mut handshake, cb_hello, cb_ctx, dir);
+        }
+    }
+}
+
+// 处理已经收齐的握手消息。返回true表示hello已经处理或者无法继续解析
+fn handshake_msgs(
+    buf: &mut Vec<u8>,
+    cb_hello: Option<&CbTlsHello>,
+    cb_ctx: *mut c_void,
+    dir: Direction,
+) -> bool {
+    let mut off = 0;
+    while buf.len() - off >= 4 {
+        let msg_type = buf[off];
+        let len = u32::from_be_bytes([0, buf[off + 1], buf[off + 2], buf[off + 3]]) as usize;
+        if len > MAX_HANDSHAKE {
+            return true;
+        }
+        if buf.len() - off - 4 < len {
+            break;
+        }
+        let body = &buf[off + 4..off + 4 + len];
+        off += 4 + len;
+        if msg_type == TLS_CLIENT_HELLO || msg_type == TLS_SERVER_HELLO {
+            if let Ok((_, hello)) = hello_parser(msg_type == TLS_CLIENT_HELLO, body)
+                && let Some(cb) = cb_hello
+            {
+                cb.borrow_mut()(&hello, cb_ctx, dir);
+            }
+            return true;
+        }
+    }
+    buf.drain(..off);
+    false
+}
+
+fn hello_parser(client: bool, input: &[u8]) -> IResult<&[u8], TlsHello<'_>> {
+    let (input, version) = be_u16(input)?;
+    let (input, random) = take(32usize)(input)?;
+    let (input, session_id) = length_data(be_u8)(input)?;
+    let (input, cipher_suites) = if client {
+        let (input, suites) = length_data(be_u16)(input)?;
+        (input, u16_list(suites))
+    } else {
+        let (input, suite) = be_u16(input)?;
+        (input, vec![suite])
+    };
+    let (input, _compression) = if client {
+        length_data(be_u8)(input)?
+    } else {
+        take(1usize)(input)?
+    };
+    let mut hello = TlsHello {
+        client,
+        version,
+        versions: Vec::new(),
+        random,
+        session_id,
+        cipher_suites,
+        sni: None,
+        alpn: Vec::new(),
+    };
+    // 老的实现可以没有扩展
+    if input.is_empty() {
+        return Ok((input, hello));
+    }
+
+    let (input, mut exts) = length_data(be_u16)(input)?;
+    while !exts.is_empty() {
+        let (rest, ext_type) = be_u16(exts)?;
+        let (rest, data) = length_data(be_u16)(rest)?;
+        exts = rest;
+        match ext_type {
+            EXT_SERVER_NAME if client => {
+                hello.sni = sni_parser(data).map_or(None, |(_, sni)| sni);
+            }
+            EXT_ALPN => hello.alpn = alpn_parser(data).map_or(Vec::new(), |(_, alpn)| alpn),
+            EXT_SUPPORTED_VERSIONS => {
+                hello.versions = versions_parser(client, data).map_or(Vec::new(), |(_, v)| v);
+            }
+            _ => {}
+        }
+    }
+    Ok((input, hello))
+}
+
+fn u16_list(data: &[u8]) -> Vec<u16> {
+    data.chunks_exact(2)
+        .map(|v| u16::from_be_bytes([v[0], v[1]]))
+        .collect()
+}
+
+// 只取第一个host_name
+fn sni_parser(input: &[u8]) -> IResult<&[u8], Option<&str>> {
+    let (input, mut list) = length_data(be_u16)(input)?;
+    while !list.is_empty() {
+        let (rest, name_type) = be_u8(list)?;
+        let (rest, name) = length_data(be_u16)(rest)?;
+        list = rest;
+        if name_type == 0 {
+            return Ok((input, std::str::from_utf8(name).ok()));
+        }
+    }
+    Ok((input, None))
+}
+
+fn alpn_parser(input: &[u8]) -> IResult<&[u8], Vec<&[u8]>> {
+    let (input, mut list) = length_data(be_u16)(input)?;
+    let mut protos = Vec::new();
+    while !list.is_empty() {
+        let (rest, proto) = length_data(be_u8)(list)?;
+        list = rest;
+        protos.push(proto);
+    }
+    Ok((input, protos))
+}
+
+// ClientHello是列表，ServerHello是选中的版本
+fn versions_parser(client: bool, input: &[u8]) -> IResult<&[u8], Vec<u16>> {
+    if client {
+        let (input, list) = length_data(be_u8)(input)?;
+        Ok((input, u16_list(list)))
+    } else {
+        let (input, version) = be_u16(input)?;
+        Ok((input, vec![version]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    #[test]
+    fn test_tls_hello_parser() {
+        let rec = tls_client_hello();
+        assert!(is_tls_record(&rec));
+        let (_, hello) = hello_parser(true, &rec[9..]).unwrap();
+        assert_eq!(hello.version, 0x0303);
+        assert_eq!(hello.versions, vec![0x0304, 0x0303]);
+        assert_eq!(hello.session_id, &[0xaa, 0xbb, 0xcc, 0xdd]);
+        assert_eq!(hello.cipher_suites, vec![0x1301, 0xc02f]);
+        assert_eq!(hello.sni, Some("example.com"));
+        assert_eq!(hello.alpn, vec![&b"h2"[..], &b"http/1.1"[..]]);
+
+        let rec = tls_server_hello();
+        let (_, hello) = hello_parser(false, &rec[9..]).unwrap();
+        assert!(!hello.client);
+        assert_eq!(hello.versions, vec![0x0304]);
+        assert_eq!(hello.cipher_suites, vec![0x1301]);
+        assert_eq!(hello.sni, None);
+
+        assert!(!is_tls_record(b"GET / HTTP/1.1"));
+        assert!(hello_parser(true, &rec[9..20]).is_err());
+    }
+
+    #[test]
+    fn test_tls_handshake_split() {
+        // hello跨两个record，前面的数据不够时等待
+        let rec = tls_client_hello();
+        let body = &rec[5..];
+        let mut buf = body[..10].to_vec();
+        assert!(!handshake_msgs(
+            &mut buf,
+            None,
+            std::ptr::null_mut(),
+            Direction::C2s
+        ));
+        buf.extend_from_slice(&body[10..]);
+        assert!(handshake_msgs(
+            &mut buf,
+            None,
+            std::ptr::null_mut(),
+            Direction::C2s
+        ));
+    }
+}
@@ -0,0 +1,95 @@
+// RFC 6455 帧解析。http upgrade之后由http parser调用，不单独注册
+use crate::CbWsFrame;
+use crate::Direction;
+use crate::Packet;
+use crate::PktStrm;
+use std::ffi::c_void;
+
+const WS_CLOSE: u8 = 0x8;
+
+// 大的帧分多次回调，offset是payload在帧中的偏移。payload已经去掉掩码
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct WsFrame<'a> {
+    pub fin: bool,
+    pub opcode: u8,
+    pub len: u64,
+    pub offset: u64,
+    pub payload: &'a [u8],
+}
+
+pub(crate) async fn ws_frames<T: Packet>(
+    stm: &mut PktStrm<T>,
+    cb_frame: Option<&CbWsFrame>,
+    cb_ctx: *mut c_void,
+    dir: Direction,
+) -> Result<(), ()> {
+    loop {
+        let (head, _seq) = stm.readn(2).await?;
+        let fin = head[0] & 0x80 != 0;
+        let opcode = head[0] & 0x0f;
+        let masked = head[1] & 0x80 != 0;
+        let len = match head[1] & 0x7f {
+            126 => {
+                let (ext, _seq) = stm.readn(2).await?;
+                u16::from_be_bytes([ext[0], ext[1]]) as u64
+            }
+            127 => {
+                let (ext, _seq) = stm.readn(8).await?;
+                u64::from_be_bytes(ext.try_into().map_err(|_| ())?)
+            }
+            len => len as u64,
+        };
+        let mut mask = [0u8; 4];
+        if masked {
+            let (key, _seq) = stm.readn(4).await?;
+            mask.copy_from_slice(key);
+        }
+
+        if len == 0 {
+            if let Some(cb) = cb_frame {
+                let frame = WsFrame {
+                    fin,
+                    opcode,
+                    len,
+                    offset: 0,
+                    payload: &[],
+                };
+                cb.borrow_mut()(&frame, cb_ctx, dir);
+            }
+            continue;
+        }
+
+        let mut offset = 0;
+        let mut unmasked = Vec::new();
+        while offset < len {
+            let remain = (len - offset).min(usize::MAX as u64) as usize;
+            let (data, _seq) = stm.read(remain).await?;
+            if let Some(cb) = cb_frame {
+                let payload = if masked {
+                    unmasked.clear();
+                    unmasked.extend(
+                        data.iter()
+                            .enumerate()
+                            .map(|(i, b)| b ^ mask[((offset % 4) as usize + i) % 4]),
+                    );
+                    &unmasked[..]
+                } else {
+                    data
+                };
+                let frame = WsFrame {
+                    fin,
+                    opcode,
+                    len,
+                    offset,
+                    payload,
+                };
+                cb.borrow_mut()(&frame, cb_ctx, dir);
+            }
+            offset += data.len() as u64;
+        }
+
+        if opcode == WS_CLOSE {
+            return Ok(());
+        }
+    }
+}
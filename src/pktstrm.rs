@@ -1,4 +1,5 @@
 use crate::Heap;
+use crate::inflate::Inflate;
 use crate::packet::*;
 use futures::Future;
 use futures::future::poll_fn;
@@ -8,6 +9,7 @@ use std::cell::RefCell;
 use std::cmp::min;
 use std::ffi::c_void;
 use std::fmt;
+use std::net::IpAddr;
 use std::ptr::copy;
 use std::ptr::copy_nonoverlapping;
 use std::rc::Rc;
@@ -45,6 +47,9 @@ where
 
     next_seq: u32, // 待读取的seq
     fin: bool,
+    sip: Option<IpAddr>, // 第一个包的源地址
+    dip: Option<IpAddr>, // 第一个包的目的地址
+    timestamp: u64,      // 最近一个包的时间
 
     // 只有成功返回的才会被callback，比如hello\nxxx。对readline来说，hello\n成功读取，然后调用callback。
     // 后续的xxx不会调用callback
@@ -52,6 +57,11 @@ where
     cb_ctx: *const c_void, // 只在ffi中使用
 
     move_size: usize,
+
+    // 设置之后，包的内容先解压再放入buff。这时返回的seq没有意义
+    inflate: Option<Box<Inflate>>,
+    inflate_out: Vec<u8>,
+    inflate_off: usize,
 }
 
 impl<T> PktStrm<T>
@@ -71,11 +81,18 @@ where
 
             next_seq: 0,
             fin: false,
+            sip: None,
+            dip: None,
+            timestamp: 0,
 
             cb_strm: None,
             cb_ctx,
 
             move_size: 0,
+
+            inflate: None,
+            inflate_out: Vec::new(),
+            inflate_off: 0,
         }
     }
 
@@ -83,6 +100,24 @@ where
         self.cb_strm = Some(callback);
     }
 
+    // 之后的数据都是raw deflate流。buff中还没有读取的数据也是压缩过的
+    pub(crate) fn set_inflate(&mut self) -> Result<(), ()> {
+        if self.inflate.is_some() {
+            return Ok(());
+        }
+
+        let mut inflate = Box::new(Inflate::new());
+        let raw = &self.buff[self.buff_cur..(self.buff_start + self.buff_len)];
+        self.inflate_out.clear();
+        self.inflate_off = 0;
+        inflate
+            .inflate_max(raw, &mut self.inflate_out, self.max_buff)
+            .map_err(|_| ())?;
+        self.buff_len = self.buff_cur - self.buff_start;
+        self.inflate = Some(inflate);
+        Ok(())
+    }
+
     pub(crate) fn push(&mut self, pkt: T) {
         if self.fin {
             return;
@@ -95,9 +130,26 @@ where
             return;
         }
 
+        if self.sip.is_none() {
+            self.sip = Some(pkt.sip());
+            self.dip = Some(pkt.dip());
+        }
+        self.timestamp = pkt.timestamp();
         self.heap.push(SeqPacket::new(pkt));
     }
 
+    pub(crate) fn sip(&self) -> Option<IpAddr> {
+        self.sip
+    }
+
+    pub(crate) fn dip(&self) -> Option<IpAddr> {
+        self.dip
+    }
+
+    pub(crate) fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+
     // 无论是否严格seq连续，peek一个当前最有序的包
     // 不更新next_seq
     pub(crate) fn peek(&self) -> Option<&T> {
@@ -160,10 +212,10 @@ where
         }
 
         self.top_dedup();
-        if let Some(pkt) = self.peek() {
-            if pkt.seq() <= self.next_seq {
-                return Some((pkt, self.next_seq));
-            }
+        if let Some(pkt) = self.peek()
+            && pkt.seq() <= self.next_seq
+        {
+            return Some((pkt, self.next_seq));
         }
         None
     }
@@ -288,6 +340,9 @@ where
             if self.fin {
                 return Poll::Ready(Err(ReadError::Eof));
             }
+            if self.inflate.is_some() {
+                return self.buff_fill_inflate();
+            }
 
             let mut filled = false;
             let buff_start = self.buff_start;
@@ -332,6 +387,61 @@ where
         .await
     }
 
+    fn buff_fill_inflate(&mut self) -> Poll<Result<(), ReadError>> {
+        let mut filled = false;
+
+        loop {
+            if self.inflate_off < self.inflate_out.len() {
+                let space = self.max_buff - (self.buff_start + self.buff_len);
+                if space == 0 {
+                    break;
+                }
+                let copy_len = min(self.inflate_out.len() - self.inflate_off, space);
+                let dst = self.buff_start + self.buff_len;
+                self.buff[dst..dst + copy_len].copy_from_slice(
+                    &self.inflate_out[self.inflate_off..self.inflate_off + copy_len],
+                );
+                self.buff_len += copy_len;
+                self.inflate_off += copy_len;
+                filled = true;
+                continue;
+            }
+
+            // 每次最多解压出max_buff，剩余的留在inflate中，下次fill再继续
+            let payload = if self
+                .inflate
+                .as_ref()
+                .is_some_and(|inflate| inflate.is_paused())
+            {
+                Vec::new()
+            } else {
+                let Some((pkt, next_seq)) = self.peek_ord_data_with_next_seq() else {
+                    break;
+                };
+                let payload_off = (next_seq - pkt.seq()) as usize;
+                let payload = pkt.payload()[payload_off..].to_vec();
+                self.next_seq += payload.len() as u32;
+                payload
+            };
+
+            self.inflate_out.clear();
+            self.inflate_off = 0;
+            if let Some(ref mut inflate) = self.inflate
+                && inflate
+                    .inflate_max(&payload, &mut self.inflate_out, self.max_buff)
+                    .is_err()
+            {
+                return Poll::Ready(Err(ReadError::NoData));
+            }
+        }
+
+        if filled {
+            Poll::Ready(Ok(()))
+        } else {
+            Poll::Pending
+        }
+    }
+
     fn buff_move_start(&mut self) {
         if self.buff_start == 0 {
             return;
@@ -357,7 +467,7 @@ where
     // 返回试读过的数据, start到next - 1
     // ignore: 忽略尾部的数据长度。比如boundary \r\n
     fn get_buff_data(&mut self, ignore: usize) -> Result<(&[u8], u32), ReadError> {
-        let seq = self.next_seq - self.buff_len as u32;
+        let seq = self.next_seq.wrapping_sub(self.buff_len as u32); // 解压模式下seq没有意义
         let data_len = self.buff_cur - self.buff_start;
         let data = &self.buff[self.buff_start..(self.buff_start + data_len - ignore)];
 
@@ -1563,4 +1673,31 @@ mod tests {
         assert_eq!(fin_seq, ret_fin.as_ref().unwrap().seq());
         assert_eq!(0, ret_fin.as_ref().unwrap().payload_len());
     }
+
+    #[test]
+    fn test_pktstrm_inflate_cap() {
+        use futures::FutureExt;
+
+        // 100000个0压缩成114字节，单次fill的解压输出不能超过read buff
+        let mut deflate = vec![
+            0xed, 0xc1, 0x31, 0x01, 0x00, 0x00, 0x00, 0xc2, 0xa0, 0xf5, 0x4f, 0x6d, 0x0d, 0x0f,
+            0xa0,
+        ];
+        deflate.extend_from_slice(&[0u8; 96]);
+        deflate.extend_from_slice(&[0x80, 0x57, 0x03]);
+
+        let pkt = build_pkt_payload(1, &deflate);
+        let _ = pkt.decode();
+        let mut stm = PktStrm::<CapPacket>::new(MAX_PKT_BUFF, MAX_READ_BUFF, ptr::null_mut());
+        stm.push(pkt);
+        assert!(stm.set_inflate().is_ok());
+
+        let mut total = 0;
+        while let Some(Ok((data, _))) = stm.readn(1000).now_or_never() {
+            assert!(data.iter().all(|byte| *byte == 0));
+            total += data.len();
+            assert!(stm.inflate_out.len() <= MAX_READ_BUFF + 258);
+        }
+        assert_eq!(100000, total);
+    }
 }
@@ -14,6 +14,9 @@ use core::{
 use std::ffi::c_void;
 use std::fmt;
 use std::net::IpAddr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::task::Wake;
 
 pub enum Task<T>
 where
@@ -128,6 +131,9 @@ where
     c2s_state: TaskState,
     s2c_state: TaskState,
     bdir_state: TaskState,
+    // 一个方向的parser可以唤醒另一个方向，比如http的c2s等待CONNECT的应答
+    c2s_wake: Arc<WakeFlag>,
+    s2c_wake: Arc<WakeFlag>,
     cb_ctx: *mut c_void,
 }
 
@@ -155,6 +161,8 @@ where
             c2s_state: TaskState::Start,
             s2c_state: TaskState::Start,
             bdir_state: TaskState::Start,
+            c2s_wake: Arc::new(WakeFlag::default()),
+            s2c_wake: Arc::new(WakeFlag::default()),
             cb_ctx,
         }
     }
@@ -179,20 +187,20 @@ where
     }
 
     fn confirm_dir(&mut self) {
-        if let Some(dir_confirm_parser) = &self.dir_confirm_parser {
-            if let Some(c2s_dir) = dir_confirm_parser(
+        if let Some(dir_confirm_parser) = &self.dir_confirm_parser
+            && let Some(c2s_dir) = dir_confirm_parser(
                 &mut self.strm_c2s,
                 &mut self.strm_s2c,
                 self.c2s_port,
                 self.s2c_port,
-            ) {
-                if !c2s_dir {
-                    std::mem::swap(&mut self.c2s_ip, &mut self.s2c_ip);
-                    std::mem::swap(&mut self.c2s_port, &mut self.s2c_port);
-                    std::mem::swap(&mut self.strm_c2s, &mut self.strm_s2c);
-                }
-                self.dir_confirm = true;
+            )
+        {
+            if !c2s_dir {
+                std::mem::swap(&mut self.c2s_ip, &mut self.s2c_ip);
+                std::mem::swap(&mut self.c2s_port, &mut self.s2c_port);
+                std::mem::swap(&mut self.strm_c2s, &mut self.strm_s2c);
             }
+            self.dir_confirm = true;
         }
     }
 
@@ -226,7 +234,7 @@ where
             }
         }
 
-        if let Some(c2s_ip) = self.c2s_ip.as_ref() {
+        let ret = if let Some(c2s_ip) = self.c2s_ip.as_ref() {
             let is_c2s = pkt_sip == *c2s_ip && pkt_sport == self.c2s_port;
             match (
                 is_c2s,
@@ -241,7 +249,24 @@ where
             }
         } else {
             None
-        }
+        };
+
+        // 被另一个方向唤醒的parser，不用等自己方向的下一个包
+        let c2s_ret = if self.c2s_wake.take() {
+            self.c2s_run()
+        } else {
+            None
+        };
+        let s2c_ret = if self.s2c_wake.take() {
+            self.s2c_run()
+        } else {
+            None
+        };
+        // 任何一个方向出错都要返回错误
+        [ret, c2s_ret, s2c_ret]
+            .into_iter()
+            .flatten()
+            .reduce(|acc, ret| acc.and(ret))
     }
 
     fn c2s_run(&mut self) -> Option<Result<(), ()>> {
@@ -249,8 +274,9 @@ where
             return None;
         }
 
+        self.c2s_wake.take();
         if let Some(parser) = &mut self.c2s_parser {
-            let waker = waker::<T>();
+            let waker = Waker::from(self.c2s_wake.clone());
             let mut context = Context::from_waker(&waker);
             match Pin::as_mut(parser).poll(&mut context) {
                 Poll::Ready(Ok(())) => {
@@ -273,8 +299,9 @@ where
             return None;
         }
 
+        self.s2c_wake.take();
         if let Some(parser) = &mut self.s2c_parser {
-            let waker = waker::<T>();
+            let waker = Waker::from(self.s2c_wake.clone());
             let mut context = Context::from_waker(&waker);
             match Pin::as_mut(parser).poll(&mut context) {
                 Poll::Ready(Ok(())) => {
@@ -344,6 +371,21 @@ enum TaskState {
     Error,
 }
 
+#[derive(Default)]
+struct WakeFlag(AtomicBool);
+
+impl WakeFlag {
+    fn take(&self) -> bool {
+        self.0.swap(false, Ordering::Relaxed)
+    }
+}
+
+impl Wake for WakeFlag {
+    fn wake(self: Arc<Self>) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+}
+
 fn raw_waker<T: Packet>() -> RawWaker {
     fn no_op(_: *const ()) {}
 
@@ -411,14 +453,14 @@ where
     }
 
     fn confirm_dir(&mut self, pkt: &T) {
-        if let Some(dir_confirm_parser) = &self.dir_confirm_parser {
-            if let Some(c2s_dir) = dir_confirm_parser(pkt) {
-                if !c2s_dir {
-                    std::mem::swap(&mut self.c2s_ip, &mut self.s2c_ip);
-                    std::mem::swap(&mut self.c2s_port, &mut self.s2c_port);
-                }
-                self.dir_confirm = true;
+        if let Some(dir_confirm_parser) = &self.dir_confirm_parser
+            && let Some(c2s_dir) = dir_confirm_parser(pkt)
+        {
+            if !c2s_dir {
+                std::mem::swap(&mut self.c2s_ip, &mut self.s2c_ip);
+                std::mem::swap(&mut self.c2s_port, &mut self.s2c_port);
             }
+            self.dir_confirm = true;
         }
     }
 
@@ -487,3 +529,70 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use std::cell::{Cell, RefCell};
+    use std::future::poll_fn;
+    use std::rc::Rc;
+
+    // c2s等s2c唤醒之后出错，s2c唤醒c2s之后正常结束
+    #[derive(Default)]
+    struct WakeParser {
+        waker: Rc<RefCell<Option<Waker>>>,
+        woken: Rc<Cell<bool>>,
+    }
+
+    impl Parser for WakeParser {
+        type T = CapPacket;
+
+        fn c2s_parser(
+            &self,
+            _strm: *mut PktStrm<CapPacket>,
+            _ctx: *mut c_void,
+        ) -> Option<ParserFuture> {
+            let waker = self.waker.clone();
+            let woken = self.woken.clone();
+            Some(Box::pin(poll_fn(move |ctx| {
+                if woken.get() {
+                    return Poll::Ready(Err(()));
+                }
+                *waker.borrow_mut() = Some(ctx.waker().clone());
+                Poll::Pending
+            })))
+        }
+
+        fn s2c_parser(
+            &self,
+            _strm: *mut PktStrm<CapPacket>,
+            _ctx: *mut c_void,
+        ) -> Option<ParserFuture> {
+            let waker = self.waker.clone();
+            let woken = self.woken.clone();
+            Some(Box::pin(poll_fn(move |_ctx| {
+                woken.set(true);
+                if let Some(waker) = waker.borrow_mut().take() {
+                    waker.wake();
+                }
+                Poll::Ready(Ok(()))
+            })))
+        }
+    }
+
+    #[test]
+    fn test_task_woken_err() {
+        let mut task = TcpTask::new(&Config::default(), std::ptr::null_mut());
+        task.set_parser(Box::new(WakeParser::default()));
+
+        let pkt = build_pkt_payload2(1000, b"req", 4000, 80, false);
+        let _ = pkt.decode();
+        assert_eq!(task.run(pkt), None);
+
+        // s2c正常结束，被唤醒的c2s出错
+        let pkt = build_pkt_payload2_rev(2000, b"rsp", 80, 4000, false);
+        let _ = pkt.decode();
+        assert_eq!(task.run(pkt), Some(Err(())));
+    }
+}
@@ -1,10 +1,14 @@
 #![allow(unused)]
 use crate::Direction;
+use crate::Encoding;
+use crate::Prolens;
+use crate::Task;
 use crate::{L7Proto, Packet, TransProto};
 use etherparse::*;
 use pcap::Capture as PcapCap;
 use pcap::Offline;
 use std::cell::RefCell;
+use std::ffi::c_void;
 use std::fmt;
 use std::net::IpAddr;
 use std::ops::Deref;
@@ -340,6 +344,10 @@ impl Packet for CapPacket {
         let len = header.as_ref().unwrap().payload_len;
         &self.data[offset..offset + len]
     }
+
+    fn timestamp(&self) -> u64 {
+        self.timestamp as u64
+    }
 }
 
 impl Ord for CapPacket {
@@ -402,6 +410,10 @@ impl Packet for Box<CapPacket> {
     fn payload(&self) -> &[u8] {
         (**self).payload()
     }
+
+    fn timestamp(&self) -> u64 {
+        (**self).timestamp()
+    }
 }
 
 #[derive(Debug)]
@@ -619,9 +631,11 @@ pub(crate) fn make_pkt_data(seq: u32) -> CapPacket {
 }
 
 // 接受任意长度的payload数组
-fn build_pkt_payload_inner(
+pub(crate) fn build_pkt_payload_inner(
     seq: u32,
     payload: &[u8],
+    sip: [u8; 4],
+    dip: [u8; 4],
     sport: u16,
     dport: u16,
     fin: bool,
@@ -632,9 +646,9 @@ fn build_pkt_payload_inner(
         [7, 8, 9, 10, 11, 12], //destionation mac
     )
     .ipv4(
-        [192, 168, 1, 1], //source ip
-        [192, 168, 1, 2], //desitionation ip
-        20,               //time to life
+        sip, //source ip
+        dip, //desitionation ip
+        20,  //time to life
     )
     .tcp(
         sport, //source port
@@ -667,11 +681,27 @@ fn build_pkt_payload_inner(
 
 // 接受任意长度的payload数组
 pub(crate) fn build_pkt_payload(seq: u32, payload: &[u8]) -> CapPacket {
-    build_pkt_payload_inner(seq, payload, TEST_UTILS_SPORT, TEST_UTILS_DPORT, false)
+    build_pkt_payload_inner(
+        seq,
+        payload,
+        [192, 168, 1, 1],
+        [192, 168, 1, 2],
+        TEST_UTILS_SPORT,
+        TEST_UTILS_DPORT,
+        false,
+    )
 }
 
 pub(crate) fn build_pkt_payload_fin(seq: u32, payload: &[u8]) -> CapPacket {
-    build_pkt_payload_inner(seq, payload, TEST_UTILS_SPORT, TEST_UTILS_DPORT, true)
+    build_pkt_payload_inner(
+        seq,
+        payload,
+        [192, 168, 1, 1],
+        [192, 168, 1, 2],
+        TEST_UTILS_SPORT,
+        TEST_UTILS_DPORT,
+        true,
+    )
 }
 
 pub(crate) fn build_pkt_payload2(
@@ -681,7 +711,224 @@ pub(crate) fn build_pkt_payload2(
     dport: u16,
     fin: bool,
 ) -> CapPacket {
-    build_pkt_payload_inner(seq, payload, sport, dport, fin)
+    build_pkt_payload_inner(
+        seq,
+        payload,
+        [192, 168, 1, 1],
+        [192, 168, 1, 2],
+        sport,
+        dport,
+        fin,
+    )
+}
+
+// 反方向的包，源和目的ip与build_pkt_payload2相反
+pub(crate) fn build_pkt_payload2_rev(
+    seq: u32,
+    payload: &[u8],
+    sport: u16,
+    dport: u16,
+    fin: bool,
+) -> CapPacket {
+    build_pkt_payload_inner(
+        seq,
+        payload,
+        [192, 168, 1, 2],
+        [192, 168, 1, 1],
+        sport,
+        dport,
+        fin,
+    )
+}
+
+// udp包。rev为true时源和目的ip与build_pkt_payload2相反
+pub(crate) fn build_udp_payload(payload: &[u8], sport: u16, dport: u16, rev: bool) -> CapPacket {
+    let (sip, dip) = if rev {
+        ([192, 168, 1, 2], [192, 168, 1, 1])
+    } else {
+        ([192, 168, 1, 1], [192, 168, 1, 2])
+    };
+    let builder = PacketBuilder::ethernet2([1, 2, 3, 4, 5, 6], [7, 8, 9, 10, 11, 12])
+        .ipv4(sip, dip, 20)
+        .udp(sport, dport);
+
+    let mut result = Vec::<u8>::with_capacity(builder.size(payload.len()));
+    builder.write(&mut result, payload).unwrap();
+
+    CapPacket::new(1, result.len(), &result)
+}
+
+// ClientHello: sni example.com, alpn h2/http1.1, supported_versions 1.3/1.2
+pub(crate) fn tls_client_hello() -> Vec<u8> {
+    let mut body = vec![0x03, 0x03];
+    body.extend_from_slice(&[0x11; 32]);
+    body.extend_from_slice(&[0x04, 0xaa, 0xbb, 0xcc, 0xdd]);
+    body.extend_from_slice(&[0x00, 0x04, 0x13, 0x01, 0xc0, 0x2f]);
+    body.extend_from_slice(&[0x01, 0x00]);
+
+    let mut exts = Vec::new();
+    exts.extend_from_slice(&[0x00, 0x00, 0x00, 0x10, 0x00, 0x0e, 0x00, 0x00, 0x0b]);
+    exts.extend_from_slice(b"example.com");
+    exts.extend_from_slice(&[0x00, 0x10, 0x00, 0x0e, 0x00, 0x0c, 0x02]);
+    exts.extend_from_slice(b"h2");
+    exts.push(0x08);
+    exts.extend_from_slice(b"http/1.1");
+    exts.extend_from_slice(&[0x00, 0x2b, 0x00, 0x05, 0x04, 0x03, 0x04, 0x03, 0x03]);
+    body.extend_from_slice(&(exts.len() as u16).to_be_bytes());
+    body.extend_from_slice(&exts);
+    tls_record(1, &body)
+}
+
+pub(crate) fn tls_server_hello() -> Vec<u8> {
+    let mut body = vec![0x03, 0x03];
+    body.extend_from_slice(&[0x22; 32]);
+    body.extend_from_slice(&[0x00, 0x13, 0x01, 0x00]);
+    let exts = [0x00, 0x2b, 0x00, 0x02, 0x03, 0x04];
+    body.extend_from_slice(&(exts.len() as u16).to_be_bytes());
+    body.extend_from_slice(&exts);
+    tls_record(2, &body)
+}
+
+fn tls_record(msg_type: u8, body: &[u8]) -> Vec<u8> {
+    let len = body.len() + 4;
+    let mut rec = vec![22, 0x03, 0x01];
+    rec.extend_from_slice(&(len as u16).to_be_bytes());
+    rec.push(msg_type);
+    rec.extend_from_slice(&(body.len() as u32).to_be_bytes()[1..]);
+    rec.extend_from_slice(body);
+    rec
+}
+
+// 测试用的tcp会话。客户端4000端口连到服务端port，c2s和s2c的seq从1000和2000开始
+pub(crate) struct TestSession {
+    pub(crate) task: Task<CapPacket>,
+    port: u16,
+    seqs: (u32, u32),
+    chunk: usize, // 每段数据按chunk切成小包，0表示不切
+}
+
+impl TestSession {
+    pub(crate) fn new(protolens: &mut Prolens<CapPacket>, proto: L7Proto, port: u16) -> Self {
+        let mut task = protolens.new_task(TransProto::Tcp);
+        protolens.set_task_parser(&mut task, proto);
+        Self {
+            task,
+            port,
+            seqs: (1000, 2000),
+            chunk: 0,
+        }
+    }
+
+    pub(crate) fn chunk(mut self, size: usize) -> Self {
+        self.chunk = size;
+        self
+    }
+
+    // fin为true时这段数据的最后一个包带fin
+    pub(crate) fn send(
+        &mut self,
+        protolens: &mut Prolens<CapPacket>,
+        c2s: bool,
+        data: &[u8],
+        fin: bool,
+    ) {
+        let size = if self.chunk == 0 {
+            data.len().max(1)
+        } else {
+            self.chunk
+        };
+        let num = data.chunks(size).len();
+        for (i, bytes) in data.chunks(size).enumerate() {
+            let pkt_fin = fin && i + 1 == num;
+            let pkt = if c2s {
+                let pkt = build_pkt_payload2(self.seqs.0, bytes, 4000, self.port, pkt_fin);
+                self.seqs.0 += bytes.len() as u32;
+                pkt
+            } else {
+                let pkt = build_pkt_payload2_rev(self.seqs.1, bytes, self.port, 4000, pkt_fin);
+                self.seqs.1 += bytes.len() as u32;
+                pkt
+            };
+            let _ = pkt.decode();
+            protolens.run_task(&mut self.task, pkt);
+        }
+    }
+
+    pub(crate) fn send_all<D: AsRef<[u8]>>(
+        &mut self,
+        protolens: &mut Prolens<CapPacket>,
+        session: &[(bool, D)],
+    ) {
+        for (c2s, data) in session {
+            self.send(protolens, *c2s, data.as_ref(), false);
+        }
+    }
+}
+
+// 按顺序发完整个会话。fin为true时最后一个s2c包带fin
+pub(crate) fn run_session<D: AsRef<[u8]>>(
+    protolens: &mut Prolens<CapPacket>,
+    proto: L7Proto,
+    port: u16,
+    session: &[(bool, D)],
+    fin: bool,
+) {
+    let mut sess = TestSession::new(protolens, proto, port);
+    let last_s2c = session.iter().rposition(|(c2s, _)| !*c2s);
+    for (i, (c2s, data)) in session.iter().enumerate() {
+        sess.send(protolens, *c2s, data.as_ref(), fin && Some(i) == last_s2c);
+    }
+}
+
+pub(crate) fn h2_frame(ftype: u8, flags: u8, id: u32, payload: &[u8]) -> Vec<u8> {
+    let mut buf = (payload.len() as u32).to_be_bytes()[1..].to_vec();
+    buf.extend_from_slice(&[ftype, flags]);
+    buf.extend_from_slice(&id.to_be_bytes());
+    buf.extend_from_slice(payload);
+    buf
+}
+
+// http2回调的记录，行去掉了结尾的\r\n
+#[derive(Default)]
+pub(crate) struct H2Record {
+    pub(crate) lines: Vec<(u32, String, Direction)>,
+    pub(crate) headers: Vec<(u32, String, Direction)>,
+    pub(crate) bodies: Vec<(u32, Vec<u8>, Direction)>,
+    pub(crate) body_stops: Vec<(u32, Direction)>,
+}
+
+pub(crate) fn record_h2(protolens: &mut Prolens<CapPacket>, rec: &Rc<RefCell<H2Record>>) {
+    let r = rec.clone();
+    protolens.set_cb_http2_start_line(move |id, line: &[u8], _ctx: *mut c_void, dir| {
+        let line = String::from_utf8_lossy(line).trim_end().to_string();
+        r.borrow_mut().lines.push((id, line, dir));
+    });
+    let r = rec.clone();
+    protolens.set_cb_http2_header(move |id, line: &[u8], _ctx: *mut c_void, dir| {
+        let line = String::from_utf8_lossy(line).trim_end().to_string();
+        r.borrow_mut().headers.push((id, line, dir));
+    });
+    let r = rec.clone();
+    protolens.set_cb_http2_body_start(move |id, _ctx: *mut c_void, dir| {
+        r.borrow_mut().bodies.push((id, Vec::new(), dir));
+    });
+    let r = rec.clone();
+    protolens.set_cb_http2_body(
+        move |id, data: &[u8], _ctx: *mut c_void, dir, _ce: &Option<Vec<Encoding>>| {
+            let mut rec = r.borrow_mut();
+            let body = rec
+                .bodies
+                .iter_mut()
+                .rev()
+                .find(|(sid, _, sdir)| *sid == id && *sdir == dir)
+                .unwrap();
+            body.1.extend_from_slice(data);
+        },
+    );
+    let r = rec.clone();
+    protolens.set_cb_http2_body_stop(move |id, _ctx: *mut c_void, dir| {
+        r.borrow_mut().body_stops.push((id, dir));
+    });
 }
 
 #[cfg(test)]